@@ -0,0 +1,67 @@
+/**
+ * penrose :: external control
+ *
+ * Opens the IPC control socket so that penrose can be driven from scripts using the bundled
+ * penrose-msg client, and persists state to files so that restarting in place (M-A-r) keeps
+ * every window where it was. Try the following from a terminal running under penrose:
+ *
+ *   penrose-msg focus_workspace 2
+ *   penrose-msg create_workspace scratch
+ *   penrose-msg state | jq .
+ *
+ *   cargo run --example ipc
+ */
+#[macro_use]
+extern crate penrose;
+
+use penrose::store::FileStore;
+use penrose::{Config, Hook, WindowManager, XcbConnection};
+
+// Let anything watching the root window name (and our logs) know when workspaces change
+struct AnnounceWorkspaces;
+
+impl Hook for AnnounceWorkspaces {
+    fn workspaces_updated(&mut self, wm: &mut WindowManager, names: &[&str], active: usize) {
+        let summary = format!("{} [{}]", names.join(" "), names[active]);
+        println!("workspaces: {}", summary);
+        wm.set_root_window_name(&summary);
+    }
+}
+
+fn main() {
+    simplelog::SimpleLogger::init(simplelog::LevelFilter::Info, simplelog::Config::default())
+        .unwrap();
+
+    let config = Config {
+        show_bar: false,
+        hooks: vec![Box::new(AnnounceWorkspaces)],
+        ipc_socket_path: Some("/tmp/penrose-example.sock"),
+        state_store: Some(Box::new(
+            FileStore::new("/tmp/penrose-example.state")
+                .with_journal("/tmp/penrose-example.journal"),
+        )),
+        ..Config::default()
+    };
+    let workspaces = config.workspaces;
+
+    let key_bindings = gen_keybindings! {
+        "M-Return" => run_external!("xterm"),
+        "M-j" => run_internal!(next_client),
+        "M-k" => run_internal!(previous_client),
+        "M-S-q" => run_internal!(kill_client),
+        "M-S-s" => run_internal!(toggle_sticky),
+        "M-grave" => run_internal!(next_layout),
+        "M-A-r" => run_internal!(restart),
+        "M-A-Escape" => run_internal!(exit);
+
+        forall_workspaces: workspaces => {
+            "M-{}" => focus_workspace,
+            "M-S-{}" => client_to_workspace,
+        }
+    };
+
+    let conn = XcbConnection::new();
+    let mut wm = WindowManager::init(config, &conn)
+        .unwrap_or_else(|e| panic!("unable to start penrose: {}", e));
+    wm.grab_keys_and_run(key_bindings);
+}
@@ -0,0 +1,49 @@
+/**
+ * penrose :: minimal configuration
+ *
+ * The smallest useful config: the default Config with a terminal, basic client / layout
+ * management and workspace bindings. No bar, nothing persisted and no external control.
+ *
+ *   cargo run --example minimal
+ */
+#[macro_use]
+extern crate penrose;
+
+use penrose::{Config, WindowManager, XcbConnection};
+
+fn main() {
+    simplelog::SimpleLogger::init(simplelog::LevelFilter::Info, simplelog::Config::default())
+        .unwrap();
+
+    let config = Config {
+        show_bar: false,
+        ..Config::default()
+    };
+    let workspaces = config.workspaces;
+
+    let key_bindings = gen_keybindings! {
+        "M-Return" => run_external!("xterm"),
+        "M-j" => run_internal!(next_client),
+        "M-k" => run_internal!(previous_client),
+        "M-S-j" => run_internal!(drag_client_forward),
+        "M-S-k" => run_internal!(drag_client_backward),
+        "M-S-q" => run_internal!(kill_client),
+        "M-Tab" => run_internal!(toggle_workspace),
+        "M-grave" => run_internal!(next_layout),
+        "M-A-Up" => run_internal!(inc_main),
+        "M-A-Down" => run_internal!(dec_main),
+        "M-A-Right" => run_internal!(inc_ratio),
+        "M-A-Left" => run_internal!(dec_ratio),
+        "M-A-Escape" => run_internal!(exit);
+
+        forall_workspaces: workspaces => {
+            "M-{}" => focus_workspace,
+            "M-S-{}" => client_to_workspace,
+        }
+    };
+
+    let conn = XcbConnection::new();
+    let mut wm = WindowManager::init(config, &conn)
+        .unwrap_or_else(|e| panic!("unable to start penrose: {}", e));
+    wm.grab_keys_and_run(key_bindings);
+}
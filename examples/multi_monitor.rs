@@ -0,0 +1,113 @@
+/**
+ * penrose :: multiple monitors
+ *
+ * Bindings for moving focus and clients between screens, swapping the workspaces shown on
+ * each screen and dragging clients between them with the mouse (with a preview of where they
+ * will land). Turning the displays off needs penrose to be built with the 'dpms' feature:
+ *
+ *   cargo run --example multi_monitor --features dpms
+ */
+#[macro_use]
+extern crate penrose;
+
+use penrose::data_types::{FireAndForget, ScreenChangePolicy, ScreenDirection};
+use penrose::drag::DragGhost;
+use penrose::draw::XcbDraw;
+use penrose::helpers::{keycodes_from_xmodmap, parse_key_binding};
+use penrose::{Config, Hook, WindowManager, XcbConnection};
+
+// Log each change of focused screen so that it is easy to see what penrose thinks is going on
+struct LogScreenChanges;
+
+impl Hook for LogScreenChanges {
+    fn screen_change(&mut self, wm: &mut WindowManager, screen_index: usize) {
+        println!(
+            "screen {} focused: layout is {}",
+            screen_index,
+            wm.current_layout_symbol()
+        );
+    }
+}
+
+// Send the focused client to the screen in the given direction and follow it there
+fn throw_towards(direction: ScreenDirection) -> FireAndForget {
+    Box::new(move |wm: &mut WindowManager| {
+        wm.throw_client_to_screen(direction);
+        None
+    })
+}
+
+fn main() {
+    simplelog::SimpleLogger::init(simplelog::LevelFilter::Info, simplelog::Config::default())
+        .unwrap();
+
+    let defaults = Config::default();
+    let workspaces = defaults.workspaces;
+
+    let mut hooks: Vec<Box<dyn Hook>> = vec![Box::new(LogScreenChanges)];
+    match XcbDraw::new("fixed") {
+        Ok(draw) => hooks.push(Box::new(DragGhost::new(
+            draw,
+            defaults.color_scheme.highlight,
+            0.3,
+        ))),
+        Err(e) => eprintln!("unable to create drag preview: {}", e),
+    }
+
+    let mut key_bindings = gen_keybindings! {
+        "M-Return" => run_external!("xterm"),
+        "M-j" => run_internal!(next_client),
+        "M-k" => run_internal!(previous_client),
+        "M-S-q" => run_internal!(kill_client),
+        "M-period" => run_internal!(next_screen),
+        "M-comma" => run_internal!(previous_screen),
+        "M-S-period" => run_internal!(client_to_next_screen),
+        "M-S-comma" => run_internal!(client_to_previous_screen),
+        "M-S-Left" => throw_towards(ScreenDirection::Left),
+        "M-S-Right" => throw_towards(ScreenDirection::Right),
+        "M-C-period" => run_internal!(swap_screens),
+        "M-C-r" => run_internal!(toggle_screen_rotation),
+        "M-A-m" => run_external!("xrandr --auto"),
+        "M-A-Escape" => run_internal!(exit);
+
+        forall_workspaces: workspaces => {
+            "M-{}" => focus_workspace,
+            "M-S-{}" => client_to_workspace,
+            "M-C-S-{}" => client_to_workspace_and_follow,
+        }
+    };
+
+    if cfg!(feature = "dpms") {
+        let codes = keycodes_from_xmodmap();
+        for (pattern, on) in &[("M-A-b", false), ("M-A-S-b", true)] {
+            let on = *on;
+            if let Some(code) = parse_key_binding(*pattern, &codes) {
+                key_bindings.insert(
+                    code,
+                    Box::new(move |wm: &mut WindowManager| {
+                        if on {
+                            wm.displays_on()
+                        } else {
+                            wm.displays_off()
+                        }
+                        None
+                    }),
+                );
+            }
+        }
+    }
+
+    let conn = XcbConnection::new();
+    let mut wm = WindowManager::init(
+        Config {
+            show_bar: false,
+            hooks,
+            // keep working on the same workspace when undocking a laptop
+            screen_change_policy: ScreenChangePolicy::MoveToPrimary,
+            ..defaults
+        },
+        &conn,
+    )
+    .unwrap_or_else(|e| panic!("unable to start penrose: {}", e));
+    wm.grab_keys_and_run(key_bindings);
+}
@@ -0,0 +1,75 @@
+/**
+ * penrose :: scratchpads and client rules
+ *
+ * Two scratchpads (a terminal and a calculator) toggled from key bindings, along with client
+ * rules that float, position and spread out some programs as they are spawned. A terminal is
+ * started on the first workspace and top is run the first time that the third is viewed.
+ *
+ *   cargo run --example scratchpads
+ */
+#[macro_use]
+extern crate penrose;
+
+use penrose::data_types::InsertPoint;
+use penrose::rules::{Assignment, Rule, SpawnRule};
+use penrose::scratchpad::Scratchpad;
+use penrose::{Config, WindowManager, XcbConnection};
+
+fn main() {
+    simplelog::SimpleLogger::init(simplelog::LevelFilter::Info, simplelog::Config::default())
+        .unwrap();
+
+    let config = Config {
+        show_bar: false,
+        scratchpads: vec![
+            Scratchpad::new("term", "xterm -class scratch-term", "scratch-term"),
+            Scratchpad::new("calc", "xcalc", "XCalc"),
+        ],
+        client_rules: vec![
+            Rule {
+                class: Some("Gimp"),
+                floating: Some(true),
+                ..Rule::default()
+            },
+            Rule {
+                class: Some("Firefox"),
+                workspace: Some(1),
+                ..Rule::default()
+            },
+            // spread terminals out over the first three workspaces
+            Rule {
+                class: Some("XTerm"),
+                assign: Some(Assignment::LeastPopulated(&[0, 1, 2])),
+                position: Some(InsertPoint::AfterFocused),
+                ..Rule::default()
+            },
+        ],
+        spawn_rules: vec![
+            SpawnRule::on_startup("xterm", 0),
+            SpawnRule::on_first_view("xterm -e top", 2),
+        ],
+        ..Config::default()
+    };
+    let workspaces = config.workspaces;
+
+    let key_bindings = gen_keybindings! {
+        "M-Return" => run_external!("xterm"),
+        "M-slash" => Box::new(|wm: &mut WindowManager| wm.toggle_scratchpad("term")),
+        "M-equal" => Box::new(|wm: &mut WindowManager| wm.toggle_scratchpad("calc")),
+        "M-j" => run_internal!(next_client),
+        "M-k" => run_internal!(previous_client),
+        "M-S-q" => run_internal!(kill_client),
+        "M-grave" => run_internal!(next_layout),
+        "M-A-Escape" => run_internal!(exit);
+
+        forall_workspaces: workspaces => {
+            "M-{}" => focus_workspace,
+            "M-S-{}" => client_to_workspace,
+        }
+    };
+
+    let conn = XcbConnection::new();
+    let mut wm = WindowManager::init(config, &conn)
+        .unwrap_or_else(|e| panic!("unable to start penrose: {}", e));
+    wm.grab_keys_and_run(key_bindings);
+}
@@ -0,0 +1,88 @@
+/**
+ * penrose :: built in status bar
+ *
+ * A dwm style StatusBar on every screen, with a custom widget and a different set of widgets
+ * on the "web" workspace. The bar is hidden entirely on the "media" workspace.
+ *
+ *   cargo run --example status_bar
+ */
+#[macro_use]
+extern crate penrose;
+
+use penrose::bar::{
+    ActiveWindowTitle, BarState, CurrentLayout, Segment, StatusBar, Widget, Workspaces,
+};
+use penrose::draw::XcbDraw;
+use penrose::{ColorScheme, Config, Hook, WindowManager, XcbConnection};
+
+// Show how many workspaces currently have clients on them
+struct OccupiedWorkspaces;
+
+impl Widget for OccupiedWorkspaces {
+    fn segments(&self, state: &BarState, colors: &ColorScheme) -> Vec<Segment> {
+        let n_occupied = state.workspaces.iter().filter(|w| w.occupied).count();
+        vec![Segment {
+            text: format!(" {} occupied ", n_occupied),
+            fg: colors.fg_3,
+            bg: colors.fg_1,
+            workspace: None,
+        }]
+    }
+}
+
+fn main() {
+    simplelog::SimpleLogger::init(simplelog::LevelFilter::Info, simplelog::Config::default())
+        .unwrap();
+
+    let workspaces = &["main", "web", "media"];
+    let defaults = Config::default();
+    let (bar_height, top_bar, colors) =
+        (defaults.bar_height, defaults.top_bar, defaults.color_scheme);
+
+    let mut hooks: Vec<Box<dyn Hook>> = vec![];
+    match XcbDraw::new("fixed") {
+        Ok(draw) => {
+            let widgets: Vec<Box<dyn Widget>> = vec![
+                Box::new(Workspaces { show_empty: true }),
+                Box::new(CurrentLayout),
+                Box::new(OccupiedWorkspaces),
+                Box::new(ActiveWindowTitle),
+            ];
+            let web_widgets: Vec<Box<dyn Widget>> = vec![
+                Box::new(Workspaces { show_empty: true }),
+                Box::new(ActiveWindowTitle),
+            ];
+            let bar = StatusBar::new(draw, bar_height, top_bar, colors, widgets)
+                .with_workspace_widgets("web", web_widgets);
+            hooks.push(Box::new(bar));
+        }
+        Err(e) => eprintln!("unable to create status bar: {}", e),
+    }
+
+    let key_bindings = gen_keybindings! {
+        "M-Return" => run_external!("xterm"),
+        "M-j" => run_internal!(next_client),
+        "M-k" => run_internal!(previous_client),
+        "M-S-q" => run_internal!(kill_client),
+        "M-grave" => run_internal!(next_layout),
+        "M-A-Escape" => run_internal!(exit);
+
+        forall_workspaces: workspaces => {
+            "M-{}" => focus_workspace,
+            "M-S-{}" => client_to_workspace,
+        }
+    };
+
+    let conn = XcbConnection::new();
+    let mut wm = WindowManager::init(
+        Config {
+            workspaces,
+            hooks,
+            ..defaults
+        },
+        &conn,
+    )
+    .unwrap_or_else(|e| panic!("unable to start penrose: {}", e));
+    wm.set_bar_hidden(2, true);
+    wm.grab_keys_and_run(key_bindings);
+}
@@ -0,0 +1,599 @@
+/*!
+ * A built in dwm style status bar, drawn using the Draw API and updated via Hooks
+ *
+ * A StatusBar is a Hook so it is enabled by adding it to `Config::hooks`. The bar is
+ * redrawn whenever the WindowManager state that it displays changes. The height and position
+ * of the bar need to match `Config::bar_height` and `Config::top_bar` so that layouts leave
+ * space for it on each screen.
+ *
+ * Workspaces can hide the bar (see `WindowManager::set_bar_hidden`) or be given their own set
+ * of widgets using `StatusBar::with_workspace_widgets`, keyed by workspace name.
+ *
+ * Segments that represent a workspace (such as those from the Workspaces widget) act as drop
+ * targets for clients that are being dragged with the mouse.
+ */
+use crate::data_types::{ColorScheme, Region, WinId};
+use crate::draw::Draw;
+use crate::hooks::Hook;
+use crate::manager::WindowManager;
+use std::collections::HashMap;
+
+/// A block of text to render in the bar along with its colors
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// The text to render
+    pub text: String,
+    /// Foreground (text) color
+    pub fg: u32,
+    /// Background color
+    pub bg: u32,
+    /// The index of the workspace this segment represents, if any
+    pub workspace: Option<usize>,
+}
+
+/// The display state of a single workspace as seen from a particular screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceState {
+    /// The workspace name
+    pub name: String,
+    /// Does this workspace have any clients
+    pub occupied: bool,
+    /// Is this workspace shown on the screen being drawn
+    pub active: bool,
+    /// Is this workspace shown on any screen
+    pub visible: bool,
+    /// Does this workspace have any clients asking for attention
+    pub urgent: bool,
+    /// The number of minimized clients on this workspace
+    pub minimized: usize,
+}
+
+/// A snapshot of WindowManager state for drawing the bar on a given screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarState {
+    /// All workspaces in index order
+    pub workspaces: Vec<WorkspaceState>,
+    /// The symbol of the active layout on this screen
+    pub layout_symbol: String,
+    /// The title of the focused client, if this screen is focused
+    pub title: String,
+    /// Is the screen being drawn the focused screen
+    pub focused: bool,
+    /// Is do not disturb mode enabled
+    pub dnd: bool,
+}
+
+impl BarState {
+    /// Capture the current state of the WindowManager for the given screen
+    pub fn new(wm: &WindowManager, screen_index: usize) -> BarState {
+        let shown: Vec<usize> = (0..wm.screen_regions().len())
+            .filter_map(|i| wm.workspace_on_screen(i))
+            .collect();
+        let active = wm.workspace_on_screen(screen_index);
+        let focused = wm.focused_screen_index() == screen_index;
+
+        BarState {
+            workspaces: wm
+                .workspace_names()
+                .iter()
+                .enumerate()
+                .map(|(i, name)| WorkspaceState {
+                    name: (*name).to_string(),
+                    occupied: wm.workspace_has_clients(i),
+                    active: active == Some(i),
+                    visible: shown.contains(&i),
+                    urgent: wm.workspace_is_urgent(i),
+                    minimized: wm.minimized_clients(i).len(),
+                })
+                .collect(),
+            layout_symbol: active
+                .and_then(|i| wm.workspace_layout_symbol(i))
+                .unwrap_or_default(),
+            title: match wm.focused_client_id() {
+                Some(id) if focused => wm.client_title(id),
+                _ => String::new(),
+            },
+            focused,
+            dnd: wm.dnd_enabled(),
+        }
+    }
+}
+
+/// A section of the status bar. Widgets are drawn left to right in the order they are given.
+pub trait Widget {
+    /// The segments to render for the given state
+    fn segments(&self, state: &BarState, colors: &ColorScheme) -> Vec<Segment>;
+}
+
+/// Workspace names, highlighting the active workspace and any with urgent clients and
+/// hiding unoccupied ones
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Workspaces {
+    /// Show workspaces that have no clients and are not visible
+    pub show_empty: bool,
+}
+
+impl Widget for Workspaces {
+    fn segments(&self, state: &BarState, colors: &ColorScheme) -> Vec<Segment> {
+        state
+            .workspaces
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| self.show_empty || w.occupied || w.visible)
+            .map(|(i, w)| {
+                let (fg, bg) = if w.urgent && !w.active {
+                    (colors.bg, colors.urgent)
+                } else if w.active && state.focused {
+                    (colors.bg, colors.highlight)
+                } else if w.active {
+                    (colors.bg, colors.fg_2)
+                } else if w.visible {
+                    (colors.highlight, colors.bg)
+                } else {
+                    (colors.fg_1, colors.bg)
+                };
+                Segment {
+                    text: format!(" {} ", w.name),
+                    fg,
+                    bg,
+                    workspace: Some(i),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The symbol of the active layout
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CurrentLayout;
+
+impl Widget for CurrentLayout {
+    fn segments(&self, state: &BarState, colors: &ColorScheme) -> Vec<Segment> {
+        vec![Segment {
+            text: format!(" {} ", state.layout_symbol),
+            fg: colors.fg_1,
+            bg: colors.bg,
+            workspace: None,
+        }]
+    }
+}
+
+/// The title of the focused client
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ActiveWindowTitle;
+
+impl Widget for ActiveWindowTitle {
+    fn segments(&self, state: &BarState, colors: &ColorScheme) -> Vec<Segment> {
+        if state.title.is_empty() {
+            return vec![];
+        }
+        vec![Segment {
+            text: format!(" {} ", state.title),
+            fg: colors.fg_1,
+            bg: colors.bg,
+            workspace: None,
+        }]
+    }
+}
+
+/// An indicator shown while do not disturb mode is enabled
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DoNotDisturb;
+
+impl Widget for DoNotDisturb {
+    fn segments(&self, state: &BarState, colors: &ColorScheme) -> Vec<Segment> {
+        if !state.dnd {
+            return vec![];
+        }
+        vec![Segment {
+            text: " DND ".into(),
+            fg: colors.bg,
+            bg: colors.urgent,
+            workspace: None,
+        }]
+    }
+}
+
+/// A text status bar shown on each screen
+pub struct StatusBar<D: Draw> {
+    draw: D,
+    height: u32,
+    top: bool,
+    colors: ColorScheme,
+    widgets: Vec<Box<dyn Widget>>,
+    workspace_widgets: HashMap<String, Vec<Box<dyn Widget>>>,
+    windows: Vec<(WinId, Region)>,
+    hidden: Vec<bool>,
+    pager: Vec<(usize, Region)>,
+}
+
+impl<D: Draw> StatusBar<D> {
+    /// Create a new StatusBar. Bar windows are created the first time the bar is drawn.
+    pub fn new(
+        draw: D,
+        height: u32,
+        top: bool,
+        colors: ColorScheme,
+        widgets: Vec<Box<dyn Widget>>,
+    ) -> StatusBar<D> {
+        StatusBar {
+            draw,
+            height,
+            top,
+            colors,
+            widgets,
+            workspace_widgets: HashMap::new(),
+            windows: vec![],
+            hidden: vec![],
+            pager: vec![],
+        }
+    }
+
+    /// Use a different set of widgets on screens showing the named workspace
+    pub fn with_workspace_widgets(
+        mut self,
+        workspace: impl Into<String>,
+        widgets: Vec<Box<dyn Widget>>,
+    ) -> StatusBar<D> {
+        self.workspace_widgets.insert(workspace.into(), widgets);
+        self
+    }
+
+    /// A bar showing workspaces, the current layout and the focused window title
+    pub fn dwm_style(draw: D, height: u32, top: bool, colors: ColorScheme) -> StatusBar<D> {
+        let widgets: Vec<Box<dyn Widget>> = vec![
+            Box::new(Workspaces::default()),
+            Box::new(CurrentLayout),
+            Box::new(DoNotDisturb),
+            Box::new(ActiveWindowTitle),
+        ];
+        StatusBar::new(draw, height, top, colors, widgets)
+    }
+
+    // The region the bar should occupy on a screen
+    fn bar_region(&self, screen: &Region) -> Region {
+        let (x, y, w, h) = screen.values();
+        let y = if self.top { y } else { y + h - self.height };
+        Region::new(x, y, w, self.height)
+    }
+
+    // Make sure that we have a bar window for each screen
+    fn ensure_windows(&mut self, screens: &[Region]) {
+        for s in screens.iter().skip(self.windows.len()) {
+            let r = self.bar_region(s);
+            match self.draw.new_window(&r) {
+                Ok(id) => {
+                    self.windows.push((id, r));
+                    self.hidden.push(false);
+                }
+                Err(e) => {
+                    warn!("unable to create status bar window: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Redraw the bar on every screen using the current WindowManager state
+    pub fn redraw(&mut self, wm: &WindowManager) {
+        self.ensure_windows(&wm.screen_regions());
+        self.pager.clear();
+
+        let names = wm.workspace_names();
+        for (i, (id, r)) in self.windows.clone().into_iter().enumerate() {
+            let wix = wm.workspace_on_screen(i);
+            let hide = wix.is_some_and(|w| wm.workspace_bar_hidden(w));
+            match (hide, self.hidden[i]) {
+                (true, false) => self.draw.hide_window(id),
+                (false, true) => self.draw.show_window(id, &r),
+                _ => (),
+            }
+            self.hidden[i] = hide;
+            if hide {
+                continue;
+            }
+
+            let overrides = &self.workspace_widgets;
+            let widgets = wix
+                .and_then(|w| overrides.get(names[w]))
+                .unwrap_or(&self.widgets);
+            let state = BarState::new(wm, i);
+            let full = Region::new(0, 0, r.width(), self.height);
+            self.draw.fill_rect(id, &full, self.colors.bg);
+
+            let mut x = 0;
+            for w in widgets.iter() {
+                for s in w.segments(&state, &self.colors) {
+                    let width = self.draw.text_width(&s.text);
+                    let bg = Region::new(x, 0, width, self.height);
+                    self.draw.fill_rect(id, &bg, s.bg);
+                    self.draw.text(id, x, self.height, &s.text, s.fg, s.bg);
+                    if let Some(wix) = s.workspace {
+                        let (bx, by, _, _) = r.values();
+                        self.pager
+                            .push((wix, Region::new(bx + x, by, width, self.height)));
+                    }
+                    x += width;
+                }
+            }
+        }
+
+        self.draw.flush();
+    }
+}
+
+impl<D: Draw> Hook for StatusBar<D> {
+    fn remove_client(&mut self, wm: &mut WindowManager, _: WinId) {
+        self.redraw(wm);
+    }
+
+    fn layout_change(&mut self, wm: &mut WindowManager, _: usize, _: usize) {
+        self.redraw(wm);
+    }
+
+    fn workspace_change(&mut self, wm: &mut WindowManager, _: usize, _: usize) {
+        self.redraw(wm);
+    }
+
+    fn workspaces_updated(&mut self, wm: &mut WindowManager, _: &[&str], _: usize) {
+        self.redraw(wm);
+    }
+
+    fn screen_change(&mut self, wm: &mut WindowManager, _: usize) {
+        self.redraw(wm);
+    }
+
+    fn focus_change(&mut self, wm: &mut WindowManager, _: WinId) {
+        self.redraw(wm);
+    }
+
+    fn title_change(&mut self, wm: &mut WindowManager, id: WinId, _: &str) {
+        if wm.focused_client_id() == Some(id) {
+            self.redraw(wm);
+        }
+    }
+
+    fn urgency_change(&mut self, wm: &mut WindowManager, _: WinId, _: bool) {
+        self.redraw(wm);
+    }
+
+    fn dnd_change(&mut self, wm: &mut WindowManager, _: bool) {
+        self.redraw(wm);
+    }
+
+    fn workspace_at_point(&self, _: &WindowManager, x: u32, y: u32) -> Option<(usize, Region)> {
+        self.pager
+            .iter()
+            .find(|(_, r)| r.contains_point(x, y))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{
+        ClassFilter, Config, InsertPoint, ScreenChangePolicy, WorkspaceSwitch,
+    };
+    use crate::layout::{mock_layout, Layout, LayoutConf};
+    use crate::screen::Screen;
+    use crate::xconnection::MockXConn;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Records text draw calls as (window, x, text)
+    struct MockDraw {
+        calls: Rc<RefCell<Vec<(WinId, u32, String)>>>,
+        next_id: WinId,
+    }
+
+    impl Draw for MockDraw {
+        fn new_window(&mut self, _: &Region) -> Result<WinId, String> {
+            self.next_id += 1;
+            Ok(self.next_id)
+        }
+        fn show_window(&mut self, _: WinId, _: &Region) {}
+        fn hide_window(&mut self, _: WinId) {}
+        fn set_opacity(&mut self, _: WinId, _: f32) {}
+        fn fill_rect(&mut self, _: WinId, _: &Region, _: u32) {}
+        fn text(&mut self, id: WinId, x: u32, _: u32, s: &str, _: u32, _: u32) {
+            self.calls.borrow_mut().push((id, x, s.to_string()));
+        }
+        fn text_width(&self, s: &str) -> u32 {
+            s.len() as u32
+        }
+        fn flush(&self) {}
+    }
+
+    fn colors() -> ColorScheme {
+        ColorScheme {
+            bg: 0,
+            fg_1: 1,
+            fg_2: 2,
+            fg_3: 3,
+            highlight: 4,
+            urgent: 5,
+        }
+    }
+
+    fn test_wm(conn: &MockXConn) -> WindowManager<'_> {
+        let conf = Config {
+            workspaces: &["1", "2", "3"],
+            fonts: &[],
+            floating_classes: &[],
+            class_filter: ClassFilter::Deny(&[]),
+            layouts: vec![Layout::new(
+                "[t]",
+                LayoutConf::default(),
+                mock_layout,
+                1,
+                0.6,
+            )],
+            color_scheme: colors(),
+            border_px: 0,
+            gap_px: 0,
+            main_ratio_step: 0.1,
+            workspace_cleanup: None,
+            placement_grid: None,
+            systray_spacing_px: 0,
+            show_systray: false,
+            show_bar: true,
+            top_bar: true,
+            bar_height: 10,
+            respect_resize_hints: false,
+            hooks: vec![],
+            scratchpads: vec![],
+            client_rules: vec![],
+            insert_point: InsertPoint::First,
+            focus_new_clients: true,
+            state_store: None,
+            ipc_socket_path: None,
+            remote_border_color: None,
+            border_style: None,
+            callback_budget: None,
+            notify_slow_callbacks: false,
+            command_palette: None,
+            expose: None,
+            rotated_screens: &[],
+            replace_existing_wm: false,
+            startup_hook: None,
+            error_handler: None,
+            screen_change_policy: ScreenChangePolicy::ByIndex,
+            warp_pointer: true,
+            terminal_classes: &[],
+            config_file: None,
+            dnd_workspace: None,
+            workspace_switch: WorkspaceSwitch::UnmapFirst,
+            opacity: None,
+            workspace_layouts: HashMap::new(),
+            spawn_rules: vec![],
+        };
+        WindowManager::init(conf, conn).unwrap()
+    }
+
+    fn screens() -> Vec<Screen> {
+        let r = Region::new(0, 0, 1000, 600);
+        vec![Screen {
+            true_region: r,
+            effective_region: r,
+            wix: 0,
+            view_mask: 0,
+        }]
+    }
+
+    #[test]
+    fn bar_state_reflects_the_window_manager() {
+        let conn = MockXConn::new(screens());
+        let wm = test_wm(&conn);
+        let state = BarState::new(&wm, 0);
+
+        assert_eq!(state.layout_symbol, "[t]");
+        assert!(state.focused);
+        assert!(state.workspaces[0].active);
+        assert!(!state.workspaces[1].visible);
+    }
+
+    #[test]
+    fn widgets_are_drawn_left_to_right() {
+        let conn = MockXConn::new(screens());
+        let wm = test_wm(&conn);
+        let calls = Rc::new(RefCell::new(vec![]));
+        let draw = MockDraw {
+            calls: Rc::clone(&calls),
+            next_id: 0,
+        };
+        let mut bar = StatusBar::dwm_style(draw, 10, true, colors());
+        bar.redraw(&wm);
+
+        // only the visible workspace is shown as the others are empty
+        assert_eq!(
+            *calls.borrow(),
+            vec![(1, 0, " 1 ".to_string()), (1, 3, " [t] ".to_string())]
+        );
+        assert_eq!(bar.windows, vec![(1, Region::new(0, 0, 1000, 10))]);
+    }
+
+    #[test]
+    fn pager_entries_are_drop_targets() {
+        let conn = MockXConn::new(screens());
+        let wm = test_wm(&conn);
+        let draw = MockDraw {
+            calls: Rc::new(RefCell::new(vec![])),
+            next_id: 0,
+        };
+        let mut bar = StatusBar::new(
+            draw,
+            10,
+            true,
+            colors(),
+            vec![
+                Box::new(CurrentLayout),
+                Box::new(Workspaces { show_empty: true }),
+            ],
+        );
+        bar.redraw(&wm);
+
+        // " [t] " takes up the first 5 pixels
+        assert_eq!(bar.workspace_at_point(&wm, 2, 5), None);
+        assert_eq!(
+            bar.workspace_at_point(&wm, 9, 5),
+            Some((1, Region::new(8, 0, 3, 10)))
+        );
+        assert_eq!(bar.workspace_at_point(&wm, 9, 50), None);
+    }
+
+    #[test]
+    fn workspaces_can_hide_or_override_the_bar() {
+        let conn = MockXConn::new(screens());
+        let mut wm = test_wm(&conn);
+        let calls = Rc::new(RefCell::new(vec![]));
+        let draw = MockDraw {
+            calls: Rc::clone(&calls),
+            next_id: 0,
+        };
+        let mut bar = StatusBar::dwm_style(draw, 10, true, colors())
+            .with_workspace_widgets("2", vec![Box::new(CurrentLayout)]);
+
+        wm.set_bar_hidden(0, true);
+        bar.redraw(&wm);
+        assert!(calls.borrow().is_empty());
+        assert_eq!(bar.hidden, vec![true]);
+
+        wm.focus_workspace(1);
+        bar.redraw(&wm);
+        assert_eq!(*calls.borrow(), vec![(1, 0, " [t] ".to_string())]);
+        assert_eq!(bar.hidden, vec![false]);
+    }
+
+    #[test]
+    fn urgent_workspaces_are_highlighted() {
+        let ws = |name: &str, active, urgent| WorkspaceState {
+            name: name.to_string(),
+            occupied: true,
+            active,
+            visible: active,
+            urgent,
+            minimized: 0,
+        };
+        let state = BarState {
+            workspaces: vec![
+                ws("1", true, true),
+                ws("2", false, true),
+                ws("3", false, false),
+            ],
+            layout_symbol: "[t]".into(),
+            title: String::new(),
+            focused: true,
+            dnd: false,
+        };
+        let c = colors();
+
+        let bgs: Vec<u32> = Workspaces { show_empty: true }
+            .segments(&state, &c)
+            .iter()
+            .map(|s| s.bg)
+            .collect();
+
+        assert_eq!(bgs, vec![c.highlight, c.urgent, c.bg]);
+    }
+}
@@ -0,0 +1,39 @@
+/**
+ * penrose-msg :: send a request to a running penrose instance over IPC
+ *
+ * Usage: penrose-msg <action> [args...]
+ *
+ * The socket path is taken from $PENROSE_SOCKET, which penrose exports to the programs that
+ * it spawns.
+ */
+use penrose::ipc::{send, SOCKET_ENV};
+use std::env;
+use std::process::exit;
+
+fn main() {
+    let request: Vec<String> = env::args().skip(1).collect();
+    if request.is_empty() {
+        eprintln!("usage: penrose-msg <action> [args...]");
+        exit(2);
+    }
+
+    let path = match env::var(SOCKET_ENV) {
+        Ok(p) => p,
+        Err(_) => {
+            eprintln!("${} is not set: is penrose running?", SOCKET_ENV);
+            exit(2);
+        }
+    };
+
+    match send(&path, &request.join(" ")) {
+        Ok(response) if response.starts_with("error:") => {
+            eprintln!("{}", response);
+            exit(1);
+        }
+        Ok(response) => println!("{}", response),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
@@ -1,5 +1,10 @@
 //! Metadata around X clients and manipulating them
-use crate::data_types::WinId;
+use crate::data_types::{Region, SizeHints, WinId};
+use crate::helpers::{remove_tag_bit, tag_bit};
+use serde::{Deserialize, Serialize};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
 
 /**
  * Meta-data around a client window that we are handling.
@@ -7,14 +12,32 @@ use crate::data_types::WinId;
  * Primarily state flags and information used when determining which clients
  * to show for a given monitor and how they are tiled.
  */
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Client {
     id: WinId,
     wm_class: String,
+    wm_name: String,
     workspace: usize,
+    #[serde(default)]
+    tags: u64,
+    region: Option<Region>,
+    machine: Option<String>,
+    protocols: Vec<String>,
+    #[serde(default)]
+    size_hints: Option<SizeHints>,
+    #[serde(default)]
+    transient_for: Option<WinId>,
+    #[serde(default)]
+    no_input: bool,
+    #[serde(skip)]
+    data: ClientData,
     // state flags
     floating: bool,
     fullscreen: bool,
+    remote: bool,
+    sticky: bool,
+    following: bool,
+    urgent: bool,
 }
 
 impl Client {
@@ -23,9 +46,22 @@ impl Client {
         Client {
             id,
             wm_class,
+            wm_name: String::new(),
             workspace,
-            floating: floating,
+            tags: 0,
+            region: None,
+            machine: None,
+            protocols: vec![],
+            size_hints: None,
+            transient_for: None,
+            no_input: false,
+            data: ClientData::default(),
+            floating,
             fullscreen: false,
+            remote: false,
+            sticky: false,
+            following: false,
+            urgent: false,
         }
     }
 
@@ -44,8 +80,366 @@ impl Client {
         self.workspace = workspace
     }
 
+    /**
+     * The workspaces that this client is tagged with as a bit mask (see helpers::tag_bit).
+     * A client is always tagged with the workspace that it is on and is also shown on any
+     * screen that is viewing one of its other tags.
+     */
+    pub fn tags(&self) -> u64 {
+        self.tags | tag_bit(self.workspace)
+    }
+
+    /// Whether or not this client is tagged with the workspace at `index`
+    pub fn has_tag(&self, index: usize) -> bool {
+        self.tags() & tag_bit(index) != 0
+    }
+
+    /// Add or remove a tag for the workspace at `index`
+    pub fn set_tag(&mut self, index: usize, tagged: bool) {
+        if tagged {
+            self.tags |= tag_bit(index);
+        } else {
+            self.tags &= !tag_bit(index);
+        }
+    }
+
+    /// Drop the tag for a deleted workspace, shifting the tags for later workspaces down
+    pub fn remove_workspace_tag(&mut self, index: usize) {
+        self.tags = remove_tag_bit(self.tags, index);
+    }
+
+    /// The window title (_NET_WM_NAME, falling back to WM_NAME) as of the last time it changed
+    pub fn wm_name(&self) -> &str {
+        &self.wm_name
+    }
+
+    /// Update the cached window title
+    pub fn set_wm_name(&mut self, name: impl Into<String>) {
+        self.wm_name = name.into()
+    }
+
+    /**
+     * Whether or not this client is asking for the user's attention, either through the
+     * ICCCM urgency hint or _NET_WM_STATE_DEMANDS_ATTENTION.
+     */
+    pub fn is_urgent(&self) -> bool {
+        self.urgent
+    }
+
+    /// Mark this client as needing (or no longer needing) the user's attention
+    pub fn set_urgent(&mut self, urgent: bool) {
+        self.urgent = urgent
+    }
+
     /// The WM_CLASS of the window that this Client is tracking
     pub fn class(&self) -> &str {
         &self.wm_class
     }
+
+    /**
+     * The last region this client was positioned at by penrose (including gaps and borders).
+     * This is cached state rather than a query to the X server so it will be None if the
+     * client has not been positioned yet.
+     */
+    pub fn region(&self) -> Option<Region> {
+        self.region
+    }
+
+    /// Record the region that this client has been positioned at
+    pub fn set_region(&mut self, region: Region) {
+        self.region = Some(region)
+    }
+
+    /// The size hints (WM_NORMAL_HINTS) set by this client, if any
+    pub fn size_hints(&self) -> Option<SizeHints> {
+        self.size_hints
+    }
+
+    /// Update the cached size hints for this client
+    pub fn set_size_hints(&mut self, hints: Option<SizeHints>) {
+        self.size_hints = hints
+    }
+
+    /**
+     * The client that this one is a transient for (WM_TRANSIENT_FOR), such as the main window
+     * of the application that opened a dialog.
+     */
+    pub fn transient_for(&self) -> Option<WinId> {
+        self.transient_for
+    }
+
+    /// Set (or clear) the client that this one is a transient for
+    pub fn set_transient_for(&mut self, parent: Option<WinId>) {
+        self.transient_for = parent
+    }
+
+    /**
+     * Whether or not this client should be given input focus directly. Clients that set the
+     * input field of WM_HINTS to false either never take keyboard input or (if they support
+     * WM_TAKE_FOCUS) set the focus themselves.
+     */
+    pub fn accepts_input(&self) -> bool {
+        !self.no_input
+    }
+
+    /// Record whether or not this client should be given input focus directly
+    pub fn set_accepts_input(&mut self, accepts_input: bool) {
+        self.no_input = !accepts_input
+    }
+
+    /// Whether or not this client is floating (not managed by the workspace layout)
+    pub fn is_floating(&self) -> bool {
+        self.floating
+    }
+
+    /**
+     * Mark this client as floating (or not). This is only respected for clients that are
+     * not yet being managed, such as from a client Rule or the new_client Hook.
+     */
+    pub fn set_floating(&mut self, floating: bool) {
+        self.floating = floating
+    }
+
+    /// Whether or not this client is currently covering its whole screen
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    /// Mark this client as being fullscreen (or not)
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.fullscreen = fullscreen
+    }
+
+    /// Whether or not this client follows the focused screen on to every workspace
+    pub fn is_sticky(&self) -> bool {
+        self.sticky
+    }
+
+    /// Mark this client as being sticky (or not)
+    pub fn set_sticky(&mut self, sticky: bool) {
+        self.sticky = sticky
+    }
+
+    /**
+     * Whether or not this client stays on its screen when the workspace shown there changes.
+     * Unlike sticky clients, following clients ignore changes on other screens.
+     */
+    pub fn is_following(&self) -> bool {
+        self.following
+    }
+
+    /// Mark this client as following the workspace shown on its screen (or not)
+    pub fn set_following(&mut self, following: bool) {
+        self.following = following
+    }
+
+    /// The host that this client is running on (WM_CLIENT_MACHINE) if it was set
+    pub fn client_machine(&self) -> Option<&str> {
+        self.machine.as_deref()
+    }
+
+    /**
+     * Whether or not this client is running on a different host to the window manager, such
+     * as when using SSH X forwarding.
+     */
+    pub fn is_remote(&self) -> bool {
+        self.remote
+    }
+
+    /// Record the host this client is running on, comparing it against the local hostname
+    pub fn set_client_machine(&mut self, machine: impl Into<String>, local_host: &str) {
+        let machine = machine.into();
+        self.remote = !same_host(&machine, local_host);
+        self.machine = Some(machine);
+    }
+
+    /// The ICCCM protocols (from WM_PROTOCOLS) that this client said it supported when mapped
+    pub fn protocols(&self) -> &[String] {
+        &self.protocols
+    }
+
+    /// Record the ICCCM protocols supported by this client
+    pub fn set_protocols(&mut self, protocols: Vec<String>) {
+        self.protocols = protocols
+    }
+
+    /// Whether or not this client supports the named protocol (such as WM_DELETE_WINDOW)
+    pub fn supports_protocol(&self, protocol: &str) -> bool {
+        self.protocols.iter().any(|p| p == protocol)
+    }
+
+    /// The value of type T attached to this client, if there is one
+    pub fn data<T: Any + Clone>(&self) -> Option<&T> {
+        self.data.get()
+    }
+
+    /// A mutable reference to the value of type T attached to this client, if there is one
+    pub fn data_mut<T: Any + Clone>(&mut self) -> Option<&mut T> {
+        self.data.get_mut()
+    }
+
+    /// Attach a value of type T to this client, returning the previous value if there was one
+    pub fn set_data<T: Any + Clone>(&mut self, value: T) -> Option<T> {
+        self.data.insert(value)
+    }
+
+    /// Remove the value of type T attached to this client, returning it if there was one
+    pub fn remove_data<T: Any + Clone>(&mut self) -> Option<T> {
+        self.data.remove()
+    }
+}
+
+/**
+ * Arbitrary state attached to a Client by hooks and user code, holding at most one value of
+ * each type. Wrapping values in a type that is private to your own code avoids clashing with
+ * data attached by anything else.
+ *
+ * Values need to be Clone as Clients are, but they are not serialized so they are lost when
+ * penrose restarts and they are ignored when comparing Clients.
+ */
+#[derive(Default)]
+struct ClientData {
+    values: HashMap<TypeId, Box<dyn CloneAny>>,
+}
+
+impl ClientData {
+    fn get<T: Any + Clone>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|v| (**v).as_any().downcast_ref())
+    }
+
+    fn get_mut<T: Any + Clone>(&mut self) -> Option<&mut T> {
+        self.values
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|v| (**v).as_any_mut().downcast_mut())
+    }
+
+    fn insert<T: Any + Clone>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|v| v.into_any().downcast().ok())
+            .map(|v| *v)
+    }
+
+    fn remove<T: Any + Clone>(&mut self) -> Option<T> {
+        self.values
+            .remove(&TypeId::of::<T>())
+            .and_then(|v| v.into_any().downcast().ok())
+            .map(|v| *v)
+    }
+}
+
+impl Clone for ClientData {
+    fn clone(&self) -> ClientData {
+        ClientData {
+            values: self
+                .values
+                .iter()
+                .map(|(k, v)| (*k, (**v).clone_box()))
+                .collect(),
+        }
+    }
+}
+
+impl PartialEq for ClientData {
+    fn eq(&self, _: &ClientData) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for ClientData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ClientData({} values)", self.values.len())
+    }
+}
+
+// Any that can also be cloned through a trait object
+trait CloneAny: Any {
+    fn clone_box(&self) -> Box<dyn CloneAny>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Any + Clone> CloneAny for T {
+    fn clone_box(&self) -> Box<dyn CloneAny> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+// Hostnames are compared without their domain as WM_CLIENT_MACHINE is not always fully qualified
+fn same_host(a: &str, b: &str) -> bool {
+    let short = |h: &str| h.split('.').next().unwrap_or_default().to_lowercase();
+    a == "localhost" || short(a) == short(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clients_on_other_hosts_are_remote() {
+        let mut c = Client::new(1, "st".into(), 0, false);
+        assert!(!c.is_remote());
+        assert_eq!(c.client_machine(), None);
+
+        c.set_client_machine("box.example.com", "box");
+        assert!(!c.is_remote());
+        c.set_client_machine("localhost", "box");
+        assert!(!c.is_remote());
+        c.set_client_machine("server", "box");
+        assert!(c.is_remote());
+        assert_eq!(c.client_machine(), Some("server"));
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Opacity(f32);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Marker;
+
+    #[test]
+    fn typed_data_can_be_attached_to_clients() {
+        let mut c = Client::new(1, "st".into(), 0, false);
+        assert_eq!(c.data::<Opacity>(), None);
+
+        assert_eq!(c.set_data(Opacity(0.5)), None);
+        c.set_data(Marker);
+        if let Some(o) = c.data_mut::<Opacity>() {
+            o.0 = 0.8;
+        }
+        assert_eq!(c.set_data(Opacity(0.9)), Some(Opacity(0.8)));
+
+        let copy = c.clone();
+        assert_eq!(c.remove_data::<Marker>(), Some(Marker));
+        assert_eq!(c.data::<Marker>(), None);
+        assert_eq!(copy.data::<Marker>(), Some(&Marker));
+        assert_eq!(copy.data::<Opacity>(), Some(&Opacity(0.9)));
+    }
+
+    #[test]
+    fn clients_are_always_tagged_with_their_workspace() {
+        let mut c = Client::new(1, "st".into(), 2, false);
+        c.set_tag(0, true);
+        c.set_tag(2, false);
+        assert_eq!(c.tags(), 0b101);
+
+        c.remove_workspace_tag(1);
+        c.set_workspace(1);
+        assert!(c.has_tag(0) && c.has_tag(1));
+        assert!(!c.has_tag(2));
+    }
 }
@@ -0,0 +1,355 @@
+/*!
+ * Loading (and reloading) parts of the Config from a TOML file
+ *
+ * Penrose is configured in code but the values that people tend to tweak the most (workspace
+ * names, colors, gaps, client rules and key bindings to named actions) can also be kept in a
+ * config file. Anything set in the file overrides the value set in code, and everything other
+ * than fonts and the status bar can be changed on a running WindowManager using the
+ * `reload_config` action:
+ *
+ * ```toml
+ * workspaces = ["term", "web", "chat"]
+ * gap_px = 4
+ * floating_classes = ["dmenu", "pavucontrol"]
+ *
+ * [colors]
+ * highlight = "#cc241d"
+ *
+ * [keys]
+ * "M-Return" = "spawn xterm"
+ * "M-j" = "next_client"
+ *
+ * [[rules]]
+ * class = "Firefox"
+ * workspace = 1
+ * ```
+ *
+ * Key bindings map a key pattern (see helpers::parse_key_binding) to the name of an action
+ * (see WindowManager::run_action), to "set_layout <symbol>" or to "spawn <command>". Bindings
+ * made in code take precedence over those in the file.
+ */
+use crate::data_types::{CodeMap, ColorScheme, Config, KeyCode};
+use crate::errors::{PenroseError, Result};
+use crate::helpers::parse_key_binding;
+use crate::rules::Rule;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const MODIFIERS: &[&str] = &["A", "M", "S", "C"];
+
+/// The contents of a config file: anything left unset keeps its current value
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub workspaces: Option<Vec<String>>,
+    pub floating_classes: Option<Vec<String>>,
+    pub border_px: Option<u32>,
+    pub gap_px: Option<u32>,
+    pub main_ratio_step: Option<f32>,
+    pub colors: ColorsFile,
+    pub keys: BTreeMap<String, String>,
+    pub rules: Option<Vec<RuleFile>>,
+}
+
+/// Colors given as "#rrggbb" (or "0xrrggbb") strings, overriding the current ColorScheme
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorsFile {
+    pub bg: Option<String>,
+    pub fg_1: Option<String>,
+    pub fg_2: Option<String>,
+    pub fg_3: Option<String>,
+    pub highlight: Option<String>,
+    pub urgent: Option<String>,
+}
+
+/// A client Rule as written in a config file
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleFile {
+    pub class: Option<String>,
+    pub title: Option<String>,
+    pub role: Option<String>,
+    pub workspace: Option<usize>,
+    pub floating: Option<bool>,
+    pub sticky: Option<bool>,
+    pub follow: Option<bool>,
+}
+
+impl ConfigFile {
+    /// Read and parse the config file at `path`, reporting all problems found with it
+    pub fn load(path: impl AsRef<Path>) -> Result<ConfigFile> {
+        let path = path.as_ref();
+        let invalid =
+            |msg: String| PenroseError::InvalidConfig(format!("{}: {}", path.display(), msg));
+        let raw = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let file = ConfigFile::parse(&raw).map_err(invalid)?;
+
+        match file.problems().as_slice() {
+            [] => Ok(file),
+            problems => Err(invalid(problems.join("; "))),
+        }
+    }
+
+    /// Parse the contents of a config file without validating the values it contains
+    pub fn parse(raw: &str) -> std::result::Result<ConfigFile, String> {
+        toml::from_str(raw).map_err(|e| e.to_string())
+    }
+
+    /**
+     * Everything wrong with the values in this file that can be checked without a running
+     * WindowManager. Key bindings to unknown actions are only reported when they are bound.
+     */
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        if self.workspaces.as_ref().is_some_and(|ws| ws.is_empty()) {
+            problems.push("at least one workspace is required".to_string());
+        }
+        if let Some(step) = self.main_ratio_step {
+            if !(step > 0.0 && step < 1.0) {
+                problems.push(format!("main_ratio_step must be in (0, 1): {}", step));
+            }
+        }
+        for (name, color) in self.colors.named() {
+            if parse_color(color).is_none() {
+                problems.push(format!("invalid color for {}: '{}'", name, color));
+            }
+        }
+        for pattern in self.keys.keys() {
+            let mut parts: Vec<&str> = pattern.split('-').collect();
+            parts.pop();
+            if let Some(m) = parts.iter().find(|m| !MODIFIERS.contains(m)) {
+                problems.push(format!("invalid modifier '{}' in '{}'", m, pattern));
+            }
+        }
+        for (pattern, action) in self.keys.iter() {
+            if let Some(cmd) = action.strip_prefix("spawn ") {
+                if cmd.trim().is_empty() {
+                    problems.push(format!("empty spawn command for '{}'", pattern));
+                }
+            }
+        }
+        if let Some(n) = self.workspaces.as_ref().map(|ws| ws.len()) {
+            for rule in self.rules.iter().flatten() {
+                match rule.workspace {
+                    Some(wix) if wix >= n => {
+                        problems.push(format!("rule workspace {} is out of range", wix))
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Override the values in `conf` with those set in this file
+    pub fn apply_to(&self, conf: Config) -> Config {
+        let color_scheme = self.colors.apply_to(conf.color_scheme);
+        Config {
+            workspaces: self
+                .workspaces
+                .as_ref()
+                .map_or(conf.workspaces, |ws| leak_strs(ws)),
+            floating_classes: self
+                .floating_classes
+                .as_ref()
+                .map_or(conf.floating_classes, |cs| leak_strs(cs)),
+            border_px: self.border_px.unwrap_or(conf.border_px),
+            gap_px: self.gap_px.unwrap_or(conf.gap_px),
+            main_ratio_step: self.main_ratio_step.unwrap_or(conf.main_ratio_step),
+            client_rules: self.client_rules().unwrap_or(conf.client_rules),
+            color_scheme,
+            ..conf
+        }
+    }
+
+    /// The rules from this file (if it sets any) as client Rules
+    pub fn client_rules(&self) -> Option<Vec<Rule>> {
+        self.rules
+            .as_ref()
+            .map(|rules| rules.iter().map(|r| r.as_rule()).collect())
+    }
+
+    /**
+     * The key bindings in this file as (KeyCode, action) pairs, along with the patterns that
+     * do not correspond to any known key. Modifiers must have been checked using `problems`.
+     */
+    pub fn key_bindings(&self, codes: &CodeMap) -> (Vec<(KeyCode, String)>, Vec<String>) {
+        let mut unknown = vec![];
+        let bindings = self
+            .keys
+            .iter()
+            .filter_map(
+                |(pattern, action)| match parse_key_binding(pattern, codes) {
+                    Some(code) => Some((code, action.clone())),
+                    None => {
+                        unknown.push(pattern.clone());
+                        None
+                    }
+                },
+            )
+            .collect();
+
+        (bindings, unknown)
+    }
+}
+
+impl ColorsFile {
+    /// Whether or not any colors have been set
+    pub fn is_empty(&self) -> bool {
+        self.named().is_empty()
+    }
+
+    /// Override the colors in `scheme` with any that have been set
+    pub fn apply_to(&self, scheme: ColorScheme) -> ColorScheme {
+        let color = |c: &Option<String>, current: u32| {
+            c.as_deref().and_then(parse_color).unwrap_or(current)
+        };
+        ColorScheme {
+            bg: color(&self.bg, scheme.bg),
+            fg_1: color(&self.fg_1, scheme.fg_1),
+            fg_2: color(&self.fg_2, scheme.fg_2),
+            fg_3: color(&self.fg_3, scheme.fg_3),
+            highlight: color(&self.highlight, scheme.highlight),
+            urgent: color(&self.urgent, scheme.urgent),
+        }
+    }
+
+    fn named(&self) -> Vec<(&str, &str)> {
+        vec![
+            ("bg", &self.bg),
+            ("fg_1", &self.fg_1),
+            ("fg_2", &self.fg_2),
+            ("fg_3", &self.fg_3),
+            ("highlight", &self.highlight),
+            ("urgent", &self.urgent),
+        ]
+        .into_iter()
+        .filter_map(|(name, c)| c.as_deref().map(|c| (name, c)))
+        .collect()
+    }
+}
+
+impl RuleFile {
+    fn as_rule(&self) -> Rule {
+        Rule {
+            class: self.class.as_deref().map(leak_str),
+            title: self.title.as_deref().map(leak_str),
+            role: self.role.as_deref().map(leak_str),
+            workspace: self.workspace,
+            floating: self.floating,
+            sticky: self.sticky,
+            follow: self.follow,
+            ..Rule::default()
+        }
+    }
+}
+
+/// Parse a "#rrggbb" or "0xrrggbb" hex color
+pub fn parse_color(s: &str) -> Option<u32> {
+    let hex = s.strip_prefix('#').or_else(|| s.strip_prefix("0x"))?;
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+// The Config holds &'static strs so values read from a file are leaked. Config files are small
+// and only reloaded by hand so this is not worth complicating the Config types over.
+fn leak_str(s: &str) -> &'static str {
+    Box::leak(s.to_string().into_boxed_str())
+}
+
+pub(crate) fn leak_strs(strs: &[String]) -> &'static [&'static str] {
+    Box::leak(
+        strs.iter()
+            .map(|s| leak_str(s))
+            .collect::<Vec<_>>()
+            .into_boxed_slice(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_are_parsed_from_hex_strings() {
+        assert_eq!(parse_color("#cc241d"), Some(0xcc241d));
+        assert_eq!(parse_color("0x282828"), Some(0x282828));
+        assert_eq!(parse_color("cc241d"), None);
+        assert_eq!(parse_color("#fff"), None);
+    }
+
+    #[test]
+    fn unset_values_are_left_alone() {
+        let file = ConfigFile::parse(
+            r##"
+            workspaces = ["a", "b"]
+            gap_px = 10
+
+            [colors]
+            highlight = "#ffffff"
+
+            [[rules]]
+            class = "Firefox"
+            workspace = 1
+            "##,
+        )
+        .unwrap();
+        assert!(file.problems().is_empty());
+
+        let defaults = Config::default();
+        let conf = file.apply_to(Config::default());
+        assert_eq!(conf.workspaces, &["a", "b"]);
+        assert_eq!(conf.gap_px, 10);
+        assert_eq!(conf.border_px, defaults.border_px);
+        assert_eq!(conf.color_scheme.highlight, 0xffffff);
+        assert_eq!(conf.color_scheme.bg, defaults.color_scheme.bg);
+        assert_eq!(conf.floating_classes, defaults.floating_classes);
+        assert_eq!(conf.client_rules[0].class, Some("Firefox"));
+        assert_eq!(conf.client_rules[0].workspace, Some(1));
+    }
+
+    #[test]
+    fn all_problems_are_reported() {
+        let file = ConfigFile::parse(
+            r##"
+            workspaces = ["a"]
+            main_ratio_step = 2.0
+
+            [colors]
+            bg = "black"
+
+            [keys]
+            "X-j" = "next_client"
+            "M-Return" = "spawn   "
+
+            [[rules]]
+            workspace = 3
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(
+            file.problems(),
+            vec![
+                "main_ratio_step must be in (0, 1): 2",
+                "invalid color for bg: 'black'",
+                "invalid modifier 'X' in 'X-j'",
+                "empty spawn command for 'M-Return'",
+                "rule workspace 3 is out of range",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_parse_errors() {
+        assert!(ConfigFile::parse("gaps = 3").is_err());
+        assert!(ConfigFile::parse("gap_px = \"3\"").is_err());
+    }
+}
@@ -1,9 +1,22 @@
 //! Simple data types and enums
-use crate::layout::Layout;
+use crate::config_file::ConfigFile;
+use crate::errors::{ErrorHandler, Result};
+use crate::expose::Expose;
+use crate::hooks::Hooks;
+use crate::layout::{side_stack, Layout, LayoutConf};
 use crate::manager::WindowManager;
+use crate::palette::CommandPalette;
+use crate::placement::PlacementGrid;
+use crate::rules::{Rule, SpawnRule};
+use crate::scratchpad::Scratchpad;
+use crate::store::StateStore;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
+use std::fmt;
 use std::ops;
+use std::path::PathBuf;
 use std::process::Child;
+use std::time::Duration;
 use xcb;
 
 /// Some action to be run by a user key binding
@@ -27,21 +40,199 @@ pub struct Config {
     pub workspaces: &'static [&'static str],
     pub fonts: &'static [&'static str],
     pub floating_classes: &'static [&'static str],
+    pub class_filter: ClassFilter,
     pub layouts: Vec<Layout>,
     pub color_scheme: ColorScheme,
     pub border_px: u32,
     pub gap_px: u32,
     pub main_ratio_step: f32,
+    pub workspace_cleanup: Option<Duration>,
+    pub placement_grid: Option<PlacementGrid>,
     pub systray_spacing_px: u32,
     pub show_systray: bool,
     pub show_bar: bool,
     pub top_bar: bool,
     pub bar_height: u32,
     pub respect_resize_hints: bool,
+    pub hooks: Hooks,
+    pub scratchpads: Vec<Scratchpad>,
+    pub client_rules: Vec<Rule>,
+    pub insert_point: InsertPoint,
+    pub focus_new_clients: bool,
+    pub state_store: Option<Box<dyn StateStore>>,
+    pub ipc_socket_path: Option<&'static str>,
+    pub remote_border_color: Option<u32>,
+    pub border_style: Option<BorderStyle>,
+    pub callback_budget: Option<Duration>,
+    pub notify_slow_callbacks: bool,
+    pub command_palette: Option<CommandPalette>,
+    pub expose: Option<Expose>,
+    pub rotated_screens: &'static [usize],
+    pub replace_existing_wm: bool,
+    pub startup_hook: Option<fn(&mut WindowManager)>,
+    pub spawn_rules: Vec<SpawnRule>,
+    pub error_handler: Option<ErrorHandler>,
+    pub screen_change_policy: ScreenChangePolicy,
+    pub warp_pointer: bool,
+    pub terminal_classes: &'static [&'static str],
+    pub config_file: Option<PathBuf>,
+    pub dnd_workspace: Option<usize>,
+    pub workspace_switch: WorkspaceSwitch,
+    pub opacity: Option<Opacity>,
+    pub workspace_layouts: HashMap<&'static str, Vec<Layout>>,
+}
+
+impl Default for Config {
+    /// Nine numbered workspaces, side_stack and floating layouts, no hooks and nothing persisted
+    fn default() -> Config {
+        Config {
+            workspaces: &["1", "2", "3", "4", "5", "6", "7", "8", "9"],
+            fonts: &[],
+            floating_classes: &["dmenu", "dunst"],
+            class_filter: ClassFilter::Deny(&[]),
+            layouts: vec![
+                Layout::new("[side]", LayoutConf::default(), side_stack, 1, 0.6),
+                Layout::floating("[----]"),
+            ],
+            color_scheme: ColorScheme {
+                bg: 0x282828,
+                fg_1: 0x3c3836,
+                fg_2: 0xa89984,
+                fg_3: 0xf2e5bc,
+                highlight: 0xcc241d,
+                urgent: 0x458588,
+            },
+            border_px: 2,
+            gap_px: 5,
+            main_ratio_step: 0.05,
+            workspace_cleanup: None,
+            placement_grid: None,
+            systray_spacing_px: 2,
+            show_systray: true,
+            show_bar: true,
+            top_bar: true,
+            bar_height: 18,
+            respect_resize_hints: true,
+            hooks: vec![],
+            scratchpads: vec![],
+            client_rules: vec![],
+            insert_point: InsertPoint::First,
+            focus_new_clients: true,
+            state_store: None,
+            ipc_socket_path: None,
+            remote_border_color: None,
+            border_style: None,
+            callback_budget: None,
+            notify_slow_callbacks: false,
+            command_palette: None,
+            expose: None,
+            rotated_screens: &[],
+            replace_existing_wm: false,
+            startup_hook: None,
+            error_handler: None,
+            screen_change_policy: ScreenChangePolicy::ByIndex,
+            warp_pointer: true,
+            terminal_classes: &[],
+            config_file: None,
+            dnd_workspace: None,
+            workspace_switch: WorkspaceSwitch::UnmapFirst,
+            opacity: None,
+            workspace_layouts: HashMap::new(),
+            spawn_rules: vec![],
+        }
+    }
+}
+
+impl Config {
+    /**
+     * Override the values in this Config with those set in the TOML file at `path` (see the
+     * config_file module), remembering the path so that the file can be re-read by the
+     * `reload_config` action. Fails with all of the problems found if the file is invalid.
+     */
+    pub fn with_config_file(self, path: impl Into<PathBuf>) -> Result<Config> {
+        let path = path.into();
+        let conf = ConfigFile::load(&path)?.apply_to(self);
+        Ok(Config {
+            config_file: Some(path),
+            ..conf
+        })
+    }
 }
 
 /* Argument enums */
 
+/**
+ * Which windows penrose should manage, based on their WM_CLASS.
+ *
+ * Windows that are filtered out are never managed or focused and any events
+ * relating to them are ignored. Both the instance and class name parts of
+ * WM_CLASS are checked when matching.
+ */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClassFilter {
+    /// manage everything other than windows with one of these classes
+    Deny(&'static [&'static str]),
+    /// only manage windows with one of these classes
+    Allow(&'static [&'static str]),
+}
+
+impl ClassFilter {
+    /// Should a window with the given WM_CLASS parts be ignored
+    pub fn should_ignore(&self, wm_class: &[&str]) -> bool {
+        match self {
+            ClassFilter::Deny(classes) => wm_class.iter().any(|c| classes.contains(c)),
+            ClassFilter::Allow(classes) => !wm_class.iter().any(|c| classes.contains(c)),
+        }
+    }
+}
+
+/// How workspaces are reassigned to screens when monitors are connected or disconnected
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScreenChangePolicy {
+    /// Each screen keeps the workspace shown on the screen with the same index
+    ByIndex,
+    /// Each screen keeps the workspace shown on the screen with the same geometry
+    MatchGeometry,
+    /**
+     * As MatchGeometry, but if the focused screen was disconnected then its workspace is
+     * moved to the primary (first) screen and focus goes with it.
+     */
+    MoveToPrimary,
+}
+
+/**
+ * How the clients on a workspace are swapped out for those on another when the workspace shown on
+ * a screen changes. Unmapping the outgoing clients first briefly shows the root window which is
+ * visible as a flicker on slow machines.
+ */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WorkspaceSwitch {
+    /// Unmap the outgoing clients and then map and lay out the incoming ones
+    UnmapFirst,
+    /// Lay out and map the incoming clients and only then unmap the outgoing ones
+    MapFirst,
+    /**
+     * Lay out and map the incoming clients and then move the outgoing ones off screen rather
+     * than unmapping them, so that switching back to them again does not need them to redraw.
+     * Clients moved off screen are marked as hidden (ICCCM IconicState and
+     * _NET_WM_STATE_HIDDEN) so that pagers and taskbars do not treat them as visible.
+     */
+    Offscreen,
+}
+
+/// Where in a workspace stack a new client should be inserted
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InsertPoint {
+    /// At the top of the stack (the main area for most layouts)
+    First,
+    /// At the bottom of the stack
+    Last,
+    /// Directly after the focused client
+    AfterFocused,
+    /// Directly before the focused client, taking its position
+    BeforeFocused,
+}
+
 /// A direction to permute a Ring
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Direction {
@@ -51,6 +242,24 @@ pub enum Direction {
     Backward,
 }
 
+/// An axis on screen to reflect regions in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Axis {
+    /// Reflect left to right
+    Horizontal,
+    /// Reflect top to bottom
+    Vertical,
+}
+
+/// A direction on screen, used for moving between clients based on where they are positioned
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScreenDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
 impl Direction {
     pub fn reverse(&self) -> Direction {
         match self {
@@ -69,6 +278,19 @@ pub enum Change {
     Less,
 }
 
+/// DPMS power level for connected displays
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DpmsState {
+    /// displays are powered on
+    On,
+    /// displays are blanked with minimal power savings
+    Standby,
+    /// displays are blanked with increased power savings
+    Suspend,
+    /// displays are powered off
+    Off,
+}
+
 /// X window border kind
 #[derive(Debug)]
 pub enum Border {
@@ -81,7 +303,7 @@ pub enum Border {
 }
 
 /// An X window / screen position: top left corner + extent
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub struct Region {
     x: u32,
     y: u32,
@@ -105,6 +327,322 @@ impl Region {
     pub fn values(&self) -> (u32, u32, u32, u32) {
         (self.x, self.y, self.w, self.h)
     }
+
+    /**
+     * This region moved so that it is centered within `outer`. Regions that are too large to fit
+     * are aligned with the top (or left) edge of `outer` instead.
+     */
+    pub fn centered_in(&self, outer: &Region) -> Region {
+        let (ox, oy, ow, oh) = outer.values();
+        Region::new(
+            ox + ow.saturating_sub(self.w) / 2,
+            oy + oh.saturating_sub(self.h) / 2,
+            self.w,
+            self.h,
+        )
+    }
+
+    /// The smallest region that covers both this region and `other`
+    pub fn union(&self, other: &Region) -> Region {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let w = (self.x + self.w).max(other.x + other.w) - x;
+        let h = (self.y + self.h).max(other.y + other.h) - y;
+        Region::new(x, y, w, h)
+    }
+
+    /// Whether or not the point (x, y) lies inside of this region
+    pub fn contains_point(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+    }
+
+    /**
+     * Move this region from the region `from` to the region `to`, keeping the same offset from
+     * the top left corner where it still fits and otherwise pushing it back inside of `to`.
+     */
+    pub fn relocate(&self, from: &Region, to: &Region) -> Region {
+        let x = to.x
+            + self
+                .x
+                .saturating_sub(from.x)
+                .min(to.w.saturating_sub(self.w));
+        let y = to.y
+            + self
+                .y
+                .saturating_sub(from.y)
+                .min(to.h.saturating_sub(self.h));
+        Region::new(x, y, self.w, self.h)
+    }
+
+    /**
+     * Reflect this region in the line x = y, swapping the x and y coordinates along with the
+     * width and height. Transposing twice gives back the original region.
+     */
+    pub fn transpose(&self) -> Region {
+        Region::new(self.y, self.x, self.h, self.w)
+    }
+
+    /// Reflect this region from left to right within the bounds of `within`
+    pub fn reflect_horizontally(&self, within: &Region) -> Region {
+        let right = within.x + within.w;
+        Region::new(
+            within.x + right.saturating_sub(self.x + self.w),
+            self.y,
+            self.w,
+            self.h,
+        )
+    }
+
+    /// Reflect this region from top to bottom within the bounds of `within`
+    pub fn reflect_vertically(&self, within: &Region) -> Region {
+        let bottom = within.y + within.h;
+        Region::new(
+            self.x,
+            within.y + bottom.saturating_sub(self.y + self.h),
+            self.w,
+            self.h,
+        )
+    }
+
+    /// Reflect this region in the given Axis within the bounds of `within`
+    pub fn reflect(&self, axis: Axis, within: &Region) -> Region {
+        match axis {
+            Axis::Horizontal => self.reflect_horizontally(within),
+            Axis::Vertical => self.reflect_vertically(within),
+        }
+    }
+
+    /**
+     * Rotate this region a quarter turn clockwise within the bounds of `within`. The result is
+     * only contained in `within` if this region is contained in `within.transpose()`.
+     */
+    pub fn rotate_clockwise(&self, within: &Region) -> Region {
+        self.transpose().reflect_horizontally(within)
+    }
+
+    // Twice the midpoint so that we can stay in integer coordinates
+    fn double_midpoint(&self) -> (i64, i64) {
+        (
+            2 * self.x as i64 + self.w as i64,
+            2 * self.y as i64 + self.h as i64,
+        )
+    }
+
+    fn overlaps_horizontally(&self, other: &Region) -> bool {
+        self.x < other.x + other.w && other.x < self.x + self.w
+    }
+
+    fn overlaps_vertically(&self, other: &Region) -> bool {
+        self.y < other.y + other.h && other.y < self.y + self.h
+    }
+
+    /**
+     * The index of the region in `candidates` that is nearest to this one in the given
+     * direction. Only regions whose midpoint lies beyond ours in that direction are
+     * considered: those that line up with us are preferred, then the closest in the direction
+     * of travel and finally the closest on the other axis. Ties go to the earliest candidate.
+     */
+    pub fn nearest_in_direction(
+        &self,
+        candidates: &[Region],
+        direction: ScreenDirection,
+    ) -> Option<usize> {
+        let (x, y) = self.double_midpoint();
+
+        candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| {
+                let (cx, cy) = r.double_midpoint();
+                let (along, across, in_line) = match direction {
+                    ScreenDirection::Right => (cx - x, cy - y, self.overlaps_vertically(r)),
+                    ScreenDirection::Left => (x - cx, cy - y, self.overlaps_vertically(r)),
+                    ScreenDirection::Down => (cy - y, cx - x, self.overlaps_horizontally(r)),
+                    ScreenDirection::Up => (y - cy, cx - x, self.overlaps_horizontally(r)),
+                };
+
+                if along > 0 {
+                    Some(((!in_line, along, across.abs()), i))
+                } else {
+                    None
+                }
+            })
+            .min()
+            .map(|(_, i)| i)
+    }
+}
+
+/// Space reserved along one edge of the root window: `width` pixels between `start` and `end`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StrutEdge {
+    pub width: u32,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl StrutEdge {
+    // Does the reserved range overlap the span [lo, lo + len) of this edge
+    fn overlaps(&self, lo: u32, len: u32) -> bool {
+        self.width > 0 && len > 0 && self.start < lo + len && self.end >= lo
+    }
+}
+
+/**
+ * Space reserved at the edges of the X root window by a dock or status bar.
+ *
+ * Taken from _NET_WM_STRUT_PARTIAL if it is set: each edge only reserves space for the range
+ * given, so on a multi-monitor setup a bar only takes space from the screen that it is on.
+ * The older _NET_WM_STRUT property always reserves space along the whole of each edge.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Strut {
+    pub left: StrutEdge,
+    pub right: StrutEdge,
+    pub top: StrutEdge,
+    pub bottom: StrutEdge,
+}
+
+impl Strut {
+    /**
+     * Parse the twelve values of _NET_WM_STRUT_PARTIAL or the four values of _NET_WM_STRUT.
+     * Returns None for any other number of values.
+     */
+    pub fn from_cardinals(values: &[u32]) -> Option<Strut> {
+        let edge = |i: usize| StrutEdge {
+            width: values[i],
+            start: values.get(4 + 2 * i).copied().unwrap_or(0),
+            end: values.get(5 + 2 * i).copied().unwrap_or(u32::MAX),
+        };
+
+        match values.len() {
+            4 | 12 => Some(Strut {
+                left: edge(0),
+                right: edge(1),
+                top: edge(2),
+                bottom: edge(3),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Remove any space reserved by this strut from the region `r` within the root window
+    pub fn reserve(&self, r: &Region, root: &Region) -> Region {
+        let (x, y, w, h) = r.values();
+        let (mut left, mut top, mut right, mut bottom) = (x, y, x + w, y + h);
+
+        if self.left.overlaps(y, h) {
+            left = left.max(root.x + self.left.width);
+        }
+        if self.right.overlaps(y, h) {
+            right = right.min((root.x + root.w).saturating_sub(self.right.width));
+        }
+        if self.top.overlaps(x, w) {
+            top = top.max(root.y + self.top.width);
+        }
+        if self.bottom.overlaps(x, w) {
+            bottom = bottom.min((root.y + root.h).saturating_sub(self.bottom.width));
+        }
+
+        Region::new(
+            left,
+            top,
+            right.saturating_sub(left),
+            bottom.saturating_sub(top),
+        )
+    }
+}
+
+/**
+ * The size constraints that a client has set in its WM_NORMAL_HINTS property.
+ *
+ * Only the hints flagged as being set by the client are populated: sizes are (width, height)
+ * pairs and aspect ratios are (numerator, denominator) pairs.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeHints {
+    pub min: Option<(u32, u32)>,
+    pub max: Option<(u32, u32)>,
+    pub base: Option<(u32, u32)>,
+    pub inc: Option<(u32, u32)>,
+    pub min_aspect: Option<(u32, u32)>,
+    pub max_aspect: Option<(u32, u32)>,
+}
+
+impl SizeHints {
+    /**
+     * Parse the raw values of a WM_NORMAL_HINTS property (the XSizeHints struct). Returns
+     * None if there are too few values for this to be a valid set of hints.
+     */
+    pub fn from_cardinals(values: &[u32]) -> Option<SizeHints> {
+        if values.len() < 15 {
+            return None;
+        }
+
+        let flags = values[0];
+        let pair = |flag: u32, i: usize| {
+            if flags & flag != 0 && values.len() > i + 1 {
+                Some((values[i], values[i + 1]))
+            } else {
+                None
+            }
+        };
+        let non_zero = |p: Option<(u32, u32)>| p.filter(|&(a, b)| a > 0 && b > 0);
+
+        Some(SizeHints {
+            min: pair(1 << 4, 5),
+            max: non_zero(pair(1 << 5, 7)),
+            inc: non_zero(pair(1 << 6, 9)),
+            min_aspect: non_zero(pair(1 << 7, 11)),
+            max_aspect: non_zero(pair(1 << 7, 13)),
+            base: pair(1 << 8, 15),
+        })
+    }
+
+    /**
+     * The largest region within `r` that satisfies these hints, centered in `r`. Aspect ratios
+     * and resize increments are applied to the size over the base size (falling back to the
+     * minimum size as per the ICCCM) before clamping to the min and max sizes. A client
+     * asking for a minimum size larger than `r` will overflow it.
+     */
+    pub fn apply(&self, r: &Region) -> Region {
+        let (x, y, w, h) = r.values();
+        let (bw, bh) = self.base.or(self.min).unwrap_or((0, 0));
+        let (mut dw, mut dh) = (w.saturating_sub(bw) as u64, h.saturating_sub(bh) as u64);
+
+        if let Some((n, d)) = self.max_aspect {
+            let (n, d) = (n as u64, d as u64);
+            if dw * d > dh * n {
+                dw = dh * n / d;
+            }
+        }
+        if let Some((n, d)) = self.min_aspect {
+            let (n, d) = (n as u64, d as u64);
+            if dw * d < dh * n {
+                dh = dw * d / n;
+            }
+        }
+        if let Some((iw, ih)) = self.inc {
+            dw -= dw % iw as u64;
+            dh -= dh % ih as u64;
+        }
+
+        let (mut cw, mut ch) = (dw as u32 + bw, dh as u32 + bh);
+        if let Some((mw, mh)) = self.max {
+            cw = cw.min(mw);
+            ch = ch.min(mh);
+        }
+        if let Some((mw, mh)) = self.min {
+            cw = cw.max(mw);
+            ch = ch.max(mh);
+        }
+
+        Region::new(
+            x + w.saturating_sub(cw) / 2,
+            y + h.saturating_sub(ch) / 2,
+            cw,
+            ch,
+        )
+    }
 }
 
 /// A set of named color codes
@@ -118,6 +656,101 @@ pub struct ColorScheme {
     pub urgent: u32,
 }
 
+/**
+ * The width and colors used for client borders.
+ *
+ * The focused client is given the `focused` color, clients asking for attention the `urgent`
+ * color and everything else the `unfocused` color. If `alternate` is set it is used in place
+ * of `unfocused` for the client in the main area of each workspace.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorderStyle {
+    pub width: u32,
+    pub focused: u32,
+    pub unfocused: u32,
+    pub urgent: u32,
+    pub alternate: Option<u32>,
+}
+
+impl BorderStyle {
+    /// The default border style for a given ColorScheme: this is what is used if no
+    /// BorderStyle is set in the Config.
+    pub fn from_color_scheme(width: u32, colors: &ColorScheme) -> BorderStyle {
+        BorderStyle {
+            width,
+            focused: colors.highlight,
+            unfocused: colors.fg_1,
+            urgent: colors.urgent,
+            alternate: None,
+        }
+    }
+}
+
+/**
+ * The opacity (from 0.0 for fully transparent to 1.0 for fully opaque) to give focused and
+ * unfocused clients. This is set in _NET_WM_WINDOW_OPACITY so it only has an effect when a
+ * compositor such as picom is running.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Opacity {
+    pub focused: f32,
+    pub unfocused: f32,
+}
+
+impl Opacity {
+    /// The opacity to use for a client with the given focus state
+    pub fn for_focus(&self, focused: bool) -> f32 {
+        if focused {
+            self.focused
+        } else {
+            self.unfocused
+        }
+    }
+}
+
+/// How important a Notification is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotificationLevel {
+    /// An action completed and changed something the user may want to know about
+    Info,
+    /// An action could not be carried out
+    Error,
+}
+
+/// Feedback on the outcome of an action, reported using WindowManager::notify
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub message: String,
+}
+
+impl Notification {
+    /// Report the outcome of an action that completed successfully
+    pub fn info(message: impl Into<String>) -> Notification {
+        Notification {
+            level: NotificationLevel::Info,
+            message: message.into(),
+        }
+    }
+
+    /// Report an action that could not be carried out
+    pub fn error(message: impl Into<String>) -> Notification {
+        Notification {
+            level: NotificationLevel::Error,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level {
+            NotificationLevel::Info => write!(f, "{}", self.message),
+            NotificationLevel::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
 /// An X key-code along with a modifier mask
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct KeyCode {
@@ -141,7 +774,7 @@ impl KeyCode {
  * Supports rotating the position of the elements and rotating which element
  * is focused independently of one another.
  */
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct Ring<T> {
     elements: VecDeque<T>,
     focused: usize,
@@ -162,6 +795,14 @@ impl<T> Ring<T> {
         wrap_back || wrap_forward
     }
 
+    pub fn focused_index(&self) -> Option<usize> {
+        if self.elements.is_empty() {
+            None
+        } else {
+            Some(self.focused)
+        }
+    }
+
     pub fn focused(&self) -> Option<&T> {
         self.elements.get(self.focused)
     }
@@ -200,6 +841,15 @@ impl<T> Ring<T> {
         }
     }
 
+    /// Reverse the order of the elements, keeping focus on the same element
+    pub fn reverse(&mut self) {
+        if self.elements.is_empty() {
+            return;
+        }
+        self.elements.make_contiguous().reverse();
+        self.focused = self.elements.len() - 1 - self.focused;
+    }
+
     pub fn cycle_focus(&mut self, direction: Direction) -> Option<&T> {
         self.focused = self.next_index(direction);
         self.focused()
@@ -215,6 +865,15 @@ impl<T> Ring<T> {
         self.cycle_focus(direction)
     }
 
+    /// Swap the focused element with the first element matching `cond`, keeping focus on the
+    /// element that was focused (now at its new position)
+    pub fn swap_focused_with(&mut self, cond: impl Fn(&T) -> bool) -> Option<&T> {
+        let i = self.elements.iter().position(cond)?;
+        self.elements.swap(self.focused, i);
+        self.focused = i;
+        self.focused()
+    }
+
     pub fn focus_by(&mut self, cond: impl Fn(&T) -> bool) -> Option<&T> {
         if let Some((i, _)) = self.elements.iter().enumerate().find(|(_, e)| cond(*e)) {
             self.focused = i;
@@ -244,7 +903,7 @@ impl<T> Ring<T> {
     }
 
     pub fn remove_focused(&mut self) -> Option<T> {
-        if self.elements.len() == 0 {
+        if self.elements.is_empty() {
             return None;
         }
 
@@ -253,10 +912,10 @@ impl<T> Ring<T> {
             self.focused -= 1;
         }
 
-        return c;
+        c
     }
 
-    pub fn iter(&self) -> std::collections::vec_deque::Iter<T> {
+    pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, T> {
         self.elements.iter()
     }
 }
@@ -286,6 +945,41 @@ impl<T> ops::IndexMut<usize> for Ring<T> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn class_filter_deny() {
+        let f = ClassFilter::Deny(&["screen-share"]);
+        assert!(f.should_ignore(&["overlay", "screen-share"]));
+        assert!(!f.should_ignore(&["st", "St"]));
+        assert!(!f.should_ignore(&[]));
+    }
+
+    #[test]
+    fn class_filter_allow() {
+        let f = ClassFilter::Allow(&["St"]);
+        assert!(!f.should_ignore(&["st", "St"]));
+        assert!(f.should_ignore(&["overlay", "screen-share"]));
+        assert!(f.should_ignore(&[]));
+    }
+
+    #[test]
+    fn union_covers_both_regions() {
+        let a = Region::new(10, 20, 30, 40);
+        let b = Region::new(100, 0, 10, 10);
+        assert_eq!(a.union(&b), Region::new(10, 0, 100, 60));
+        assert_eq!(b.union(&a), a.union(&b));
+        assert_eq!(a.union(&a), a);
+    }
+
+    #[test]
+    fn reverse_keeps_focus_on_the_same_element() {
+        let mut r = Ring::new(vec![1, 2, 3, 4]);
+        r.focus_by(|e| *e == 2);
+
+        r.reverse();
+        assert_eq!(r.as_vec(), vec![4, 3, 2, 1]);
+        assert_eq!(r.focused(), Some(&2));
+    }
+
     #[test]
     fn rotate_holds_focus_but_permutes_order() {
         let mut r = Ring::new(vec![1, 2, 3]);
@@ -377,4 +1071,221 @@ mod tests {
         assert_eq!(r.cycle_focus(Direction::Backward), Some(&1));
         assert_eq!(r.as_vec(), vec![1, 2, 3]);
     }
+
+    #[test]
+    fn swap_focused_with() {
+        let mut r = Ring::new(vec![1, 2, 3, 4]);
+        r.focus_by(|e| *e == 2);
+        assert_eq!(r.swap_focused_with(|e| *e == 4), Some(&2));
+        assert_eq!(r.as_vec(), vec![1, 4, 3, 2]);
+        assert_eq!(r.swap_focused_with(|e| *e == 5), None);
+        assert_eq!(r.as_vec(), vec![1, 4, 3, 2]);
+    }
+
+    #[test]
+    fn transposing_swaps_axes() {
+        let r = Region::new(10, 20, 300, 400);
+
+        assert_eq!(r.transpose(), Region::new(20, 10, 400, 300));
+        assert_eq!(r.transpose().transpose(), r);
+    }
+
+    // a side_stack style arrangement: main on the left with a stack of three on the right
+    fn side_stack_regions() -> Vec<Region> {
+        vec![
+            Region::new(0, 0, 600, 900),
+            Region::new(600, 0, 400, 300),
+            Region::new(600, 300, 400, 300),
+            Region::new(600, 600, 400, 300),
+        ]
+    }
+
+    #[test]
+    fn nearest_in_direction_prefers_regions_in_line() {
+        let rs = side_stack_regions();
+
+        assert_eq!(
+            rs[0].nearest_in_direction(&rs, ScreenDirection::Right),
+            Some(2)
+        );
+        assert_eq!(
+            rs[3].nearest_in_direction(&rs, ScreenDirection::Left),
+            Some(0)
+        );
+        assert_eq!(
+            rs[3].nearest_in_direction(&rs, ScreenDirection::Up),
+            Some(2)
+        );
+        assert_eq!(
+            rs[1].nearest_in_direction(&rs, ScreenDirection::Down),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn nearest_in_direction_is_none_at_the_edge() {
+        let rs = side_stack_regions();
+
+        assert_eq!(rs[0].nearest_in_direction(&rs, ScreenDirection::Left), None);
+        assert_eq!(rs[1].nearest_in_direction(&rs, ScreenDirection::Up), None);
+        assert_eq!(
+            rs[2].nearest_in_direction(&rs, ScreenDirection::Right),
+            None
+        );
+    }
+
+    #[test]
+    fn nearest_in_direction_falls_back_to_regions_out_of_line() {
+        let from = Region::new(0, 0, 100, 100);
+        let rs = vec![
+            Region::new(500, 500, 100, 100),
+            Region::new(200, 300, 100, 100),
+        ];
+
+        assert_eq!(
+            from.nearest_in_direction(&rs, ScreenDirection::Right),
+            Some(1)
+        );
+        assert_eq!(
+            from.nearest_in_direction(&rs, ScreenDirection::Down),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn regions_can_be_reflected_and_rotated() {
+        let screen = Region::new(100, 0, 1000, 600);
+        let r = Region::new(100, 0, 300, 200);
+
+        assert_eq!(
+            r.reflect_horizontally(&screen),
+            Region::new(800, 0, 300, 200)
+        );
+        assert_eq!(
+            r.reflect_vertically(&screen),
+            Region::new(100, 400, 300, 200)
+        );
+        assert_eq!(
+            r.reflect(Axis::Horizontal, &screen)
+                .reflect(Axis::Horizontal, &screen),
+            r
+        );
+
+        // top left of the transposed screen ends up in the top right
+        let t = Region::new(0, 100, 200, 300);
+        assert_eq!(t.rotate_clockwise(&screen), Region::new(800, 0, 300, 200));
+    }
+
+    #[test]
+    fn partial_struts_only_reserve_space_on_overlapping_screens() {
+        let root = Region::new(0, 0, 2000, 600);
+        let (left, right) = (
+            Region::new(0, 0, 1000, 600),
+            Region::new(1000, 0, 1000, 600),
+        );
+        // a 20px bar at the top of the right hand screen
+        let mut values = vec![0, 0, 20, 0];
+        values.extend(&[0, 0, 0, 0, 1000, 1999, 0, 0]);
+        let strut = Strut::from_cardinals(&values).unwrap();
+
+        assert_eq!(strut.reserve(&left, &root), left);
+        assert_eq!(
+            strut.reserve(&right, &root),
+            Region::new(1000, 20, 1000, 580)
+        );
+    }
+
+    #[test]
+    fn full_struts_reserve_whole_edges() {
+        let root = Region::new(0, 0, 2000, 600);
+        let strut = Strut::from_cardinals(&[0, 30, 0, 25]).unwrap();
+
+        assert_eq!(
+            strut.reserve(&Region::new(1000, 0, 1000, 600), &root),
+            Region::new(1000, 0, 970, 575)
+        );
+        assert_eq!(
+            strut.reserve(&Region::new(0, 0, 1000, 600), &root),
+            Region::new(0, 0, 1000, 575)
+        );
+        assert_eq!(Strut::from_cardinals(&[1, 2]), None);
+    }
+
+    #[test]
+    fn relocated_regions_keep_their_offset_where_they_fit() {
+        let (left, right) = (Region::new(0, 0, 1000, 600), Region::new(1000, 0, 800, 400));
+
+        assert_eq!(
+            Region::new(100, 50, 200, 100).relocate(&left, &right),
+            Region::new(1100, 50, 200, 100)
+        );
+        assert_eq!(
+            Region::new(700, 450, 200, 100).relocate(&left, &right),
+            Region::new(1600, 300, 200, 100)
+        );
+    }
+
+    #[test]
+    fn centered_regions_align_to_the_edge_when_too_big() {
+        let outer = Region::new(100, 100, 400, 300);
+
+        assert_eq!(
+            Region::new(0, 0, 200, 100).centered_in(&outer),
+            Region::new(200, 200, 200, 100)
+        );
+        assert_eq!(
+            Region::new(0, 0, 500, 100).centered_in(&outer),
+            Region::new(100, 200, 500, 100)
+        );
+    }
+
+    #[test]
+    fn size_hints_only_include_flagged_values() {
+        let mut values = vec![0; 18];
+        values[0] = (1 << 4) | (1 << 5) | (1 << 6);
+        values[5..=14].copy_from_slice(&[100, 50, 800, 600, 10, 20, 16, 9, 16, 9]);
+
+        assert_eq!(
+            SizeHints::from_cardinals(&values),
+            Some(SizeHints {
+                min: Some((100, 50)),
+                max: Some((800, 600)),
+                inc: Some((10, 20)),
+                ..SizeHints::default()
+            })
+        );
+        assert_eq!(SizeHints::from_cardinals(&[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn size_hints_shrink_regions_to_fit() {
+        let term = SizeHints {
+            base: Some((4, 4)),
+            inc: Some((10, 20)),
+            ..SizeHints::default()
+        };
+        assert_eq!(
+            term.apply(&Region::new(0, 0, 505, 310)),
+            Region::new(0, 3, 504, 304)
+        );
+
+        let small = SizeHints {
+            max: Some((300, 200)),
+            ..SizeHints::default()
+        };
+        assert_eq!(
+            small.apply(&Region::new(100, 100, 1000, 800)),
+            Region::new(450, 400, 300, 200)
+        );
+
+        let video = SizeHints {
+            min_aspect: Some((16, 9)),
+            max_aspect: Some((16, 9)),
+            ..SizeHints::default()
+        };
+        assert_eq!(
+            video.apply(&Region::new(0, 0, 1000, 900)),
+            Region::new(0, 169, 1000, 562)
+        );
+    }
 }
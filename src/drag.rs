@@ -0,0 +1,90 @@
+/*!
+ * Dragging clients between workspaces, screens and stack positions with the mouse
+ *
+ * While a client is being dragged (mod + left button) the WindowManager works out where it
+ * would land if the button was released and passes that to the `drag_preview` Hook method.
+ * DragGhost is a Hook that uses this to draw a translucent rectangle over the target area
+ * so that the result of the drop is visible before the button is released.
+ */
+use crate::data_types::{Region, WinId};
+use crate::draw::Draw;
+use crate::hooks::Hook;
+use crate::manager::WindowManager;
+
+/// Where a dragged client will end up if it is dropped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DropTarget {
+    /// The client being dragged
+    pub client: WinId,
+    /// The screen that the client will be shown on, None if it will not be visible
+    pub screen: Option<usize>,
+    /// The workspace that the client will be moved to
+    pub workspace: usize,
+    /// The position in the workspace stack for tiled clients
+    pub position: Option<usize>,
+    /// The area that the drop corresponds to: the new client region for screen drops or the
+    /// pager entry for drops on a bar
+    pub region: Region,
+    /// Whether the client is being dropped on to a workspace entry in a bar pager
+    pub pager: bool,
+}
+
+/// Draws a translucent ghost over the area a dragged client will be dropped on to
+pub struct DragGhost<D: Draw> {
+    draw: D,
+    color: u32,
+    opacity: f32,
+    window: Option<WinId>,
+}
+
+impl<D: Draw> DragGhost<D> {
+    /// Create a new DragGhost. The ghost window is created the first time it is shown.
+    pub fn new(draw: D, color: u32, opacity: f32) -> DragGhost<D> {
+        DragGhost {
+            draw,
+            color,
+            opacity,
+            window: None,
+        }
+    }
+
+    fn show(&mut self, r: &Region) {
+        let id = match self.window {
+            Some(id) => {
+                self.draw.show_window(id, r);
+                id
+            }
+            None => match self.draw.new_window(r) {
+                Ok(id) => {
+                    self.draw.set_opacity(id, self.opacity);
+                    self.window = Some(id);
+                    id
+                }
+                Err(e) => {
+                    warn!("unable to create drag ghost window: {}", e);
+                    return;
+                }
+            },
+        };
+
+        let full = Region::new(0, 0, r.width(), r.height());
+        self.draw.fill_rect(id, &full, self.color);
+        self.draw.flush();
+    }
+
+    fn hide(&mut self) {
+        if let Some(id) = self.window {
+            self.draw.hide_window(id);
+            self.draw.flush();
+        }
+    }
+}
+
+impl<D: Draw> Hook for DragGhost<D> {
+    fn drag_preview(&mut self, _: &mut WindowManager, target: Option<&DropTarget>) {
+        match target {
+            Some(t) => self.show(&t.region),
+            None => self.hide(),
+        }
+    }
+}
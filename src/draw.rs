@@ -0,0 +1,198 @@
+/*!
+ * A minimal drawing API for rendering simple text based UI elements such as a status bar
+ *
+ * Drawing is carried out on a separate connection to the X server from the one used by the
+ * WindowManager so that it can be owned by user code (such as a Hook) without needing access
+ * to WindowManager internals. Windows created through a Draw implementation are marked as
+ * override-redirect so they will never be managed as clients.
+ */
+use crate::data_types::{Region, WinId};
+use xcb;
+
+/// An X11 drawing context that can be used to render text and solid blocks of color
+pub trait Draw {
+    /// Create and map a new window covering the given region for drawing on to
+    fn new_window(&mut self, r: &Region) -> Result<WinId, String>;
+
+    /// Move and resize a window to cover the given region, mapping it above all other windows
+    fn show_window(&mut self, id: WinId, r: &Region);
+
+    /// Unmap a window without destroying it so that it can be shown again later
+    fn hide_window(&mut self, id: WinId);
+
+    /**
+     * Set the opacity of a window, from 0.0 (transparent) to 1.0 (opaque). This is only a
+     * hint for the running compositor (if there is one) so windows may still be drawn opaque.
+     */
+    fn set_opacity(&mut self, id: WinId, opacity: f32);
+
+    /// Fill a rectangle (relative to the window origin) with a solid color
+    fn fill_rect(&mut self, id: WinId, r: &Region, color: u32);
+
+    /// Render `s` with its left edge at `x`, vertically centered within the given height
+    fn text(&mut self, id: WinId, x: u32, h: u32, s: &str, fg: u32, bg: u32);
+
+    /// The width in pixels that `s` will take up when rendered
+    fn text_width(&self, s: &str) -> u32;
+
+    /// Flush any pending drawing requests to the X server
+    fn flush(&self);
+}
+
+/**
+ * A Draw implementation using the X core font system via xcb.
+ *
+ * Only core X fonts are supported (see `xlsfonts` for the fonts available on your system)
+ * and text width calculations assume a monospaced font.
+ */
+pub struct XcbDraw {
+    conn: xcb::Connection,
+    root: WinId,
+    depth: u8,
+    visual: u32,
+    gc: u32,
+    opacity_atom: u32,
+    char_width: u32,
+    ascent: u32,
+    descent: u32,
+}
+
+impl XcbDraw {
+    /// Connect to the X server and load the named core font
+    pub fn new(font: &str) -> Result<XcbDraw, String> {
+        let (conn, _) = xcb::Connection::connect(None)
+            .map_err(|e| format!("unable to establish connection to X server: {}", e))?;
+
+        let (root, depth, visual) = match conn.get_setup().roots().next() {
+            None => return Err("unable to get handle for screen".into()),
+            Some(s) => (s.root(), s.root_depth(), s.root_visual()),
+        };
+
+        let fid = conn.generate_id();
+        xcb::open_font_checked(&conn, fid, font)
+            .request_check()
+            .map_err(|e| format!("unable to open font '{}': {}", font, e))?;
+
+        let (char_width, ascent, descent) = match xcb::query_font(&conn, fid).get_reply() {
+            Err(e) => return Err(format!("unable to query font '{}': {}", font, e)),
+            Ok(r) => (
+                r.max_bounds().character_width().max(0) as u32,
+                r.font_ascent().max(0) as u32,
+                r.font_descent().max(0) as u32,
+            ),
+        };
+
+        let gc = conn.generate_id();
+        // xcb docs: https://www.mankier.com/3/xcb_create_gc
+        xcb::create_gc(&conn, gc, root, &[(xcb::GC_FONT, fid)]);
+
+        let opacity_atom = xcb::intern_atom(&conn, false, "_NET_WM_WINDOW_OPACITY")
+            .get_reply()
+            .map_err(|e| format!("unable to intern opacity atom: {}", e))?
+            .atom();
+
+        Ok(XcbDraw {
+            conn,
+            root,
+            depth,
+            visual,
+            gc,
+            opacity_atom,
+            char_width,
+            ascent,
+            descent,
+        })
+    }
+}
+
+impl Draw for XcbDraw {
+    fn new_window(&mut self, r: &Region) -> Result<WinId, String> {
+        let (x, y, w, h) = r.values();
+        let id = self.conn.generate_id();
+
+        // xcb docs: https://www.mankier.com/3/xcb_create_window
+        xcb::create_window_checked(
+            &self.conn,                            // xcb connection to X11
+            self.depth,                            // new window's depth
+            id,                                    // ID to be used for referring to the window
+            self.root,                             // parent window
+            x as i16,                              // x-coordinate
+            y as i16,                              // y-coordinate
+            w as u16,                              // width
+            h as u16,                              // height
+            0,                                     // border width
+            xcb::WINDOW_CLASS_INPUT_OUTPUT as u16, // class
+            self.visual,                           // visual
+            &[(xcb::CW_OVERRIDE_REDIRECT, 1)],     // never manage our own windows
+        )
+        .request_check()
+        .map_err(|e| format!("unable to create window: {}", e))?;
+
+        xcb::map_window(&self.conn, id);
+        Ok(id)
+    }
+
+    fn show_window(&mut self, id: WinId, r: &Region) {
+        let (x, y, w, h) = r.values();
+        xcb::configure_window(
+            &self.conn,
+            id,
+            &[
+                (xcb::CONFIG_WINDOW_X as u16, x),
+                (xcb::CONFIG_WINDOW_Y as u16, y),
+                (xcb::CONFIG_WINDOW_WIDTH as u16, w),
+                (xcb::CONFIG_WINDOW_HEIGHT as u16, h),
+                (xcb::CONFIG_WINDOW_STACK_MODE as u16, xcb::STACK_MODE_ABOVE),
+            ],
+        );
+        xcb::map_window(&self.conn, id);
+    }
+
+    fn hide_window(&mut self, id: WinId) {
+        xcb::unmap_window(&self.conn, id);
+    }
+
+    fn set_opacity(&mut self, id: WinId, opacity: f32) {
+        let value = (opacity.clamp(0.0, 1.0) as f64 * u32::MAX as f64) as u32;
+        xcb::change_property(
+            &self.conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            id,
+            self.opacity_atom,
+            xcb::xproto::ATOM_CARDINAL,
+            32,
+            &[value],
+        );
+    }
+
+    fn fill_rect(&mut self, id: WinId, r: &Region, color: u32) {
+        let (x, y, w, h) = r.values();
+        xcb::change_gc(&self.conn, self.gc, &[(xcb::GC_FOREGROUND, color)]);
+        xcb::poly_fill_rectangle(
+            &self.conn,
+            id,
+            self.gc,
+            &[xcb::Rectangle::new(x as i16, y as i16, w as u16, h as u16)],
+        );
+    }
+
+    fn text(&mut self, id: WinId, x: u32, h: u32, s: &str, fg: u32, bg: u32) {
+        // image_text_8 is limited to 255 bytes and utf8 chars are up to 4 bytes each
+        let s: String = s.chars().take(63).collect();
+        let baseline = (h.saturating_sub(self.ascent + self.descent)) / 2 + self.ascent;
+        xcb::change_gc(
+            &self.conn,
+            self.gc,
+            &[(xcb::GC_FOREGROUND, fg), (xcb::GC_BACKGROUND, bg)],
+        );
+        xcb::image_text_8(&self.conn, id, self.gc, x as i16, baseline as i16, &s);
+    }
+
+    fn text_width(&self, s: &str) -> u32 {
+        s.chars().count() as u32 * self.char_width
+    }
+
+    fn flush(&self) {
+        self.conn.flush();
+    }
+}
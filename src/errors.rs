@@ -0,0 +1,93 @@
+/*!
+ * Errors that can be raised while managing windows
+ *
+ * Anything that can go wrong in response to an event from the X server (a client that has
+ * already been destroyed, a property that was never set...) is reported as a PenroseError
+ * rather than a panic so that a single misbehaving client is unable to take down the whole
+ * session. Errors raised while handling events are logged and passed to the error_handler
+ * set on the Config, if there is one.
+ */
+use crate::data_types::WinId;
+use crate::manager::WindowManager;
+use std::fmt;
+
+/// A Result with a PenroseError as the error type
+pub type Result<T> = std::result::Result<T, PenroseError>;
+
+/// Called with any errors raised while handling events
+pub type ErrorHandler = fn(&mut WindowManager, &PenroseError);
+
+/// Everything that can go wrong inside of penrose
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PenroseError {
+    /// A workspace was created without any layouts
+    NoLayouts(String),
+    /// A client was referenced that is not currently being managed
+    UnknownClient(WinId),
+    /// A workspace was arranged when it is not displayed on any screen
+    WorkspaceNotVisible(usize),
+    /// Another window manager is already running and we were not asked to replace it
+    OtherWmRunning,
+    /// A property was not set (or was empty) on a window
+    MissingProperty(WinId, String),
+    /// A request to the X server failed
+    X(String),
+    /// A config file could not be read or contained invalid values
+    InvalidConfig(String),
+    /// An action was requested by name that does not exist
+    UnknownAction(String),
+    /// A workspace was referenced that does not exist
+    UnknownWorkspace(String),
+    /// A workspace could not be created, deleted or reorganised as requested
+    InvalidWorkspaceChange(String),
+    /// An IPC request was malformed or had invalid arguments
+    InvalidRequest(String),
+    /// State could not be saved to (or loaded from) a StateStore or journal
+    Store(String),
+}
+
+impl fmt::Display for PenroseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PenroseError::NoLayouts(name) => {
+                write!(f, "{}: require at least one layout function", name)
+            }
+            PenroseError::UnknownClient(id) => write!(f, "{} is not a known client", id),
+            PenroseError::WorkspaceNotVisible(wix) => {
+                write!(f, "workspace {} is not on any screen", wix)
+            }
+            PenroseError::OtherWmRunning => {
+                write!(f, "another window manager is already running")
+            }
+            PenroseError::MissingProperty(id, name) => {
+                write!(f, "property '{}' was empty for id: {}", name, id)
+            }
+            PenroseError::X(msg) => write!(f, "{}", msg),
+            PenroseError::InvalidConfig(msg) => write!(f, "invalid config: {}", msg),
+            PenroseError::UnknownAction(name) => write!(f, "unknown action: {}", name),
+            PenroseError::UnknownWorkspace(ws) => write!(f, "unknown workspace: {}", ws),
+            PenroseError::InvalidWorkspaceChange(msg) => write!(f, "{}", msg),
+            PenroseError::InvalidRequest(msg) => write!(f, "{}", msg),
+            PenroseError::Store(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PenroseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_display_as_log_friendly_messages() {
+        assert_eq!(
+            PenroseError::NoLayouts("1".into()).to_string(),
+            "1: require at least one layout function"
+        );
+        assert_eq!(
+            PenroseError::MissingProperty(42, "WM_HINTS".into()).to_string(),
+            "property 'WM_HINTS' was empty for id: 42"
+        );
+    }
+}
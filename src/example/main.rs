@@ -10,12 +10,65 @@
 #[macro_use]
 extern crate penrose;
 
+use penrose::bar::StatusBar;
+use penrose::data_types::{
+    Axis, BorderStyle, ClassFilter, FireAndForget, InsertPoint, Opacity, ScreenChangePolicy,
+    ScreenDirection, WorkspaceSwitch,
+};
+use penrose::drag::DragGhost;
+use penrose::draw::XcbDraw;
+use penrose::expose::Expose;
 use penrose::helpers::spawn;
-use penrose::layout::{bottom_stack, paper, side_stack};
-use penrose::{ColorScheme, Config, Layout, LayoutConf, WindowManager, XcbConnection};
-use simplelog;
+use penrose::hooks::NotifySend;
+use penrose::layout::{
+    bottom_stack, centered_master, grid, paper, side_stack, LayoutMessage, LayoutTransform,
+};
+use penrose::palette::{launcher, CommandPalette, PaletteSource};
+use penrose::placement::PlacementGrid;
+use penrose::rules::Rule;
+use penrose::scratchpad::Scratchpad;
+use penrose::store::FileStore;
+use penrose::{ColorScheme, Config, Hook, Layout, LayoutConf, WindowManager, XcbConnection};
 use std::env;
 use std::process::Command;
+use std::time::Duration;
+
+// Set the root X window name to be the active layout symbol so it can be picked up by polybar
+struct ActiveLayoutAsRootName {}
+
+impl Hook for ActiveLayoutAsRootName {
+    fn layout_change(&mut self, wm: &mut WindowManager, _: usize, _: usize) {
+        wm.set_root_window_name(&wm.current_layout_symbol());
+    }
+
+    fn workspace_change(&mut self, wm: &mut WindowManager, _: usize, _: usize) {
+        wm.set_root_window_name(&wm.current_layout_symbol());
+    }
+}
+
+// Move focus based on where clients are on screen rather than their position in the stack
+fn focus_towards(direction: ScreenDirection) -> FireAndForget {
+    Box::new(move |wm: &mut WindowManager| {
+        wm.focus_in_direction(direction);
+        None
+    })
+}
+
+// Move the focused client to where the client next to it on screen currently is
+fn swap_towards(direction: ScreenDirection) -> FireAndForget {
+    Box::new(move |wm: &mut WindowManager| {
+        wm.swap_in_direction(direction);
+        None
+    })
+}
+
+// Flip the active layout between its normal and mirrored orientation
+fn toggle_mirrored() -> FireAndForget {
+    Box::new(|wm: &mut WindowManager| {
+        wm.send_layout_message(&LayoutMessage::ToggleTransform(LayoutTransform::Mirrored));
+        None
+    })
+}
 
 fn main() {
     // Turn on debug logging for non-release builds
@@ -45,15 +98,35 @@ fn main() {
         floating: false,
         gapless: true,
         follow_focus: true,
+        ..LayoutConf::default()
     };
     let n_main = 1;
     let ratio = 0.6;
     let layouts = vec![
         Layout::new("[side]", LayoutConf::default(), side_stack, n_main, ratio),
+        Layout::new("[edis]", LayoutConf::default(), side_stack, n_main, ratio)
+            .reflected(Axis::Horizontal),
         Layout::new("[botm]", LayoutConf::default(), bottom_stack, n_main, ratio),
         Layout::new("[papr]", follow_focus_conf, paper, n_main, ratio),
+        Layout::new("[grid]", LayoutConf::default(), grid, n_main, ratio),
+        Layout::new(
+            "[cent]",
+            LayoutConf::default(),
+            centered_master,
+            n_main,
+            ratio,
+        ),
+        Layout::monocle("[ {} ]"),
         Layout::floating("[----]"),
     ];
+    // the browser workspace is mostly used full screen and chat works best as a grid
+    let workspace_layouts = map! {
+        "2" => vec![
+            Layout::monocle("[ {} ]"),
+            Layout::new("[side]", LayoutConf::default(), side_stack, n_main, ratio),
+        ],
+        "8" => vec![Layout::new("[grid]", LayoutConf::default(), grid, n_main, ratio)],
+    };
 
     // I run penrose wrapped in a shell script that redirects the log output to a file and allows
     // me to restart without killing the session. "real" exit is done via 'pkill x'
@@ -64,24 +137,23 @@ fn main() {
         ))
         .output()
         .unwrap();
-        match String::from_utf8(choice.stdout).unwrap().as_str() {
-            "restart-wm\n" => wm.exit(),
-            _ => (), // 'no', user exited out or something went wrong
+        if String::from_utf8(choice.stdout).unwrap().as_str() == "restart-wm\n" {
+            wm.exit(); // otherwise 'no', user exited out or something went wrong
         }
         None
     });
 
-    // Set the root X window name to be the active layout symbol so it can be picked up by polybar
-    let active_layout_as_root_name = |wm: &mut WindowManager| {
-        wm.set_root_window_name(wm.current_layout_symbol());
-    };
-
     let browser = "qutebrowser";
     let terminal = "st";
 
     let key_bindings = gen_keybindings! {
         // Program launch
         "M-semicolon" => run_external!("rofi-apps"),
+        "M-p" => run_internal!(open_command_palette),
+        "M-S-p" => launcher(PaletteSource::Programs),
+        "M-S-slash" => launcher(PaletteSource::Windows),
+        "M-e" => run_internal!(open_expose, false),
+        "M-S-e" => run_internal!(open_expose, true),
         "M-b" => run_external!(browser),
         "M-Return" => run_external!(terminal),
 
@@ -89,69 +161,207 @@ fn main() {
         "M-A-s" => run_external!("screenshot"),
         "M-A-k" => run_external!("toggle-kb-for-tada"),
         "M-A-l" => run_external!("lock-screen"),
+        "M-A-b" => run_internal!(displays_off),
         "M-A-m" => run_external!("xrandr --output HDMI-1 --auto --right-of eDP-1 "),
 
         // client management
         "M-j" => run_internal!(next_client),
         "M-k" => run_internal!(previous_client),
+        "M-Left" => focus_towards(ScreenDirection::Left),
+        "M-Right" => focus_towards(ScreenDirection::Right),
+        "M-Up" => focus_towards(ScreenDirection::Up),
+        "M-Down" => focus_towards(ScreenDirection::Down),
+        "M-S-Left" => swap_towards(ScreenDirection::Left),
+        "M-S-Right" => swap_towards(ScreenDirection::Right),
+        "M-S-Up" => swap_towards(ScreenDirection::Up),
+        "M-S-Down" => swap_towards(ScreenDirection::Down),
         "M-S-j" => run_internal!(drag_client_forward),
         "M-S-k" => run_internal!(drag_client_backward),
+        "M-C-j" => run_internal!(rotate_clients_forward),
+        "M-C-k" => run_internal!(rotate_clients_backward),
+        "M-S-Return" => run_internal!(swap_with_main),
         "M-S-q" => run_internal!(kill_client),
+        "M-S-f" => run_internal!(toggle_fullscreen),
+        "M-g" => run_internal!(open_placement_grid),
+        "M-S-s" => run_internal!(toggle_sticky),
+        "M-S-o" => run_internal!(toggle_follow),
+        "M-n" => run_internal!(minimize_client),
+        "M-S-n" => run_internal!(restore_minimized),
+        "M-C-n" => run_internal!(cycle_minimized),
+        "M-slash" => Box::new(|wm: &mut WindowManager| wm.toggle_scratchpad("term")),
 
         // workspace management
         "M-Tab" => run_internal!(toggle_workspace),
+        "M-S-Tab" => run_internal!(focus_previous_client),
+        "M-u" => run_internal!(focus_urgent),
+        "M-S-u" => run_internal!(toggle_dnd),
+        "M-period" => run_internal!(next_screen),
+        "M-comma" => run_internal!(previous_screen),
+        "M-S-period" => run_internal!(client_to_next_screen),
+        "M-S-comma" => run_internal!(client_to_previous_screen),
+        "M-C-period" => run_internal!(swap_screens),
+        "M-C-r" => run_internal!(toggle_screen_rotation),
+        // M-w followed by a workspace number sends the focused client there (and follows it
+        // there with shift held)
+        "M-w" => gen_submap! {
+            "Tab" => run_internal!(toggle_workspace);
+            forall_workspaces: workspaces => {
+                "{}" => client_to_workspace,
+                "S-{}" => client_to_workspace_and_follow,
+            }
+        },
 
         // Layout & window management
-        "M-grave" => Box::new(move |wm| {
-            wm.next_layout();
-            active_layout_as_root_name(wm);
-            None
-        }),
-        "M-S-grave" => Box::new(move |wm| {
-            wm.previous_layout();
-            active_layout_as_root_name(wm);
-            None
-        }),
+        "M-grave" => run_internal!(next_layout),
+        "M-S-grave" => run_internal!(previous_layout),
+        "M-C-m" => run_internal!(set_layout_by_symbol, "[ {} ]"),
+        "M-C-f" => run_internal!(set_layout_by_symbol, "[----]"),
         "M-A-Up" => run_internal!(inc_main),
         "M-A-Down" => run_internal!(dec_main),
         "M-A-Right" => run_internal!(inc_ratio),
         "M-A-Left" => run_internal!(dec_ratio),
+        "M-A-equal" => run_internal!(inc_gaps),
+        "M-A-minus" => run_internal!(dec_gaps),
+        "M-A-g" => run_internal!(toggle_gaps),
+        "M-A-t" => toggle_mirrored(),
+        "M-A-r" => run_internal!(restart),
         "M-A-C-Escape" => run_internal!(exit),
         "M-A-Escape" => power_menu;
 
         forall_workspaces: workspaces => {
             "M-{}" => focus_workspace,
             "M-S-{}" => client_to_workspace,
+            "M-C-{}" => toggle_view,
+            "M-C-S-{}" => toggle_tag,
+        }
+    };
+
+    let placement_grid = match XcbDraw::new("fixed") {
+        Ok(draw) => Some(PlacementGrid::new(draw, 4, 3, color_scheme)),
+        Err(e) => {
+            eprintln!("unable to create placement grid: {}", e);
+            None
         }
     };
 
     let conn = XcbConnection::new();
 
+    let bar_height = 18;
+    let mut hooks: Vec<Box<dyn Hook>> = vec![
+        Box::new(ActiveLayoutAsRootName {}),
+        Box::new(NotifySend::new(Some(1500))),
+    ];
+    match XcbDraw::new("fixed") {
+        Ok(draw) => hooks.push(Box::new(StatusBar::dwm_style(
+            draw,
+            bar_height,
+            true,
+            color_scheme,
+        ))),
+        Err(e) => eprintln!("unable to create status bar: {}", e),
+    }
+    match XcbDraw::new("fixed") {
+        Ok(draw) => hooks.push(Box::new(DragGhost::new(draw, color_scheme.highlight, 0.3))),
+        Err(e) => eprintln!("unable to create drag preview: {}", e),
+    }
+    let expose = match XcbDraw::new("fixed") {
+        Ok(draw) => Some(Expose::new(draw, bar_height * 2, color_scheme)),
+        Err(e) => {
+            eprintln!("unable to create expose overlay: {}", e);
+            None
+        }
+    };
+    let command_palette = match XcbDraw::new("fixed") {
+        Ok(draw) => Some(CommandPalette::new(draw, bar_height, color_scheme)),
+        Err(e) => {
+            eprintln!("unable to create command palette: {}", e);
+            None
+        }
+    };
+
     let mut wm = WindowManager::init(
         Config {
-            workspaces: workspaces,
-            fonts: fonts,
-            floating_classes: floating_classes,
-            layouts: layouts,
-            color_scheme: color_scheme,
+            workspaces,
+            fonts,
+            floating_classes,
+            class_filter: ClassFilter::Deny(&[]),
+            layouts,
+            color_scheme,
             border_px: 2,
             gap_px: 5,
             main_ratio_step: 0.05,
+            workspace_cleanup: Some(Duration::from_secs(5)),
+            placement_grid,
             systray_spacing_px: 2,
             show_systray: true,
             show_bar: true,
             top_bar: true,
-            bar_height: 18,
+            bar_height,
             respect_resize_hints: true,
+            hooks,
+            scratchpads: vec![Scratchpad::new("term", "st -c scratch", "scratch")],
+            client_rules: vec![
+                Rule {
+                    class: Some("Gimp"),
+                    floating: Some(true),
+                    ..Rule::default()
+                },
+                Rule {
+                    class: Some("mpv"),
+                    opacity: Some(Opacity {
+                        focused: 1.0,
+                        unfocused: 1.0,
+                    }),
+                    ..Rule::default()
+                },
+            ],
+            insert_point: InsertPoint::First,
+            focus_new_clients: true,
+            state_store: Some(Box::new(
+                FileStore::new("/tmp/penrose.state")
+                    .with_journal("/tmp/penrose.journal")
+                    .with_layout_settings("/tmp/penrose.layouts"),
+            )),
+            ipc_socket_path: Some("/tmp/penrose.sock"),
+            remote_border_color: Some(0xd79921), // #d79921
+            border_style: Some(BorderStyle {
+                alternate: Some(color_scheme.fg_2),
+                ..BorderStyle::from_color_scheme(2, &color_scheme)
+            }),
+            callback_budget: Some(Duration::from_millis(50)),
+            notify_slow_callbacks: false,
+            command_palette,
+            expose,
+            rotated_screens: &[],
+            replace_existing_wm: env::args().any(|a| a == "--replace"),
+            startup_hook: None,
+            error_handler: None,
+            screen_change_policy: ScreenChangePolicy::MoveToPrimary,
+            warp_pointer: true,
+            terminal_classes: &["st-256color"],
+            config_file: None,
+            dnd_workspace: None,
+            workspace_switch: WorkspaceSwitch::MapFirst,
+            opacity: Some(Opacity {
+                focused: 1.0,
+                unfocused: 0.85,
+            }),
+            workspace_layouts,
+            spawn_rules: vec![],
         },
         &conn,
-    );
+    )
+    .unwrap_or_else(|e| panic!("unable to start penrose: {}", e));
 
     spawn(format!(
         "{}/bin/scripts/penrose-startup.sh",
         env::var("HOME").unwrap()
     ));
 
-    active_layout_as_root_name(&mut wm);
+    // workspace 9 is for watching things so keep the bar out of the way
+    wm.set_bar_hidden(8, true);
+    wm.register_action("screenshot", run_external!("screenshot"));
+    wm.register_action("lock_screen", run_external!("lock-screen"));
+    wm.set_root_window_name(&wm.current_layout_symbol());
     wm.grab_keys_and_run(key_bindings);
 }
@@ -0,0 +1,300 @@
+/*!
+ * A keyboard driven overview for picking a client to focus
+ *
+ * When opened (see `WindowManager::open_expose`) the clients on the active workspace (or on
+ * every workspace) are temporarily arranged in a grid on the focused screen with a letter drawn
+ * over the middle of each one. While it is open the keyboard is grabbed: pressing one of the
+ * letters focuses that client (switching to its workspace if needed) and Escape closes the
+ * overview without changing focus. Either way, the clients are put back where they were once
+ * the overview is closed.
+ */
+use crate::data_types::{CodeMap, ColorScheme, KeyCode, Region, ResizeAction, WinId};
+use crate::draw::Draw;
+use crate::helpers::keycodes_from_xmodmap;
+use crate::placement::LABELS;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// What the WindowManager should do after passing a key press to an open overview
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExposeInput {
+    /// Keep the overview open
+    Continue,
+    /// Close the overview without changing focus
+    Cancel,
+    /// Close the overview and focus the given client
+    Select(WinId),
+}
+
+// The keys that the overview responds to: everything else is ignored while it is open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExposeKey {
+    Label(char),
+    Escape,
+}
+
+/// An overlay labelling clients so that one can be picked with a single key press
+pub struct Expose {
+    draw: Box<dyn Draw>,
+    label_size: u32,
+    colors: ColorScheme,
+    keys: Option<HashMap<u8, ExposeKey>>,
+    // one window per label
+    windows: Vec<WinId>,
+    targets: Vec<(char, WinId)>,
+    open: bool,
+}
+
+impl fmt::Debug for Expose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expose")
+            .field("label_size", &self.label_size)
+            .field("targets", &self.targets)
+            .field("open", &self.open)
+            .finish()
+    }
+}
+
+impl Expose {
+    /**
+     * Create a new Expose overlay that draws each label in a square `label_size` pixels wide.
+     * Key names are looked up using xmodmap the first time that the overlay is opened.
+     */
+    pub fn new(draw: impl Draw + 'static, label_size: u32, colors: ColorScheme) -> Self {
+        Expose {
+            draw: Box::new(draw),
+            label_size,
+            colors,
+            keys: None,
+            windows: vec![],
+            targets: vec![],
+            open: false,
+        }
+    }
+
+    /// Use an existing CodeMap (see helpers::keycodes_from_xmodmap) rather than running xmodmap
+    pub fn with_key_codes(mut self, codes: CodeMap) -> Self {
+        self.set_key_codes(&codes);
+        self
+    }
+
+    fn set_key_codes(&mut self, codes: &CodeMap) {
+        let keys = codes
+            .iter()
+            .filter_map(|(name, code)| match name.as_ref() {
+                "Escape" => Some((*code, ExposeKey::Escape)),
+                _ => {
+                    let mut chars = name.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if LABELS.contains(c) => Some((*code, ExposeKey::Label(c))),
+                        _ => None,
+                    }
+                }
+            })
+            .collect();
+        self.keys = Some(keys);
+    }
+
+    /// Whether or not the overview is currently shown and accepting input
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// The label for each client in the overview, in the order that they were given
+    pub fn targets(&self) -> &[(char, WinId)] {
+        &self.targets
+    }
+
+    /**
+     * Label the clients that have been arranged at the given positions. Only the first 26
+     * clients can be given a label: any others are shown but can not be selected.
+     */
+    pub(crate) fn open(&mut self, positions: &[ResizeAction]) {
+        if self.keys.is_none() {
+            self.set_key_codes(&keycodes_from_xmodmap());
+        }
+
+        let s = self.label_size;
+        let labelled: Vec<(char, WinId, Region)> = LABELS
+            .chars()
+            .zip(positions.iter())
+            .map(|(c, (id, r))| (c, *id, Region::new(0, 0, s, s).centered_in(r)))
+            .collect();
+
+        for (i, (c, _, r)) in labelled.iter().enumerate() {
+            if let Some(id) = self.label_window(i, r) {
+                let text = c.to_string();
+                let x = s.saturating_sub(self.draw.text_width(&text)) / 2;
+                self.draw
+                    .fill_rect(id, &Region::new(0, 0, s, s), self.colors.highlight);
+                self.draw
+                    .text(id, x, s, &text, self.colors.bg, self.colors.highlight);
+            }
+        }
+        for id in self.windows.iter().skip(labelled.len()) {
+            self.draw.hide_window(*id);
+        }
+
+        self.targets = labelled.into_iter().map(|(c, id, _)| (c, id)).collect();
+        self.open = true;
+        self.draw.flush();
+    }
+
+    /// Hide the overview
+    pub(crate) fn close(&mut self) {
+        self.open = false;
+        self.targets.clear();
+        for id in self.windows.iter() {
+            self.draw.hide_window(*id);
+        }
+        self.draw.flush();
+    }
+
+    /// Select a client (or close the overview) in response to a key press
+    pub(crate) fn handle_key(&mut self, key: KeyCode) -> ExposeInput {
+        let key = self.keys.as_ref().and_then(|keys| keys.get(&key.code));
+        match key {
+            Some(ExposeKey::Escape) => ExposeInput::Cancel,
+            Some(ExposeKey::Label(c)) => match self.targets.iter().find(|(l, _)| l == c) {
+                Some((_, id)) => ExposeInput::Select(*id),
+                None => ExposeInput::Continue,
+            },
+            None => ExposeInput::Continue, // modifiers and unsupported keys
+        }
+    }
+
+    // Make sure that there is a window for the given label, returning its id
+    fn label_window(&mut self, index: usize, r: &Region) -> Option<WinId> {
+        if let Some(id) = self.windows.get(index) {
+            self.draw.show_window(*id, r);
+            return Some(*id);
+        }
+
+        match self.draw.new_window(r) {
+            Ok(id) => {
+                self.windows.push(id);
+                Some(id)
+            }
+            Err(e) => {
+                warn!("unable to create expose label window: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct MockDraw {
+        text: Rc<RefCell<HashMap<WinId, String>>>,
+        next_id: WinId,
+    }
+
+    impl Draw for MockDraw {
+        fn new_window(&mut self, _: &Region) -> Result<WinId, String> {
+            self.next_id += 1;
+            Ok(self.next_id)
+        }
+        fn show_window(&mut self, _: WinId, _: &Region) {}
+        fn hide_window(&mut self, id: WinId) {
+            self.text.borrow_mut().remove(&id);
+        }
+        fn set_opacity(&mut self, _: WinId, _: f32) {}
+        fn fill_rect(&mut self, _: WinId, _: &Region, _: u32) {}
+        fn text(&mut self, id: WinId, _: u32, _: u32, s: &str, _: u32, _: u32) {
+            self.text.borrow_mut().insert(id, s.to_string());
+        }
+        fn text_width(&self, s: &str) -> u32 {
+            s.len() as u32
+        }
+        fn flush(&self) {}
+    }
+
+    fn codes() -> CodeMap {
+        let mut codes: CodeMap = "abcdefghijklmnopqrstuvwxyz"
+            .chars()
+            .enumerate()
+            .map(|(i, c)| (c.to_string(), 30 + i as u8))
+            .collect();
+        codes.insert("Escape".into(), 3);
+        codes
+    }
+
+    fn key(name: &str) -> KeyCode {
+        KeyCode {
+            mask: 0,
+            code: codes()[name],
+        }
+    }
+
+    fn test_expose() -> (Expose, Rc<RefCell<HashMap<WinId, String>>>) {
+        let text = Rc::new(RefCell::new(HashMap::new()));
+        let draw = MockDraw {
+            text: Rc::clone(&text),
+            next_id: 0,
+        };
+        let colors = ColorScheme {
+            bg: 0,
+            fg_1: 1,
+            fg_2: 2,
+            fg_3: 3,
+            highlight: 4,
+            urgent: 5,
+        };
+        (Expose::new(draw, 20, colors).with_key_codes(codes()), text)
+    }
+
+    fn positions(n: u32) -> Vec<ResizeAction> {
+        (0..n)
+            .map(|i| (10 * (i + 1), Region::new(i * 100, 0, 100, 100)))
+            .collect()
+    }
+
+    #[test]
+    fn clients_are_labelled_from_the_home_row() {
+        let (mut e, text) = test_expose();
+        e.open(&positions(3));
+
+        assert!(e.is_open());
+        assert_eq!(e.targets(), &[('a', 10), ('s', 20), ('d', 30)]);
+        let mut drawn: Vec<String> = text.borrow().values().cloned().collect();
+        drawn.sort();
+        assert_eq!(drawn, vec!["a", "d", "s"]);
+    }
+
+    #[test]
+    fn only_the_first_26_clients_are_labelled() {
+        let (mut e, _) = test_expose();
+        e.open(&positions(30));
+
+        assert_eq!(e.targets().len(), 26);
+        assert_eq!(e.targets()[25], ('m', 260));
+    }
+
+    #[test]
+    fn labels_select_clients_and_escape_cancels() {
+        let (mut e, _) = test_expose();
+        e.open(&positions(2));
+
+        assert_eq!(e.handle_key(key("s")), ExposeInput::Select(20));
+        assert_eq!(e.handle_key(key("f")), ExposeInput::Continue);
+        assert_eq!(e.handle_key(key("Escape")), ExposeInput::Cancel);
+    }
+
+    #[test]
+    fn closing_hides_all_labels() {
+        let (mut e, text) = test_expose();
+        e.open(&positions(2));
+        e.close();
+
+        assert!(!e.is_open());
+        assert!(e.targets().is_empty());
+        assert!(text.borrow().is_empty());
+    }
+}
@@ -1,5 +1,8 @@
 //! Utility functions for use in other parts of penrose
 use crate::data_types::{CodeMap, KeyCode};
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
 use std::process::{Child, Command, Stdio};
 use xcb;
 
@@ -11,19 +14,49 @@ use xcb;
  */
 pub fn spawn<S: Into<String>>(cmd: S) -> Option<Child> {
     let s = cmd.into();
-    let parts: Vec<&str> = s.split_whitespace().collect();
-    let result = if parts.len() > 1 {
+    let parts = command_parts(&s)?;
+    run_command(Command::new(parts[0]).args(&parts[1..]))
+}
+
+/**
+ * Run an external command with DESKTOP_STARTUP_ID set to `startup_id`
+ *
+ * Programs following the startup notification spec copy this on to the _NET_STARTUP_ID
+ * property of their windows, allowing them to be matched up with the command that was run.
+ * As with spawn, stdout and stderr are redirected to /dev/null.
+ */
+pub fn spawn_with_startup_id<S: Into<String>>(cmd: S, startup_id: &str) -> Option<Child> {
+    let s = cmd.into();
+    let parts = command_parts(&s)?;
+    run_command(
         Command::new(parts[0])
             .args(&parts[1..])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-    } else {
-        Command::new(parts[0])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-    };
+            .env("DESKTOP_STARTUP_ID", startup_id),
+    )
+}
+
+/**
+ * Run an external command with the given arguments
+ *
+ * Unlike spawn, the arguments are passed through as they are rather than being split on
+ * whitespace so they may contain spaces. stdout and stderr are redirected to /dev/null.
+ */
+pub fn spawn_with_args<S: Into<String>>(cmd: S, args: &[&str]) -> Option<Child> {
+    run_command(Command::new(cmd.into()).args(args))
+}
+
+// Split a command on whitespace, warning (rather than panicking) if there is nothing to run
+fn command_parts(cmd: &str) -> Option<Vec<&str>> {
+    let parts: Vec<&str> = cmd.split_whitespace().collect();
+    if parts.is_empty() {
+        warn!("unable to spawn an empty command");
+        return None;
+    }
+    Some(parts)
+}
+
+fn run_command(cmd: &mut Command) -> Option<Child> {
+    let result = cmd.stdout(Stdio::null()).stderr(Stdio::null()).spawn();
 
     match result {
         Err(e) => {
@@ -34,6 +67,79 @@ pub fn spawn<S: Into<String>>(cmd: S) -> Option<Child> {
     }
 }
 
+/// The parent of the process `pid`, read from /proc/<pid>/stat
+pub fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // the command name is in parens and can contain spaces so skip past it before splitting
+    let fields = &stat[stat.rfind(')')? + 1..];
+    fields.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Walk up the process tree from `pid` using `parent` to see if we reach `ancestor`
+pub fn is_descendant_of(pid: u32, ancestor: u32, parent: fn(u32) -> Option<u32>) -> bool {
+    let mut current = pid;
+    while let Some(p) = parent(current) {
+        if p == ancestor {
+            return true;
+        }
+        if p <= 1 {
+            return false;
+        }
+        current = p;
+    }
+    false
+}
+
+/**
+ * The bit representing the workspace at `index` in client tag masks and screen view masks.
+ * Only the first 64 workspaces can be used as tags: this is 0 for anything after that.
+ */
+pub fn tag_bit(index: usize) -> u64 {
+    if index < 64 {
+        1 << index
+    } else {
+        0
+    }
+}
+
+/// Remove the bit for `index` from a tag mask, shifting the bits above it down by one
+pub fn remove_tag_bit(mask: u64, index: usize) -> u64 {
+    let below = tag_bit(index).wrapping_sub(1);
+    let above = if index < 63 { mask >> (index + 1) } else { 0 };
+    (mask & below) | (above << index.min(63))
+}
+
+/// The hostname of the machine penrose is running on, if it can be determined
+pub fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    let res = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if res != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec()).ok()
+}
+
+/// The names of the executable files in each directory on $PATH, sorted and without duplicates
+pub fn programs_on_path() -> Vec<String> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    let mut programs: Vec<String> = env::split_paths(&path)
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .metadata()
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        })
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    programs.sort();
+    programs.dedup();
+
+    programs
+}
+
 /**
  * Run the xmodmap command to dump the system keymap table.
  *
@@ -81,17 +187,17 @@ where
     S: Into<String>,
 {
     let s = pattern.into();
-    let mut parts: Vec<&str> = s.split("-").collect();
+    let mut parts: Vec<&str> = s.split('-').collect();
     match known_codes.get(parts.remove(parts.len() - 1)) {
         Some(code) => {
             let mask = parts
                 .iter()
-                .map(|s| match s {
-                    &"A" => xcb::MOD_MASK_1,
-                    &"M" => xcb::MOD_MASK_4,
-                    &"S" => xcb::MOD_MASK_SHIFT,
-                    &"C" => xcb::MOD_MASK_CONTROL,
-                    &_ => panic!("invalid key binding prefix: {}", s),
+                .map(|s| match *s {
+                    "A" => xcb::MOD_MASK_1,
+                    "M" => xcb::MOD_MASK_4,
+                    "S" => xcb::MOD_MASK_SHIFT,
+                    "C" => xcb::MOD_MASK_CONTROL,
+                    _ => panic!("invalid key binding prefix: {}", s),
                 })
                 .fold(0, |acc, v| acc | v);
 
@@ -104,3 +210,30 @@ where
         None => None,
     }
 }
+
+/**
+ * The inverse of parse_key_binding: render a KeyCode in the '<MOD>-<key name>' form used by
+ * gen_keybindings. Where several names share a key code (e.g. 'a' and 'A') the unshifted,
+ * shortest name is used. Returns None if the code is not in known_codes.
+ */
+pub fn key_binding_label(key: &KeyCode, known_codes: &CodeMap) -> Option<String> {
+    let name = known_codes
+        .iter()
+        .filter(|(_, code)| **code == key.code)
+        .map(|(name, _)| name)
+        .min_by_key(|name| (name.chars().any(|c| c.is_uppercase()), name.len(), *name))?;
+
+    let mut parts: Vec<&str> = [
+        (xcb::MOD_MASK_4, "M"),
+        (xcb::MOD_MASK_1, "A"),
+        (xcb::MOD_MASK_CONTROL, "C"),
+        (xcb::MOD_MASK_SHIFT, "S"),
+    ]
+    .iter()
+    .filter(|(mask, _)| key.mask & *mask as u16 != 0)
+    .map(|(_, prefix)| *prefix)
+    .collect();
+    parts.push(name);
+
+    Some(parts.join("-"))
+}
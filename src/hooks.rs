@@ -0,0 +1,162 @@
+/*!
+ * Hook for adding additional functionality around standard WindowManager actions
+ *
+ * Hooks are registered on the Config passed to WindowManager::init and are run in
+ * the order they are registered whenever the corresponding action takes place.
+ * All methods have a default no-op implementation so a Hook only needs to define
+ * the methods it is interested in.
+ *
+ * Hooks are given mutable access to the WindowManager so they are free to call
+ * any of its public methods. While a hook is running, the registered hooks are
+ * not available to the WindowManager so any actions triggered from inside of a
+ * hook will not themselves trigger further hooks.
+ */
+use crate::client::Client;
+use crate::data_types::{Notification, NotificationLevel, Region, WinId};
+use crate::drag::DropTarget;
+use crate::helpers::spawn_with_args;
+use crate::manager::WindowManager;
+use std::fmt;
+use std::process::Child;
+
+/// User defined hooks to be run by the WindowManager
+pub type Hooks = Vec<Box<dyn Hook>>;
+
+/**
+ * Handlers for WindowManager actions and state changes.
+ *
+ * Each method is called after the WindowManager has finished updating its own
+ * internal state so queries made against the WindowManager will reflect the
+ * change that triggered the hook. The only exception to this is `new_client`
+ * which is called before the client is added to a workspace so that hooks are
+ * able to modify it.
+ */
+pub trait Hook {
+    /// A name for this hook to use in log messages. Defaults to the name of the type.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /**
+     * Called when a new Client is about to be managed by the WindowManager. The client
+     * has not yet been added to its workspace so any changes made here (such as
+     * moving it to a different workspace) will be respected.
+     */
+    fn new_client(&mut self, _wm: &mut WindowManager, _c: &mut Client) {}
+
+    /// Called after a Client has been removed from the WindowManager
+    fn remove_client(&mut self, _wm: &mut WindowManager, _id: WinId) {}
+
+    /// Called after the layout for a workspace has been changed
+    fn layout_change(&mut self, _wm: &mut WindowManager, _ws_index: usize, _screen_index: usize) {}
+
+    /// Called after the active workspace on the focused screen has changed
+    fn workspace_change(&mut self, _wm: &mut WindowManager, _prev: usize, _new: usize) {}
+
+    /**
+     * Called after workspaces have been created, deleted or renamed. `names` are the names
+     * of all workspaces in order and `active` is the index of the active workspace.
+     */
+    fn workspaces_updated(&mut self, _wm: &mut WindowManager, _names: &[&str], _active: usize) {}
+
+    /**
+     * Called after a workspace created at runtime has been removed automatically for being
+     * empty and hidden for longer than `Config::workspace_cleanup`. `index` is the position
+     * that the workspace had before it was removed.
+     */
+    fn workspace_removed(&mut self, _wm: &mut WindowManager, _index: usize, _name: &str) {}
+
+    /// Called after the focused screen has changed
+    fn screen_change(&mut self, _wm: &mut WindowManager, _screen_index: usize) {}
+
+    /// Called after X input focus has moved to a new Client
+    fn focus_change(&mut self, _wm: &mut WindowManager, _id: WinId) {}
+
+    /// Called after the title of a Client has changed
+    fn title_change(&mut self, _wm: &mut WindowManager, _id: WinId, _title: &str) {}
+
+    /// Called after a Client has started (or stopped) asking for the user's attention
+    fn urgency_change(&mut self, _wm: &mut WindowManager, _id: WinId, _urgent: bool) {}
+
+    /// Called after do not disturb mode has been turned on or off
+    fn dnd_change(&mut self, _wm: &mut WindowManager, _enabled: bool) {}
+
+    /**
+     * Called as a client is dragged with the mouse with the location it would be dropped at
+     * if released now. Called with `None` once the drag has finished.
+     */
+    fn drag_preview(&mut self, _wm: &mut WindowManager, _target: Option<&DropTarget>) {}
+
+    /**
+     * Called when an action reports its outcome (such as switching layout or being unable to
+     * find a workspace) so that it can be shown to the user. See WindowManager::notify.
+     */
+    fn notification(&mut self, _wm: &mut WindowManager, _n: &Notification) {}
+
+    /**
+     * The workspace represented by the point (x, y) on the root window (such as an entry
+     * in a bar pager) along with the region of that entry, if there is one. Dragged clients
+     * dropped on to this point are sent to that workspace.
+     */
+    fn workspace_at_point(&self, _wm: &WindowManager, _x: u32, _y: u32) -> Option<(usize, Region)> {
+        None
+    }
+
+    /**
+     * The opacity to give a client when it gains or loses focus. The first hook to return a
+     * value takes priority over any client rules and Config::opacity: returning None leaves
+     * the decision to later hooks and then to the WindowManager.
+     */
+    fn client_opacity(&self, _wm: &WindowManager, _id: WinId, _focused: bool) -> Option<f32> {
+        None
+    }
+}
+
+impl fmt::Debug for dyn Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hook")
+    }
+}
+
+/**
+ * Forward notifications to a notification daemon (such as dunst) using `notify-send`.
+ * Errors are always shown but other notifications are dropped while do not disturb mode
+ * is enabled.
+ */
+#[derive(Debug, Default)]
+pub struct NotifySend {
+    timeout_ms: Option<u32>,
+    running: Vec<Child>,
+}
+
+impl NotifySend {
+    /// Show each notification for `timeout_ms` milliseconds (or the daemon default if None)
+    pub fn new(timeout_ms: Option<u32>) -> NotifySend {
+        NotifySend {
+            timeout_ms,
+            running: vec![],
+        }
+    }
+}
+
+impl Hook for NotifySend {
+    fn notification(&mut self, wm: &mut WindowManager, n: &Notification) {
+        // notify-send exits straight away so clean up after any earlier calls as we go
+        self.running
+            .retain_mut(|c| matches!(c.try_wait(), Ok(None)));
+
+        let urgency = match n.level {
+            NotificationLevel::Info if wm.dnd_enabled() => return,
+            NotificationLevel::Info => "normal",
+            NotificationLevel::Error => "critical",
+        };
+
+        let timeout = self.timeout_ms.map(|t| t.to_string());
+        let mut args = vec!["-u", urgency, "-a", "penrose"];
+        if let Some(t) = timeout.as_deref() {
+            args.extend(&["-t", t]);
+        }
+        args.extend(&["penrose", &n.message]);
+        self.running.extend(spawn_with_args("notify-send", &args));
+    }
+}
@@ -0,0 +1,192 @@
+/*!
+ * A Unix socket for controlling and querying a running WindowManager from external programs
+ *
+ * Each connection sends a single request as a line of text and receives a single line in
+ * response before the connection is closed. Requests are the name of a WindowManager action
+ * followed by any arguments, separated by whitespace (e.g. `focus_workspace 2`). Successful
+ * actions respond with `ok`, queries respond with JSON and failures respond with a line
+ * starting with `error:`.
+ *
+ * The path of the socket is exported to child processes as $PENROSE_SOCKET so that the
+ * bundled `penrose-msg` client (and scripts) can find it.
+ */
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The environment variable used to pass the socket path to child processes
+pub const SOCKET_ENV: &str = "PENROSE_SOCKET";
+
+// How long we will wait on a connected client before giving up on it
+const CLIENT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The response sent for actions that completed successfully
+pub const OK: &str = "ok";
+
+/// A listening IPC socket. The socket file is removed when the server is dropped.
+#[derive(Debug)]
+pub struct IpcServer {
+    path: PathBuf,
+    listener: UnixListener,
+}
+
+impl IpcServer {
+    /// Start listening on `path`, replacing any stale socket left behind by a previous run
+    pub fn bind(path: impl Into<PathBuf>) -> Result<IpcServer, String> {
+        let path = path.into();
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| format!("unable to remove {:?}: {}", path, e))?;
+        }
+
+        let listener = UnixListener::bind(&path)
+            .map_err(|e| format!("unable to bind IPC socket {:?}: {}", path, e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("unable to configure IPC socket: {}", e))?;
+
+        Ok(IpcServer { path, listener })
+    }
+
+    /// The path of the underlying socket file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file descriptor of the listening socket for use in polling
+    pub fn fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+
+    /**
+     * Accept all pending connections, reading a single request from each. Connections that
+     * fail to send a request in time are dropped.
+     */
+    pub fn pending_requests(&self) -> Vec<(String, UnixStream)> {
+        let mut requests = vec![];
+        while let Ok((stream, _)) = self.listener.accept() {
+            match read_request(&stream) {
+                Ok(req) => requests.push((req, stream)),
+                Err(e) => warn!("dropping IPC client: {}", e),
+            }
+        }
+        requests
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn read_request(stream: &UnixStream) -> Result<String, String> {
+    stream
+        .set_nonblocking(false)
+        .and_then(|_| stream.set_read_timeout(Some(CLIENT_TIMEOUT)))
+        .map_err(|e| e.to_string())?;
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|e| e.to_string())?;
+    Ok(line.trim().to_string())
+}
+
+/// Send a response line back to a connected client
+pub fn respond(mut stream: UnixStream, response: &str) {
+    let _ = stream.set_write_timeout(Some(CLIENT_TIMEOUT));
+    if let Err(e) = writeln!(stream, "{}", response) {
+        warn!("unable to respond to IPC client: {}", e);
+    }
+}
+
+/// Split a request into the action name and its arguments
+pub fn parse_request(request: &str) -> Option<(&str, Vec<&str>)> {
+    let mut parts = request.split_whitespace();
+    let action = parts.next()?;
+    Some((action, parts.collect()))
+}
+
+/// Send a request to the socket at `path` and wait for the response
+pub fn send(path: impl AsRef<Path>, request: &str) -> Result<String, String> {
+    let path = path.as_ref();
+    let mut stream =
+        UnixStream::connect(path).map_err(|e| format!("unable to connect to {:?}: {}", path, e))?;
+    writeln!(stream, "{}", request).map_err(|e| e.to_string())?;
+
+    let mut response = String::new();
+    BufReader::new(stream)
+        .read_line(&mut response)
+        .map_err(|e| e.to_string())?;
+    Ok(response.trim_end().to_string())
+}
+
+/**
+ * Block until one of the given file descriptors is readable (or `timeout` has passed),
+ * returning which of them are. Interrupted and timed out waits return an empty Vec.
+ */
+pub fn wait_for_readable(fds: &[RawFd], timeout: Option<Duration>) -> Vec<RawFd> {
+    let mut pollfds: Vec<libc::pollfd> = fds
+        .iter()
+        .map(|fd| libc::pollfd {
+            fd: *fd,
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+
+    // rounded up so that we never spin with a zero timeout while waiting on less than 1ms
+    let timeout = timeout.map_or(-1, |t| {
+        t.as_micros().div_ceil(1000).min(i32::MAX as u128) as libc::c_int
+    });
+    let n = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, timeout) };
+    if n <= 0 {
+        return vec![];
+    }
+
+    pollfds
+        .iter()
+        .filter(|p| p.revents != 0)
+        .map(|p| p.fd)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn requests_are_split_on_whitespace() {
+        assert_eq!(parse_request(""), None);
+        assert_eq!(parse_request("next_layout"), Some(("next_layout", vec![])));
+        assert_eq!(
+            parse_request(" rename_workspace 2  web \n"),
+            Some(("rename_workspace", vec!["2", "web"]))
+        );
+    }
+
+    #[test]
+    fn a_request_round_trips_through_the_socket() {
+        let path = std::env::temp_dir().join(format!("penrose-ipc-{}", std::process::id()));
+        let server = IpcServer::bind(&path).unwrap();
+        assert!(server.pending_requests().is_empty());
+
+        let client_path = path.clone();
+        let client = thread::spawn(move || send(&client_path, "focus_workspace 3"));
+        while wait_for_readable(&[server.fd()], None).is_empty() {}
+
+        let mut requests = server.pending_requests();
+        assert_eq!(requests.len(), 1);
+        let (req, stream) = requests.remove(0);
+        assert_eq!(req, "focus_workspace 3");
+        respond(stream, OK);
+
+        assert_eq!(client.join().unwrap(), Ok(OK.to_string()));
+        drop(server);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,220 @@
+/*!
+ * An append-only journal of client state that can be used to recover window placement
+ *
+ * Each change to which workspace a client is on (or where a floating client is positioned)
+ * is written to the journal as a single line as soon as it happens, so that if penrose
+ * crashes the state can be recovered on the next startup without needing a graceful
+ * shutdown. Replaying the journal from the start gives the last known state of each client
+ * and the journal is compacted down to just that state whenever it is opened.
+ */
+use crate::data_types::{Region, WinId};
+use crate::errors::{PenroseError, Result};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+/// A single change to client state
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Entry {
+    /// A client is now on the workspace with the given index
+    Workspace(WinId, usize),
+    /// A floating client is now positioned at the given region
+    Floating(WinId, Region),
+    /// A client is no longer being managed
+    Removed(WinId),
+}
+
+impl Entry {
+    pub(crate) fn to_line(self) -> String {
+        match self {
+            Entry::Workspace(id, wix) => format!("ws {} {}\n", id, wix),
+            Entry::Floating(id, r) => {
+                let (x, y, w, h) = r.values();
+                format!("float {} {} {} {} {}\n", id, x, y, w, h)
+            }
+            Entry::Removed(id) => format!("rm {}\n", id),
+        }
+    }
+
+    pub(crate) fn from_line(line: &str) -> Option<Entry> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.is_empty() {
+            return None;
+        }
+        let nums: Vec<u32> = parts
+            .iter()
+            .skip(1)
+            .filter_map(|p| p.parse().ok())
+            .collect();
+        if nums.len() != parts.len() - 1 {
+            return None;
+        }
+
+        match (parts.first().copied(), nums.as_slice()) {
+            (Some("ws"), [id, wix]) => Some(Entry::Workspace(*id, *wix as usize)),
+            (Some("float"), [id, x, y, w, h]) => {
+                Some(Entry::Floating(*id, Region::new(*x, *y, *w, *h)))
+            }
+            (Some("rm"), [id]) => Some(Entry::Removed(*id)),
+            _ => None,
+        }
+    }
+}
+
+/// The last known state of a client, recovered from the journal
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct JournaledClient {
+    /// The workspace index the client was last on
+    pub workspace: usize,
+    /// The last position of the client if it was floating
+    pub floating: Option<Region>,
+}
+
+/// Replay a set of journal entries to determine the final state of each client
+pub fn replay(entries: &[Entry]) -> HashMap<WinId, JournaledClient> {
+    let mut state: HashMap<WinId, JournaledClient> = HashMap::new();
+    for e in entries {
+        match *e {
+            Entry::Workspace(id, wix) => state.entry(id).or_default().workspace = wix,
+            Entry::Floating(id, r) => state.entry(id).or_default().floating = Some(r),
+            Entry::Removed(id) => {
+                state.remove(&id);
+            }
+        }
+    }
+
+    state
+}
+
+/// Render the given client state as the minimal set of entries needed to recreate it
+pub(crate) fn compact(state: &HashMap<WinId, JournaledClient>) -> String {
+    let mut compacted = String::new();
+    let mut ids: Vec<&WinId> = state.keys().collect();
+    ids.sort_unstable();
+    for id in ids {
+        let c = state[id];
+        compacted.push_str(&Entry::Workspace(*id, c.workspace).to_line());
+        if let Some(r) = c.floating {
+            compacted.push_str(&Entry::Floating(*id, r).to_line());
+        }
+    }
+
+    compacted
+}
+
+/// An open journal file that entries are appended to
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    /**
+     * Open the journal at `path`, creating it if it does not exist. Returns the journal along
+     * with the client state recovered from any existing entries. The file is rewritten to hold
+     * only the recovered state so that it does not grow without bound across restarts.
+     */
+    pub fn open(path: impl Into<PathBuf>) -> Result<(Journal, HashMap<WinId, JournaledClient>)> {
+        let path = path.into();
+        let entries: Vec<Entry> = match fs::read_to_string(&path) {
+            Ok(s) => s.lines().filter_map(Entry::from_line).collect(),
+            Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+            Err(e) => {
+                return Err(PenroseError::Store(format!(
+                    "unable to read journal: {}",
+                    e
+                )))
+            }
+        };
+        let state = replay(&entries);
+        fs::write(&path, compact(&state))
+            .map_err(|e| PenroseError::Store(format!("unable to write journal: {}", e)))?;
+
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .map_err(|e| PenroseError::Store(format!("unable to open journal: {}", e)))?;
+
+        Ok((Journal { path, file }, state))
+    }
+
+    /// The path of the underlying journal file
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Append an entry to the journal, writing it to disk immediately
+    pub fn record(&mut self, entry: Entry) -> Result<()> {
+        self.file
+            .write_all(entry.to_line().as_bytes())
+            .map_err(|e| {
+                PenroseError::Store(format!("unable to write to journal {:?}: {}", self.path, e))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_round_trip_through_lines() {
+        let entries = vec![
+            Entry::Workspace(1, 3),
+            Entry::Floating(2, Region::new(1, 2, 3, 4)),
+            Entry::Removed(5),
+        ];
+        for e in entries {
+            assert_eq!(Entry::from_line(e.to_line().trim()), Some(e));
+        }
+        assert_eq!(Entry::from_line("ws 1"), None);
+        assert_eq!(Entry::from_line("ws 1 x"), None);
+        assert_eq!(Entry::from_line(""), None);
+        assert_eq!(Entry::from_line("   "), None);
+    }
+
+    #[test]
+    fn replay_keeps_the_latest_state() {
+        let r = Region::new(10, 10, 100, 100);
+        let state = replay(&[
+            Entry::Workspace(1, 0),
+            Entry::Workspace(2, 1),
+            Entry::Floating(2, r),
+            Entry::Workspace(1, 4),
+            Entry::Workspace(3, 0),
+            Entry::Removed(3),
+        ]);
+
+        assert_eq!(state.len(), 2);
+        assert_eq!(state[&1].workspace, 4);
+        assert_eq!(state[&2].floating, Some(r));
+    }
+
+    #[test]
+    fn opening_recovers_and_compacts() {
+        let path = std::env::temp_dir().join(format!("penrose-journal-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let (mut j, state) = Journal::open(&path).unwrap();
+        assert!(state.is_empty());
+        j.record(Entry::Workspace(1, 0)).unwrap();
+        j.record(Entry::Workspace(1, 2)).unwrap();
+        j.record(Entry::Workspace(2, 1)).unwrap();
+        j.record(Entry::Removed(2)).unwrap();
+
+        let (_, state) = Journal::open(&path).unwrap();
+        assert_eq!(state[&1].workspace, 2);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "ws 1 2\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn unreadable_journals_are_left_alone() {
+        assert!(matches!(
+            Journal::open(std::env::temp_dir()),
+            Err(PenroseError::Store(_))
+        ));
+    }
+}
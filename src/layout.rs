@@ -1,4 +1,4 @@
-/**!
+/*!
  * Layouts are user definable window arangements for a Workspace.
  *
  * Layouts are maintained per monitor and allow for indepent management of the two
@@ -10,13 +10,15 @@
  * for the layout to position windows.
  */
 use crate::client::Client;
-use crate::data_types::{Change, Region, ResizeAction, WinId};
+use crate::data_types::{Axis, Change, Region, ResizeAction, WinId};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
 use std::fmt;
 
 /**
  * When and how a Layout should be applied.
  */
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub struct LayoutConf {
     /// If true, this layout function will not be called to produce resize actions
     pub floating: bool,
@@ -24,16 +26,85 @@ pub struct LayoutConf {
     pub gapless: bool,
     /// Should this layout be triggered by window focus as well as add/remove client
     pub follow_focus: bool,
+    /// Gap around each client (so adjacent clients are twice this apart). Config::gap_px if None
+    pub inner_gap_px: Option<u32>,
+    /// Additional gap between clients and the edge of the screen
+    pub outer_gap_px: Option<u32>,
+    /// Drop borders and gaps when there is only a single tiled client
+    pub smart_borders: bool,
+    /**
+     * Unmap tiled clients that the layout function does not position (mapping them again
+     * once they are positioned) rather than leaving them where they were. `{}` in the layout
+     * symbol is replaced with the number of hidden clients.
+     */
+    pub hide_unpositioned: bool,
+    /**
+     * Shrink tiled clients to honour the size hints (WM_NORMAL_HINTS) that they have set,
+     * centering them in the space given to them by the layout function.
+     */
+    #[serde(default)]
+    pub respect_resize_hints: bool,
 }
 
 impl LayoutConf {
+    /// The (inner, outer) gaps to use, falling back to `default_px` for the inner gap
+    pub fn gaps(&self, default_px: u32) -> (u32, u32) {
+        if self.gapless {
+            (0, 0)
+        } else {
+            (
+                self.inner_gap_px.unwrap_or(default_px),
+                self.outer_gap_px.unwrap_or(0),
+            )
+        }
+    }
+}
+
+impl Default for LayoutConf {
     /// A layout config that only triggers when clients are added / removed and follows user
     /// defined config options.
-    pub fn default() -> LayoutConf {
+    fn default() -> LayoutConf {
         LayoutConf {
             floating: false,
             gapless: false,
             follow_focus: false,
+            inner_gap_px: None,
+            outer_gap_px: None,
+            smart_borders: false,
+            hide_unpositioned: false,
+            respect_resize_hints: false,
+        }
+    }
+}
+
+/**
+ * A column of screen space within a slotted Layout (see `Layout::with_slots`).
+ *
+ * Fixed size slots always take up exactly the requested number of pixels, with any space that is
+ * left over being split evenly between the flexible slots. Spacers are never given clients so
+ * they can be used to keep part of the screen clear.
+ */
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Slot {
+    /// Holds clients, sharing the remaining space with other flexible slots
+    Flexible,
+    /// Holds clients and is always exactly this many pixels wide
+    Fixed(u32),
+    /// Always empty, sharing the remaining space with other flexible slots
+    FlexibleSpacer,
+    /// Always empty and exactly this many pixels wide
+    Spacer(u32),
+}
+
+impl Slot {
+    fn holds_clients(&self) -> bool {
+        matches!(self, Slot::Flexible | Slot::Fixed(_))
+    }
+
+    fn fixed_width(&self) -> Option<u32> {
+        match self {
+            Slot::Fixed(w) | Slot::Spacer(w) => Some(*w),
+            _ => None,
         }
     }
 }
@@ -45,6 +116,61 @@ impl LayoutConf {
  */
 pub type LayoutFunc = fn(&[&Client], Option<WinId>, &Region, u32, f32) -> Vec<ResizeAction>;
 
+/**
+ * A function that is given messages sent to a Layout (see `Layout::handle_message`) before they
+ * are checked against the built in LayoutMessages. Handlers can downcast messages to any type
+ * they understand and are free to modify the Layout (including swapping out its layout function)
+ * in response. Returns true if the message was handled.
+ */
+pub type MessageHandler = fn(&mut Layout, &dyn Any) -> bool;
+
+/**
+ * A transformation applied to the regions produced by a layout function, allowing an existing
+ * layout to be reused in a different orientation (see `Layout::with_transform`).
+ */
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum LayoutTransform {
+    /// Swap the x and y axes: a main area on the left moves to the top of the screen
+    Mirrored,
+    /// Rotate a quarter turn clockwise: a main area on the left moves to the top of the screen
+    /// with the remaining clients stacked from right to left
+    Rotated,
+    /// Reflect in the given Axis: Horizontal moves a main area on the left to the right
+    Reflected(Axis),
+}
+
+impl LayoutTransform {
+    // The region that the wrapped layout should be run against
+    fn layout_region(&self, r: &Region) -> Region {
+        match self {
+            LayoutTransform::Mirrored | LayoutTransform::Rotated => r.transpose(),
+            LayoutTransform::Reflected(_) => *r,
+        }
+    }
+
+    // Map a region produced by the wrapped layout back on to the screen region `r`
+    fn screen_region(&self, client: &Region, r: &Region) -> Region {
+        match self {
+            LayoutTransform::Mirrored => client.transpose(),
+            LayoutTransform::Rotated => client.rotate_clockwise(r),
+            LayoutTransform::Reflected(axis) => client.reflect(*axis, r),
+        }
+    }
+}
+
+/// Messages that are understood by every Layout
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LayoutMessage {
+    /// Drop (or restore) the gaps between clients
+    ToggleGaps,
+    /// Set the number of clients in the main area
+    SetMaxMain(u32),
+    /// Set the size of the main area relative to secondary areas (clamped to 0.0..1.0)
+    SetRatio(f32),
+    /// Add the given transform to the layout, or remove it if it is already being applied
+    ToggleTransform(LayoutTransform),
+}
+
 /**
  * Responsible for arranging Clients within a Workspace.
  *
@@ -60,16 +186,28 @@ pub type LayoutFunc = fn(&[&Client], Option<WinId>, &Region, u32, f32) -> Vec<Re
  * which should determine the relative size of the main area compared to other cliens.  Layouts
  * maintain their own state for number of clients in the main area and ratio which will be passed
  * through to the layout function when it is called.
+ *
+ * Layouts can optionally be given a set of Slots that split the screen into columns. When slots
+ * are set, they are used in place of the layout function and ratio.
+ *
+ * Layout functions can not be serialized: a deserialized Layout will not position any clients
+ * until its settings are applied to a configured Layout using `restore_settings`.
  */
-#[derive(Clone, Copy)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Layout {
     /// How this layout should be applied by the WindowManager
     pub conf: LayoutConf,
     /// User defined symbol for displaying in the status bar
-    pub symbol: &'static str,
+    pub symbol: String,
     max_main: u32,
     ratio: f32,
+    #[serde(skip, default = "unbound")]
     f: LayoutFunc,
+    #[serde(skip)]
+    handler: Option<MessageHandler>,
+    slots: Vec<Slot>,
+    #[serde(default)]
+    transforms: Vec<LayoutTransform>,
 }
 
 impl fmt::Debug for Layout {
@@ -79,7 +217,10 @@ impl fmt::Debug for Layout {
             .field("symbol", &self.symbol)
             .field("max_main", &self.max_main)
             .field("ratio", &self.ratio)
+            .field("slots", &self.slots)
+            .field("transforms", &self.transforms)
             .field("f", &stringify!(&self.f))
+            .field("handler", &self.handler.is_some())
             .finish()
     }
 }
@@ -89,36 +230,119 @@ fn floating(_: &[&Client], _: Option<WinId>, _: &Region, _: u32, _: f32) -> Vec<
     vec![]
 }
 
+// Used in place of the layout function when deserializing
+fn unbound() -> LayoutFunc {
+    floating
+}
+
 impl Layout {
     /// Create a new Layout for a specific monitor
     pub fn new(
-        symbol: &'static str,
+        symbol: impl Into<String>,
         conf: LayoutConf,
         f: LayoutFunc,
         max_main: u32,
         ratio: f32,
     ) -> Layout {
         Layout {
-            symbol,
+            symbol: symbol.into(),
             conf,
             max_main,
             ratio,
             f,
+            handler: None,
+            slots: vec![],
+            transforms: vec![],
         }
     }
 
+    /// Pass messages sent to this layout to `handler` before handling any LayoutMessages
+    pub fn with_message_handler(mut self, handler: MessageHandler) -> Layout {
+        self.handler = Some(handler);
+        self
+    }
+
+    /**
+     * Split the screen into columns using the given slots in place of running the layout
+     * function. The first slot that holds clients is treated as the main area and is given
+     * n_main clients, any further slots that hold clients are given a single client each with the
+     * final slot taking all remaining clients. Clients are stacked vertically within each slot.
+     * Slots that would not be given any clients are dropped and their space redistributed.
+     */
+    pub fn with_slots(mut self, slots: Vec<Slot>) -> Layout {
+        self.slots = slots;
+        self
+    }
+
+    /**
+     * Transform the regions produced by this layout. Transforms are applied in the order that
+     * they are added, so `.with_transform(Mirrored).with_transform(Reflected(Vertical))`
+     * places a main area on the left at the bottom of the screen.
+     */
+    pub fn with_transform(mut self, transform: LayoutTransform) -> Layout {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Swap the x and y axes of this layout
+    pub fn mirrored(self) -> Layout {
+        self.with_transform(LayoutTransform::Mirrored)
+    }
+
+    /// Rotate this layout a quarter turn clockwise
+    pub fn rotated(self) -> Layout {
+        self.with_transform(LayoutTransform::Rotated)
+    }
+
+    /// Reflect this layout in the given Axis
+    pub fn reflected(self, axis: Axis) -> Layout {
+        self.with_transform(LayoutTransform::Reflected(axis))
+    }
+
     /// A default floating layout that will not attempt to manage windows
-    pub fn floating(symbol: &'static str) -> Layout {
+    pub fn floating(symbol: impl Into<String>) -> Layout {
         Layout {
-            symbol,
+            symbol: symbol.into(),
             conf: LayoutConf {
                 floating: true,
-                gapless: false,
-                follow_focus: false,
+                ..LayoutConf::default()
             },
             f: floating,
+            handler: None,
+            max_main: 1,
+            ratio: 1.0,
+            slots: vec![],
+            transforms: vec![],
+        }
+    }
+
+    /**
+     * A monocle layout: only the focused client is shown, covering the whole screen, and all
+     * other clients are unmapped. Use `{}` in the symbol to show the number of hidden clients.
+     */
+    pub fn monocle(symbol: impl Into<String>) -> Layout {
+        Layout {
+            symbol: symbol.into(),
+            conf: LayoutConf {
+                follow_focus: true,
+                hide_unpositioned: true,
+                ..LayoutConf::default()
+            },
+            f: monocle,
+            handler: None,
             max_main: 1,
             ratio: 1.0,
+            slots: vec![],
+            transforms: vec![],
+        }
+    }
+
+    /// The symbol to display for this layout when `n_hidden` clients are not being shown
+    pub fn display_symbol(&self, n_hidden: usize) -> String {
+        if self.conf.hide_unpositioned {
+            self.symbol.replace("{}", &n_hidden.to_string())
+        } else {
+            self.symbol.clone()
         }
     }
 
@@ -129,7 +353,132 @@ impl Layout {
         focused: Option<WinId>,
         r: &Region,
     ) -> Vec<ResizeAction> {
-        (self.f)(clients, focused, r, self.max_main, self.ratio)
+        self.arrange_transformed(&self.transforms, clients, focused, r)
+    }
+
+    // Run the layout against the region given by the first transform and map the results back
+    fn arrange_transformed(
+        &self,
+        transforms: &[LayoutTransform],
+        clients: &[&Client],
+        focused: Option<WinId>,
+        r: &Region,
+    ) -> Vec<ResizeAction> {
+        if let Some((t, rest)) = transforms.split_first() {
+            return self
+                .arrange_transformed(rest, clients, focused, &t.layout_region(r))
+                .into_iter()
+                .map(|(id, region)| (id, t.screen_region(&region, r)))
+                .collect();
+        }
+
+        if self.slots.is_empty() || self.conf.floating {
+            (self.f)(clients, focused, r, self.max_main, self.ratio)
+        } else {
+            slotted(clients, r, self.max_main, &self.slots)
+        }
+    }
+
+    /// The transforms currently being applied to this layout
+    pub fn transforms(&self) -> &[LayoutTransform] {
+        &self.transforms
+    }
+
+    /// Add the given transform, or remove it if it is already being applied
+    pub fn toggle_transform(&mut self, transform: LayoutTransform) {
+        match self.transforms.iter().position(|t| *t == transform) {
+            Some(i) => {
+                self.transforms.remove(i);
+            }
+            None => self.transforms.push(transform),
+        }
+    }
+
+    /// Take on the main area, ratio, slot and conf settings of another Layout
+    pub fn restore_settings(&mut self, saved: &Layout) {
+        self.conf = saved.conf;
+        self.max_main = saved.max_main;
+        self.ratio = saved.ratio;
+        self.slots = saved.slots.clone();
+        self.transforms = saved.transforms.clone();
+    }
+
+    /**
+     * Pass an arbitrary message to this layout, returning whether or not it was handled. The
+     * message handler (if there is one) is tried first, followed by the built in LayoutMessages.
+     */
+    pub fn handle_message(&mut self, msg: &dyn Any) -> bool {
+        if let Some(handler) = self.handler {
+            if handler(self, msg) {
+                return true;
+            }
+        }
+
+        match msg.downcast_ref::<LayoutMessage>() {
+            Some(LayoutMessage::ToggleGaps) => self.conf.gapless = !self.conf.gapless,
+            Some(LayoutMessage::SetMaxMain(n)) => self.max_main = *n,
+            Some(LayoutMessage::SetRatio(r)) => self.ratio = r.clamp(0.0, 1.0),
+            Some(LayoutMessage::ToggleTransform(t)) => self.toggle_transform(*t),
+            None => return false,
+        }
+
+        true
+    }
+
+    /// The function currently being used to position clients
+    pub fn layout_func(&self) -> LayoutFunc {
+        self.f
+    }
+
+    /// Position clients using a different function, keeping all other settings
+    pub fn set_layout_func(&mut self, f: LayoutFunc) {
+        self.f = f;
+    }
+
+    /// The number of clients in the main area and the main area ratio
+    pub fn main_settings(&self) -> (u32, f32) {
+        (self.max_main, self.ratio)
+    }
+
+    /// The slots currently in use by this layout
+    pub fn slots(&self) -> &[Slot] {
+        &self.slots
+    }
+
+    /// Replace the slots used by this layout. An empty Vec reverts to the layout function.
+    pub fn set_slots(&mut self, slots: Vec<Slot>) {
+        self.slots = slots;
+    }
+
+    /// Increase/decrease the width of the fixed size slot at index by step pixels
+    pub fn update_slot_width(&mut self, index: usize, change: Change, step: u32) {
+        if let Some(slot) = self.slots.get_mut(index) {
+            let update = |w: u32| match change {
+                Change::More => w.saturating_add(step),
+                Change::Less => w.saturating_sub(step),
+            };
+            match slot {
+                Slot::Fixed(w) => *slot = Slot::Fixed(update(*w)),
+                Slot::Spacer(w) => *slot = Slot::Spacer(update(*w)),
+                _ => (),
+            }
+        }
+    }
+
+    /// The number of clients in the main area
+    pub fn max_main(&self) -> u32 {
+        self.max_main
+    }
+
+    /// The proportion of the screen given to the main area
+    pub fn ratio(&self) -> f32 {
+        self.ratio
+    }
+
+    /// Set the number of clients in the main area and the proportion of the screen it takes up
+    pub fn set_main(&mut self, max_main: u32, ratio: f32) {
+        self.max_main = max_main;
+        self.ratio = ratio.clamp(0.0, 1.0);
     }
 
     /// Increase/decrease the number of clients in the main area by 1
@@ -152,11 +501,21 @@ impl Layout {
             Change::Less => self.ratio -= step,
         }
 
-        if self.ratio < 0.0 {
-            self.ratio = 0.0
-        } else if self.ratio > 1.0 {
-            self.ratio = 1.0;
-        }
+        self.ratio = self.ratio.clamp(0.0, 1.0);
+    }
+
+    /**
+     * Increase/decrease the inner and outer gaps for this layout by step pixels. Gaps that
+     * have not been set on the LayoutConf start from the (inner, outer) values in `current`.
+     */
+    pub fn update_gaps(&mut self, change: Change, step: u32, current: (u32, u32)) {
+        let update = |px: u32| match change {
+            Change::More => px.saturating_add(step),
+            Change::Less => px.saturating_sub(step),
+        };
+        self.conf.gapless = false;
+        self.conf.inner_gap_px = Some(update(current.0));
+        self.conf.outer_gap_px = Some(update(current.1));
     }
 }
 
@@ -174,6 +533,124 @@ pub fn client_breakdown<T>(clients: &[T], n_main: u32) -> (u32, u32) {
     }
 }
 
+/// Split a region into columns of the given widths
+fn columns(r: &Region, widths: &[u32]) -> Vec<Region> {
+    let (mut x, y, _, h) = r.values();
+    widths
+        .iter()
+        .map(|w| {
+            let col = Region::new(x, y, *w, h);
+            x += w;
+            col
+        })
+        .collect()
+}
+
+// Split len pixels from start into n (offset, length) pairs that exactly cover the span: any
+// remainder is handed out a pixel at a time from the start so that odd sizes leave no gaps
+fn split_evenly(start: u32, len: u32, n: u32) -> Vec<(u32, u32)> {
+    let base = len.checked_div(n).unwrap_or(0);
+    let extra = len.checked_rem(n).unwrap_or(0);
+    let mut offset = start;
+    (0..n)
+        .map(|i| {
+            let l = if i < extra { base + 1 } else { base };
+            offset += l;
+            (offset - l, l)
+        })
+        .collect()
+}
+
+// n regions stacked on top of one another in a column of r starting at x
+fn column_rows(r: &Region, x: u32, w: u32, n: usize) -> Vec<Region> {
+    let (_, y, _, h) = r.values();
+    split_evenly(y, h, n as u32)
+        .into_iter()
+        .map(|(cy, ch)| Region::new(x, cy, w, ch))
+        .collect()
+}
+
+// Width of the main column: all of the screen if there is no stack and none of it if there
+// are no main clients
+fn main_width(w: u32, n_main: u32, n_stack: u32, ratio: f32) -> u32 {
+    match (n_main, n_stack) {
+        (_, 0) => w,
+        (0, _) => 0,
+        _ => (w as f32 * ratio) as u32,
+    }
+}
+
+// Split clients between the client holding slots then tile each slot as a single column
+fn slotted(clients: &[&Client], r: &Region, max_main: u32, slots: &[Slot]) -> Vec<ResizeAction> {
+    let n_holding = slots.iter().filter(|s| s.holds_clients()).count();
+    if n_holding == 0 {
+        return vec![];
+    }
+
+    // number of clients for each holding slot in order
+    let mut remaining = clients.len();
+    let counts: Vec<usize> = (0..n_holding)
+        .map(|i| {
+            let n = if i == n_holding - 1 {
+                remaining
+            } else if i == 0 {
+                remaining.min(max_main.max(1) as usize)
+            } else {
+                remaining.min(1)
+            };
+            remaining -= n;
+            n
+        })
+        .collect();
+
+    let mut holding = counts.iter();
+    let used: Vec<(Slot, usize)> = slots
+        .iter()
+        .map(|s| {
+            (
+                *s,
+                if s.holds_clients() {
+                    *holding.next().unwrap()
+                } else {
+                    0
+                },
+            )
+        })
+        .filter(|(s, n)| !s.holds_clients() || *n > 0)
+        .collect();
+
+    let fixed: u32 = used.iter().filter_map(|(s, _)| s.fixed_width()).sum();
+    let n_flex = used
+        .iter()
+        .filter(|(s, _)| s.fixed_width().is_none())
+        .count() as u32;
+    let w_flex = r
+        .width()
+        .saturating_sub(fixed)
+        .checked_div(n_flex)
+        .unwrap_or(0);
+    let widths: Vec<u32> = used
+        .iter()
+        .map(|(s, _)| s.fixed_width().unwrap_or(w_flex))
+        .collect();
+
+    let mut to_place = clients.iter();
+    columns(r, &widths)
+        .iter()
+        .zip(used.iter())
+        .flat_map(|(col, (_, n))| {
+            let (x, y, w, h) = col.values();
+            let h_client = h.checked_div(*n as u32).unwrap_or(0);
+            (0..*n as u32)
+                .map(|i| {
+                    let c = to_place.next().unwrap();
+                    (c.id(), Region::new(x, y + i * h_client, w, h_client))
+                })
+                .collect::<Vec<ResizeAction>>()
+        })
+        .collect()
+}
+
 /*
  * Layout functions
  *
@@ -216,9 +693,9 @@ pub fn side_stack(
     ratio: f32,
 ) -> Vec<ResizeAction> {
     let (mx, my, mw, mh) = monitor_region.values();
-    let (n_main, n_stack) = client_breakdown(&clients, max_main);
-    let h_stack = if n_stack > 0 { mh / n_stack } else { 0 };
-    let h_main = if n_main > 0 { mh / n_main } else { 0 };
+    let (n_main, n_stack) = client_breakdown(clients, max_main);
+    let h_stack = mh.checked_div(n_stack).unwrap_or(0);
+    let h_main = mh.checked_div(n_main).unwrap_or(0);
     let split = if max_main > 0 {
         (mw as f32 * ratio) as u32
     } else {
@@ -254,15 +731,15 @@ pub fn bottom_stack(
     ratio: f32,
 ) -> Vec<ResizeAction> {
     let (mx, my, mw, mh) = monitor_region.values();
-    let (n_main, n_stack) = client_breakdown(&clients, max_main);
+    let (n_main, n_stack) = client_breakdown(clients, max_main);
     let split = if max_main > 0 {
         (mh as f32 * ratio) as u32
     } else {
         0
     };
     let h_main_full = if n_stack > 0 { split } else { mh };
-    let h_main = if n_main > 0 { h_main_full / n_main } else { 0 };
-    let w_stack = if n_stack > 0 { mw / n_stack } else { 0 };
+    let h_main = h_main_full.checked_div(n_main).unwrap_or(0);
+    let w_stack = mw.checked_div(n_stack).unwrap_or(0);
 
     clients
         .iter()
@@ -281,6 +758,225 @@ pub fn bottom_stack(
         .collect()
 }
 
+/**
+ * Only the focused client (or the first client if none are focused) is positioned, taking up the
+ * whole screen. Intended for use with `LayoutConf::hide_unpositioned` so that the remaining clients
+ * are unmapped: see `Layout::monocle`.
+ */
+pub fn monocle(
+    clients: &[&Client],
+    focused: Option<WinId>,
+    monitor_region: &Region,
+    _: u32,
+    _: f32,
+) -> Vec<ResizeAction> {
+    let first = match clients.first() {
+        Some(c) => c.id(),
+        None => return vec![],
+    };
+    let shown = focused
+        .filter(|id| clients.iter().any(|c| c.id() == *id))
+        .unwrap_or(first);
+    vec![(shown, *monitor_region)]
+}
+
+/**
+ * An even grid of clients filling rows from left to right. The number of columns is chosen to
+ * keep the grid as close to square as possible and clients on a partially filled final row
+ * share its width between them. n_main and ratio are ignored.
+ */
+pub fn grid(
+    clients: &[&Client],
+    _: Option<WinId>,
+    monitor_region: &Region,
+    _: u32,
+    _: f32,
+) -> Vec<ResizeAction> {
+    if clients.is_empty() {
+        return vec![];
+    }
+
+    let (mx, my, mw, mh) = monitor_region.values();
+    let n = clients.len() as u32;
+    let cols = (1..=n).find(|c| c * c >= n).unwrap_or(1);
+    let rows = n.div_ceil(cols);
+    let h = mh / rows;
+
+    clients
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let (row, col) = (i as u32 / cols, i as u32 % cols);
+            let in_row = if row == rows - 1 {
+                n - row * cols
+            } else {
+                cols
+            };
+            let w = mw / in_row;
+            (c.id(), Region::new(mx + col * w, my + row * h, w, h))
+        })
+        .collect()
+}
+
+// Each client takes a slice of the space left over by the previous clients, with the final
+// client taking everything that remains. The first split is made at ratio and later splits
+// are made in half. Dwindle always splits off to the left then top while spiral works its way
+// round clockwise: left, top, right then bottom.
+fn fibonacci(clients: &[&Client], r: &Region, ratio: f32, spiral: bool) -> Vec<ResizeAction> {
+    let (mut x, mut y, mut w, mut h) = r.values();
+    let n = clients.len();
+
+    clients
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            if i == n - 1 {
+                return (c.id(), Region::new(x, y, w, h));
+            }
+
+            let split = if i == 0 { ratio } else { 0.5 };
+            let (cw, ch) = ((w as f32 * split) as u32, (h as f32 * split) as u32);
+            let region = match if spiral { i % 4 } else { i % 2 } {
+                0 => {
+                    x += cw;
+                    w -= cw;
+                    Region::new(x - cw, y, cw, h)
+                }
+                1 => {
+                    y += ch;
+                    h -= ch;
+                    Region::new(x, y - ch, w, ch)
+                }
+                2 => {
+                    w -= cw;
+                    Region::new(x + w, y, cw, h)
+                }
+                _ => {
+                    h -= ch;
+                    Region::new(x, y + h, w, ch)
+                }
+            };
+            (c.id(), region)
+        })
+        .collect()
+}
+
+/**
+ * Each client takes half of the space remaining after the previous client, alternating between
+ * splitting to the left and the top so that clients dwindle towards the bottom right of the
+ * screen. The first client takes ratio% of the screen width. n_main is ignored.
+ */
+pub fn dwindle(
+    clients: &[&Client],
+    _: Option<WinId>,
+    monitor_region: &Region,
+    _: u32,
+    ratio: f32,
+) -> Vec<ResizeAction> {
+    fibonacci(clients, monitor_region, ratio, false)
+}
+
+/**
+ * A fibonacci spiral: each client takes half of the space remaining after the previous client,
+ * working clockwise around the screen (left, top, right, bottom) and spiralling in towards the
+ * center. The first client takes ratio% of the screen width. n_main is ignored.
+ */
+pub fn spiral(
+    clients: &[&Client],
+    _: Option<WinId>,
+    monitor_region: &Region,
+    _: u32,
+    ratio: f32,
+) -> Vec<ResizeAction> {
+    fibonacci(clients, monitor_region, ratio, true)
+}
+
+/**
+ * The main clients are stacked in a column in the center of the screen taking up ratio% of its
+ * width, with the remaining clients alternating between columns to the right and left of it
+ * (starting on the right). With a single stacked client the main column is placed on the left
+ * and with no stacked clients it takes the whole screen. Odd pixel counts are shared out so that
+ * clients always exactly cover the screen.
+ */
+pub fn centered_master(
+    clients: &[&Client],
+    _: Option<WinId>,
+    monitor_region: &Region,
+    max_main: u32,
+    ratio: f32,
+) -> Vec<ResizeAction> {
+    let (mx, _, mw, _) = monitor_region.values();
+    let (n_main, n_stack) = client_breakdown(clients, max_main);
+    let w_main = main_width(mw, n_main, n_stack, ratio);
+    let w_sides = mw - w_main;
+    let (w_left, w_right) = if n_stack > 1 {
+        (w_sides / 2, w_sides - w_sides / 2)
+    } else {
+        (0, w_sides)
+    };
+
+    let n_right = n_stack.div_ceil(2) as usize;
+    let main = column_rows(monitor_region, mx + w_left, w_main, n_main as usize);
+    let mut right = column_rows(monitor_region, mx + w_left + w_main, w_right, n_right).into_iter();
+    let mut left = column_rows(monitor_region, mx, w_left, n_stack as usize - n_right).into_iter();
+
+    clients
+        .iter()
+        .enumerate()
+        .map(|(n, c)| {
+            let region = if n < n_main as usize {
+                main[n]
+            } else if (n - n_main as usize).is_multiple_of(2) {
+                right.next().unwrap()
+            } else {
+                left.next().unwrap()
+            };
+            (c.id(), region)
+        })
+        .collect()
+}
+
+/**
+ * The main clients are stacked in a column on the left taking up ratio% of the screen width and
+ * the remaining clients are split between a middle and right column of equal width, with the
+ * middle column taking the extra client when there are an odd number. With a single stacked
+ * client it takes all of the space to the right of the main column. Odd pixel counts are shared
+ * out so that clients always exactly cover the screen.
+ */
+pub fn three_column(
+    clients: &[&Client],
+    _: Option<WinId>,
+    monitor_region: &Region,
+    max_main: u32,
+    ratio: f32,
+) -> Vec<ResizeAction> {
+    let (mx, _, mw, _) = monitor_region.values();
+    let (n_main, n_stack) = client_breakdown(clients, max_main);
+    let w_main = main_width(mw, n_main, n_stack, ratio);
+    let w_rest = mw - w_main;
+    let n_mid = n_stack.div_ceil(2);
+    let (w_mid, w_right) = if n_stack > 1 {
+        (w_rest / 2, w_rest - w_rest / 2)
+    } else {
+        (w_rest, 0)
+    };
+
+    let main = column_rows(monitor_region, mx, w_main, n_main as usize);
+    let mid = column_rows(monitor_region, mx + w_main, w_mid, n_mid as usize);
+    let right = column_rows(
+        monitor_region,
+        mx + w_main + w_mid,
+        w_right,
+        (n_stack - n_mid) as usize,
+    );
+
+    clients
+        .iter()
+        .zip(main.into_iter().chain(mid).chain(right))
+        .map(|(c, region)| (c.id(), region))
+        .collect()
+}
+
 /**
  * A layout that aims to mimic the feel of having multiple pieces of paper fanned out on a desk,
  * inspired by http://10gui.com/
@@ -327,3 +1023,319 @@ pub fn paper(
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clients(n: usize) -> Vec<Client> {
+        (0..n)
+            .map(|i| Client::new(i as WinId, "test".into(), 0, false))
+            .collect()
+    }
+
+    fn slotted_layout(slots: Vec<Slot>) -> Layout {
+        Layout::new("t", LayoutConf::default(), side_stack, 1, 0.6).with_slots(slots)
+    }
+
+    fn arrange(layout: &Layout, n: usize) -> Vec<ResizeAction> {
+        let cs = clients(n);
+        let refs: Vec<&Client> = cs.iter().collect();
+        layout.arrange(&refs, None, &Region::new(0, 0, 1000, 600))
+    }
+
+    #[test]
+    fn fixed_slots_keep_their_width() {
+        let l = slotted_layout(vec![Slot::Fixed(300), Slot::Flexible]);
+        assert_eq!(
+            arrange(&l, 3),
+            vec![
+                (0, Region::new(0, 0, 300, 600)),
+                (1, Region::new(300, 0, 700, 300)),
+                (2, Region::new(300, 300, 700, 300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn spacers_are_left_empty() {
+        let l = slotted_layout(vec![Slot::Flexible, Slot::Spacer(200), Slot::Flexible]);
+        assert_eq!(
+            arrange(&l, 2),
+            vec![
+                (0, Region::new(0, 0, 400, 600)),
+                (1, Region::new(600, 0, 400, 600)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_slots_give_up_their_space() {
+        let l = slotted_layout(vec![
+            Slot::Flexible,
+            Slot::FlexibleSpacer,
+            Slot::Fixed(100),
+            Slot::Flexible,
+        ]);
+        assert_eq!(arrange(&l, 1), vec![(0, Region::new(0, 0, 500, 600))]);
+    }
+
+    #[test]
+    fn gaps_fall_back_to_the_default() {
+        let mut conf = LayoutConf::default();
+        assert_eq!(conf.gaps(5), (5, 0));
+        conf.outer_gap_px = Some(3);
+        assert_eq!(conf.gaps(5), (5, 3));
+        conf.gapless = true;
+        assert_eq!(conf.gaps(5), (0, 0));
+    }
+
+    #[test]
+    fn updating_gaps() {
+        let mut l = slotted_layout(vec![]);
+        l.update_gaps(Change::More, 2, l.conf.gaps(5));
+        assert_eq!(l.conf.gaps(5), (7, 2));
+        l.update_gaps(Change::Less, 4, l.conf.gaps(5));
+        assert_eq!(l.conf.gaps(5), (3, 0));
+    }
+
+    #[test]
+    fn updating_slot_widths() {
+        let mut l = slotted_layout(vec![Slot::Fixed(100), Slot::Flexible]);
+        l.update_slot_width(0, Change::More, 20);
+        l.update_slot_width(1, Change::More, 20);
+        assert_eq!(l.slots(), &[Slot::Fixed(120), Slot::Flexible]);
+        l.update_slot_width(0, Change::Less, 200);
+        assert_eq!(l.slots(), &[Slot::Fixed(0), Slot::Flexible]);
+    }
+
+    fn arrange_with(f: LayoutFunc, n: usize) -> Vec<ResizeAction> {
+        arrange(&Layout::new("t", LayoutConf::default(), f, 1, 0.5), n)
+    }
+
+    #[test]
+    fn grid_fills_rows_evenly() {
+        assert_eq!(arrange_with(grid, 0), vec![]);
+        assert_eq!(
+            arrange_with(grid, 1),
+            vec![(0, Region::new(0, 0, 1000, 600))]
+        );
+        assert_eq!(
+            arrange_with(grid, 3),
+            vec![
+                (0, Region::new(0, 0, 500, 300)),
+                (1, Region::new(500, 0, 500, 300)),
+                (2, Region::new(0, 300, 1000, 300)),
+            ]
+        );
+        assert_eq!(
+            arrange_with(grid, 9)[8],
+            (8, Region::new(666, 400, 333, 200))
+        );
+    }
+
+    #[test]
+    fn dwindle_splits_left_then_top() {
+        assert_eq!(
+            arrange_with(dwindle, 4),
+            vec![
+                (0, Region::new(0, 0, 500, 600)),
+                (1, Region::new(500, 0, 500, 300)),
+                (2, Region::new(500, 300, 250, 300)),
+                (3, Region::new(750, 300, 250, 300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn spiral_works_round_clockwise() {
+        assert_eq!(
+            arrange_with(spiral, 5),
+            vec![
+                (0, Region::new(0, 0, 500, 600)),
+                (1, Region::new(500, 0, 500, 300)),
+                (2, Region::new(750, 300, 250, 300)),
+                (3, Region::new(500, 450, 250, 150)),
+                (4, Region::new(500, 300, 250, 150)),
+            ]
+        );
+    }
+
+    #[test]
+    fn centered_master_places_the_stack_either_side() {
+        assert_eq!(
+            arrange_with(centered_master, 4),
+            vec![
+                (0, Region::new(250, 0, 500, 600)),
+                (1, Region::new(750, 0, 250, 300)),
+                (2, Region::new(0, 0, 250, 600)),
+                (3, Region::new(750, 300, 250, 300)),
+            ]
+        );
+        assert_eq!(
+            arrange_with(centered_master, 2),
+            vec![
+                (0, Region::new(0, 0, 500, 600)),
+                (1, Region::new(500, 0, 500, 600)),
+            ]
+        );
+    }
+
+    #[test]
+    fn three_column_fills_the_middle_column_first() {
+        assert_eq!(
+            arrange_with(three_column, 4),
+            vec![
+                (0, Region::new(0, 0, 500, 600)),
+                (1, Region::new(500, 0, 250, 300)),
+                (2, Region::new(500, 300, 250, 300)),
+                (3, Region::new(750, 0, 250, 600)),
+            ]
+        );
+    }
+
+    #[test]
+    fn main_count_and_ratio_are_respected() {
+        let mut l = Layout::new("t", LayoutConf::default(), three_column, 1, 0.5);
+        l.update_max_main(Change::More);
+        l.update_main_ratio(Change::More, 0.1);
+        let actions = arrange(&l, 3);
+        assert_eq!(actions[0], (0, Region::new(0, 0, 600, 300)));
+        assert_eq!(actions[1], (1, Region::new(0, 300, 600, 300)));
+        assert_eq!(actions[2], (2, Region::new(600, 0, 400, 600)));
+    }
+
+    // every pixel of r is covered by exactly one client
+    fn assert_tiled(actions: &[ResizeAction], r: &Region) {
+        let (rx, ry, rw, rh) = r.values();
+        let area: u32 = actions.iter().map(|(_, c)| c.width() * c.height()).sum();
+        assert_eq!(area, rw * rh, "{:?}", actions);
+
+        for (i, (_, a)) in actions.iter().enumerate() {
+            let (ax, ay, aw, ah) = a.values();
+            assert!(ax >= rx && ay >= ry && ax + aw <= rx + rw && ay + ah <= ry + rh);
+            for (_, b) in actions[i + 1..].iter() {
+                let (bx, by, bw, bh) = b.values();
+                let overlap = ax < bx + bw && bx < ax + aw && ay < by + bh && by < ay + ah;
+                assert!(!overlap, "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn column_layouts_leave_no_gaps_on_odd_sizes() {
+        let r = Region::new(3, 7, 1001, 599);
+        for f in &[centered_master as LayoutFunc, three_column] {
+            for max_main in 0..4 {
+                for n in 1..9 {
+                    let l = Layout::new("t", LayoutConf::default(), *f, max_main, 0.37);
+                    let cs = clients(n);
+                    let refs: Vec<&Client> = cs.iter().collect();
+                    assert_tiled(&l.arrange(&refs, None, &r), &r);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn monocle_only_positions_the_focused_client() {
+        let l = Layout::monocle("[{}]");
+        let cs = clients(3);
+        let refs: Vec<&Client> = cs.iter().collect();
+        let r = Region::new(0, 0, 1000, 600);
+
+        assert_eq!(l.arrange(&refs, Some(1), &r), vec![(1, r)]);
+        assert_eq!(l.arrange(&refs, None, &r), vec![(0, r)]);
+        assert_eq!(l.arrange(&[], None, &r), vec![]);
+        assert_eq!(l.display_symbol(2), "[2]");
+        assert_eq!(slotted_layout(vec![]).display_symbol(2), "t");
+    }
+
+    struct UseBottomStack;
+
+    fn bottom_stack_handler(layout: &mut Layout, msg: &dyn Any) -> bool {
+        if msg.is::<UseBottomStack>() {
+            layout.set_layout_func(bottom_stack);
+            return true;
+        }
+        // claim SetRatio so that the built in handling is skipped
+        matches!(msg.downcast_ref(), Some(LayoutMessage::SetRatio(_)))
+    }
+
+    #[test]
+    fn message_handlers_are_tried_before_built_in_messages() {
+        let mut l = Layout::new("t", LayoutConf::default(), side_stack, 1, 0.5)
+            .with_message_handler(bottom_stack_handler);
+
+        assert!(l.handle_message(&UseBottomStack));
+        assert_eq!(arrange(&l, 2), arrange_with(bottom_stack, 2));
+        assert!(l.handle_message(&LayoutMessage::SetMaxMain(2)));
+        assert!(l.handle_message(&LayoutMessage::SetRatio(0.8)));
+        assert!(l.handle_message(&LayoutMessage::ToggleGaps));
+        assert_eq!(l.main_settings(), (2, 0.5));
+        assert!(l.conf.gapless);
+        assert!(!l.handle_message(&"unknown"));
+    }
+
+    fn side_stack_with(transform: LayoutTransform) -> Vec<ResizeAction> {
+        let l =
+            Layout::new("t", LayoutConf::default(), side_stack, 1, 0.5).with_transform(transform);
+        arrange(&l, 3)
+    }
+
+    #[test]
+    fn mirrored_layouts_swap_axes() {
+        assert_eq!(
+            side_stack_with(LayoutTransform::Mirrored),
+            vec![
+                (0, Region::new(0, 0, 1000, 300)),
+                (1, Region::new(0, 300, 500, 300)),
+                (2, Region::new(500, 300, 500, 300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn rotated_layouts_turn_clockwise() {
+        assert_eq!(
+            side_stack_with(LayoutTransform::Rotated),
+            vec![
+                (0, Region::new(0, 0, 1000, 300)),
+                (1, Region::new(500, 300, 500, 300)),
+                (2, Region::new(0, 300, 500, 300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn reflected_layouts_flip_within_the_screen() {
+        assert_eq!(
+            side_stack_with(LayoutTransform::Reflected(Axis::Horizontal)),
+            vec![
+                (0, Region::new(500, 0, 500, 600)),
+                (1, Region::new(0, 0, 500, 300)),
+                (2, Region::new(0, 300, 500, 300)),
+            ]
+        );
+        assert_eq!(
+            side_stack_with(LayoutTransform::Reflected(Axis::Vertical)),
+            vec![
+                (0, Region::new(0, 0, 500, 600)),
+                (1, Region::new(500, 300, 500, 300)),
+                (2, Region::new(500, 0, 500, 300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn transforms_can_be_toggled_with_messages() {
+        let mut l = Layout::new("t", LayoutConf::default(), side_stack, 1, 0.5).mirrored();
+        let msg = LayoutMessage::ToggleTransform(LayoutTransform::Mirrored);
+
+        assert!(l.handle_message(&msg));
+        assert!(l.transforms().is_empty());
+        assert_eq!(arrange(&l, 3), arrange_with(side_stack, 3));
+        assert!(l.handle_message(&msg));
+        assert_eq!(arrange(&l, 3), side_stack_with(LayoutTransform::Mirrored));
+    }
+}
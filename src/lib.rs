@@ -9,17 +9,34 @@ extern crate log;
 #[macro_use]
 pub mod macros;
 
+pub mod bar;
 pub mod client;
+pub mod config_file;
 pub mod data_types;
+pub mod drag;
+pub mod draw;
+pub mod errors;
+pub mod expose;
 pub mod helpers;
+pub mod hooks;
+pub mod ipc;
+pub mod journal;
 pub mod layout;
 pub mod manager;
+pub mod palette;
+pub mod placement;
+pub mod rules;
+pub mod scratchpad;
 pub mod screen;
+pub mod snapshot;
+pub mod store;
 pub mod workspace;
 pub mod xconnection;
 
 // top level re-exports
 pub use data_types::{ColorScheme, Config};
+pub use errors::PenroseError;
+pub use hooks::Hook;
 pub use layout::{Layout, LayoutConf};
 pub use manager::WindowManager;
 pub use xconnection::XcbConnection;
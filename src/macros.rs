@@ -78,3 +78,69 @@ macro_rules! gen_keybindings(
         }
     };
 );
+
+/// make creating a submap of key bindings less verbose. The result is a binding action that
+/// enters the submap: the next key press is looked up in the bindings given here (which may
+/// themselves contain further submaps) instead of the top level key bindings.
+/// Bindings inside of a submap do not need a modifier, e.g. "M-w" => gen_submap! { "h" => ... }
+#[macro_export]
+macro_rules! gen_submap(
+    { $($binding:expr => $action:expr),+ $(,)? } => {
+        {
+            let mut _map: $crate::data_types::KeyBindings = ::std::collections::HashMap::new();
+            let keycodes = $crate::helpers::keycodes_from_xmodmap();
+
+            $(
+                match $crate::helpers::parse_key_binding($binding, &keycodes) {
+                    None => panic!("invalid key binding: {}", $binding),
+                    Some(key_code) => _map.insert(key_code, $action),
+                };
+            )+
+
+            $crate::__enter_submap!(_map)
+        }
+    };
+
+    {
+        $($binding:expr => $action:expr),*;
+        forall_workspaces: $ws_array:expr => { $($ws_binding:expr => $ws_action:tt),+, }
+    } => {
+        {
+            let mut _map: $crate::data_types::KeyBindings = ::std::collections::HashMap::new();
+            let keycodes = $crate::helpers::keycodes_from_xmodmap();
+
+            $(
+                match $crate::helpers::parse_key_binding($binding, &keycodes) {
+                    None => panic!("invalid key binding: {}", $binding),
+                    Some(key_code) => _map.insert(key_code, $action),
+                };
+            )*
+
+            for i in 0..$ws_array.len() {
+                $(
+                    let for_ws = format!($ws_binding, i+1);
+                    match $crate::helpers::parse_key_binding(for_ws.clone(), &keycodes) {
+                        None => panic!("invalid key binding: {}", for_ws),
+                        Some(key_code) => _map.insert(key_code, run_internal!($ws_action, i)),
+                    };
+                )+
+            }
+
+            $crate::__enter_submap!(_map)
+        }
+    };
+);
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __enter_submap(
+    ($map:expr) => {
+        {
+            let submap = ::std::rc::Rc::new($map);
+            Box::new(move |wm: &mut $crate::manager::WindowManager| {
+                wm.enter_submap(submap.clone());
+                None
+            }) as $crate::data_types::FireAndForget
+        }
+    };
+);
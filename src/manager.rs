@@ -1,13 +1,257 @@
 //! Main logic for running Penrose
 use crate::client::Client;
+use crate::config_file::{leak_strs, ConfigFile};
 use crate::data_types::{
-    Change, ColorScheme, Config, Direction, KeyBindings, KeyCode, Region, WinId,
+    BorderStyle, Change, ClassFilter, CodeMap, ColorScheme, Config, Direction, DpmsState,
+    FireAndForget, InsertPoint, KeyBindings, KeyCode, Notification, NotificationLevel, Opacity,
+    Region, ResizeAction, ScreenChangePolicy, ScreenDirection, Strut, WinId, WorkspaceSwitch,
 };
-use crate::screen::Screen;
-use crate::workspace::Workspace;
+use crate::drag::DropTarget;
+use crate::errors::{self, ErrorHandler, PenroseError};
+use crate::expose::{Expose, ExposeInput};
+use crate::helpers::{
+    hostname, is_descendant_of, key_binding_label, keycodes_from_xmodmap, parent_pid,
+    programs_on_path, remove_tag_bit, spawn, spawn_with_startup_id, tag_bit,
+};
+use crate::hooks::Hooks;
+use crate::ipc::{self, IpcServer};
+use crate::journal::{Entry, JournaledClient};
+use crate::layout::{grid, Layout, LayoutMessage, Slot};
+use crate::palette::{CommandPalette, PaletteAction, PaletteEntry, PaletteInput, PaletteSource};
+use crate::placement::{PlacementGrid, PlacementInput};
+use crate::rules::{Rule, SpawnRule, SpawnTrigger};
+use crate::scratchpad::ScratchpadState;
+use crate::screen::{reassign_workspaces, Screen};
+use crate::snapshot::{ClientSnapshot, ScreenSnapshot, Snapshot, WorkspaceSnapshot};
+use crate::store::{FileStore, StateStore};
+use crate::workspace::{LayoutSettings, Workspace};
 use crate::xconnection::{XConn, XEvent};
-use std::collections::HashMap;
-use std::process::{exit, Child};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::os::unix::io::RawFd;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{self, exit, Child, Command};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+// Set when re-executing as part of a restart so that the new process knows to load the
+// WindowManagerState that was saved to the StateStore
+const RESTART_STATE_ENV: &str = "PENROSE_RESTART_STATE";
+
+// The number of pixels to grow/shrink gaps by when using inc_gaps / dec_gaps
+const GAP_STEP_PX: u32 = 2;
+
+// The UrgencyHint flag in WM_HINTS (ICCCM 4.1.2.4)
+const URGENCY_HINT: u32 = 1 << 8;
+
+// Lock and Mod2 (NumLock) are reported in key press state while the keyboard is grabbed
+const IGNORED_SUBMAP_MODIFIERS: u16 = (1 << 1) | (1 << 4);
+
+/// The built in actions that can be run by name using WindowManager::run_action
+pub const NAMED_ACTIONS: &[&str] = &[
+    "next_client",
+    "previous_client",
+    "focus_left",
+    "focus_right",
+    "focus_up",
+    "focus_down",
+    "swap_left",
+    "swap_right",
+    "swap_up",
+    "swap_down",
+    "drag_client_forward",
+    "drag_client_backward",
+    "rotate_clients_forward",
+    "rotate_clients_backward",
+    "swap_with_main",
+    "reverse_clients",
+    "kill_client",
+    "kill_focused_client",
+    "toggle_fullscreen",
+    "placement_grid",
+    "toggle_sticky",
+    "toggle_follow",
+    "minimize_client",
+    "restore_minimized",
+    "cycle_minimized",
+    "toggle_workspace",
+    "focus_urgent",
+    "toggle_dnd",
+    "focus_previous_client",
+    "next_screen",
+    "previous_screen",
+    "client_to_next_screen",
+    "client_to_previous_screen",
+    "throw_left",
+    "throw_right",
+    "throw_up",
+    "throw_down",
+    "swap_screens",
+    "toggle_screen_rotation",
+    "next_layout",
+    "previous_layout",
+    "inc_main",
+    "dec_main",
+    "inc_ratio",
+    "dec_ratio",
+    "inc_gaps",
+    "dec_gaps",
+    "toggle_gaps",
+    "open_command_palette",
+    "open_program_launcher",
+    "open_window_launcher",
+    "open_workspace_launcher",
+    "expose",
+    "expose_all",
+    "reload_config",
+];
+
+// Run the given method on all registered hooks. The hooks are removed from the WindowManager
+// while they are running so that they can be given a mutable reference to it: any
+// notifications raised in the meantime are held back until the hooks have been restored.
+macro_rules! run_hooks {
+    ($method:ident, $_self:expr, $($arg:expr),*) => {{
+        let mut hooks = std::mem::take(&mut $_self.hooks);
+        $_self.running_hooks += 1;
+        for h in hooks.iter_mut() {
+            let started = Instant::now();
+            h.$method($_self, $($arg),*);
+            $_self.check_callback_budget(started, || {
+                format!("hook {}::{}", h.name(), stringify!($method))
+            });
+        }
+        $_self.running_hooks -= 1;
+        $_self.hooks = hooks;
+        if $_self.running_hooks == 0 {
+            $_self.flush_notifications();
+        }
+    }};
+}
+
+/**
+ * The state of a running WindowManager that is carried over when restarting in place.
+ * Configuration is not included as it is provided by the new process.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowManagerState {
+    /// All workspaces, including their client stacks and layout settings
+    pub workspaces: Vec<Workspace>,
+    /// All managed clients, in the order that they were mapped
+    pub clients: Vec<Client>,
+    /// The index of the workspace shown on each screen
+    pub screen_workspaces: Vec<usize>,
+    /// The index of the focused screen
+    pub focused_screen: usize,
+    /// The index of the previously focused workspace
+    pub previous_workspace: usize,
+}
+
+// A program launched with spawn_on_workspace that has not yet mapped a window
+#[derive(Debug)]
+struct PendingSpawn {
+    startup_id: String,
+    pid: u32,
+    workspace: usize,
+}
+
+// Load (and remove) the state saved by a previous process that restarted in place
+fn load_restart_state(store: &mut dyn StateStore) -> Option<WindowManagerState> {
+    env::var_os(RESTART_STATE_ENV)?;
+    env::remove_var(RESTART_STATE_ENV);
+
+    match store.load_state() {
+        Ok(state) => state,
+        Err(e) => {
+            warn!("unable to load restart state from {}: {}", store.name(), e);
+            None
+        }
+    }
+}
+
+// Apply the layout settings saved at the end of the previous session to matching workspaces
+fn restore_layout_settings(store: &mut dyn StateStore, workspaces: &mut [Workspace]) {
+    match store.load_layout_settings() {
+        Ok(settings) => workspaces.iter_mut().for_each(|ws| {
+            if let Some(s) = settings.get(ws.name()) {
+                ws.apply_layout_settings(s);
+            }
+        }),
+        Err(e) => warn!(
+            "unable to load layout settings from {}: {}",
+            store.name(),
+            e
+        ),
+    }
+}
+
+// Every workspace needs at least one layout so the defaults and any per-workspace overrides
+// are checked up front rather than each time a workspace is created
+fn check_layouts(
+    defaults: &[Layout],
+    overrides: &HashMap<&'static str, Vec<Layout>>,
+) -> errors::Result<()> {
+    if defaults.is_empty() {
+        return Err(PenroseError::NoLayouts("default layouts".into()));
+    }
+    match overrides.iter().find(|(_, layouts)| layouts.is_empty()) {
+        Some((name, _)) => Err(PenroseError::NoLayouts(name.to_string())),
+        None => Ok(()),
+    }
+}
+
+// The layouts for the workspace called `name`: its override if it has one, otherwise the defaults
+fn layouts_for<'l>(
+    name: &str,
+    defaults: &'l [Layout],
+    overrides: &'l HashMap<&'static str, Vec<Layout>>,
+) -> &'l [Layout] {
+    overrides.get(name).map_or(defaults, |layouts| layouts)
+}
+
+// Used when no StateStore is configured: exec keeps our pid so the restarted process
+// will look for the same file.
+fn default_state_store() -> Box<dyn StateStore> {
+    let path = env::temp_dir().join(format!("penrose-restart-{}.json", process::id()));
+    Box::new(FileStore::new(path))
+}
+
+// Drop any spawned child processes that have now completed
+fn reap_children(spawned: Vec<Child>) -> Vec<Child> {
+    spawned
+        .into_iter()
+        .filter_map(|mut c| {
+            match c.try_wait() {
+                Ok(None) => Some(c), // still running
+                Ok(Some(_)) => None, // clean exit
+                Err(e) => {
+                    warn!("subprocess [{}] errored: {}", c.id(), e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+// Errors in IPC requests are reported back to the client that sent them
+fn invalid_request(e: impl std::fmt::Display) -> PenroseError {
+    PenroseError::InvalidRequest(e.to_string())
+}
+
+// Key bindings from a config file are to actions by name (or external commands to spawn)
+fn file_binding(action: String) -> FireAndForget {
+    Box::new(
+        move |wm: &mut WindowManager| match action.strip_prefix("spawn ") {
+            Some(cmd) => spawn(cmd),
+            None => wm.run_action(&action).unwrap_or_else(|e| {
+                wm.notify(Notification::error(e.to_string()));
+                None
+            }),
+        },
+    )
+}
 
 /**
  * WindowManager is the primary struct / owner of the event loop ofr penrose.
@@ -20,26 +264,100 @@ pub struct WindowManager<'a> {
     conn: &'a dyn XConn,
     screens: Vec<Screen>,
     workspaces: Vec<Workspace>,
+    n_static_workspaces: usize,
     client_map: HashMap<WinId, Client>,
+    // managed clients in the order that they were mapped, for _NET_CLIENT_LIST
+    mapping_order: Vec<WinId>,
     focused_screen: usize,
     previous_workspace: usize,
     // config
     // fonts: &'static [&'static str],
     floating_classes: &'static [&'static str],
-    color_scheme: ColorScheme,
-    border_px: u32,
+    class_filter: ClassFilter,
+    client_rules: Vec<Rule>,
+    assigned: HashMap<usize, usize>,
+    insert_point: InsertPoint,
+    focus_new_clients: bool,
+    layouts: Vec<Layout>,
+    workspace_layouts: HashMap<&'static str, Vec<Layout>>,
+    remote_border_color: Option<u32>,
+    hostname: String,
+    border_style: BorderStyle,
+    border_focus: Option<WinId>,
+    tag_shown: HashSet<WinId>,
+    focus_history: Vec<WinId>,
     gap_px: u32,
     main_ratio_step: f32,
+    workspace_cleanup: Option<Duration>,
+    // when each dynamic workspace that is empty and hidden was first seen that way
+    empty_since: HashMap<usize, Instant>,
+    placement: Option<PlacementGrid>,
+    // the client that will be moved once a cell has been picked from the placement grid
+    placing: Option<WinId>,
     // systray_spacing_px: u32,
     // show_systray: bool,
     show_bar: bool,
+    bar_height: u32,
+    top_bar: bool,
     // respect_resize_hints: bool,
+    hooks: Hooks,
+    running_hooks: usize,
+    pending_notifications: VecDeque<Notification>,
+    scratchpads: Vec<ScratchpadState>,
+    store: Box<dyn StateStore>,
+    recovered: HashMap<WinId, JournaledClient>,
+    callback_budget: Option<Duration>,
+    notify_slow_callbacks: bool,
+    drag: Option<WinId>,
+    hidden: HashSet<WinId>,
+    // clients left mapped off screen by WorkspaceSwitch::Offscreen
+    parked: HashSet<WinId>,
+    // the geometry (and border width) that each window was last positioned with
+    applied: HashMap<WinId, (Region, u32)>,
+    restored: Option<WindowManagerState>,
+    ipc: Option<IpcServer>,
+    palette: Option<CommandPalette>,
+    expose: Option<Expose>,
+    exposed: Vec<WinId>,
+    actions: HashMap<String, FireAndForget>,
+    binding_codes: Vec<KeyCode>,
+    rotated_screens: HashSet<usize>,
+    urgent: Vec<WinId>,
+    dnd: bool,
+    dnd_workspace: Option<usize>,
+    opacity: Option<Opacity>,
+    submap: Option<Rc<KeyBindings>>,
+    startup_hook: Option<fn(&mut WindowManager)>,
+    spawn_rules: Vec<SpawnRule>,
+    pending_spawns: Vec<PendingSpawn>,
+    autostarted: Vec<Child>,
+    n_spawned: usize,
+    docks: HashMap<WinId, Option<Strut>>,
+    error_handler: Option<ErrorHandler>,
+    screen_change_policy: ScreenChangePolicy,
+    workspace_switch: WorkspaceSwitch,
+    warp_pointer: bool,
+    pointer_event: bool,
+    terminal_classes: &'static [&'static str],
+    swallowed: HashMap<WinId, Client>,
+    minimized: Vec<WinId>,
+    parent_pid: fn(u32) -> Option<u32>,
+    config_file: Option<PathBuf>,
+    color_scheme: ColorScheme,
+    file_bindings: HashMap<KeyCode, String>,
+    key_codes: Option<CodeMap>,
 }
 
 impl<'a> WindowManager<'a> {
     /// Initialise a new window manager instance using an existing connection to
-    /// the X server.
-    pub fn init(conf: Config, conn: &'a dyn XConn) -> WindowManager {
+    /// the X server. Fails if another window manager is running or the Config is invalid.
+    pub fn init(conf: Config, conn: &'a dyn XConn) -> errors::Result<WindowManager<'a>> {
+        check_layouts(&conf.layouts, &conf.workspace_layouts)?;
+
+        // when restarting in place we are replacing ourselves
+        let replace = conf.replace_existing_wm || env::var_os(RESTART_STATE_ENV).is_some();
+        conn.acquire_wm_selection(replace)?;
+
         let mut screens = conn.current_outputs();
         info!("connected to X server: {} screens detected", screens.len());
         for (i, s) in screens.iter().enumerate() {
@@ -50,65 +368,624 @@ impl<'a> WindowManager<'a> {
             .iter_mut()
             .for_each(|s| s.update_effective_region(conf.bar_height, conf.top_bar));
 
-        let workspaces: Vec<Workspace> = conf
+        let mut workspaces: Vec<Workspace> = conf
             .workspaces
             .iter()
-            .map(|name| Workspace::new(name, conf.layouts.clone().to_vec()))
-            .collect();
+            .map(|name| {
+                let layouts = layouts_for(name, &conf.layouts, &conf.workspace_layouts);
+                Workspace::new(*name, layouts.to_vec())
+            })
+            .collect::<errors::Result<_>>()?;
 
         conn.set_wm_properties(conf.workspaces);
 
-        WindowManager {
-            conn: conn,
+        let mut store = conf.state_store.unwrap_or_else(default_state_store);
+        let recovered = match store.open_journal() {
+            Ok(state) => {
+                info!("recovered {} clients from journal", state.len());
+                state
+            }
+            Err(e) => {
+                warn!("unable to open journal: {}", e);
+                HashMap::new()
+            }
+        };
+        let restored = load_restart_state(store.as_mut());
+        if restored.is_none() {
+            // after a restart the layouts come along with the rest of the saved state
+            restore_layout_settings(store.as_mut(), &mut workspaces);
+        }
+
+        let ipc = match conf.ipc_socket_path.map(IpcServer::bind) {
+            Some(Ok(server)) => {
+                info!("listening for IPC requests on {:?}", server.path());
+                env::set_var(ipc::SOCKET_ENV, server.path());
+                Some(server)
+            }
+            Some(Err(e)) => {
+                warn!("unable to start IPC server: {}", e);
+                None
+            }
+            None => None,
+        };
+        let (border_px, color_scheme) = (conf.border_px, conf.color_scheme);
+        let border_style = conf
+            .border_style
+            .unwrap_or_else(|| BorderStyle::from_color_scheme(border_px, &color_scheme));
+
+        Ok(WindowManager {
+            conn,
             screens,
+            n_static_workspaces: workspaces.len(),
             workspaces,
             client_map: HashMap::new(),
+            mapping_order: vec![],
             focused_screen: 0,
             previous_workspace: 0,
             // fonts: conf.fonts,
             floating_classes: conf.floating_classes,
-            color_scheme: conf.color_scheme,
-            border_px: conf.border_px,
+            class_filter: conf.class_filter,
+            client_rules: conf.client_rules,
+            assigned: HashMap::new(),
+            insert_point: conf.insert_point,
+            focus_new_clients: conf.focus_new_clients,
+            layouts: conf.layouts,
+            workspace_layouts: conf.workspace_layouts,
+            remote_border_color: conf.remote_border_color,
+            hostname: hostname().unwrap_or_default(),
+            border_style,
+            border_focus: None,
+            tag_shown: HashSet::new(),
+            focus_history: vec![],
             gap_px: conf.gap_px,
             main_ratio_step: conf.main_ratio_step,
+            workspace_cleanup: conf.workspace_cleanup,
+            empty_since: HashMap::new(),
+            placement: conf.placement_grid,
+            placing: None,
             // systray_spacing_px: conf.systray_spacing_px,
             // show_systray: conf.show_systray,
             show_bar: conf.show_bar,
+            bar_height: conf.bar_height,
+            top_bar: conf.top_bar,
             // respect_resize_hints: conf.respect_resize_hints,
+            hooks: conf.hooks,
+            running_hooks: 0,
+            pending_notifications: VecDeque::new(),
+            scratchpads: conf
+                .scratchpads
+                .into_iter()
+                .map(ScratchpadState::new)
+                .collect(),
+            store,
+            recovered,
+            callback_budget: conf.callback_budget,
+            notify_slow_callbacks: conf.notify_slow_callbacks,
+            drag: None,
+            hidden: HashSet::new(),
+            parked: HashSet::new(),
+            applied: HashMap::new(),
+            restored,
+            ipc,
+            palette: conf.command_palette,
+            expose: conf.expose,
+            exposed: vec![],
+            actions: HashMap::new(),
+            binding_codes: vec![],
+            rotated_screens: conf.rotated_screens.iter().copied().collect(),
+            urgent: vec![],
+            dnd: false,
+            dnd_workspace: conf.dnd_workspace,
+            opacity: conf.opacity,
+            submap: None,
+            startup_hook: conf.startup_hook,
+            spawn_rules: conf.spawn_rules,
+            pending_spawns: vec![],
+            autostarted: vec![],
+            n_spawned: 0,
+            docks: HashMap::new(),
+            error_handler: conf.error_handler,
+            screen_change_policy: conf.screen_change_policy,
+            workspace_switch: conf.workspace_switch,
+            warp_pointer: conf.warp_pointer,
+            pointer_event: false,
+            terminal_classes: conf.terminal_classes,
+            swallowed: HashMap::new(),
+            minimized: vec![],
+            parent_pid,
+            config_file: conf.config_file,
+            color_scheme,
+            file_bindings: HashMap::new(),
+            key_codes: None,
+        })
+    }
+
+    // Errors raised while handling events are logged and passed on to the user's error
+    // handler rather than being allowed to take down the whole window manager.
+    fn handle_error(&mut self, e: PenroseError) {
+        error!("{}", e);
+        if let Some(handler) = self.error_handler {
+            handler(self, &e);
+        }
+    }
+
+    fn apply_layout(&mut self, workspace: usize) {
+        if let Err(e) = self.try_apply_layout(workspace) {
+            self.handle_error(e);
         }
     }
 
-    fn apply_layout(&self, workspace: usize) {
+    fn try_apply_layout(&mut self, workspace: usize) -> errors::Result<()> {
         let ws = &self.workspaces[workspace];
-        let lc = ws.layout_conf();
+        let lc = ws.layout_conf()?;
         if lc.floating {
-            return;
+            let ids: Vec<WinId> = ws.iter().copied().collect();
+            ids.iter().for_each(|id| self.show_hidden_client(*id));
+            return Ok(());
+        }
+
+        let screen = self
+            .screens
+            .iter()
+            .position(|s| s.wix == workspace)
+            .ok_or(PenroseError::WorkspaceNotVisible(workspace))?;
+        let border = ws.border_px(self.border_style.width, &self.client_map);
+        let actions = self.arrange_for_screen(ws, screen)?;
+
+        if lc.hide_unpositioned {
+            let client_map = &self.client_map;
+            let hide: Vec<WinId> = ws
+                .iter()
+                .filter(|id| !actions.iter().any(|(a, _)| a == *id))
+                .filter(|id| client_map.get(id).is_some_and(|c| !c.is_fullscreen()))
+                .copied()
+                .collect();
+            for id in hide {
+                self.conn.unmap_window(id);
+                self.hidden.insert(id);
+            }
+        }
+
+        // only clients that have actually moved need to be reconfigured
+        let mut changed = false;
+        for (id, r) in actions {
+            self.show_hidden_client(id);
+            if self.applied.get(&id) != Some(&(r, border)) {
+                debug!("configuring {} with {:?}", id, r);
+                self.position_client(id, r, border);
+                changed = true;
+            }
+        }
+        if changed {
+            self.conn.flush();
+        }
+
+        // the client in the main area may have changed
+        if self.border_style.alternate.is_some() {
+            self.workspaces[workspace]
+                .iter()
+                .for_each(|id| self.refresh_border(*id));
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Arrange a workspace for display on the given screen. Layouts on rotated screens are run
+     * with the axes swapped (and the result swapped back) so that, for example, side_stack
+     * places the main area at the top of the screen rather than on the left.
+     */
+    fn arrange_for_screen(
+        &self,
+        ws: &Workspace,
+        screen: usize,
+    ) -> errors::Result<Vec<ResizeAction>> {
+        let r = self.usable_screen_region(screen);
+        let extra: Vec<WinId> = self
+            .tagged_clients_for_screen(screen)
+            .into_iter()
+            .filter(|id| {
+                self.client_map
+                    .get(id)
+                    .is_some_and(|c| !c.is_floating() && !c.is_fullscreen())
+            })
+            .collect();
+        if !self.rotated_screens.contains(&screen) {
+            return ws.arrange_with(
+                &extra,
+                &r,
+                &self.client_map,
+                self.gap_px,
+                self.border_style.width,
+            );
+        }
+
+        let actions = ws.arrange_with(
+            &extra,
+            &r.transpose(),
+            &self.client_map,
+            self.gap_px,
+            self.border_style.width,
+        )?;
+
+        Ok(actions
+            .into_iter()
+            .map(|(id, r)| (id, r.transpose()))
+            .collect())
+    }
+
+    // Map a client that was hidden by a layout using LayoutConf::hide_unpositioned
+    fn show_hidden_client(&mut self, id: WinId) {
+        if self.hidden.remove(&id) {
+            self.conn.map_window(id);
+        }
+    }
+
+    // The number of clients on a workspace that are currently hidden by its layout
+    fn n_hidden_clients(&self, workspace: usize) -> usize {
+        self.workspaces[workspace]
+            .iter()
+            .filter(|id| self.hidden.contains(id))
+            .count()
+    }
+
+    // The region available to clients on a screen: this leaves space for the bar unless the
+    // workspace shown on the screen has hidden it, along with any space reserved by docks.
+    fn usable_screen_region(&self, screen_index: usize) -> Region {
+        let s = &self.screens[screen_index];
+        let effective_only = self.show_bar && !self.workspaces[s.wix].bar_hidden();
+        let struts: Vec<Strut> = self.docks.values().flatten().copied().collect();
+        if struts.is_empty() {
+            return *s.region(effective_only);
+        }
+
+        s.usable_region(effective_only, &struts, &self.root_region())
+    }
+
+    // The region covered by the root window: the bounding box of all screens
+    fn root_region(&self) -> Region {
+        let (w, h) = self.screens.iter().fold((0, 0), |(w, h), s| {
+            let (x, y, sw, sh) = s.true_region.values();
+            (w.max(x + sw), h.max(y + sh))
+        });
+        Region::new(0, 0, w, h)
+    }
+
+    // Docks and external status bars are never managed but the space they reserve is
+    // removed from the screens they are on.
+    fn add_dock(&mut self, id: WinId) {
+        let strut = self.conn.window_strut(id);
+        debug!("tracking dock {} with strut {:?}", id, strut);
+        self.docks.insert(id, strut);
+        self.conn.map_window(id);
+        self.layout_visible_workspaces();
+    }
+
+    fn remove_dock(&mut self, id: WinId) {
+        debug!("dock {} removed", id);
+        self.docks.remove(&id);
+        self.layout_visible_workspaces();
+    }
+
+    fn layout_visible_workspaces(&mut self) {
+        for wix in self.screens.iter().map(|s| s.wix).collect::<Vec<_>>() {
+            self.apply_layout(wix);
+        }
+    }
+
+    // Position a client window and cache the region so it can be queried without a round
+    // trip to the X server.
+    fn position_client(&mut self, id: WinId, r: Region, border: u32) {
+        self.place_window(id, r, border);
+        if let Some(c) = self.client_map.get_mut(&id) {
+            c.set_region(r);
+            if c.is_floating() && !c.is_fullscreen() {
+                self.journal(Entry::Floating(id, r));
+            }
         }
+    }
 
-        let s = self.screens.iter().find(|s| s.wix == workspace).unwrap();
-        let gpx = if lc.gapless { 0 } else { self.gap_px };
-        let padding = 2 * (self.border_px + gpx);
+    // Move a window without updating the region stored for its client (if it has one)
+    fn place_window(&mut self, id: WinId, r: Region, border: u32) {
+        self.conn.position_window(id, r, border);
+        self.applied.insert(id, (r, border));
+    }
 
-        for (id, region) in ws.arrange(s.region(self.show_bar), &self.client_map) {
-            debug!("configuring {} with {:?}", id, region);
-            let (x, y, w, h) = region.values();
-            let r = Region::new(x + gpx, y + gpx, w - padding, h - padding);
-            self.conn.position_window(id, r, self.border_px);
+    fn journal(&mut self, entry: Entry) {
+        if let Err(e) = self.store.record(entry) {
+            warn!("unable to record journal entry: {}", e);
         }
     }
 
     fn remove_client(&mut self, win_id: WinId) {
         match self.client_map.get(&win_id) {
             Some(client) => {
-                self.workspaces[client.workspace()].remove_client(win_id);
-                self.client_map.remove(&win_id).map(|c| {
+                let wix = client.workspace();
+                if !self.restore_swallowed(win_id, wix) {
+                    self.workspaces[wix].remove_client(win_id);
+                }
+                if let Some(c) = self.client_map.remove(&win_id) {
                     debug!("removing ref to client {} ({})", c.id(), c.class());
-                });
+                }
+                self.mapping_order.retain(|id| *id != win_id);
+                self.client_map
+                    .values_mut()
+                    .filter(|c| c.transient_for() == Some(win_id))
+                    .for_each(|c| c.set_transient_for(None));
+                self.hidden.remove(&win_id);
+                self.parked.remove(&win_id);
+                self.applied.remove(&win_id);
+                self.minimized.retain(|id| *id != win_id);
+                self.urgent.retain(|id| *id != win_id);
+                self.focus_history.retain(|id| *id != win_id);
+                self.journal(Entry::Removed(win_id));
+                self.update_x_client_list();
+                run_hooks!(remove_client, self, win_id);
             }
             None => warn!("attempt to remove unknown client {}", win_id),
         }
     }
 
+    /*
+     * A terminal that the program owning this new window was run from, if there is one. The
+     * process tree is walked up from the window's _NET_WM_PID to find a managed terminal from
+     * Config::terminal_classes. Terminals never swallow other terminals.
+     */
+    fn swallowing_terminal(&self, client: &Client) -> Option<WinId> {
+        if self.terminal_classes.is_empty()
+            || client.is_floating()
+            || self.terminal_classes.contains(&client.class())
+        {
+            return None;
+        }
+
+        let pid = self.conn.atom_prop(client.id(), "_NET_WM_PID").ok()?;
+        self.client_map
+            .values()
+            .filter(|c| !c.is_floating() && self.terminal_classes.contains(&c.class()))
+            .find(|c| match self.conn.atom_prop(c.id(), "_NET_WM_PID") {
+                Ok(term_pid) => is_descendant_of(pid, term_pid, self.parent_pid),
+                Err(_) => false,
+            })
+            .map(|c| c.id())
+    }
+
+    // Hide a terminal and put the client that it launched in its place in the stack
+    fn swallow(&mut self, term: WinId, id: WinId) {
+        let terminal = match self.client_map.remove(&term) {
+            Some(c) => c,
+            None => return,
+        };
+
+        debug!("client {} is swallowing terminal {}", id, term);
+        self.workspaces[terminal.workspace()].replace_client(term, id);
+        self.hidden.remove(&term);
+        self.mapping_order.retain(|c| *c != term);
+        self.urgent.retain(|c| *c != term);
+        self.focus_history.retain(|c| *c != term);
+        self.conn.unmap_window(term);
+        self.swallowed.insert(id, terminal);
+    }
+
+    // Put a swallowed terminal back in place of the client that swallowed it, returning
+    // whether or not there was one.
+    fn restore_swallowed(&mut self, id: WinId, wix: usize) -> bool {
+        let mut terminal = match self.swallowed.remove(&id) {
+            Some(c) => c,
+            None => return false,
+        };
+
+        let term = terminal.id();
+        debug!("restoring terminal {} swallowed by {}", term, id);
+        if !self.workspaces[wix].replace_client(id, term) {
+            self.workspaces[wix].add_client(term, InsertPoint::First);
+        }
+        terminal.set_workspace(wix);
+        self.client_map.insert(term, terminal);
+        self.mapping_order.push(term);
+        self.conn.set_client_workspace(term, wix);
+        if self.screen_for_workspace(wix).is_some() {
+            self.conn.map_window(term);
+        }
+
+        true
+    }
+
+    // Swallowed terminals are not part of our saved state so make sure that they are not left
+    // hidden when we stop managing windows.
+    fn release_swallowed(&mut self) {
+        for (_, terminal) in self.swallowed.drain() {
+            self.conn.map_window(terminal.id());
+        }
+    }
+
+    // _NET_CLIENT_LIST is expected to be in mapping order (oldest first)
+    fn update_x_client_list(&self) {
+        self.conn.update_client_list(&self.mapping_order);
+    }
+
+    // Publish the current set of workspaces to X and any registered hooks
+    fn workspaces_updated(&mut self) {
+        let names: Vec<String> = self
+            .workspace_names()
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let names: Vec<&str> = names.iter().map(|s| s.as_str()).collect();
+        let active = self.active_ws_index();
+        self.conn.update_desktops(&names);
+        self.conn.set_current_workspace(active);
+        run_hooks!(workspaces_updated, self, &names, active);
+    }
+
+    // Give X input focus to the focused client of the active workspace (if there is one)
+    fn focus_active_workspace_client(&mut self) {
+        match self.workspaces[self.active_ws_index()].focused_client() {
+            Some(id) => self.handle_enter_notify(id),
+            None => self.conn.clear_focus(),
+        }
+        self.warp_pointer_to_focus();
+    }
+
+    /*
+     * Move the pointer to the middle of the focused client, or the middle of the focused
+     * screen if there is nothing to focus, so that the mouse follows focus changes made from
+     * the keyboard. Focus changes caused by the pointer itself leave it where it is.
+     */
+    fn warp_pointer_to_focus(&self) {
+        if !self.warp_pointer || self.pointer_event {
+            return;
+        }
+
+        match self.focused_client_id() {
+            Some(id) => self.conn.warp_cursor(Some(id)),
+            None => {
+                let (x, y, w, h) = self.screens[self.focused_screen].true_region.values();
+                self.conn.warp_cursor_to_point(x + w / 2, y + h / 2);
+            }
+        }
+    }
+
+    /*
+     * Move a client to the workspace at 'index', mapping or unmapping it as required
+     * depending on whether the target workspace is currently visible.
+     */
+    fn move_client_to_workspace(&mut self, id: WinId, index: usize) {
+        let prev = match self.transfer_client(id, index) {
+            Some(prev) => prev,
+            None => return,
+        };
+
+        let transients = self.transients_of(id);
+        if self.screen_for_workspace(index).is_none() {
+            self.conn.unmap_window(id);
+            transients.iter().for_each(|t| self.conn.unmap_window(*t));
+        } else {
+            self.apply_layout(index);
+            for t in transients {
+                if let Some(r) = self.dialog_region(t) {
+                    self.position_client(t, r, self.border_style.width);
+                }
+            }
+        }
+        if self.screen_for_workspace(prev).is_some() {
+            self.apply_layout(prev);
+        }
+    }
+
+    // The clients that are mapped and unmapped along with a workspace: its stack and any dialogs
+    // that are transients for clients in it
+    fn mapped_with_workspace(&self, wix: usize) -> Vec<WinId> {
+        let mut ids: Vec<WinId> = self.workspaces[wix].iter().copied().collect();
+        let transients: Vec<WinId> = ids.iter().flat_map(|id| self.transients_of(*id)).collect();
+        ids.extend(transients);
+        ids
+    }
+
+    // The clients that are transients for the client with the given ID
+    fn transients_of(&self, id: WinId) -> Vec<WinId> {
+        self.client_map
+            .values()
+            .filter(|c| c.transient_for() == Some(id))
+            .map(|c| c.id())
+            .collect()
+    }
+
+    // Where a dialog should be placed: centered over the client it is a transient for or over
+    // the screen showing its workspace if it does not have one (or it has not been positioned)
+    fn dialog_region(&self, id: WinId) -> Option<Region> {
+        let c = self.client_map.get(&id)?;
+        let parent = c
+            .transient_for()
+            .and_then(|p| self.client_map.get(&p))
+            .and_then(|p| p.region());
+        let outer = match parent {
+            Some(r) => r,
+            None => self.screen_for_workspace(c.workspace())?.effective_region,
+        };
+
+        Some(c.region()?.centered_in(&outer))
+    }
+
+    // Move a client to the top of the stack for the workspace at 'index' and focus it there
+    // without touching what is mapped, returning the workspace that it was moved from.
+    fn transfer_client(&mut self, id: WinId, index: usize) -> Option<usize> {
+        let prev = match self.client_map.get_mut(&id) {
+            Some(c) if c.workspace() != index => {
+                let prev = c.workspace();
+                c.set_workspace(index);
+                prev
+            }
+            _ => return None,
+        };
+
+        if self.workspaces[prev].remove_client(id).is_some() {
+            self.workspaces[index].add_client(id, InsertPoint::First);
+            self.workspaces[index].focus_client(id);
+        }
+        self.conn.set_client_workspace(id, index);
+        self.journal(Entry::Workspace(id, index));
+        for t in self.transients_of(id) {
+            self.transfer_client(t, index);
+        }
+
+        Some(prev)
+    }
+
+    // Move clients to the end of the given workspace, preserving their relative order
+    fn move_clients_to_workspace(&mut self, ids: &[WinId], index: usize) {
+        let dst_visible = self.screen_for_workspace(index).is_some();
+        let mut affected = vec![index];
+
+        for id in ids {
+            self.set_fullscreen(*id, false);
+            let prev = match self.client_map.get_mut(id) {
+                Some(c) if c.workspace() != index => {
+                    let prev = c.workspace();
+                    c.set_workspace(index);
+                    prev
+                }
+                _ => continue,
+            };
+
+            if self.workspaces[prev].remove_client(*id).is_some() {
+                self.workspaces[index].append_client(*id);
+            }
+            self.conn.set_client_workspace(*id, index);
+            self.journal(Entry::Workspace(*id, index));
+
+            match (self.screen_for_workspace(prev).is_some(), dst_visible) {
+                (true, false) => self.conn.unmap_window(*id),
+                (false, true) => self.map_client(*id),
+                _ => (),
+            }
+            if !affected.contains(&prev) {
+                affected.push(prev);
+            }
+        }
+
+        for wix in affected {
+            if self.screen_for_workspace(wix).is_some() {
+                self.apply_layout(wix);
+            }
+        }
+        self.focus_active_workspace_client();
+    }
+
+    // All clients on a workspace: tiled clients in stack order followed by floating clients
+    fn clients_on_workspace(&self, index: usize) -> Vec<WinId> {
+        let mut ids: Vec<WinId> = self.workspaces[index].iter().copied().collect();
+        let mut floating: Vec<WinId> = self
+            .client_map
+            .values()
+            .filter(|c| c.workspace() == index && !ids.contains(&c.id()))
+            .map(|c| c.id())
+            .collect();
+        floating.sort_unstable();
+        ids.extend(floating);
+        ids
+    }
+
     /*
      * Helpers for indexing into WindowManager state
      */
@@ -127,10 +1004,37 @@ impl<'a> WindowManager<'a> {
 
     fn cycle_layout(&mut self, direction: Direction) {
         let wix = self.active_ws_index();
-        self.workspace_for_screen_mut(self.focused_screen)
-            .cycle_layout(direction);
+        let symbol = match self
+            .workspace_for_screen_mut(self.focused_screen)
+            .cycle_layout(direction)
+        {
+            Ok(symbol) => symbol.to_string(),
+            Err(e) => return self.handle_error(e),
+        };
         self.apply_layout(wix);
-        info!("ACTIVE_LAYOUT {}", self.workspaces[wix].layout_symbol());
+        info!("ACTIVE_LAYOUT {}", symbol);
+        run_hooks!(layout_change, self, wix, self.focused_screen);
+        self.notify_layout(wix);
+    }
+
+    // Let the user know which layout is now active for the workspace at `wix`
+    fn notify_layout(&mut self, wix: usize) {
+        let ws = &self.workspaces[wix];
+        let msg = format!(
+            "{}: layout switched to \"{}\"",
+            ws.name(),
+            ws.layout_symbol().unwrap_or_default()
+        );
+        self.notify(Notification::info(msg));
+    }
+
+    // Report an error if `index` is not a valid workspace index, returning whether it was valid
+    fn check_workspace_index(&mut self, index: usize) -> bool {
+        if index < self.workspaces.len() {
+            return true;
+        }
+        self.notify(Notification::error(format!("no such workspace: {}", index)));
+        false
     }
 
     fn update_max_main(&mut self, change: Change) {
@@ -146,6 +1050,48 @@ impl<'a> WindowManager<'a> {
         self.apply_layout(self.active_ws_index());
     }
 
+    fn next_screen_index(&self, direction: Direction) -> usize {
+        let n = self.screens.len();
+        match direction {
+            Direction::Forward => (self.focused_screen + 1) % n,
+            Direction::Backward => (self.focused_screen + n - 1) % n,
+        }
+    }
+
+    fn cycle_screen(&mut self, direction: Direction) {
+        if self.screens.len() < 2 {
+            return;
+        }
+        self.focused_screen = self.next_screen_index(direction);
+        self.conn.set_current_workspace(self.active_ws_index());
+        self.focus_active_workspace_client();
+        run_hooks!(screen_change, self, self.focused_screen);
+    }
+
+    fn client_to_screen(&mut self, direction: Direction) {
+        if self.screens.len() < 2 {
+            return;
+        }
+        let wix = self.screens[self.next_screen_index(direction)].wix;
+        if let Some(id) = self.workspaces[self.active_ws_index()].focused_client() {
+            self.move_client_to_workspace(id, wix);
+            self.focus_active_workspace_client();
+        }
+    }
+
+    // The nearest screen to the focused screen in the given direction
+    fn screen_in_direction(&self, direction: ScreenDirection) -> Option<usize> {
+        let regions: Vec<Region> = self.screens.iter().map(|s| s.true_region).collect();
+        regions[self.focused_screen].nearest_in_direction(&regions, direction)
+    }
+
+    fn update_gaps(&mut self, change: Change) {
+        let gap_px = self.gap_px;
+        self.workspace_for_screen_mut(self.focused_screen)
+            .update_gaps(change, GAP_STEP_PX, gap_px);
+        self.apply_layout(self.active_ws_index());
+    }
+
     fn focused_client(&self) -> Option<&Client> {
         self.workspaces[self.active_ws_index()]
             .focused_client()
@@ -159,17 +1105,101 @@ impl<'a> WindowManager<'a> {
         if let Some((prev, new)) = cycled {
             self.handle_leave_notify(prev); // treat like losing x focus
             self.handle_enter_notify(new); // treat like gaining x focus
-            self.conn.warp_cursor(Some(new));
+            self.warp_pointer_to_focus();
         }
     }
 
+    fn screen_for_workspace(&self, wix: usize) -> Option<&Screen> {
+        self.screens.iter().find(|s| s.wix == wix)
+    }
+
+    fn set_fullscreen(&mut self, id: WinId, should_fullscreen: bool) {
+        let (wix, floating) = match self.client_map.get(&id) {
+            Some(c) if c.is_fullscreen() != should_fullscreen => (c.workspace(), c.is_floating()),
+            _ => return,
+        };
+
+        if should_fullscreen {
+            if let Some(prev) = self.workspaces[wix].fullscreen_client() {
+                self.set_fullscreen(prev, false);
+            }
+
+            let r = match self.screen_for_workspace(wix) {
+                Some(s) => *s.region(false),
+                None => *self.screens[self.focused_screen].region(false),
+            };
+            let prev_region = self.conn.window_geometry(id).unwrap_or(r);
+            self.workspaces[wix].set_fullscreen(id, prev_region);
+            self.conn.toggle_client_fullscreen(id, true);
+            self.set_client_fullscreen_flag(id, true);
+            self.position_client(id, r, 0);
+            self.conn.raise_window(id);
+        } else {
+            self.conn.toggle_client_fullscreen(id, false);
+            self.set_client_fullscreen_flag(id, false);
+            if let Some((_, prev_region)) = self.workspaces[wix].clear_fullscreen() {
+                if floating {
+                    self.position_client(id, prev_region, self.border_style.width);
+                }
+            }
+        }
+
+        if self.screen_for_workspace(wix).is_some() {
+            self.apply_layout(wix);
+        }
+    }
+
+    // The flag is updated before the client is positioned so that only non-fullscreen
+    // floating geometry ends up in the journal
+    fn set_client_fullscreen_flag(&mut self, id: WinId, fullscreen: bool) {
+        if let Some(c) = self.client_map.get_mut(&id) {
+            c.set_fullscreen(fullscreen);
+        }
+    }
+
+    // Capture a newly mapped window as the client for a pending scratchpad if the class matches
+    fn capture_scratchpad_client(&mut self, win_id: WinId, class_parts: &[&str]) -> bool {
+        let focused_region = self.usable_screen_region(self.focused_screen);
+        let wix = self.active_ws_index();
+        let s = match self
+            .scratchpads
+            .iter_mut()
+            .find(|s| s.pending && class_parts.contains(&s.conf.class))
+        {
+            Some(s) => s,
+            None => return false,
+        };
+
+        debug!("capturing {} as scratchpad '{}'", win_id, s.conf.name);
+        let mut client = Client::new(win_id, s.conf.class.into(), wix, true);
+        let r = s.conf.initial_region(&focused_region);
+        client.set_region(r);
+        s.client = Some(client);
+        s.pending = false;
+        s.visible = true;
+
+        self.conn.mark_new_window(win_id);
+        self.place_window(win_id, r, self.border_style.width);
+        self.conn.raise_window(win_id);
+        self.handle_enter_notify(win_id);
+        true
+    }
+
     fn drag_client(&mut self, direction: Direction) {
-        if let Some(id) = self.focused_client().and_then(|c| Some(c.id())) {
+        self.restack(|ws| {
+            ws.drag_client(direction);
+        });
+    }
+
+    // Rearrange the stack of the active workspace, keeping focus (and the pointer) on the
+    // focused client as it moves
+    fn restack(&mut self, f: impl FnOnce(&mut Workspace)) {
+        if let Some(id) = self.focused_client().map(|c| c.id()) {
             let wix = self.active_ws_index();
-            self.workspaces[wix].drag_client(direction);
+            f(&mut self.workspaces[wix]);
             self.apply_layout(wix);
             self.handle_enter_notify(id); // treat like gaining x focus
-            self.conn.warp_cursor(Some(id));
+            self.warp_pointer_to_focus();
         }
     }
 
@@ -179,50 +1209,225 @@ impl<'a> WindowManager<'a> {
      * mapped to a handler
      */
     pub fn grab_keys_and_run(&mut self, bindings: KeyBindings) {
-        // TODO: need to be smarter about this. This will also map all of the systray apps
-        //       as tiled windows currently.
-        // for id in self.conn.query_for_active_windows() {
-        //     self.handle_map_notify(id, false);
-        // }
-
-        self.conn.grab_keys(&bindings);
-        self.focus_workspace(0);
+        self.start(&bindings);
 
         let mut spawned = Vec::new();
+        let poll_fds = self.poll_fds();
 
         loop {
-            if let Some(event) = self.conn.wait_for_event() {
-                match event {
-                    XEvent::KeyPress { code } => {
-                        self.handle_key_press(code, &bindings, &mut spawned)
+            spawned.append(&mut self.autostarted);
+            match &poll_fds {
+                // without IPC or workspace cleanup there is only the X connection to block on
+                None => {
+                    if let Some(event) = self.conn.wait_for_event() {
+                        self.handle_event(event, &bindings, &mut spawned);
+                    }
+                }
+                Some(fds) => {
+                    self.handle_ipc_requests();
+                    while let Some(event) = self.conn.poll_for_event() {
+                        self.handle_event(event, &bindings, &mut spawned);
                     }
-                    XEvent::Map { window, ignore } => self.handle_map_notify(window, ignore),
-                    XEvent::Enter { window } => self.handle_enter_notify(window),
-                    XEvent::Leave { window } => self.handle_leave_notify(window),
-                    XEvent::Destroy { window } => self.handle_destroy_notify(window),
-                    // XEvent::ButtonPress => self.handle_button_press(),
-                    // XEvent::ButtonRelease => self.handle_button_release(),
-                    _ => (),
+                    let next_cleanup = self.remove_empty_workspaces();
+                    self.conn.flush();
+                    ipc::wait_for_readable(fds, next_cleanup);
                 }
             }
 
             self.conn.flush();
+            spawned = reap_children(spawned);
+        }
+    }
 
-            // reap any spawned child processes that have now completed
-            spawned = spawned
-                .into_iter()
-                .filter_map(|mut c| {
-                    match c.try_wait() {
-                        Ok(None) => Some(c), // still running
-                        Ok(Some(_)) => None, // clean exit
-                        Err(e) => {
-                            warn!("subprocess [{}] errored: {}", c.id(), e);
-                            None
-                        }
-                    }
-                })
-                .collect();
+    /**
+     * Start up in the same way as grab_keys_and_run but then, rather than waiting for new
+     * events forever, return once every event that is already queued on the connection has
+     * been handled. This is intended for driving the WindowManager from a scripted connection
+     * such as MockXConn in tests. Returns any processes that were spawned and are still running.
+     */
+    pub fn run_until_idle(&mut self, bindings: KeyBindings) -> Vec<Child> {
+        self.start(&bindings);
+
+        let mut spawned = Vec::new();
+        self.handle_ipc_requests();
+        while let Some(event) = self.conn.poll_for_event() {
+            spawned.append(&mut self.autostarted);
+            self.handle_event(event, &bindings, &mut spawned);
+        }
+        spawned.append(&mut self.autostarted);
+        self.remove_empty_workspaces();
+        self.conn.flush();
+
+        reap_children(spawned)
+    }
+
+    // Adopt existing windows, grab the key bindings and show the first workspace
+    fn start(&mut self, bindings: &KeyBindings) {
+        // TODO: need to be smarter about this. This will also map all of the systray apps
+        //       as tiled windows currently.
+        // for id in self.conn.query_for_active_windows() {
+        //     self.handle_map_notify(id, false);
+        // }
+        for id in self.conn.query_for_docks() {
+            self.add_dock(id);
+        }
+        let restarted = self.adopt_restored_windows();
+        self.adopt_journaled_windows();
+
+        self.conn.grab_keys(bindings);
+        self.binding_codes = bindings.keys().copied().collect();
+        if let Some(path) = self.config_file.clone() {
+            // everything else in the file was applied to the Config we were given
+            match ConfigFile::load(&path).and_then(|f| self.bind_file_keys(&f)) {
+                Ok(()) => (),
+                Err(e) => self.handle_error(e),
+            }
+        }
+        if restarted {
+            // everything was launched by the process we replaced
+            self.spawn_rules.clear();
+            self.focus_active_workspace_client();
+        } else {
+            self.focus_workspace(0);
+            self.autostart();
+        }
+    }
+
+    // The file descriptors to wait on in the main loop when IPC or workspace cleanup is enabled
+    fn poll_fds(&self) -> Option<Vec<RawFd>> {
+        if self.ipc.is_none() && self.workspace_cleanup.is_none() {
+            return None;
+        }
+        match self.conn.connection_fd() {
+            Some(fd) => Some(
+                std::iter::once(fd)
+                    .chain(self.ipc.as_ref().map(|s| s.fd()))
+                    .collect(),
+            ),
+            None => {
+                warn!("X connection can not be polled: IPC and workspace cleanup are disabled");
+                None
+            }
+        }
+    }
+
+    fn handle_event(&mut self, event: XEvent, bindings: &KeyBindings, spawned: &mut Vec<Child>) {
+        self.pointer_event = matches!(
+            event,
+            XEvent::Enter { .. }
+                | XEvent::Leave { .. }
+                | XEvent::ButtonPress { .. }
+                | XEvent::ButtonRelease { .. }
+                | XEvent::Motion { .. }
+        );
+
+        match event {
+            XEvent::KeyPress { code } => self.handle_key_press(code, bindings, spawned),
+            XEvent::Map { window, ignore } => self.handle_map_notify(window, ignore),
+            XEvent::Enter { window } => self.handle_enter_notify(window),
+            XEvent::Leave { window } => self.handle_leave_notify(window),
+            XEvent::Destroy { window } => self.handle_destroy_notify(window),
+            XEvent::ClientMessage {
+                window,
+                dtype,
+                data,
+            } => self.handle_client_message(window, &dtype, &data),
+            XEvent::RandrNotify => self.detect_screens(),
+            XEvent::ButtonPress {
+                window,
+                button,
+                x,
+                y,
+            } => self.handle_button_press(window, button, x, y),
+            XEvent::ButtonRelease { x, y } => self.handle_button_release(x, y),
+            XEvent::Motion { x, y } => self.handle_motion_notify(x, y),
+            XEvent::PropertyNotify { window, atom } => self.handle_property_notify(window, &atom),
+            XEvent::Replaced => self.handle_replaced(),
+        }
+    }
+
+    // Another window manager has taken over the screen: leave every client mapped so that it
+    // can find them and then get out of the way.
+    fn handle_replaced(&mut self) {
+        info!("replaced by another window manager: exiting");
+        let ids: Vec<WinId> = self.client_map.keys().copied().collect();
+        for id in ids {
+            self.conn.map_window(id);
+        }
+        self.release_swallowed();
+        self.exit();
+    }
+
+    fn handle_ipc_requests(&mut self) {
+        let requests = match &self.ipc {
+            Some(server) => server.pending_requests(),
+            None => return,
+        };
+
+        for (request, stream) in requests {
+            debug!("handling IPC request: {}", request);
+            let response = self
+                .run_ipc_request(&request)
+                .unwrap_or_else(|e| format!("error: {}", e));
+            ipc::respond(stream, &response);
+        }
+    }
+
+    // Parse a workspace index argument for an IPC request
+    fn ipc_workspace_index(&self, arg: Option<&&str>) -> errors::Result<usize> {
+        let arg = arg.ok_or_else(|| invalid_request("a workspace index is required"))?;
+        match arg.parse() {
+            Ok(i) if i < self.workspaces.len() => Ok(i),
+            _ => Err(invalid_request(format!("invalid workspace index: {}", arg))),
+        }
+    }
+
+    /*
+     * Run a single IPC request (see the ipc module for the protocol) and return the response.
+     * Actions map directly on to the public WindowManager method of the same name.
+     */
+    fn run_ipc_request(&mut self, request: &str) -> errors::Result<String> {
+        let (action, args) =
+            ipc::parse_request(request).ok_or_else(|| invalid_request("empty request"))?;
+
+        match action {
+            "state" => return serde_json::to_string(&self.state()).map_err(invalid_request),
+            "snapshot" => return serde_json::to_string(&self.snapshot()).map_err(invalid_request),
+            "focus_workspace" => self.focus_workspace(self.ipc_workspace_index(args.first())?),
+            "client_to_workspace" => {
+                self.client_to_workspace(self.ipc_workspace_index(args.first())?)
+            }
+            "client_to_workspace_and_follow" => {
+                self.client_to_workspace_and_follow(self.ipc_workspace_index(args.first())?)
+            }
+            "delete_workspace" => self.delete_workspace(self.ipc_workspace_index(args.first())?)?,
+            "rename_workspace" => {
+                let index = self.ipc_workspace_index(args.first())?;
+                let name = args[1..].join(" ");
+                if name.is_empty() {
+                    return Err(invalid_request("a workspace name is required"));
+                }
+                self.rename_workspace(index, name);
+            }
+            "create_workspace" => match args.join(" ") {
+                name if name.is_empty() => {
+                    return Err(invalid_request("a workspace name is required"))
+                }
+                name => return Ok(self.create_workspace(name).to_string()),
+            },
+            "toggle_scratchpad" => {
+                let name = args
+                    .first()
+                    .ok_or_else(|| invalid_request("a scratchpad name is required"))?;
+                self.toggle_scratchpad(name);
+            }
+            _ => {
+                self.run_action(action)?;
+            }
         }
+
+        self.conn.flush();
+        Ok(ipc::OK.to_string())
     }
 
     /*
@@ -239,10 +1444,162 @@ impl<'a> WindowManager<'a> {
         bindings: &KeyBindings,
         spawned: &mut Vec<Child>,
     ) {
+        if self.palette.as_ref().is_some_and(|p| p.is_open()) {
+            self.handle_palette_key_press(key_code, bindings, spawned);
+            return;
+        }
+
+        if self.expose.as_ref().is_some_and(|e| e.is_open()) {
+            self.handle_expose_key_press(key_code);
+            return;
+        }
+
+        if self.placement_grid_is_open() {
+            self.handle_placement_key_press(key_code);
+            return;
+        }
+
+        if self.submap.is_some() {
+            self.handle_submap_key_press(key_code, spawned);
+            return;
+        }
+
         if let Some(action) = bindings.get(&key_code) {
-            debug!("handling key code: {:?}", key_code);
-            if let Some(child) = action(self) {
-                spawned.push(child);
+            self.run_key_binding(key_code, action, spawned);
+        } else if let Some(name) = self.file_bindings.get(&key_code) {
+            let action = file_binding(name.clone());
+            self.run_key_binding(key_code, &action, spawned);
+        }
+    }
+
+    fn run_key_binding(
+        &mut self,
+        key_code: KeyCode,
+        action: &FireAndForget,
+        spawned: &mut Vec<Child>,
+    ) {
+        debug!("handling key code: {:?}", key_code);
+        let started = Instant::now();
+        let child = action(self);
+        self.check_callback_budget(started, || format!("key binding {:?}", key_code));
+        if let Some(child) = child {
+            spawned.push(child);
+        }
+    }
+
+    // While a submap is active the next (non-modifier) key press either runs the matching
+    // binding from the submap or cancels it. Either way the submap is left first so that
+    // bindings are free to enter another one.
+    fn handle_submap_key_press(&mut self, key_code: KeyCode, spawned: &mut Vec<Child>) {
+        if self.conn.modifier_keycodes().contains(&key_code.code) {
+            return;
+        }
+
+        let submap = match self.submap.take() {
+            Some(submap) => submap,
+            None => return,
+        };
+        self.conn.ungrab_keyboard();
+
+        let key_code = KeyCode {
+            mask: key_code.mask & !IGNORED_SUBMAP_MODIFIERS,
+            code: key_code.code,
+        };
+        match submap.get(&key_code) {
+            Some(action) => self.run_key_binding(key_code, action, spawned),
+            None => debug!("{:?} is not bound in the active submap: leaving", key_code),
+        }
+    }
+
+    // While the command palette is open it receives every key press
+    fn handle_palette_key_press(
+        &mut self,
+        key_code: KeyCode,
+        bindings: &KeyBindings,
+        spawned: &mut Vec<Child>,
+    ) {
+        let input = match self.palette.as_mut() {
+            Some(p) => p.handle_key(key_code),
+            None => return,
+        };
+
+        let action = match input {
+            PaletteInput::Continue => return,
+            PaletteInput::Cancel => return self.close_command_palette(),
+            PaletteInput::Run(action) => action,
+        };
+
+        self.close_command_palette();
+        match action {
+            PaletteAction::Binding(code) => self.handle_key_press(code, bindings, spawned),
+            PaletteAction::Named(name) => match self.run_action(&name) {
+                Ok(child) => spawned.extend(child),
+                Err(e) => self.notify(Notification::error(e.to_string())),
+            },
+            PaletteAction::Spawn(cmd) => spawned.extend(spawn(cmd)),
+            PaletteAction::FocusClient(id) => self.handle_active_window_request(id),
+            PaletteAction::FocusWorkspace(i) => self.focus_workspace(i),
+        }
+    }
+
+    // While the expose overview is open it receives every key press
+    fn handle_expose_key_press(&mut self, key_code: KeyCode) {
+        let input = match self.expose.as_mut() {
+            Some(e) => e.handle_key(key_code),
+            None => return,
+        };
+
+        match input {
+            ExposeInput::Continue => (),
+            ExposeInput::Cancel => self.close_expose(),
+            ExposeInput::Select(id) => {
+                self.close_expose();
+                self.handle_active_window_request(id);
+            }
+        }
+    }
+
+    // While the placement grid is open it receives every key press
+    fn handle_placement_key_press(&mut self, key_code: KeyCode) {
+        let input = match self.placement.as_mut() {
+            Some(g) => g.handle_key(key_code),
+            None => return,
+        };
+
+        match input {
+            PlacementInput::Continue => (),
+            PlacementInput::Cancel => self.close_placement_grid(),
+            PlacementInput::Place(r) => {
+                let id = self.placing;
+                self.close_placement_grid();
+                if let Some(id) = id.filter(|id| self.client_map.contains_key(id)) {
+                    self.position_client(id, r, self.border_style.width);
+                    self.conn.raise_window(id);
+                    self.handle_enter_notify(id);
+                }
+            }
+        }
+    }
+
+    fn over_callback_budget(&self, elapsed: Duration) -> bool {
+        matches!(self.callback_budget, Some(budget) if elapsed > budget)
+    }
+
+    // Warn if user code run from a hook or key binding took longer than the configured budget
+    fn check_callback_budget(&self, started: Instant, describe: impl FnOnce() -> String) {
+        let elapsed = started.elapsed();
+        if !self.over_callback_budget(elapsed) {
+            return;
+        }
+
+        let msg = format!("{} took {}ms", describe(), elapsed.as_millis());
+        warn!("slow callback: {}", msg);
+        if self.notify_slow_callbacks {
+            if let Err(e) = Command::new("notify-send")
+                .args(["penrose: slow callback", &msg])
+                .spawn()
+            {
+                warn!("unable to run notify-send: {}", e);
             }
         }
     }
@@ -251,394 +1608,5892 @@ impl<'a> WindowManager<'a> {
         if override_redirect || self.client_map.contains_key(&win_id) {
             return;
         }
+        if self.docks.contains_key(&win_id) {
+            return;
+        }
+        if self.conn.window_is_dock(win_id) {
+            self.add_dock(win_id);
+            return;
+        }
 
-        let wm_class = match self.conn.str_prop(win_id, "WM_CLASS") {
-            Ok(s) => s.split("\0").collect::<Vec<&str>>()[0].into(),
-            Err(_) => String::new(),
-        };
+        let raw_class = self.conn.str_prop(win_id, "WM_CLASS").unwrap_or_default();
+        let class_parts: Vec<&str> = raw_class.split('\0').filter(|s| !s.is_empty()).collect();
+        if self.class_filter.should_ignore(&class_parts) {
+            debug!("ignoring filtered window {} ({:?})", win_id, class_parts);
+            return;
+        }
+        if self.capture_scratchpad_client(win_id, &class_parts) {
+            return;
+        }
+        let wm_class: String = class_parts.first().copied().unwrap_or_default().into();
 
         let floating = self.floating_classes.contains(&wm_class.as_ref());
         let wix = self.active_ws_index();
-        let client = Client::new(win_id, wm_class, wix, floating);
+        let mut client = Client::new(win_id, wm_class, wix, floating);
+        match self.conn.str_prop(win_id, "WM_CLIENT_MACHINE") {
+            Ok(m) if !m.trim_end_matches('\0').is_empty() => {
+                client.set_client_machine(m.trim_end_matches('\0'), &self.hostname)
+            }
+            _ => (),
+        }
+        client.set_protocols(self.conn.window_protocols(win_id));
+        client.set_accepts_input(self.conn.window_accepts_input(win_id));
+        client.set_wm_name(self.fetch_title(win_id));
+        client.set_size_hints(self.conn.window_size_hints(win_id));
+        let position = self.apply_client_rules(&mut client, &class_parts);
+        if let Some(wix) = self.take_pending_spawn(win_id) {
+            client.set_workspace(wix);
+        }
+        let transient_for = self.conn.window_transient_for(win_id);
+        let dialog = transient_for.is_some() || self.conn.window_is_dialog(win_id);
+        if dialog {
+            // dialogs always float, and stay with the client that opened them
+            let parent = transient_for.and_then(|p| self.client_map.get(&p));
+            if let Some(p) = parent {
+                client.set_workspace(p.workspace());
+                client.set_transient_for(Some(p.id()));
+            }
+            client.set_floating(true);
+        }
+        if client.is_floating() {
+            // floating clients position themselves so ask X where they are
+            if let Ok(r) = self.conn.window_geometry(win_id) {
+                client.set_region(r);
+            }
+        }
+        let recovered = self.recovered.remove(&win_id);
+        if let Some(state) = recovered {
+            if state.workspace < self.workspaces.len() {
+                client.set_workspace(state.workspace);
+            }
+        }
+        if self.dnd && recovered.is_none() {
+            if let Some(wix) = self.dnd_workspace.filter(|w| *w < self.workspaces.len()) {
+                client.set_workspace(wix);
+            }
+        }
+        run_hooks!(new_client, self, &mut client);
         debug!("mapping client: {:?}", client);
 
+        // hooks are free to modify the client before we start managing it
+        let swallowing = self.swallowing_terminal(&client);
+        if let Some(term) = swallowing {
+            client.set_workspace(self.client_map[&term].workspace());
+        }
+        let wix = client.workspace();
+        let floating = client.is_floating();
+        if let Some(prev) = self.workspaces[wix].focused_client() {
+            self.handle_leave_notify(prev);
+        }
         self.client_map.insert(win_id, client);
-        if !floating {
-            self.workspaces[wix].add_client(win_id);
+        self.mapping_order.push(win_id);
+        match swallowing {
+            Some(term) => self.swallow(term, win_id),
+            None if !floating => {
+                let insert_point = position.unwrap_or(self.insert_point);
+                self.workspaces[wix].add_client(win_id, insert_point);
+            }
+            None => (),
         }
 
-        self.conn.focus_client(win_id);
         self.conn.mark_new_window(win_id);
-        let color = self.color_scheme.highlight;
-        self.conn.set_client_border_color(win_id, color);
         self.conn.set_client_workspace(win_id, wix);
-        self.apply_layout(self.active_ws_index());
+        self.update_x_client_list();
+        self.journal(Entry::Workspace(win_id, wix));
+        if floating {
+            match recovered.and_then(|s| s.floating) {
+                Some(r) => self.position_client(win_id, r, self.border_style.width),
+                None => match self.dialog_region(win_id).filter(|_| dialog) {
+                    Some(r) => self.position_client(win_id, r, self.border_style.width),
+                    None => {
+                        if let Some(r) = self.client_map[&win_id].region() {
+                            self.journal(Entry::Floating(win_id, r));
+                        }
+                    }
+                },
+            }
+        }
+
+        if self.screen_for_workspace(wix).is_some() {
+            self.apply_layout(wix);
+            let focus_new = self.focus_new_clients && !self.dnd;
+            match self.workspaces[wix].focused_client() {
+                Some(prev) if !focus_new && prev != win_id => {
+                    self.handle_leave_notify(win_id);
+                    self.handle_enter_notify(prev);
+                }
+                _ if self.dnd => self.handle_leave_notify(win_id),
+                _ => self.handle_enter_notify(win_id),
+            }
+        } else {
+            self.conn.unmap_window(win_id);
+            self.refresh_client_opacity(win_id);
+        }
+
+        if self.window_is_urgent(win_id) {
+            self.set_client_urgent(win_id, true);
+        }
     }
 
-    fn handle_enter_notify(&mut self, id: WinId) {
-        let color_focus = self.color_scheme.highlight;
-        let color_normal = self.color_scheme.fg_1;
-        self.focused_client()
-            .map(|c| self.conn.set_client_border_color(c.id(), color_normal));
-        self.conn.focus_client(id);
-        self.conn.set_client_border_color(id, color_focus);
+    // The workspace requested for this window if it was opened by spawn_on_workspace
+    fn take_pending_spawn(&mut self, id: WinId) -> Option<usize> {
+        if self.pending_spawns.is_empty() {
+            return None;
+        }
 
-        if let Some(wix) = self.workspace_index_for_client(id) {
-            let ws = &mut self.workspaces[wix];
-            ws.focus_client(id);
-            if ws.layout_conf().follow_focus {
-                self.apply_layout(wix);
-            }
+        let startup_id = self
+            .conn
+            .str_prop(id, "_NET_STARTUP_ID")
+            .unwrap_or_default();
+        let startup_id = startup_id.trim_end_matches('\0');
+        let pid = self.conn.atom_prop(id, "_NET_WM_PID").ok();
+        let ix = self
+            .pending_spawns
+            .iter()
+            .position(|p| p.startup_id == startup_id || Some(p.pid) == pid)?;
+
+        let pending = self.pending_spawns.remove(ix);
+        debug!("placing client {} from {:?}", id, pending);
+        Some(pending.workspace)
+    }
+
+    fn window_is_urgent(&self, id: WinId) -> bool {
+        self.conn
+            .atom_prop(id, "WM_HINTS")
+            .is_ok_and(|flags| flags & URGENCY_HINT != 0)
+    }
+
+    // Update whether or not a client is asking for attention, running the urgency_change hook
+    // if this is a change. The focused client already has the user's attention so it is never
+    // marked as urgent.
+    fn set_client_urgent(&mut self, id: WinId, urgent: bool) {
+        let urgent = urgent && self.focused_client().map(|c| c.id()) != Some(id);
+        match self.client_map.get_mut(&id) {
+            Some(c) if c.is_urgent() != urgent => c.set_urgent(urgent),
+            _ => return,
+        }
+
+        debug!("client {} urgent: {}", id, urgent);
+        self.urgent.retain(|u| *u != id);
+        if urgent {
+            self.urgent.push(id);
         }
+        self.refresh_border(id);
+        run_hooks!(urgency_change, self, id, urgent);
     }
 
-    fn handle_leave_notify(&self, id: WinId) {
-        let color = self.color_scheme.fg_1;
-        self.conn.set_client_border_color(id, color);
+    // Re-manage the windows that we were managing before restarting in place, returning
+    // whether or not there was any saved state to restore.
+    fn adopt_restored_windows(&mut self) -> bool {
+        let state = match self.restored.take() {
+            Some(state) => state,
+            None => return false,
+        };
+
+        let existing = self.conn.query_for_active_windows();
+        for (i, saved) in state.workspaces.iter().enumerate() {
+            if i >= self.workspaces.len() {
+                let ws = self.new_workspace(saved.name());
+                self.workspaces.push(ws);
+            }
+        }
+
+        let n_workspaces = self.workspaces.len();
+        for c in state.clients.into_iter() {
+            if existing.contains(&c.id()) && c.workspace() < n_workspaces {
+                debug!("adopting client {} after restart", c.id());
+                self.mapping_order.push(c.id());
+                self.client_map.insert(c.id(), c);
+            }
+        }
+        for (i, saved) in state.workspaces.iter().enumerate() {
+            let client_map = &self.client_map;
+            self.workspaces[i].restore(saved, |id| client_map.contains_key(id));
+        }
+
+        // screen layout may have changed since the restart so only restore what still fits
+        if state.screen_workspaces.len() == self.screens.len()
+            && state
+                .screen_workspaces
+                .iter()
+                .all(|wix| *wix < n_workspaces)
+        {
+            for (s, wix) in self.screens.iter_mut().zip(state.screen_workspaces) {
+                s.wix = wix;
+            }
+            self.focused_screen = state.focused_screen.min(self.screens.len() - 1);
+        }
+        if state.previous_workspace < n_workspaces {
+            self.previous_workspace = state.previous_workspace;
+        }
+
+        let clients: Vec<Client> = self.client_map.values().cloned().collect();
+        for c in clients {
+            let id = c.id();
+            self.conn.mark_new_window(id);
+            self.conn.set_client_workspace(id, c.workspace());
+            if self.screen_for_workspace(c.workspace()).is_none() {
+                self.conn.unmap_window(id);
+                continue;
+            }
+            self.conn.map_window(id);
+            if let (true, Some(r)) = (c.is_floating() || c.is_fullscreen(), c.region()) {
+                self.place_window(id, r, self.border_style.width);
+            }
+        }
+
+        self.layout_visible_workspaces();
+        self.update_x_client_list();
+        self.workspaces_updated();
+        info!("restored {} clients after restart", self.client_map.len());
+
+        true
     }
 
-    // fn handle_motion_notify(&mut self, event: &xcb::MotionNotifyEvent) {}
-    // fn handle_button_press(&mut self, event: &xcb::ButtonPressEvent) {}
-    // fn handle_button_release(&mut self, event: &xcb::ButtonReleaseEvent) {}
+    // Windows that were recorded in the journal before a restart are known to be clients
+    // that we were managing so it is safe to adopt them rather than waiting for a map.
+    fn adopt_journaled_windows(&mut self) {
+        if self.recovered.is_empty() {
+            return;
+        }
+
+        for id in self.conn.query_for_active_windows() {
+            if self.recovered.contains_key(&id) {
+                debug!("adopting journaled client {}", id);
+                self.handle_map_notify(id, false);
+            }
+        }
 
-    fn handle_destroy_notify(&mut self, win_id: WinId) {
-        self.remove_client(win_id);
-        self.apply_layout(self.active_ws_index());
+        // anything left over no longer exists
+        let stale: Vec<WinId> = self.recovered.drain().map(|(id, _)| id).collect();
+        for id in stale {
+            self.journal(Entry::Removed(id));
+        }
     }
 
-    /*
-     * Public methods that can be triggered by user bindings
-     *
-     * User defined hooks can be implemented by adding additional logic to these
-     * handlers which will then be run each time they are triggered
-     */
+    // Apply all matching client rules in order, returning the requested stack position
+    fn apply_client_rules(
+        &mut self,
+        client: &mut Client,
+        class_parts: &[&str],
+    ) -> Option<InsertPoint> {
+        if self.client_rules.is_empty() {
+            return None;
+        }
 
-    /// Shut down the WindowManager, running any required cleanup and exiting penrose
-    pub fn exit(&mut self) {
-        self.conn.cleanup();
-        self.conn.flush();
-        exit(0);
+        let id = client.id();
+        let title = self.client_title(id);
+        let role = self.conn.str_prop(id, "WM_WINDOW_ROLE").unwrap_or_default();
+
+        let mut populations = vec![0; self.workspaces.len()];
+        self.client_map
+            .values()
+            .for_each(|c| populations[c.workspace()] += 1);
+
+        let mut position = None;
+        for (i, rule) in self.client_rules.iter().enumerate() {
+            if !rule.matches(class_parts, &title, &role) {
+                continue;
+            }
+            debug!("applying rule to client {}: {:?}", id, rule);
+            match rule.workspace {
+                Some(wix) if wix < self.workspaces.len() => client.set_workspace(wix),
+                Some(wix) => warn!("rule has invalid workspace index: {}", wix),
+                None => (),
+            }
+            if let Some(assignment) = rule.assign {
+                // rules are counted separately so each one cycles through its own workspaces
+                let n_assigned = self.assigned.entry(i).or_insert(0);
+                match assignment.choose(*n_assigned, &populations) {
+                    Some(wix) => {
+                        client.set_workspace(wix);
+                        *n_assigned += 1;
+                    }
+                    None => warn!("rule has no valid workspaces to assign: {:?}", assignment),
+                }
+            }
+            if let Some(floating) = rule.floating {
+                client.set_floating(floating);
+            }
+            if let Some(sticky) = rule.sticky {
+                client.set_sticky(sticky);
+            }
+            if let Some(follow) = rule.follow {
+                client.set_following(follow);
+            }
+            if rule.position.is_some() {
+                position = rule.position;
+            }
+            if let Some(opacity) = rule.opacity {
+                client.set_data(opacity);
+            }
+        }
+
+        position
     }
 
-    /// The layout symbol for the Layout currently being used on the active workspace
-    pub fn current_layout_symbol(&self) -> &str {
-        self.workspaces[self.active_ws_index()].layout_symbol()
+    // Urgent clients take priority over everything other than focus, then remote clients can
+    // be given a different border color to make them easy to pick out.
+    fn unfocused_border_color(&self, id: WinId) -> u32 {
+        let style = &self.border_style;
+        match (self.remote_border_color, self.client_map.get(&id)) {
+            (_, Some(c)) if c.is_urgent() => style.urgent,
+            (Some(color), Some(c)) if c.is_remote() => color,
+            (_, Some(c)) => match style.alternate {
+                Some(color) if self.is_main_client(id, c.workspace()) => color,
+                _ => style.unfocused,
+            },
+            _ => style.unfocused,
+        }
     }
 
-    /// Set the root X window name. Useful for exposing information to external programs
-    pub fn set_root_window_name(&self, s: &str) {
-        self.conn.set_root_window_name(s);
+    fn is_main_client(&self, id: WinId, wix: usize) -> bool {
+        self.workspaces
+            .get(wix)
+            .is_some_and(|ws| ws.iter().next() == Some(&id))
     }
 
-    /**
-     * Set the displayed workspace for the focused screen to be `index` in the list of
-     * workspaces passed at `init`. This will panic if the index passed is out of
-     * bounds which is only possible if you manually bind an action to this with an
-     * invalid index. You should almost always be using the `gen_keybindings!` macro
-     * to set up your keybindings so this is not normally an issue.
-     */
-    pub fn focus_workspace(&mut self, index: usize) {
-        info!("ACTIVE_LAYOUT {}", self.workspaces[index].layout_symbol());
-        if self.active_ws_index() == index {
-            return; // already focused on the current screen
+    fn border_color(&self, id: WinId) -> u32 {
+        if self.border_focus == Some(id) {
+            self.border_style.focused
         } else {
-            self.previous_workspace = self.active_ws_index();
+            self.unfocused_border_color(id)
         }
+    }
 
-        for i in 0..self.screens.len() {
-            if self.screens[i].wix == index {
-                // The workspace we want is currently displayed on another screen so
-                // pull the target workspace to the focused screen, and place the
-                // workspace we had on the screen where the target was
-                self.screens[i].wix = self.screens[self.focused_screen].wix;
-                self.screens[self.focused_screen].wix = index;
+    fn refresh_border(&self, id: WinId) {
+        self.conn.set_client_border_color(id, self.border_color(id));
+    }
 
-                // re-apply layouts as screen dimensions may differ
-                self.apply_layout(self.active_ws_index());
-                self.apply_layout(index);
-                return;
-            }
+    // Hooks get the first say, then any Opacity attached to the client (by a Rule) and finally
+    // Config::opacity. Nothing is set if none of them have an opinion.
+    fn refresh_client_opacity(&self, id: WinId) {
+        let client = match self.client_map.get(&id) {
+            Some(c) => c,
+            None => return,
+        };
+        let focused = self.border_focus == Some(id);
+        let opacity = self
+            .hooks
+            .iter()
+            .find_map(|h| h.client_opacity(self, id, focused))
+            .or_else(|| {
+                client
+                    .data::<Opacity>()
+                    .or(self.opacity.as_ref())
+                    .map(|o| o.for_focus(focused))
+            });
+
+        if let Some(opacity) = opacity {
+            self.conn.set_client_opacity(id, opacity);
         }
+    }
+
+    /*
+     * Follow the ICCCM input focus model (4.1.7): clients that set the input field of WM_HINTS
+     * to false are never focused directly, and clients supporting WM_TAKE_FOCUS are asked to
+     * take focus themselves. Either way they are still marked as the active window.
+     */
+    fn give_input_focus(&self, id: WinId) {
+        let (accepts_input, take_focus) = match self.client_map.get(&id) {
+            Some(c) => (c.accepts_input(), c.supports_protocol("WM_TAKE_FOCUS")),
+            None => (true, false),
+        };
+
+        if accepts_input {
+            self.conn.focus_client(id);
+        } else {
+            self.conn.set_active_window(id);
+        }
+        if take_focus {
+            self.conn.send_client_event(id, "WM_TAKE_FOCUS");
+        }
+    }
+
+    fn handle_enter_notify(&mut self, id: WinId) {
+        let prev = self.focused_client().map(|c| c.id());
+        self.show_hidden_client(id);
+        self.give_input_focus(id);
+        self.set_client_urgent(id, false);
+        if self.client_map.contains_key(&id) {
+            self.focus_history.retain(|h| *h != id);
+            self.focus_history.push(id);
+        }
+        self.border_focus = Some(id);
+        if let Some(prev) = prev.filter(|p| *p != id) {
+            self.refresh_border(prev);
+            self.refresh_client_opacity(prev);
+        }
+        self.refresh_border(id);
+        self.refresh_client_opacity(id);
+
+        if let Some(wix) = self.workspace_index_for_client(id) {
+            let ws = &mut self.workspaces[wix];
+            ws.focus_client(id);
+            if ws.layout_conf().is_ok_and(|lc| lc.follow_focus) {
+                self.apply_layout(wix);
+            }
+
+            // focus follows the client across screens
+            if let Some(i) = self.screens.iter().position(|s| s.wix == wix) {
+                if i != self.focused_screen {
+                    self.focused_screen = i;
+                    run_hooks!(screen_change, self, i);
+                }
+            }
+        }
+
+        run_hooks!(focus_change, self, id);
+    }
+
+    fn handle_leave_notify(&mut self, id: WinId) {
+        if self.border_focus == Some(id) {
+            self.border_focus = None;
+        }
+        self.refresh_border(id);
+        self.refresh_client_opacity(id);
+    }
+
+    // mod + left click on a client starts dragging it
+    fn handle_button_press(&mut self, id: WinId, button: u8, x: u32, y: u32) {
+        match self.client_map.get(&id) {
+            Some(c) if button == 1 && !c.is_fullscreen() => {
+                debug!("starting drag of client {}", id);
+                self.drag = Some(id);
+                self.handle_enter_notify(id);
+                self.handle_motion_notify(x, y);
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_motion_notify(&mut self, x: u32, y: u32) {
+        if let Some(id) = self.drag {
+            let target = self.drop_target(id, x, y);
+            run_hooks!(drag_preview, self, target.as_ref());
+        }
+    }
+
+    fn handle_button_release(&mut self, x: u32, y: u32) {
+        if let Some(id) = self.drag.take() {
+            run_hooks!(drag_preview, self, None);
+            if let Some(target) = self.drop_target(id, x, y) {
+                self.drop_client(target);
+            }
+        }
+    }
+
+    /*
+     * Work out where a dragged client would land if dropped at (x, y). Workspaces claimed by
+     * hooks (such as bar pagers) take priority, otherwise the client is dropped on to the
+     * workspace of the screen under the pointer: tiled clients take the stack position of
+     * the client under the pointer (or the end of the stack) and floating clients are moved
+     * to the pointer position.
+     */
+    fn drop_target(&self, id: WinId, x: u32, y: u32) -> Option<DropTarget> {
+        let client = self.client_map.get(&id)?;
+        if let Some((workspace, region)) = self
+            .hooks
+            .iter()
+            .find_map(|h| h.workspace_at_point(self, x, y))
+        {
+            return Some(DropTarget {
+                client: id,
+                screen: self.screens.iter().position(|s| s.wix == workspace),
+                workspace,
+                position: None,
+                region,
+                pager: true,
+            });
+        }
+
+        let screen = self
+            .screens
+            .iter()
+            .position(|s| s.true_region.contains_point(x, y))?;
+        let workspace = self.screens[screen].wix;
+        let mut ws = self.workspaces[workspace].clone();
+        let (w, h) = client.region().map_or((0, 0), |r| (r.width(), r.height()));
+        let floating = Region::new(x, y, w, h);
+
+        if client.is_floating() || ws.layout_conf().is_ok_and(|lc| lc.floating) {
+            return Some(DropTarget {
+                client: id,
+                screen: Some(screen),
+                workspace,
+                position: None,
+                region: floating,
+                pager: false,
+            });
+        }
+
+        // arrange a copy of the target workspace to find the region the client will occupy
+        ws.remove_client(id);
+        let position = ws
+            .iter()
+            .position(|c| matches!(self.client_map[c].region(), Some(r) if r.contains_point(x, y)))
+            .unwrap_or_else(|| ws.len());
+        ws.insert_client(position, id);
+        let region = self
+            .arrange_for_screen(&ws, screen)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|(c, _)| *c == id)
+            .map_or(floating, |(_, r)| r);
+
+        Some(DropTarget {
+            client: id,
+            screen: Some(screen),
+            workspace,
+            position: Some(position),
+            region,
+            pager: false,
+        })
+    }
+
+    fn drop_client(&mut self, target: DropTarget) {
+        let id = target.client;
+        debug!("dropping client {}: {:?}", id, target);
+        self.move_client_to_workspace(id, target.workspace);
+
+        let ws = &mut self.workspaces[target.workspace];
+        if let Some(position) = target.position {
+            if ws.remove_client(id).is_some() {
+                ws.insert_client(position, id);
+            }
+        }
+
+        if target.screen.is_none() {
+            self.focus_active_workspace_client();
+            return;
+        }
+        if self.client_map[&id].is_floating() && !target.pager {
+            self.position_client(id, target.region, self.border_style.width);
+        }
+        self.apply_layout(target.workspace);
+        self.handle_enter_notify(id);
+    }
+
+    fn handle_destroy_notify(&mut self, win_id: WinId) {
+        if let Some(s) = self
+            .scratchpads
+            .iter_mut()
+            .find(|s| s.client_id() == Some(win_id))
+        {
+            debug!("scratchpad '{}' client destroyed", s.conf.name);
+            s.client = None;
+            s.visible = false;
+            return;
+        }
+        if self.docks.contains_key(&win_id) {
+            self.remove_dock(win_id);
+            return;
+        }
+        // a terminal can exit while the program that swallowed it is still running
+        self.swallowed.retain(|_, term| term.id() != win_id);
+
+        if !self.client_map.contains_key(&win_id) {
+            return; // not a window we are managing
+        }
+        self.remove_client(win_id);
+        self.apply_layout(self.active_ws_index());
+    }
+
+    fn handle_client_message(&mut self, win_id: WinId, dtype: &str, data: &[u32]) {
+        debug!("got client message: {} {} {:?}", win_id, dtype, data);
+        match dtype {
+            // requests from pagers (source indication 2) are made by the user so still go through
+            "_NET_ACTIVE_WINDOW" if self.dnd && data.first() != Some(&2) => {
+                debug!("do not disturb: marking {} as urgent", win_id);
+                self.set_client_urgent(win_id, true);
+            }
+            "_NET_ACTIVE_WINDOW" => self.handle_active_window_request(win_id),
+            "_NET_CURRENT_DESKTOP" if !data.is_empty() => {
+                let wix = data[0] as usize;
+                if wix < self.workspaces.len() {
+                    self.focus_workspace(wix);
+                }
+            }
+            "_NET_WM_DESKTOP" if !data.is_empty() => {
+                let wix = data[0] as usize;
+                if wix < self.workspaces.len() {
+                    self.move_client_to_workspace(win_id, wix);
+                }
+            }
+            "_NET_WM_STATE" => self.handle_wm_state_request(win_id, data),
+            _ => (),
+        }
+    }
+
+    fn handle_property_notify(&mut self, win_id: WinId, atom: &str) {
+        if !self.client_map.contains_key(&win_id) {
+            return;
+        }
+
+        match atom {
+            "WM_HINTS" => {
+                let accepts_input = self.conn.window_accepts_input(win_id);
+                if let Some(c) = self.client_map.get_mut(&win_id) {
+                    c.set_accepts_input(accepts_input);
+                }
+                let urgent = self.window_is_urgent(win_id);
+                self.set_client_urgent(win_id, urgent);
+            }
+            "WM_NAME" | "_NET_WM_NAME" => self.update_title(win_id),
+            "WM_NORMAL_HINTS" => self.update_size_hints(win_id),
+            _ => (),
+        }
+    }
+
+    // Re-tile a visible workspace if the client has changed its hints and they are in use
+    fn update_size_hints(&mut self, win_id: WinId) {
+        let hints = self.conn.window_size_hints(win_id);
+        let wix = match self.client_map.get_mut(&win_id) {
+            Some(c) if c.size_hints() != hints => {
+                c.set_size_hints(hints);
+                if c.is_floating() {
+                    return;
+                }
+                c.workspace()
+            }
+            _ => return,
+        };
+
+        let visible = self.screens.iter().any(|s| s.wix == wix);
+        let respect_hints = self.workspaces[wix]
+            .layout_conf()
+            .is_ok_and(|lc| lc.respect_resize_hints);
+        if visible && respect_hints {
+            self.apply_layout(wix);
+        }
+    }
+
+    fn update_title(&mut self, win_id: WinId) {
+        let title = self.fetch_title(win_id);
+        match self.client_map.get_mut(&win_id) {
+            Some(c) if c.wm_name() != title => c.set_wm_name(title.clone()),
+            _ => return,
+        }
+
+        debug!("client {} title: {}", win_id, title);
+        run_hooks!(title_change, self, win_id, &title);
+    }
+
+    // Query X for the current title of a window: _NET_WM_NAME if set, otherwise WM_NAME
+    fn fetch_title(&self, id: WinId) -> String {
+        match self.conn.str_prop(id, "_NET_WM_NAME") {
+            Ok(s) if !s.is_empty() => s,
+            _ => self.conn.str_prop(id, "WM_NAME").unwrap_or_default(),
+        }
+    }
+
+    fn handle_active_window_request(&mut self, win_id: WinId) {
+        let wix = match self.client_map.get(&win_id) {
+            Some(c) => c.workspace(),
+            None => return,
+        };
+        if wix != self.active_ws_index() {
+            self.focus_workspace(wix);
+        }
+        self.handle_enter_notify(win_id);
+        self.warp_pointer_to_focus();
+    }
+
+    fn handle_wm_state_request(&mut self, win_id: WinId, data: &[u32]) {
+        let (fullscreen, urgent) = match self.client_map.get(&win_id) {
+            Some(c) => (c.is_fullscreen(), c.is_urgent()),
+            None => return,
+        };
+
+        if cfg!(feature = "ewmh-fullscreen") {
+            if let Some(state) =
+                self.requested_wm_state(data, "_NET_WM_STATE_FULLSCREEN", fullscreen)
+            {
+                self.set_fullscreen(win_id, state);
+            }
+        }
+        if let Some(state) =
+            self.requested_wm_state(data, "_NET_WM_STATE_DEMANDS_ATTENTION", urgent)
+        {
+            self.set_client_urgent(win_id, state);
+        }
+    }
+
+    // The value that a _NET_WM_STATE client message is asking for the given state to be set
+    // to, or None if the message is not about that state.
+    fn requested_wm_state(&self, data: &[u32], state: &str, currently: bool) -> Option<bool> {
+        let atom = self.conn.intern_atom(state).ok()?;
+        if data.len() < 3 || (data[1] != atom && data[2] != atom) {
+            return None;
+        }
+
+        // _NET_WM_STATE_REMOVE == 0, _NET_WM_STATE_ADD == 1, _NET_WM_STATE_TOGGLE == 2
+        match data[0] {
+            0 => Some(false),
+            1 => Some(true),
+            2 => Some(!currently),
+            _ => {
+                warn!("invalid _NET_WM_STATE action: {}", data[0]);
+                None
+            }
+        }
+    }
+
+    /*
+     * Public methods that can be triggered by user bindings
+     *
+     * User defined hooks can be implemented by adding additional logic to these
+     * handlers which will then be run each time they are triggered
+     */
+
+    /**
+     * Focus the client that most recently started asking for attention, switching to its
+     * workspace if it is not currently visible.
+     */
+    pub fn focus_urgent(&mut self) {
+        if let Some(id) = self.urgent.last().copied() {
+            self.handle_active_window_request(id);
+        }
+    }
+
+    /**
+     * Report the outcome of an action to the user. Notifications are logged and then passed to
+     * the `notification` hook so that they can be shown in a status bar or forwarded on to a
+     * notification daemon (see hooks::NotifySend). Hooks are free to call this as well:
+     * notifications raised while hooks are running are passed on once they have finished.
+     */
+    pub fn notify(&mut self, n: Notification) {
+        match n.level {
+            NotificationLevel::Info => info!("{}", n),
+            NotificationLevel::Error => warn!("{}", n),
+        }
+        self.pending_notifications.push_back(n);
+        if self.running_hooks == 0 {
+            self.flush_notifications();
+        }
+    }
+
+    // Pass any queued notifications on to the notification hook in the order they were raised
+    fn flush_notifications(&mut self) {
+        while let Some(n) = self.pending_notifications.pop_front() {
+            run_hooks!(notification, self, &n);
+        }
+    }
+
+    /**
+     * Enable or disable do not disturb mode. While it is enabled new clients are managed without
+     * being focused (and are placed on `Config::dnd_workspace` if it is set) and clients asking
+     * to be focused via _NET_ACTIVE_WINDOW are marked as urgent instead.
+     */
+    pub fn set_dnd(&mut self, enabled: bool) {
+        if self.dnd == enabled {
+            return;
+        }
+        debug!("do not disturb: {}", enabled);
+        self.dnd = enabled;
+        run_hooks!(dnd_change, self, enabled);
+    }
+
+    /// Toggle do not disturb mode on and off (see `set_dnd`)
+    pub fn toggle_dnd(&mut self) {
+        self.set_dnd(!self.dnd);
+    }
+
+    /// Whether or not do not disturb mode is currently enabled
+    pub fn dnd_enabled(&self) -> bool {
+        self.dnd
+    }
+
+    /// The clients that have been focused, most recently focused first
+    pub fn focus_history(&self) -> Vec<WinId> {
+        self.focus_history.iter().rev().copied().collect()
+    }
+
+    /**
+     * Focus the client that was focused before the current one, switching workspace if needed.
+     * Running this repeatedly toggles between the two most recently focused clients.
+     */
+    pub fn focus_previous_client(&mut self) {
+        let current = self.focused_client_id();
+        let target = match self
+            .focus_history
+            .iter()
+            .rev()
+            .find(|id| Some(**id) != current)
+        {
+            Some(id) => *id,
+            None => return,
+        };
+        self.handle_active_window_request(target);
+
+        // switching workspace focuses other clients along the way so make sure that the client
+        // we came from is the next one in line
+        if let Some(id) = current {
+            self.focus_history.retain(|h| *h != id);
+            let n = self.focus_history.len();
+            self.focus_history.insert(n.saturating_sub(1), id);
+        }
+    }
+
+    /// Shut down the WindowManager, running any required cleanup and exiting penrose
+    pub fn exit(&mut self) {
+        self.save_layout_settings();
+        self.release_swallowed();
+        self.conn.cleanup();
+        self.conn.flush();
+        exit(0);
+    }
+
+    // Persist the layout settings of every workspace for the next session
+    fn save_layout_settings(&mut self) {
+        let settings: HashMap<String, LayoutSettings> = self
+            .workspaces
+            .iter()
+            .filter_map(|ws| Some((ws.name().to_string(), ws.layout_settings().ok()?)))
+            .collect();
+        if let Err(e) = self.store.save_layout_settings(&settings) {
+            warn!(
+                "unable to save layout settings to {}: {}",
+                self.store.name(),
+                e
+            );
+        }
+    }
+
+    /// A snapshot of the current state that can be serialized and restored after a restart
+    pub fn state(&self) -> WindowManagerState {
+        WindowManagerState {
+            workspaces: self.workspaces.clone(),
+            clients: self
+                .mapping_order
+                .iter()
+                .filter_map(|id| self.client_map.get(id).cloned())
+                .collect(),
+            screen_workspaces: self.screens.iter().map(|s| s.wix).collect(),
+            focused_screen: self.focused_screen,
+            previous_workspace: self.previous_workspace,
+        }
+    }
+
+    /**
+     * A read only copy of the current screens, workspaces and clients (see the snapshot
+     * module) for use in hooks, scripts and tests.
+     */
+    pub fn snapshot(&self) -> Snapshot {
+        let mut ids: Vec<&WinId> = self.client_map.keys().collect();
+        ids.sort_unstable();
+
+        Snapshot {
+            screens: self
+                .screens
+                .iter()
+                .enumerate()
+                .map(|(index, s)| ScreenSnapshot {
+                    index,
+                    region: s.true_region,
+                    effective_region: s.effective_region,
+                    workspace: s.wix,
+                    rotated: self.is_screen_rotated(index),
+                })
+                .collect(),
+            workspaces: self
+                .workspaces
+                .iter()
+                .enumerate()
+                .map(|(index, ws)| WorkspaceSnapshot {
+                    index,
+                    name: ws.name().to_string(),
+                    layout: ws
+                        .display_layout_symbol(self.n_hidden_clients(index))
+                        .unwrap_or_default(),
+                    clients: ws.iter().copied().collect(),
+                    focused_client: ws.focused_client(),
+                    screen: self.screens.iter().position(|s| s.wix == index),
+                    urgent: self.workspace_is_urgent(index),
+                    minimized: self.minimized_clients(index),
+                })
+                .collect(),
+            clients: ids
+                .into_iter()
+                .map(|id| {
+                    let c = &self.client_map[id];
+                    ClientSnapshot {
+                        id: *id,
+                        class: c.class().to_string(),
+                        title: c.wm_name().to_string(),
+                        workspace: c.workspace(),
+                        region: c.region(),
+                        visible: self.is_visible(*id) && !self.hidden.contains(id),
+                        floating: c.is_floating(),
+                        fullscreen: c.is_fullscreen(),
+                        sticky: c.is_sticky(),
+                        urgent: c.is_urgent(),
+                    }
+                })
+                .collect(),
+            focused_screen: self.focused_screen,
+            focused_workspace: self.active_ws_index(),
+            focused_client: self.focused_client_id(),
+            dnd: self.dnd,
+        }
+    }
+
+    /**
+     * Restart penrose in place: the current state is saved to the configured StateStore and
+     * the running process is replaced with a fresh copy of the binary it was started from
+     * (picking up any changes to your config after a rebuild). Existing windows are adopted
+     * back into their previous workspaces and stack positions once the new process starts.
+     */
+    pub fn restart(&mut self) {
+        let state = self.state();
+        if let Err(e) = self.store.save_state(&state) {
+            warn!("unable to save state for restart: {}", e);
+            return;
+        }
+        self.save_layout_settings();
+
+        let mut args = env::args_os();
+        let program = match args
+            .next()
+            .map(PathBuf::from)
+            .or_else(|| env::current_exe().ok())
+        {
+            Some(p) => p,
+            None => {
+                warn!("unable to determine the penrose binary to restart");
+                return;
+            }
+        };
+
+        info!("restarting: {:?}", program);
+        self.release_swallowed();
+        self.conn.cleanup();
+        self.conn.flush();
+        // exec only returns if it failed to replace the current process
+        let err = Command::new(program)
+            .args(args)
+            .env(RESTART_STATE_ENV, "1")
+            .exec();
+        error!("unable to restart penrose: {}", err);
+        exit(1);
+    }
+
+    /// The layout symbol for the Layout currently being used on the active workspace
+    pub fn current_layout_symbol(&self) -> String {
+        let wix = self.active_ws_index();
+        self.workspaces[wix]
+            .display_layout_symbol(self.n_hidden_clients(wix))
+            .unwrap_or_default()
+    }
+
+    /**
+     * The last known region of the client with the given ID. This is taken from cached
+     * state (the position penrose last gave the client) rather than querying the X server.
+     */
+    pub fn client_region(&self, id: WinId) -> Option<Region> {
+        self.client_map.get(&id).and_then(|c| c.region())
+    }
+
+    /// Whether or not the client with the given ID is on a workspace that is currently visible
+    pub fn is_visible(&self, id: WinId) -> bool {
+        match self.client_map.get(&id) {
+            Some(c) => self.screen_for_workspace(c.workspace()).is_some(),
+            None => false,
+        }
+    }
+
+    /// The index of the workspace that the client with the given ID is on
+    pub fn workspace_of(&self, id: WinId) -> Option<usize> {
+        self.client_map.get(&id).map(|c| c.workspace())
+    }
+
+    /// The value of type T attached to the client with the given ID (see `Client::data`)
+    pub fn client_data<T: Any + Clone>(&self, id: WinId) -> Option<&T> {
+        self.client_map.get(&id).and_then(|c| c.data())
+    }
+
+    /// A mutable reference to the value of type T attached to the client with the given ID
+    pub fn client_data_mut<T: Any + Clone>(&mut self, id: WinId) -> Option<&mut T> {
+        self.client_map.get_mut(&id).and_then(|c| c.data_mut())
+    }
+
+    /**
+     * Attach a value of type T to the client with the given ID, returning the previous value
+     * if there was one. Nothing is attached if the client is not being managed.
+     */
+    pub fn set_client_data<T: Any + Clone>(&mut self, id: WinId, value: T) -> Option<T> {
+        self.client_map.get_mut(&id).and_then(|c| c.set_data(value))
+    }
+
+    /// Remove the value of type T attached to the client with the given ID, returning it
+    pub fn remove_client_data<T: Any + Clone>(&mut self, id: WinId) -> Option<T> {
+        self.client_map.get_mut(&id).and_then(|c| c.remove_data())
+    }
+
+    /**
+     * The IDs of all clients on the workspace currently shown on the screen at 'screen_index'.
+     * Tiled clients are given in stack order followed by any floating clients.
+     */
+    pub fn clients_on_screen(&self, screen_index: usize) -> Vec<WinId> {
+        let wix = match self.screens.get(screen_index) {
+            Some(s) => s.wix,
+            None => return vec![],
+        };
+
+        let tiled: Vec<WinId> = self.workspaces[wix].iter().copied().collect();
+        let mut floating: Vec<WinId> = self
+            .client_map
+            .values()
+            .filter(|c| c.workspace() == wix && !tiled.contains(&c.id()))
+            .map(|c| c.id())
+            .collect();
+        floating.sort_unstable();
+
+        tiled.into_iter().chain(floating).collect()
+    }
+
+    /// Set the root X window name. Useful for exposing information to external programs
+    pub fn set_root_window_name(&self, s: &str) {
+        self.conn.set_root_window_name(s);
+    }
+
+    /**
+     * Force all connected displays into the given DPMS power level.
+     * Requires penrose to be built with the 'dpms' feature enabled.
+     */
+    pub fn set_dpms_state(&self, state: DpmsState) {
+        info!("setting DPMS state: {:?}", state);
+        self.conn.set_dpms_state(state);
+    }
+
+    /// The current DPMS power level of connected displays, None if DPMS is unavailable
+    pub fn dpms_state(&self) -> Option<DpmsState> {
+        self.conn.dpms_state()
+    }
+
+    /// Power off all connected displays (they will wake on the next user input)
+    pub fn displays_off(&mut self) {
+        self.set_dpms_state(DpmsState::Off);
+    }
+
+    /// Power on all connected displays
+    pub fn displays_on(&mut self) {
+        self.set_dpms_state(DpmsState::On);
+    }
+
+    /// Whether or not layouts on the given screen are rotated (see set_screen_rotated)
+    pub fn is_screen_rotated(&self, screen_index: usize) -> bool {
+        self.rotated_screens.contains(&screen_index)
+    }
+
+    /**
+     * Rotate the orientation of layouts shown on the given screen so that, for example,
+     * side_stack becomes a bottom stack on a portrait or very short display. This applies to
+     * whichever workspace is shown on the screen without changing its layout selection.
+     */
+    pub fn set_screen_rotated(&mut self, screen_index: usize, rotated: bool) {
+        let changed = if rotated {
+            self.rotated_screens.insert(screen_index)
+        } else {
+            self.rotated_screens.remove(&screen_index)
+        };
+
+        if changed {
+            if let Some(s) = self.screens.get(screen_index) {
+                self.apply_layout(s.wix);
+            }
+        }
+    }
+
+    /// Toggle the layout rotation of the focused screen
+    pub fn toggle_screen_rotation(&mut self) {
+        let i = self.focused_screen;
+        self.set_screen_rotated(i, !self.is_screen_rotated(i));
+    }
+
+    /// The regions of all connected screens, ignoring the space reserved for a status bar
+    pub fn screen_regions(&self) -> Vec<Region> {
+        self.screens.iter().map(|s| *s.region(false)).collect()
+    }
+
+    /**
+     * Re-query the X server for connected screens. Workspaces remain on the screens they were
+     * shown on where possible, any additional screens are given the lowest indexed workspaces
+     * that are not currently visible and workspaces from screens that have been removed are
+     * hidden.
+     */
+    pub fn detect_screens(&mut self) {
+        let mut screens = self.conn.current_outputs();
+        if screens.is_empty() {
+            warn!("no screens detected: keeping current screen configuration");
+            return;
+        }
+        info!("re-detected screens: {} screens found", screens.len());
+
+        for s in screens.iter_mut() {
+            s.update_effective_region(self.bar_height, self.top_bar);
+        }
+        let focused = reassign_workspaces(
+            &self.screens,
+            &mut screens,
+            self.focused_screen,
+            self.workspaces.len(),
+            self.screen_change_policy,
+        );
+
+        let prev: Vec<usize> = self.screens.iter().map(|s| s.wix).collect();
+
+        let now: Vec<usize> = screens.iter().map(|s| s.wix).collect();
+        let outgoing: Vec<WinId> = prev
+            .iter()
+            .filter(|w| !now.contains(w))
+            .flat_map(|wix| self.clients_on_workspace(*wix))
+            .collect();
+        self.unmap_outgoing_first(&outgoing);
+        for wix in now.iter().filter(|w| !prev.contains(w)) {
+            for id in self.clients_on_workspace(*wix) {
+                self.map_client(id);
+            }
+        }
+
+        // floating clients keep their position relative to the screen they are shown on
+        for s in screens.iter() {
+            let from = self.screen_for_workspace(s.wix).map(|p| p.true_region);
+            match from {
+                Some(r) if r != s.true_region => {
+                    self.relocate_floating_clients(s.wix, r, s.true_region)
+                }
+                _ => (),
+            }
+        }
+
+        self.screens = screens;
+        self.focused_screen = focused;
+        for wix in now {
+            self.apply_layout(wix);
+        }
+        self.hide_outgoing(outgoing);
+        self.conn.set_current_workspace(self.active_ws_index());
+        self.focus_active_workspace_client();
+        run_hooks!(screen_change, self, self.focused_screen);
+    }
+
+    // Move the floating clients on a workspace from one screen to another, keeping their
+    // position relative to the top left corner of the screen where possible.
+    fn relocate_floating_clients(&mut self, wix: usize, from: Region, to: Region) {
+        let moves: Vec<(WinId, Region)> = self
+            .client_map
+            .values()
+            .filter(|c| c.workspace() == wix && c.is_floating() && !c.is_fullscreen())
+            .filter_map(|c| c.region().map(|r| (c.id(), r)))
+            .collect();
+
+        for (id, r) in moves {
+            self.position_client(id, r.relocate(&from, &to), self.border_style.width);
+        }
+    }
+
+    /// Move focus to the next screen
+    pub fn next_screen(&mut self) {
+        self.cycle_screen(Direction::Forward);
+    }
+
+    /// Move focus to the previous screen
+    pub fn previous_screen(&mut self) {
+        self.cycle_screen(Direction::Backward);
+    }
+
+    /// Move the focused client to the workspace shown on the next screen
+    pub fn client_to_next_screen(&mut self) {
+        self.client_to_screen(Direction::Forward);
+    }
+
+    /// Move the focused client to the workspace shown on the previous screen
+    pub fn client_to_previous_screen(&mut self) {
+        self.client_to_screen(Direction::Backward);
+    }
+
+    /**
+     * Move the focused client to the workspace shown on the nearest screen in the given
+     * direction and move focus to that screen along with it.
+     */
+    pub fn throw_client_to_screen(&mut self, direction: ScreenDirection) {
+        let (screen, id) = match (self.screen_in_direction(direction), self.focused_client()) {
+            (Some(screen), Some(c)) => (screen, c.id()),
+            _ => return,
+        };
+
+        self.move_client_to_workspace(id, self.screens[screen].wix);
+        self.focused_screen = screen;
+        self.conn.set_current_workspace(self.active_ws_index());
+        self.focus_active_workspace_client();
+        run_hooks!(screen_change, self, self.focused_screen);
+    }
+
+    /// Swap the workspaces shown on the focused screen and the next screen, keeping focus
+    /// on the focused screen.
+    pub fn swap_screens(&mut self) {
+        if self.screens.len() < 2 {
+            return;
+        }
+        let (a, b) = (
+            self.focused_screen,
+            self.next_screen_index(Direction::Forward),
+        );
+        let prev = self.screens[a].wix;
+        let new = self.screens[b].wix;
+        self.move_following_clients(&[(prev, new), (new, prev)]);
+        self.switch_workspaces(&[(b, prev), (a, new)]);
+        self.refresh_tag_views();
+        self.conn.set_current_workspace(new);
+        self.focus_active_workspace_client();
+        run_hooks!(workspace_change, self, prev, new);
+    }
+
+    /// The index of the currently focused screen
+    pub fn focused_screen_index(&self) -> usize {
+        self.focused_screen
+    }
+
+    /// The index of the workspace being shown on the given screen
+    pub fn workspace_on_screen(&self, screen_index: usize) -> Option<usize> {
+        self.screens.get(screen_index).map(|s| s.wix)
+    }
+
+    /// Whether or not the workspace at `index` has any clients (tiled or floating)
+    pub fn workspace_has_clients(&self, index: usize) -> bool {
+        self.client_map.values().any(|c| c.workspace() == index)
+    }
+
+    /// Whether or not any clients on the given workspace are asking for attention
+    pub fn workspace_is_urgent(&self, index: usize) -> bool {
+        self.client_map
+            .values()
+            .any(|c| c.workspace() == index && c.is_urgent())
+    }
+
+    /// The clients that are currently asking for attention, most recently urgent last
+    pub fn urgent_clients(&self) -> &[WinId] {
+        &self.urgent
+    }
+
+    /// The layout symbol for the Layout currently being used on the given workspace
+    pub fn workspace_layout_symbol(&self, index: usize) -> Option<String> {
+        self.workspaces
+            .get(index)
+            .and_then(|w| w.display_layout_symbol(self.n_hidden_clients(index)).ok())
+    }
+
+    /// Whether the status bar is hidden while the given workspace is shown
+    pub fn workspace_bar_hidden(&self, index: usize) -> bool {
+        self.workspaces.get(index).is_some_and(|w| w.bar_hidden())
+    }
+
+    /**
+     * Hide (or show) the status bar while the workspace at `index` is shown, giving its
+     * clients the full screen. Bars are updated through the `layout_change` hook.
+     */
+    pub fn set_bar_hidden(&mut self, index: usize, hidden: bool) {
+        match self.workspaces.get_mut(index) {
+            Some(ws) => ws.set_bar_hidden(hidden),
+            None => return,
+        }
+
+        if let Some(screen) = self.screens.iter().position(|s| s.wix == index) {
+            self.apply_layout(index);
+            run_hooks!(layout_change, self, index, screen);
+        }
+    }
+
+    /**
+     * Switch the named workspace to use `layout`. If the workspace already has a layout with
+     * the same symbol it is replaced, otherwise the new layout is added to the set of layouts
+     * that the workspace cycles through. Layouts added at runtime are not carried over when
+     * restarting unless the new Config has a layout with the same symbol.
+     */
+    pub fn set_layout_for_workspace(&mut self, name: &str, layout: Layout) -> errors::Result<()> {
+        let index = self
+            .workspace_index(name)
+            .ok_or_else(|| PenroseError::UnknownWorkspace(name.to_string()))?;
+        self.workspaces[index].set_layout(layout);
+        info!("ACTIVE_LAYOUT {}", self.workspaces[index].layout_symbol()?);
+
+        if let Some(screen) = self.screens.iter().position(|s| s.wix == index) {
+            self.apply_layout(index);
+            run_hooks!(layout_change, self, index, screen);
+        }
+        self.notify_layout(index);
+        Ok(())
+    }
+
+    /**
+     * Switch the active workspace to its layout with the given symbol (such as "[side]") rather
+     * than cycling through layouts to get to it. Nothing is changed if the workspace has no
+     * layout with that symbol.
+     */
+    pub fn set_layout_by_symbol(&mut self, symbol: &str) {
+        let wix = self.active_ws_index();
+        if !self.workspaces[wix].focus_layout(symbol) {
+            let name = self.workspaces[wix].name();
+            let msg = format!("workspace {} has no layout \"{}\"", name, symbol);
+            return self.notify(Notification::error(msg));
+        }
+        self.apply_layout(wix);
+        info!(
+            "ACTIVE_LAYOUT {}",
+            self.workspaces[wix].layout_symbol().unwrap_or_default()
+        );
+        run_hooks!(layout_change, self, wix, self.focused_screen);
+        self.notify_layout(wix);
+    }
+
+    /// The X window ID of the currently focused client, if there is one
+    pub fn focused_client_id(&self) -> Option<WinId> {
+        self.focused_client().map(|c| c.id())
+    }
+
+    /**
+     * The title of a client, taken from _NET_WM_NAME or WM_NAME if that is not set. Titles of
+     * managed clients are tracked as they change so this does not need to query X.
+     */
+    pub fn client_title(&self, id: WinId) -> String {
+        match self.client_map.get(&id) {
+            Some(c) => c.wm_name().to_string(),
+            None => self.fetch_title(id),
+        }
+    }
+
+    /// The names of all current workspaces in index order
+    pub fn workspace_names(&self) -> Vec<&str> {
+        self.workspaces.iter().map(|w| w.name()).collect()
+    }
+
+    /// The index of the first workspace with the given name
+    pub fn workspace_index(&self, name: &str) -> Option<usize> {
+        self.workspaces.iter().position(|w| w.name() == name)
+    }
+
+    // The layouts from the initial Config were used to create our workspaces in init, so we
+    // know that there is at least one of them.
+    fn new_workspace(&self, name: impl Into<String>) -> Workspace {
+        let name = name.into();
+        let layouts = layouts_for(&name, &self.layouts, &self.workspace_layouts).to_vec();
+        Workspace::new(name, layouts).expect("layouts are checked in init")
+    }
+
+    /**
+     * Create a new workspace with the given name, using the layouts set for that name in the
+     * initial Config (or the default set of layouts if there are none). New workspaces are
+     * always added after all existing workspaces so that the indices of existing workspaces
+     * (and the key bindings that refer to them) are left unchanged. Returns the index of the
+     * new workspace.
+     */
+    pub fn create_workspace(&mut self, name: impl Into<String>) -> usize {
+        let ws = self.new_workspace(name);
+        info!("creating workspace '{}'", ws.name());
+        self.workspaces.push(ws);
+        self.workspaces_updated();
+        self.workspaces.len() - 1
+    }
+
+    /**
+     * Delete the workspace at `index`. Only workspaces created at runtime via
+     * `create_workspace` can be deleted, and only when they have no clients and are
+     * not currently being shown on a screen. Workspaces after the deleted one are
+     * shifted down by one.
+     */
+    pub fn delete_workspace(&mut self, index: usize) -> errors::Result<()> {
+        let invalid = |msg: &str| {
+            Err(PenroseError::InvalidWorkspaceChange(format!(
+                "workspace {} {}",
+                index, msg
+            )))
+        };
+        if index < self.n_static_workspaces || index >= self.workspaces.len() {
+            return invalid("can not be deleted");
+        }
+        if self.screen_for_workspace(index).is_some() {
+            return invalid("is currently visible");
+        }
+        if self.client_map.values().any(|c| c.workspace() == index) {
+            return invalid("still has clients");
+        }
+
+        let ws = self.workspaces.remove(index);
+        info!("deleting workspace '{}'", ws.name());
+
+        let shift = |wix: usize| if wix > index { wix - 1 } else { wix };
+        self.empty_since = self
+            .empty_since
+            .drain()
+            .filter(|(wix, _)| *wix != index)
+            .map(|(wix, t)| (shift(wix), t))
+            .collect();
+        for s in self.screens.iter_mut() {
+            s.wix = shift(s.wix);
+            s.view_mask = remove_tag_bit(s.view_mask, index);
+        }
+        self.client_map
+            .values_mut()
+            .for_each(|c| c.remove_workspace_tag(index));
+        self.previous_workspace = if self.previous_workspace == index {
+            self.active_ws_index()
+        } else {
+            shift(self.previous_workspace)
+        };
+        let store = &mut self.store;
+        for c in self
+            .client_map
+            .values_mut()
+            .filter(|c| c.workspace() > index)
+        {
+            c.set_workspace(c.workspace() - 1);
+            self.conn.set_client_workspace(c.id(), c.workspace());
+            if let Err(e) = store.record(Entry::Workspace(c.id(), c.workspace())) {
+                warn!("unable to record journal entry: {}", e);
+            }
+        }
+        for c in self
+            .scratchpads
+            .iter_mut()
+            .filter_map(|s| s.client.as_mut())
+        {
+            c.set_workspace(shift(c.workspace()));
+        }
+
+        self.workspaces_updated();
+        Ok(())
+    }
+
+    /// Rename the workspace at `index`
+    pub fn rename_workspace(&mut self, index: usize, name: impl Into<String>) {
+        if let Some(ws) = self.workspaces.get_mut(index) {
+            ws.set_name(name);
+            self.workspaces_updated();
+        }
+    }
+
+    /*
+     * Remove workspaces created at runtime once they have been empty and hidden for longer than
+     * Config::workspace_cleanup. The grace period stops status bars flickering when the last
+     * client on a workspace is closed and a new one is opened straight away. Returns how long it
+     * will be until the next workspace is due to be removed.
+     */
+    fn remove_empty_workspaces(&mut self) -> Option<Duration> {
+        let grace = self.workspace_cleanup?;
+        let now = Instant::now();
+        let idle: Vec<usize> = (self.n_static_workspaces..self.workspaces.len())
+            .filter(|wix| self.screen_for_workspace(*wix).is_none())
+            .filter(|wix| !self.client_map.values().any(|c| c.workspace() == *wix))
+            .collect();
+        self.empty_since.retain(|wix, _| idle.contains(wix));
+        for wix in idle.iter() {
+            self.empty_since.entry(*wix).or_insert(now);
+        }
+
+        // highest index first so that the indices of the others are left unchanged
+        for index in idle.into_iter().rev() {
+            if now.duration_since(self.empty_since[&index]) < grace {
+                continue;
+            }
+            let name = self.workspaces[index].name().to_string();
+            if let Err(e) = self.delete_workspace(index) {
+                self.handle_error(e);
+                continue;
+            }
+            run_hooks!(workspace_removed, self, index, &name);
+        }
+
+        self.empty_since
+            .values()
+            .map(|t| grace.saturating_sub(now.duration_since(*t)))
+            .min()
+    }
+
+    /**
+     * Set the displayed workspace for the focused screen to be `index` in the list of
+     * workspaces passed at `init`. An error notification is sent (see `notify`) if the
+     * index is out of bounds which is only possible if you manually bind an action to
+     * this with an invalid index. You should almost always be using the `gen_keybindings!`
+     * macro to set up your keybindings so this is not normally an issue.
+     */
+    pub fn focus_workspace(&mut self, index: usize) {
+        if !self.check_workspace_index(index) {
+            return;
+        }
+        info!(
+            "ACTIVE_LAYOUT {}",
+            self.workspaces[index].layout_symbol().unwrap_or_default()
+        );
+        let prev = self.active_ws_index();
+        if prev == index {
+            return; // already focused on the current screen
+        } else {
+            self.previous_workspace = prev;
+        }
+        self.spawn_first_view_programs(index);
+        self.move_sticky_clients(prev, index);
+
+        match self.screens.iter().position(|s| s.wix == index) {
+            Some(i) => {
+                // The workspace we want is currently displayed on another screen so
+                // pull the target workspace to the focused screen, and place the
+                // workspace we had on the screen where the target was
+                self.move_following_clients(&[(prev, index), (index, prev)]);
+                self.switch_workspaces(&[(i, prev), (self.focused_screen, index)]);
+            }
+            None => {
+                // target not currently displayed so swap out what we currently have
+                // displayed and replace it with the target workspace
+                self.move_following_clients(&[(prev, index)]);
+                self.switch_workspaces(&[(self.focused_screen, index)]);
+            }
+        }
+        self.refresh_tag_views();
+        self.conn.set_current_workspace(index);
+        self.focus_active_workspace_client();
+        run_hooks!(workspace_change, self, prev, index);
+    }
+
+    /*
+     * Show each of the given (screen, workspace) pairs, swapping out the clients of workspaces
+     * that are no longer displayed for those that now are using the configured WorkspaceSwitch
+     * strategy. Layouts are re-applied for every workspace given as screen dimensions may differ.
+     */
+    fn switch_workspaces(&mut self, changes: &[(usize, usize)]) {
+        let before: Vec<usize> = self.screens.iter().map(|s| s.wix).collect();
+        let mut after = before.clone();
+        changes.iter().for_each(|(s, wix)| after[*s] = *wix);
+
+        let incoming: Vec<WinId> = after
+            .iter()
+            .filter(|wix| !before.contains(wix))
+            .flat_map(|wix| self.mapped_with_workspace(*wix))
+            .collect();
+        let outgoing: Vec<WinId> = before
+            .iter()
+            .filter(|wix| !after.contains(wix))
+            .flat_map(|wix| self.mapped_with_workspace(*wix))
+            .filter(|id| !incoming.contains(id))
+            .collect();
+
+        self.unmap_outgoing_first(&outgoing);
+        for (s, wix) in changes.iter() {
+            self.screens[*s].wix = *wix;
+        }
+        changes.iter().for_each(|(_, wix)| self.apply_layout(*wix));
+        incoming.iter().for_each(|id| self.map_client(*id));
+        self.hide_outgoing(outgoing);
+        self.conn.flush();
+    }
+
+    // Clients that are being swapped out are unmapped before anything else is done when using
+    // WorkspaceSwitch::UnmapFirst
+    fn unmap_outgoing_first(&self, outgoing: &[WinId]) {
+        if self.workspace_switch == WorkspaceSwitch::UnmapFirst {
+            outgoing.iter().for_each(|id| self.conn.unmap_window(*id));
+        }
+    }
+
+    // Hide clients that have been swapped out once the incoming clients are in place
+    fn hide_outgoing(&mut self, outgoing: Vec<WinId>) {
+        match self.workspace_switch {
+            WorkspaceSwitch::UnmapFirst => (),
+            WorkspaceSwitch::MapFirst => outgoing.iter().for_each(|id| self.conn.unmap_window(*id)),
+            WorkspaceSwitch::Offscreen => {
+                // just past the right hand edge of the right most screen
+                let off_x = self
+                    .screens
+                    .iter()
+                    .map(|s| s.true_region.values())
+                    .map(|(x, _, w, _)| x + w)
+                    .max()
+                    .unwrap_or(0);
+                for id in outgoing {
+                    match self.client_map.get(&id).and_then(|c| c.region()) {
+                        Some(r) => {
+                            let (_, y, w, h) = r.values();
+                            let off = Region::new(off_x, y, w, h);
+                            self.place_window(id, off, self.border_style.width);
+                            self.conn.set_client_hidden(id, true);
+                            self.parked.insert(id);
+                        }
+                        None => self.conn.unmap_window(id),
+                    }
+                }
+            }
+        }
+    }
+
+    // Map a client that is now visible, clearing the hidden state of clients that were parked
+    // off screen
+    fn map_client(&mut self, id: WinId) {
+        self.conn.map_window(id);
+        if self.parked.remove(&id) {
+            self.conn.set_client_hidden(id, false);
+        }
+    }
+
+    /*
+     * Clients from workspaces that are not being displayed that should be shown on the given
+     * screen because they are tagged with one of the workspaces it is viewing. Clients that
+     * could be shown on more than one screen are only shown on the first of them.
+     */
+    fn tagged_clients_for_screen(&self, screen: usize) -> Vec<WinId> {
+        if self.screens.iter().all(|s| s.view_mask == 0)
+            && self
+                .client_map
+                .values()
+                .all(|c| c.tags() == tag_bit(c.workspace()))
+        {
+            return vec![];
+        }
+
+        let displayed: Vec<usize> = self.screens.iter().map(|s| s.wix).collect();
+        let mut candidates: Vec<WinId> = self
+            .workspaces
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !displayed.contains(i))
+            .flat_map(|(_, ws)| ws.iter().copied())
+            .collect();
+        let mut floating: Vec<WinId> = self
+            .client_map
+            .values()
+            .filter(|c| c.is_floating() && !displayed.contains(&c.workspace()))
+            .map(|c| c.id())
+            .collect();
+        floating.sort_unstable();
+        candidates.extend(floating);
+
+        let mut claimed = HashSet::new();
+        for (i, s) in self.screens.iter().enumerate().take(screen + 1) {
+            let views = s.views();
+            let ids: Vec<WinId> = candidates
+                .iter()
+                .filter(|id| !claimed.contains(*id))
+                .filter(|id| {
+                    self.client_map
+                        .get(id)
+                        .is_some_and(|c| c.tags() & views != 0)
+                })
+                .copied()
+                .collect();
+            if i == screen {
+                return ids;
+            }
+            claimed.extend(ids);
+        }
+
+        vec![]
+    }
+
+    // Map clients that are now visible through tags and unmap those that no longer are before
+    // laying out all visible workspaces again.
+    fn refresh_tag_views(&mut self) {
+        let shown: HashSet<WinId> = (0..self.screens.len())
+            .flat_map(|i| self.tagged_clients_for_screen(i))
+            .collect();
+        if shown.is_empty() && self.tag_shown.is_empty() {
+            return;
+        }
+
+        let displayed: Vec<usize> = self.screens.iter().map(|s| s.wix).collect();
+        for id in self.tag_shown.difference(&shown) {
+            match self.client_map.get(id) {
+                Some(c) if !displayed.contains(&c.workspace()) => self.conn.unmap_window(*id),
+                _ => (),
+            }
+        }
+        let newly_shown: Vec<WinId> = shown.difference(&self.tag_shown).copied().collect();
+        newly_shown.into_iter().for_each(|id| self.map_client(id));
+
+        self.tag_shown = shown;
+        displayed.into_iter().for_each(|wix| self.apply_layout(wix));
+    }
+
+    /**
+     * Add (or remove) the workspace at `index` as a tag on the focused client. Tagged clients
+     * are also shown on any screen that is viewing one of their tags (see toggle_view) in
+     * addition to the workspace that they are on, which can not be removed as a tag.
+     */
+    pub fn toggle_tag(&mut self, index: usize) {
+        if index >= self.workspaces.len() {
+            return warn!("unable to toggle tag: invalid workspace {}", index);
+        }
+        let c = match self
+            .focused_client_id()
+            .and_then(|id| self.client_map.get_mut(&id))
+        {
+            Some(c) => c,
+            None => return,
+        };
+        if c.workspace() == index {
+            return debug!(
+                "client {} is on workspace {}: not removing tag",
+                c.id(),
+                index
+            );
+        }
+
+        let tagged = !c.has_tag(index);
+        c.set_tag(index, tagged);
+        self.refresh_tag_views();
+    }
+
+    /**
+     * Start (or stop) viewing the clients tagged with the workspace at `index` on the focused
+     * screen alongside those from the workspace being displayed. Clients are laid out using
+     * the layout of the displayed workspace. Clients from workspaces that are displayed on a
+     * screen are only ever shown on that screen.
+     */
+    pub fn toggle_view(&mut self, index: usize) {
+        let screen = &mut self.screens[self.focused_screen];
+        if index >= self.workspaces.len() || index == screen.wix {
+            return;
+        }
+
+        screen.toggle_view(index);
+        self.refresh_tag_views();
+    }
+
+    /// The workspaces being viewed on the screen at `index`: the displayed workspace first
+    pub fn viewed_workspaces(&self, index: usize) -> Vec<usize> {
+        let screen = match self.screens.get(index) {
+            Some(s) => s,
+            None => return vec![],
+        };
+        let views = screen.views();
+        let mut viewed = vec![screen.wix];
+        viewed.extend(
+            (0..self.workspaces.len()).filter(|i| *i != screen.wix && views & tag_bit(*i) != 0),
+        );
+
+        viewed
+    }
+
+    // Run the startup hook and launch the programs from spawn rules that are due on startup
+    // or for the workspaces that are initially visible.
+    fn autostart(&mut self) {
+        if let Some(hook) = self.startup_hook {
+            hook(self);
+        }
+
+        let (startup, rest): (Vec<SpawnRule>, Vec<SpawnRule>) = self
+            .spawn_rules
+            .drain(..)
+            .partition(|r| r.trigger == SpawnTrigger::Startup);
+        self.spawn_rules = rest;
+        for rule in startup {
+            self.spawn_from_rule(rule);
+        }
+
+        let visible: Vec<usize> = self.screens.iter().map(|s| s.wix).collect();
+        for wix in visible {
+            self.spawn_first_view_programs(wix);
+        }
+    }
+
+    fn spawn_first_view_programs(&mut self, wix: usize) {
+        let (due, rest): (Vec<SpawnRule>, Vec<SpawnRule>) = self
+            .spawn_rules
+            .drain(..)
+            .partition(|r| r.trigger == SpawnTrigger::FirstView && r.workspace == wix);
+        self.spawn_rules = rest;
+        for rule in due {
+            self.spawn_from_rule(rule);
+        }
+    }
+
+    fn spawn_from_rule(&mut self, rule: SpawnRule) {
+        debug!("running spawn rule: {:?}", rule);
+        if let Some(child) = self.spawn_on_workspace(rule.cmd, rule.workspace) {
+            self.autostarted.push(child);
+        }
+    }
+
+    /**
+     * Run an external command, placing the first window that it opens on the workspace with
+     * the given index rather than the active workspace. The window is matched using either
+     * the startup ID passed to the program or its pid.
+     */
+    pub fn spawn_on_workspace(&mut self, cmd: &str, workspace: usize) -> Option<Child> {
+        if workspace >= self.workspaces.len() {
+            warn!("unable to spawn '{}': invalid workspace {}", cmd, workspace);
+            return None;
+        }
+
+        self.n_spawned += 1;
+        let startup_id = format!("penrose-{}-{}_TIME0", process::id(), self.n_spawned);
+        let child = spawn_with_startup_id(cmd, &startup_id)?;
+        self.pending_spawns.push(PendingSpawn {
+            startup_id,
+            pid: child.id(),
+            workspace,
+        });
+
+        Some(child)
+    }
+
+    // Sticky clients follow the focused screen so they are moved along with workspace changes
+    fn move_sticky_clients(&mut self, from: usize, to: usize) {
+        let mut ids: Vec<WinId> = self
+            .client_map
+            .values()
+            .filter(|c| c.is_sticky() && c.workspace() == from)
+            .map(|c| c.id())
+            .collect();
+        ids.sort_unstable();
+
+        for id in ids {
+            debug!("moving sticky client {} to workspace {}", id, to);
+            self.shift_client(id, from, to);
+        }
+    }
+
+    // Following clients stay on their screen so they are moved along with the workspace
+    // changes for that screen. Clients are found for every change before any are moved so that
+    // swapping workspaces between screens doesn't move a client twice.
+    fn move_following_clients(&mut self, changes: &[(usize, usize)]) {
+        let mut moves: Vec<(WinId, usize, usize)> = changes
+            .iter()
+            .flat_map(|&(from, to)| {
+                self.client_map
+                    .values()
+                    .filter(move |c| c.is_following() && !c.is_sticky() && c.workspace() == from)
+                    .map(move |c| (c.id(), from, to))
+            })
+            .collect();
+        moves.sort_unstable();
+
+        for (id, from, to) in moves {
+            debug!("moving following client {} to workspace {}", id, to);
+            self.shift_client(id, from, to);
+        }
+    }
+
+    // Move a client to the end of another workspace without changing what is mapped
+    fn shift_client(&mut self, id: WinId, from: usize, to: usize) {
+        if let Some(c) = self.client_map.get_mut(&id) {
+            c.set_workspace(to);
+        }
+        if self.workspaces[from].remove_client(id).is_some() {
+            self.workspaces[to].append_client(id);
+        }
+        self.conn.set_client_workspace(id, to);
+        self.journal(Entry::Workspace(id, to));
+    }
+
+    pub fn toggle_workspace(&mut self) {
+        self.focus_workspace(self.previous_workspace);
+    }
+
+    /**
+     * Move the focused client to the workspace at `index` in the workspaces list.
+     * An error notification is sent if the index is out of bounds.
+     */
+    pub fn client_to_workspace(&mut self, index: usize) {
+        if !self.check_workspace_index(index) || index == self.active_ws_index() {
+            return;
+        }
+
+        let ws = &self.workspaces[self.active_ws_index()];
+        if let Some(id) = ws.focused_client() {
+            self.move_client_to_workspace(id, index);
+            self.focus_active_workspace_client();
+            self.notify_client_sent(id, index);
+        }
+    }
+
+    // Let the user know where a client has been sent
+    fn notify_client_sent(&mut self, id: WinId, index: usize) {
+        let msg = format!(
+            "sent \"{}\" to {}",
+            self.client_title(id),
+            self.workspaces[index].name()
+        );
+        self.notify(Notification::info(msg));
+    }
+
+    /**
+     * Move the focused client to the workspace at `index` and then focus that workspace,
+     * keeping focus on the client. The client stays mapped throughout so there is no flicker
+     * between leaving the current workspace and arriving at the new one.
+     * An error notification is sent if the index is out of bounds.
+     */
+    pub fn client_to_workspace_and_follow(&mut self, index: usize) {
+        if !self.check_workspace_index(index) || index == self.active_ws_index() {
+            return;
+        }
+
+        let ws = &self.workspaces[self.active_ws_index()];
+        if let Some(id) = ws.focused_client() {
+            self.transfer_client(id, index);
+            self.focus_workspace(index);
+            self.notify_client_sent(id, index);
+        }
+    }
+
+    /**
+     * Move all clients from the workspace at `src` to the end of the workspace at `dst`,
+     * preserving their order in the stack.
+     */
+    pub fn merge_workspaces(&mut self, src: usize, dst: usize) -> errors::Result<()> {
+        let n = self.workspaces.len();
+        if src >= n || dst >= n || src == dst {
+            return Err(PenroseError::InvalidWorkspaceChange(format!(
+                "unable to merge workspace {} into {}",
+                src, dst
+            )));
+        }
+
+        let ids = self.clients_on_workspace(src);
+        self.move_clients_to_workspace(&ids, dst);
+        Ok(())
+    }
+
+    /**
+     * Move all clients on the workspace at `index` that match `pred` to a newly created
+     * workspace, preserving their order in the stack. Returns the index of the new workspace.
+     */
+    pub fn split_workspace(
+        &mut self,
+        index: usize,
+        pred: impl Fn(&Client) -> bool,
+    ) -> errors::Result<usize> {
+        if index >= self.workspaces.len() {
+            return Err(PenroseError::UnknownWorkspace(index.to_string()));
+        }
+
+        let ids: Vec<WinId> = self
+            .clients_on_workspace(index)
+            .into_iter()
+            .filter(|id| pred(&self.client_map[id]))
+            .collect();
+        if ids.is_empty() {
+            return Err(PenroseError::InvalidWorkspaceChange(format!(
+                "no clients on workspace {} to split",
+                index
+            )));
+        }
+
+        let name = format!("{}-split", self.workspaces[index].name());
+        let new = self.create_workspace(name);
+        self.move_clients_to_workspace(&ids, new);
+        Ok(new)
+    }
+
+    /// Move focus to the next client in the stack
+    pub fn next_client(&mut self) {
+        self.cycle_client(Direction::Forward);
+    }
+
+    /// Move focus to the previous client in the stack
+    pub fn previous_client(&mut self) {
+        self.cycle_client(Direction::Backward);
+    }
+
+    /**
+     * Move focus to the nearest client in the given direction on the active workspace. This
+     * uses the regions that clients were last positioned at rather than their order in the
+     * stack, so it behaves predictably in grid-like layouts.
+     */
+    pub fn focus_in_direction(&mut self, direction: ScreenDirection) {
+        if let Some((id, new)) = self.neighbour_in_direction(direction, false) {
+            self.handle_leave_notify(id); // treat like losing x focus
+            self.handle_enter_notify(new); // treat like gaining x focus
+            self.warp_pointer_to_focus();
+        }
+    }
+
+    /**
+     * Swap the stack positions of the focused client and the nearest tiled client in the given
+     * direction on the active workspace, keeping focus on the same client. Like
+     * focus_in_direction this is based on where clients are on screen so, in grid-like
+     * layouts, it moves the client where you would expect it to go.
+     */
+    pub fn swap_in_direction(&mut self, direction: ScreenDirection) {
+        if let Some((_, other)) = self.neighbour_in_direction(direction, true) {
+            let wix = self.active_ws_index();
+            if self.workspaces[wix].swap_focused_with(other) {
+                self.apply_layout(wix);
+                self.warp_pointer_to_focus();
+            }
+        }
+    }
+
+    // The focused client on the active workspace and the nearest visible client to it in the
+    // given direction, based on the regions that they were last positioned at
+    fn neighbour_in_direction(
+        &self,
+        direction: ScreenDirection,
+        tiled_only: bool,
+    ) -> Option<(WinId, WinId)> {
+        let focused = self.focused_client()?;
+        if tiled_only && focused.is_floating() {
+            return None;
+        }
+        let (id, from) = (focused.id(), focused.region()?);
+
+        let candidates: Vec<(WinId, Region)> = self.workspaces[self.active_ws_index()]
+            .iter()
+            .filter(|c| **c != id && !self.hidden.contains(c))
+            .filter_map(|c| self.client_map.get(c))
+            .filter(|c| !(tiled_only && c.is_floating()))
+            .filter_map(|c| c.region().map(|r| (c.id(), r)))
+            .collect();
+        let regions: Vec<Region> = candidates.iter().map(|(_, r)| *r).collect();
+
+        from.nearest_in_direction(&regions, direction)
+            .map(|i| (id, candidates[i].0))
+    }
+
+    pub fn drag_client_forward(&mut self) {
+        self.drag_client(Direction::Forward);
+    }
+
+    pub fn drag_client_backward(&mut self) {
+        self.drag_client(Direction::Backward);
+    }
+
+    /// Shift every client on the active workspace one position forward through the stack
+    pub fn rotate_clients_forward(&mut self) {
+        self.restack(|ws| ws.rotate_clients(Direction::Forward));
+    }
+
+    /// Shift every client on the active workspace one position backward through the stack
+    pub fn rotate_clients_backward(&mut self) {
+        self.restack(|ws| ws.rotate_clients(Direction::Backward));
+    }
+
+    /**
+     * Swap the focused client with the client in the main position. If the focused client is
+     * already in the main position it is swapped with the next client in the stack.
+     */
+    pub fn swap_with_main(&mut self) {
+        self.restack(|ws| {
+            ws.swap_focused_with_main();
+        });
+    }
+
+    /// Reverse the order of the clients on the active workspace
+    pub fn reverse_clients(&mut self) {
+        self.restack(|ws| ws.reverse_clients());
+    }
+
+    /// Kill the focused client window: the same as kill_focused_client
+    pub fn kill_client(&mut self) {
+        self.kill_focused_client();
+    }
+
+    /**
+     * Close the focused client. Clients that support the WM_DELETE_WINDOW protocol are asked to
+     * close themselves (giving them the chance to prompt about unsaved work) and stay managed
+     * until their window is destroyed. Anything else is forcibly disconnected from the X server.
+     */
+    pub fn kill_focused_client(&mut self) {
+        let (id, supports_delete) = match self.focused_client() {
+            Some(c) => (c.id(), c.supports_protocol("WM_DELETE_WINDOW")),
+            None => return,
+        };
+
+        if supports_delete {
+            self.conn.send_client_event(id, "WM_DELETE_WINDOW");
+            self.conn.flush();
+            return;
+        }
+
+        self.conn.kill_window(id);
+        self.conn.flush();
+        self.remove_client(id);
+        self.apply_layout(self.active_ws_index());
+    }
+
+    /**
+     * Toggle the focused client between being fullscreen and its normal position. While
+     * fullscreen, the client covers the whole of its screen (including any space reserved
+     * for a bar) and is skipped when the workspace layout is applied.
+     */
+    pub fn toggle_fullscreen(&mut self) {
+        if let Some((id, fullscreen)) = self.focused_client().map(|c| (c.id(), c.is_fullscreen())) {
+            self.set_fullscreen(id, !fullscreen);
+        }
+    }
+
+    /**
+     * Show the placement grid (if one was provided in the Config) over the focused screen so
+     * that the focused client can be moved and resized from the keyboard. Only floating
+     * clients (or clients on a workspace using a floating layout) can be placed. The keyboard
+     * is grabbed until a cell (or rectangle of cells) has been picked or the grid is closed
+     * again with Escape.
+     */
+    pub fn open_placement_grid(&mut self) {
+        let mut grid = match self.placement.take() {
+            Some(g) => g,
+            None => return warn!("no placement grid has been configured"),
+        };
+
+        let wix = self.active_ws_index();
+        let floating_layout = self.workspaces[wix]
+            .layout_conf()
+            .is_ok_and(|lc| lc.floating);
+        let target = self
+            .border_focus
+            .and_then(|id| self.client_map.get(&id))
+            .filter(|c| c.workspace() == wix && !c.is_fullscreen())
+            .filter(|c| c.is_floating() || floating_layout)
+            .map(|c| c.id());
+
+        match target {
+            _ if grid.is_open() => (),
+            None => self.notify(Notification::error("no floating client to place")),
+            Some(_) if !self.conn.grab_keyboard() => {
+                warn!("unable to grab the keyboard for the placement grid")
+            }
+            Some(id) => {
+                grid.open(&self.usable_screen_region(self.focused_screen));
+                self.placing = Some(id);
+            }
+        }
+
+        self.placement = Some(grid);
+    }
+
+    /// Hide the placement grid and release the keyboard without moving anything
+    pub fn close_placement_grid(&mut self) {
+        match self.placement.as_mut().filter(|g| g.is_open()) {
+            Some(g) => g.close(),
+            None => return,
+        }
+        self.conn.ungrab_keyboard();
+        self.placing = None;
+    }
+
+    /// Whether or not the placement grid is currently open
+    pub fn placement_grid_is_open(&self) -> bool {
+        self.placement.as_ref().is_some_and(|g| g.is_open())
+    }
+
+    /**
+     * Toggle whether the focused client is sticky. Sticky clients are shown on every
+     * workspace: they are moved to whichever workspace the focused screen switches to.
+     */
+    pub fn toggle_sticky(&mut self) {
+        let id = match self.focused_client() {
+            Some(c) => c.id(),
+            None => return,
+        };
+        if let Some(c) = self.client_map.get_mut(&id) {
+            c.set_sticky(!c.is_sticky());
+            debug!("client {} sticky: {}", id, c.is_sticky());
+        }
+    }
+
+    /**
+     * Minimize the focused client: it is unmapped and removed from the workspace stack but
+     * is still managed, so that it can be brought back using `restore_minimized` or
+     * `cycle_minimized`.
+     */
+    pub fn minimize_client(&mut self) {
+        let id = match self.focused_client_id() {
+            Some(id) => id,
+            None => return,
+        };
+
+        debug!("minimizing client {}", id);
+        let wix = self.active_ws_index();
+        self.workspaces[wix].remove_client(id);
+        self.hidden.remove(&id);
+        self.conn.unmap_window(id);
+        self.minimized.push(id);
+        self.apply_layout(wix);
+        self.focus_active_workspace_client();
+    }
+
+    /// Restore (and focus) the most recently minimized client on the active workspace
+    pub fn restore_minimized(&mut self) {
+        let wix = self.active_ws_index();
+        let ids = self.minimized_clients(wix);
+        if let Some(id) = ids.last() {
+            self.restore_minimized_client(*id, wix);
+        }
+    }
+
+    /**
+     * Swap the focused client with the client on the active workspace that has been
+     * minimized for the longest, so that repeated calls cycle through all of the minimized
+     * clients. If there is no focused client this is the same as restoring the oldest one.
+     */
+    pub fn cycle_minimized(&mut self) {
+        let wix = self.active_ws_index();
+        let ids = self.minimized_clients(wix);
+        if let Some(id) = ids.first() {
+            self.minimize_client();
+            self.restore_minimized_client(*id, wix);
+        }
+    }
+
+    /// The minimized clients on the workspace at `index`, in the order they were minimized
+    pub fn minimized_clients(&self, index: usize) -> Vec<WinId> {
+        self.minimized
+            .iter()
+            .filter(|id| self.workspace_of(**id) == Some(index))
+            .copied()
+            .collect()
+    }
+
+    fn restore_minimized_client(&mut self, id: WinId, wix: usize) {
+        debug!("restoring client {}", id);
+        self.minimized.retain(|m| *m != id);
+        self.workspaces[wix].add_client(id, self.insert_point);
+        self.conn.map_window(id);
+        self.apply_layout(wix);
+        self.handle_enter_notify(id);
+        self.warp_pointer_to_focus();
+    }
+
+    /**
+     * Toggle whether the focused client follows its screen. Following clients are moved to
+     * whichever workspace is shown on the screen they are on, but unlike sticky clients they
+     * are left alone when the workspace on a different screen changes.
+     */
+    pub fn toggle_follow(&mut self) {
+        let id = match self.focused_client() {
+            Some(c) => c.id(),
+            None => return,
+        };
+        if let Some(c) = self.client_map.get_mut(&id) {
+            c.set_following(!c.is_following());
+            debug!("client {} following: {}", id, c.is_following());
+        }
+    }
+
+    /**
+     * Run an action by name: either one of the built in NAMED_ACTIONS, "set_layout <symbol>"
+     * (see `set_layout_by_symbol`) or an action added using register_action. Registered
+     * actions take precedence over built in ones with the same name. Returns the spawned
+     * process if the action started one.
+     */
+    pub fn run_action(&mut self, name: &str) -> errors::Result<Option<Child>> {
+        if let Some(action) = self.actions.remove(name) {
+            let started = Instant::now();
+            let child = action(self);
+            self.check_callback_budget(started, || format!("action {}", name));
+            self.actions.insert(name.to_string(), action);
+            return Ok(child);
+        }
+        if let Some(symbol) = name.strip_prefix("set_layout ") {
+            self.set_layout_by_symbol(symbol);
+            return Ok(None);
+        }
+
+        match name {
+            "next_client" => self.next_client(),
+            "previous_client" => self.previous_client(),
+            "focus_left" => self.focus_in_direction(ScreenDirection::Left),
+            "focus_right" => self.focus_in_direction(ScreenDirection::Right),
+            "focus_up" => self.focus_in_direction(ScreenDirection::Up),
+            "focus_down" => self.focus_in_direction(ScreenDirection::Down),
+            "swap_left" => self.swap_in_direction(ScreenDirection::Left),
+            "swap_right" => self.swap_in_direction(ScreenDirection::Right),
+            "swap_up" => self.swap_in_direction(ScreenDirection::Up),
+            "swap_down" => self.swap_in_direction(ScreenDirection::Down),
+            "drag_client_forward" => self.drag_client_forward(),
+            "drag_client_backward" => self.drag_client_backward(),
+            "rotate_clients_forward" => self.rotate_clients_forward(),
+            "rotate_clients_backward" => self.rotate_clients_backward(),
+            "swap_with_main" => self.swap_with_main(),
+            "reverse_clients" => self.reverse_clients(),
+            "kill_client" => self.kill_client(),
+            "kill_focused_client" => self.kill_focused_client(),
+            "toggle_fullscreen" => self.toggle_fullscreen(),
+            "placement_grid" => self.open_placement_grid(),
+            "toggle_sticky" => self.toggle_sticky(),
+            "toggle_follow" => self.toggle_follow(),
+            "minimize_client" => self.minimize_client(),
+            "restore_minimized" => self.restore_minimized(),
+            "cycle_minimized" => self.cycle_minimized(),
+            "toggle_workspace" => self.toggle_workspace(),
+            "focus_urgent" => self.focus_urgent(),
+            "toggle_dnd" => self.toggle_dnd(),
+            "focus_previous_client" => self.focus_previous_client(),
+            "next_screen" => self.next_screen(),
+            "previous_screen" => self.previous_screen(),
+            "client_to_next_screen" => self.client_to_next_screen(),
+            "client_to_previous_screen" => self.client_to_previous_screen(),
+            "throw_left" => self.throw_client_to_screen(ScreenDirection::Left),
+            "throw_right" => self.throw_client_to_screen(ScreenDirection::Right),
+            "throw_up" => self.throw_client_to_screen(ScreenDirection::Up),
+            "throw_down" => self.throw_client_to_screen(ScreenDirection::Down),
+            "swap_screens" => self.swap_screens(),
+            "toggle_screen_rotation" => self.toggle_screen_rotation(),
+            "next_layout" => self.next_layout(),
+            "previous_layout" => self.previous_layout(),
+            "inc_main" => self.inc_main(),
+            "dec_main" => self.dec_main(),
+            "inc_ratio" => self.inc_ratio(),
+            "dec_ratio" => self.dec_ratio(),
+            "inc_gaps" => self.inc_gaps(),
+            "dec_gaps" => self.dec_gaps(),
+            "toggle_gaps" => self.toggle_gaps(),
+            "open_command_palette" => self.open_command_palette(),
+            "open_program_launcher" => self.open_launcher(PaletteSource::Programs),
+            "open_window_launcher" => self.open_launcher(PaletteSource::Windows),
+            "open_workspace_launcher" => self.open_launcher(PaletteSource::Workspaces),
+            "expose" => self.open_expose(false),
+            "expose_all" => self.open_expose(true),
+            "reload_config" => self.reload_config(),
+            _ => return Err(PenroseError::UnknownAction(name.to_string())),
+        }
+
+        Ok(None)
+    }
+
+    /**
+     * Add a named action that can be run from the command palette, over IPC or using
+     * run_action. Registering an action with an existing name replaces it.
+     */
+    pub fn register_action(&mut self, name: impl Into<String>, action: FireAndForget) {
+        self.actions.insert(name.into(), action);
+    }
+
+    /**
+     * Show the command palette (if one was provided in the Config) on the focused screen and
+     * grab the keyboard so that it receives all key presses until an entry is run or the
+     * palette is closed again with Escape.
+     */
+    pub fn open_command_palette(&mut self) {
+        self.open_launcher(PaletteSource::Actions);
+    }
+
+    /**
+     * Show the command palette (if one was provided in the Config) listing entries from the
+     * given source rather than named actions and key bindings, such as programs to run or
+     * windows to jump to. See `palette::launcher` for binding this to a key.
+     */
+    pub fn open_launcher(&mut self, source: PaletteSource) {
+        let mut palette = match self.palette.take() {
+            Some(p) => p,
+            None => return warn!("no command palette has been configured"),
+        };
+
+        if palette.is_open() {
+            // already showing
+        } else if self.conn.grab_keyboard() {
+            let entries = match source {
+                PaletteSource::Actions => self.palette_entries(palette.key_codes()),
+                _ => self.launcher_entries(source),
+            };
+            let region = *self.screens[self.focused_screen].region(false);
+            palette.open(entries, source, &region);
+        } else {
+            warn!("unable to grab the keyboard for the command palette");
+        }
+
+        self.palette = Some(palette);
+    }
+
+    /**
+     * Grab the keyboard and pass the next key press to the given submap of bindings rather
+     * than the top level key bindings. Pressing a key that is bound in the submap runs its
+     * action, anything else cancels the submap. Normally called via a binding created with
+     * the gen_submap macro rather than directly.
+     */
+    pub fn enter_submap(&mut self, bindings: Rc<KeyBindings>) {
+        if self.submap.is_none() && !self.conn.grab_keyboard() {
+            return warn!("unable to grab the keyboard for key binding submap");
+        }
+        self.submap = Some(bindings);
+    }
+
+    /// Leave the active submap (if there is one) without running any of its bindings
+    pub fn exit_submap(&mut self) {
+        if self.submap.take().is_some() {
+            self.conn.ungrab_keyboard();
+        }
+    }
+
+    /// Whether or not key presses are currently being handled by a submap
+    pub fn in_submap(&self) -> bool {
+        self.submap.is_some()
+    }
+
+    /// Hide the command palette and release the keyboard
+    pub fn close_command_palette(&mut self) {
+        if let Some(p) = self.palette.as_mut().filter(|p| p.is_open()) {
+            p.close();
+            self.conn.ungrab_keyboard();
+        }
+    }
+
+    /**
+     * Show the expose overview (if one was provided in the Config): the clients on the active
+     * workspace, or on every workspace if `all_workspaces` is true, are arranged in a grid on
+     * the focused screen and labelled with a letter. The keyboard is grabbed until a client is
+     * picked using its label or the overview is closed again with Escape, after which all of
+     * the clients are put back where they were.
+     */
+    pub fn open_expose(&mut self, all_workspaces: bool) {
+        let mut expose = match self.expose.take() {
+            Some(e) => e,
+            None => return warn!("no expose overlay has been configured"),
+        };
+
+        let workspaces: Vec<usize> = if all_workspaces {
+            (0..self.workspaces.len()).collect()
+        } else {
+            vec![self.active_ws_index()]
+        };
+        let ids: Vec<WinId> = workspaces
+            .into_iter()
+            .flat_map(|wix| self.clients_on_workspace(wix))
+            .filter(|id| !self.minimized.contains(id))
+            .collect();
+
+        if expose.is_open() || ids.is_empty() {
+            // already showing or nothing to show
+        } else if self.conn.grab_keyboard() {
+            let r = self.usable_screen_region(self.focused_screen);
+            let clients: Vec<&Client> = ids.iter().map(|id| &self.client_map[id]).collect();
+            let positions = grid(&clients, None, &r, 0, 0.0);
+            for (id, r) in positions.iter() {
+                self.place_window(*id, *r, self.border_style.width);
+                self.conn.map_window(*id);
+                self.conn.raise_window(*id);
+            }
+            expose.open(&positions);
+            self.exposed = ids;
+        } else {
+            warn!("unable to grab the keyboard for expose");
+        }
+
+        self.expose = Some(expose);
+    }
+
+    /// Hide the expose overview, release the keyboard and put all clients back in place
+    pub fn close_expose(&mut self) {
+        match self.expose.as_mut().filter(|e| e.is_open()) {
+            Some(e) => e.close(),
+            None => return,
+        }
+        self.conn.ungrab_keyboard();
+
+        for id in std::mem::take(&mut self.exposed) {
+            let c = match self.client_map.get(&id) {
+                Some(c) => c,
+                None => continue, // closed while the overview was open
+            };
+            if self.screen_for_workspace(c.workspace()).is_none() {
+                self.conn.unmap_window(id);
+            } else if let (true, Some(r)) = (c.is_floating(), c.region()) {
+                self.place_window(id, r, self.border_style.width);
+            }
+        }
+        let visible: Vec<usize> = self.screens.iter().map(|s| s.wix).collect();
+        visible.into_iter().for_each(|wix| self.apply_layout(wix));
+    }
+
+    /// Whether or not the expose overview is currently open
+    pub fn expose_is_open(&self) -> bool {
+        self.expose.as_ref().is_some_and(|e| e.is_open())
+    }
+
+    // Named actions (built in first) followed by the current key bindings
+    fn palette_entries(&self, codes: &CodeMap) -> Vec<PaletteEntry> {
+        let mut registered: Vec<&String> = self.actions.keys().collect();
+        registered.sort();
+        let mut bindings: Vec<(String, KeyCode)> = self
+            .binding_codes
+            .iter()
+            .map(|k| {
+                let label = key_binding_label(k, codes).unwrap_or_else(|| format!("{:?}", k));
+                (format!("<{}>", label), *k)
+            })
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+
+        NAMED_ACTIONS
+            .iter()
+            .filter(|name| !self.actions.contains_key(**name))
+            .map(|name| name.to_string())
+            .chain(registered.into_iter().cloned())
+            .map(|name| PaletteEntry {
+                label: name.clone(),
+                action: PaletteAction::Named(name),
+            })
+            .chain(bindings.into_iter().map(|(label, code)| PaletteEntry {
+                label,
+                action: PaletteAction::Binding(code),
+            }))
+            .collect()
+    }
+
+    // Palette entries for sources other than PaletteSource::Actions
+    fn launcher_entries(&self, source: PaletteSource) -> Vec<PaletteEntry> {
+        match source {
+            PaletteSource::Actions => vec![],
+            PaletteSource::Programs => programs_on_path()
+                .into_iter()
+                .map(|p| PaletteEntry {
+                    label: p.clone(),
+                    action: PaletteAction::Spawn(p),
+                })
+                .collect(),
+            PaletteSource::Windows => {
+                let mut clients: Vec<&Client> = self.client_map.values().collect();
+                clients.sort_by_key(|c| (c.workspace(), c.id()));
+                clients
+                    .into_iter()
+                    .map(|c| PaletteEntry {
+                        label: format!(
+                            "{} ({}) [{}]",
+                            c.wm_name(),
+                            c.class(),
+                            self.workspaces[c.workspace()].name()
+                        ),
+                        action: PaletteAction::FocusClient(c.id()),
+                    })
+                    .collect()
+            }
+            PaletteSource::Workspaces => self
+                .workspaces
+                .iter()
+                .enumerate()
+                .map(|(i, ws)| PaletteEntry {
+                    label: ws.name().to_string(),
+                    action: PaletteAction::FocusWorkspace(i),
+                })
+                .collect(),
+            PaletteSource::Custom(entries) => entries(self),
+        }
+    }
+
+    /**
+     * Show or hide the named scratchpad on top of the current workspace. If the scratchpad
+     * does not currently have a client then its program is spawned and the resulting window
+     * will be captured and shown once it is mapped.
+     *
+     * Returns the spawned process (if one was started) so that it can be tracked along with
+     * other spawned programs when called from a key binding.
+     */
+    pub fn toggle_scratchpad(&mut self, name: &str) -> Option<Child> {
+        let s = match self.scratchpads.iter_mut().find(|s| s.conf.name == name) {
+            Some(s) => s,
+            None => {
+                warn!("unknown scratchpad: {}", name);
+                return None;
+            }
+        };
+
+        let (id, visible) = match s.client {
+            Some(ref c) => (c.id(), s.visible),
+            None if s.pending => return None, // waiting for the window to be mapped
+            None => {
+                let child = spawn(s.conf.prog);
+                s.pending = child.is_some();
+                return child;
+            }
+        };
+
+        if visible {
+            // remember where the user left it
+            if let Ok(r) = self.conn.window_geometry(id) {
+                if let Some(c) = s.client.as_mut() {
+                    c.set_region(r);
+                }
+            }
+            s.visible = false;
+            self.conn.unmap_window(id);
+            self.focus_active_workspace_client();
+        } else {
+            s.visible = true;
+            let r = s.client.as_ref().and_then(|c| c.region());
+            self.conn.map_window(id);
+            if let Some(r) = r {
+                self.place_window(id, r, self.border_style.width);
+            }
+            self.conn.raise_window(id);
+            self.handle_enter_notify(id);
+        }
+
+        None
+    }
+
+    /// Rearrange the windows on the focused screen using the next available layout
+    pub fn next_layout(&mut self) {
+        self.cycle_layout(Direction::Forward);
+    }
+
+    /// Rearrange the windows on the focused screen using the previous layout
+    pub fn previous_layout(&mut self) {
+        self.cycle_layout(Direction::Backward);
+    }
+
+    /**
+     * Replace the slots used by the active layout on the focused screen. Passing an empty Vec
+     * reverts to using the layout function.
+     */
+    pub fn set_layout_slots(&mut self, slots: Vec<Slot>) {
+        self.workspace_for_screen_mut(self.focused_screen)
+            .set_layout_slots(slots);
+        self.apply_layout(self.active_ws_index());
+    }
+
+    /// Grow or shrink a fixed size slot of the active layout by `step` pixels
+    pub fn update_slot_width(&mut self, index: usize, change: Change, step: u32) {
+        self.workspace_for_screen_mut(self.focused_screen)
+            .update_slot_width(index, change, step);
+        self.apply_layout(self.active_ws_index());
+    }
+
+    /**
+     * Send a message to the active layout on the focused screen (see Layout::handle_message),
+     * re-applying the layout if the message was handled. Returns whether it was handled.
+     */
+    pub fn send_layout_message(&mut self, msg: &dyn Any) -> bool {
+        let handled = self
+            .workspace_for_screen_mut(self.focused_screen)
+            .handle_layout_message(msg);
+        if handled {
+            self.apply_layout(self.active_ws_index());
+        } else {
+            debug!("layout message was not handled by the active layout");
+        }
+
+        handled
+    }
+
+    /// Drop (or restore) gaps for the active layout on the focused screen
+    pub fn toggle_gaps(&mut self) {
+        self.send_layout_message(&LayoutMessage::ToggleGaps);
+    }
+
+    /// Increase the number of windows in the main layout area
+    pub fn inc_main(&mut self) {
+        self.update_max_main(Change::More);
+    }
+
+    /// Reduce the number of windows in the main layout area
+    pub fn dec_main(&mut self) {
+        self.update_max_main(Change::Less);
+    }
+
+    /// Make the main area larger relative to sub-areas
+    pub fn inc_ratio(&mut self) {
+        self.update_main_ratio(Change::More);
+    }
+
+    /// Increase the size of the gaps between clients for the active layout
+    pub fn inc_gaps(&mut self) {
+        self.update_gaps(Change::More);
+    }
+
+    /// Reduce the size of the gaps between clients for the active layout
+    pub fn dec_gaps(&mut self) {
+        self.update_gaps(Change::Less);
+    }
+
+    /// Make the main area smaller relative to sub-areas
+    pub fn dec_ratio(&mut self) {
+        self.update_main_ratio(Change::Less);
+    }
+
+    /// The width and colors currently being used for client borders
+    pub fn border_style(&self) -> BorderStyle {
+        self.border_style
+    }
+
+    /**
+     * Switch to a new BorderStyle, recoloring the borders of all clients. If the border width
+     * has changed then all visible clients are repositioned to account for it.
+     */
+    pub fn set_border_style(&mut self, style: BorderStyle) {
+        let resized = style.width != self.border_style.width;
+        self.border_style = style;
+
+        if resized {
+            let visible: Vec<usize> = self.screens.iter().map(|s| s.wix).collect();
+            let floating: Vec<(WinId, Region)> = self
+                .client_map
+                .values()
+                .filter(|c| c.is_floating() && visible.contains(&c.workspace()))
+                .filter_map(|c| c.region().map(|r| (c.id(), r)))
+                .collect();
+            for (id, r) in floating {
+                self.position_client(id, r, style.width);
+            }
+            visible.into_iter().for_each(|wix| self.apply_layout(wix));
+        }
+
+        self.client_map
+            .keys()
+            .for_each(|id| self.refresh_border(*id));
+    }
+
+    /// Change the width of client borders, keeping the current colors
+    pub fn set_border_width(&mut self, width: u32) {
+        self.set_border_style(BorderStyle {
+            width,
+            ..self.border_style
+        });
+    }
+
+    /// The opacity levels given to focused and unfocused clients, if any have been set
+    pub fn opacity(&self) -> Option<Opacity> {
+        self.opacity
+    }
+
+    /**
+     * Change the opacity levels given to focused and unfocused clients and re-apply them to all
+     * clients. Clients with their own Opacity (set by a Rule or with `set_client_data`) keep it,
+     * and clearing the levels leaves the current opacity of other clients as it is.
+     */
+    pub fn set_opacity(&mut self, opacity: Option<Opacity>) {
+        self.opacity = opacity;
+        self.client_map
+            .keys()
+            .for_each(|id| self.refresh_client_opacity(*id));
+    }
+
+    /**
+     * Re-read the config file set using Config::with_config_file and apply it (see
+     * `apply_config_file`). If the file can not be read or is invalid then nothing is changed
+     * and the problems found are passed to the error_handler.
+     */
+    pub fn reload_config(&mut self) {
+        let path = match &self.config_file {
+            Some(path) => path.clone(),
+            None => {
+                warn!("no config file to reload");
+                return;
+            }
+        };
+
+        info!("reloading config from {}", path.display());
+        if let Err(e) = ConfigFile::load(&path).and_then(|f| self.apply_config_file(&f)) {
+            self.handle_error(e);
+        }
+    }
+
+    /**
+     * Apply the values set in a config file to the running WindowManager. Existing workspaces
+     * are renamed, with any additional names being used to create new workspaces, and key
+     * bindings from a previous file are replaced. Colors from the file replace any BorderStyle
+     * set in the Config but are not picked up by an existing StatusBar. Nothing is changed if
+     * the file contains any invalid values.
+     */
+    pub fn apply_config_file(&mut self, file: &ConfigFile) -> errors::Result<()> {
+        let problems = file.problems();
+        if !problems.is_empty() {
+            return Err(PenroseError::InvalidConfig(problems.join("; ")));
+        }
+        self.check_file_keys(file)?;
+
+        if let Some(names) = &file.workspaces {
+            for (i, name) in names.iter().enumerate() {
+                match self.workspaces.get(i) {
+                    Some(ws) if ws.name() == name => (),
+                    Some(_) => self.rename_workspace(i, name.as_str()),
+                    None => {
+                        self.create_workspace(name.as_str());
+                    }
+                }
+            }
+        }
+        if let Some(classes) = &file.floating_classes {
+            self.floating_classes = leak_strs(classes);
+        }
+        if let Some(rules) = file.client_rules() {
+            self.client_rules = rules;
+        }
+        if let Some(step) = file.main_ratio_step {
+            self.main_ratio_step = step;
+        }
+        if let Some(gap_px) = file.gap_px.filter(|&px| px != self.gap_px) {
+            self.gap_px = gap_px;
+            self.layout_visible_workspaces();
+        }
+        if !file.colors.is_empty() || file.border_px.is_some() {
+            let width = file.border_px.unwrap_or(self.border_style.width);
+            let style = if file.colors.is_empty() {
+                BorderStyle {
+                    width,
+                    ..self.border_style
+                }
+            } else {
+                self.color_scheme = file.colors.apply_to(self.color_scheme);
+                BorderStyle::from_color_scheme(width, &self.color_scheme)
+            };
+            self.set_border_style(style);
+        }
+
+        self.bind_file_keys(file)
+    }
+
+    // Unknown keys and actions are reported before anything is changed
+    fn check_file_keys(&mut self, file: &ConfigFile) -> errors::Result<Vec<(KeyCode, String)>> {
+        let (bindings, unknown) = file.key_bindings(self.key_codes());
+        let mut problems: Vec<String> = unknown
+            .iter()
+            .map(|pattern| format!("unknown key '{}'", pattern))
+            .collect();
+        problems.extend(
+            bindings
+                .iter()
+                .filter(|(_, action)| !self.is_known_action(action))
+                .map(|(_, action)| format!("unknown action '{}'", action)),
+        );
+
+        if problems.is_empty() {
+            Ok(bindings)
+        } else {
+            Err(PenroseError::InvalidConfig(problems.join("; ")))
+        }
+    }
+
+    fn bind_file_keys(&mut self, file: &ConfigFile) -> errors::Result<()> {
+        self.file_bindings = self.check_file_keys(file)?.into_iter().collect();
+        let grabs: KeyBindings = self
+            .file_bindings
+            .iter()
+            .map(|(k, action)| (*k, file_binding(action.clone())))
+            .collect();
+        self.conn.grab_keys(&grabs);
+        Ok(())
+    }
+
+    fn is_known_action(&self, action: &str) -> bool {
+        action.starts_with("spawn ")
+            || action.starts_with("set_layout ")
+            || NAMED_ACTIONS.contains(&action)
+            || self.actions.contains_key(action)
+    }
+
+    fn key_codes(&mut self) -> &CodeMap {
+        self.key_codes.get_or_insert_with(keycodes_from_xmodmap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::*;
+    use crate::draw::Draw;
+    use crate::hooks::Hook;
+    use crate::layout::*;
+    use crate::rules::Assignment;
+    use crate::scratchpad::Scratchpad;
+    use crate::screen::*;
+    use crate::snapshot::Snapshot;
+    use crate::xconnection::*;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const FONTS: &[&str] = &["Comic Sans:size=88"];
+    const WORKSPACES: &[&str] = &["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+    const FLOATING_CLASSES: &[&str] = &["clouds", "birds"];
+    const COLOR_SCHEME: ColorScheme = ColorScheme {
+        bg: 0x282828,        // #282828
+        fg_1: 0x3c3836,      // #3c3836
+        fg_2: 0xa89984,      // #a89984
+        fg_3: 0xf2e5bc,      // #f2e5bc
+        highlight: 0xcc241d, // #cc241d
+        urgent: 0x458588,    // #458588
+    };
+
+    fn wm_with_mock_conn<'a>(layouts: Vec<Layout>, conn: &'a MockXConn) -> WindowManager<'a> {
+        let conf = Config {
+            workspaces: WORKSPACES,
+            fonts: FONTS,
+            floating_classes: FLOATING_CLASSES,
+            class_filter: ClassFilter::Deny(&[]),
+            layouts,
+            color_scheme: COLOR_SCHEME,
+            border_px: 2,
+            gap_px: 5,
+            main_ratio_step: 0.05,
+            workspace_cleanup: None,
+            placement_grid: None,
+            systray_spacing_px: 2,
+            show_systray: true,
+            show_bar: true,
+            top_bar: true,
+            bar_height: 18,
+            respect_resize_hints: true,
+            hooks: vec![],
+            scratchpads: vec![Scratchpad::new("test", "test-prog", "WM_CLASS")],
+            client_rules: vec![],
+            insert_point: InsertPoint::First,
+            focus_new_clients: true,
+            state_store: None,
+            ipc_socket_path: None,
+            remote_border_color: None,
+            border_style: None,
+            callback_budget: None,
+            notify_slow_callbacks: false,
+            command_palette: None,
+            expose: None,
+            rotated_screens: &[],
+            replace_existing_wm: false,
+            startup_hook: None,
+            error_handler: None,
+            screen_change_policy: ScreenChangePolicy::ByIndex,
+            warp_pointer: true,
+            terminal_classes: &[],
+            config_file: None,
+            dnd_workspace: None,
+            workspace_switch: WorkspaceSwitch::UnmapFirst,
+            opacity: None,
+            workspace_layouts: HashMap::new(),
+            spawn_rules: vec![],
+        };
+
+        WindowManager::init(conf, conn).unwrap()
+    }
+
+    fn test_layouts() -> Vec<Layout> {
+        vec![Layout::new("t", LayoutConf::default(), mock_layout, 1, 0.6)]
+    }
+
+    struct RecordingHook {
+        calls: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Hook for RecordingHook {
+        fn new_client(&mut self, _: &mut WindowManager, c: &mut Client) {
+            self.calls
+                .borrow_mut()
+                .push(format!("new_client {}", c.id()));
+        }
+        fn remove_client(&mut self, _: &mut WindowManager, id: WinId) {
+            self.calls
+                .borrow_mut()
+                .push(format!("remove_client {}", id));
+        }
+        fn layout_change(&mut self, _: &mut WindowManager, wix: usize, six: usize) {
+            self.calls
+                .borrow_mut()
+                .push(format!("layout_change {} {}", wix, six));
+        }
+        fn workspace_change(&mut self, _: &mut WindowManager, prev: usize, new: usize) {
+            self.calls
+                .borrow_mut()
+                .push(format!("workspace_change {} {}", prev, new));
+        }
+        fn workspace_removed(&mut self, _: &mut WindowManager, wix: usize, name: &str) {
+            self.calls
+                .borrow_mut()
+                .push(format!("workspace_removed {} {}", wix, name));
+        }
+        fn focus_change(&mut self, _: &mut WindowManager, id: WinId) {
+            self.calls.borrow_mut().push(format!("focus_change {}", id));
+        }
+        fn title_change(&mut self, _: &mut WindowManager, id: WinId, title: &str) {
+            self.calls
+                .borrow_mut()
+                .push(format!("title_change {} {}", id, title));
+        }
+        fn urgency_change(&mut self, _: &mut WindowManager, id: WinId, urgent: bool) {
+            self.calls
+                .borrow_mut()
+                .push(format!("urgency_change {} {}", id, urgent));
+        }
+        fn dnd_change(&mut self, _: &mut WindowManager, enabled: bool) {
+            self.calls
+                .borrow_mut()
+                .push(format!("dnd_change {}", enabled));
+        }
+        fn drag_preview(&mut self, _: &mut WindowManager, t: Option<&DropTarget>) {
+            self.calls
+                .borrow_mut()
+                .push(format!("drag_preview {:?}", t.map(|t| t.workspace)));
+        }
+    }
+
+    // Moves new clients with an ID that is a multiple of 20 to the second workspace
+    struct EvensToWorkspaceTwo {}
+
+    impl Hook for EvensToWorkspaceTwo {
+        fn new_client(&mut self, _: &mut WindowManager, c: &mut Client) {
+            if c.id().is_multiple_of(20) {
+                c.set_workspace(1);
+            }
+        }
+    }
+
+    fn test_screens() -> Vec<Screen> {
+        let r = Region::new(0, 0, 1366, 768);
+        vec![Screen {
+            true_region: r,
+            effective_region: r,
+            wix: 0,
+            view_mask: 0,
+        }]
+    }
+
+    fn dual_screens() -> Vec<Screen> {
+        let left = Region::new(0, 0, 1000, 600);
+        let right = Region::new(1000, 0, 1000, 600);
+        vec![
+            Screen {
+                true_region: left,
+                effective_region: left,
+                wix: 0,
+                view_mask: 0,
+            },
+            Screen {
+                true_region: right,
+                effective_region: right,
+                wix: 1,
+                view_mask: 0,
+            },
+        ]
+    }
+
+    fn add_n_clients(wm: &mut WindowManager, n: usize, offset: usize) {
+        for i in 0..n {
+            wm.handle_map_notify(10 * (i + offset + 1) as u32, false);
+        }
+    }
+
+    #[test]
+    fn worspace_switching_with_active_clients() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+
+        // add clients to the first workspace: final client should have focus
+        add_n_clients(&mut wm, 3, 0);
+        assert_eq!(wm.workspaces[0].len(), 3);
+        assert_eq!(wm.workspaces[0].focused_client(), Some(30));
+
+        // switch and add to the second workspace: final client should have focus
+        wm.focus_workspace(1);
+        add_n_clients(&mut wm, 2, 3);
+        assert_eq!(wm.workspaces[1].len(), 2);
+        assert_eq!(wm.workspaces[1].focused_client(), Some(50));
+
+        // switch back: clients should be the same, same client should have focus
+        wm.focus_workspace(0);
+        assert_eq!(wm.workspaces[0].len(), 3);
+        assert_eq!(wm.workspaces[0].focused_client(), Some(30));
+    }
+
+    #[test]
+    fn killing_a_client_removes_it_from_the_workspace() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 1, 0);
+        wm.kill_client();
+
+        assert_eq!(wm.workspaces[0].len(), 0);
+    }
+
+    #[test]
+    fn kill_client_kills_focused_not_first() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 5, 0); // 50 40 30 20 10, 50 focused
+        wm.next_client(); // 40 focused
+        wm.kill_client(); // remove 40, focus 30
+
+        let ids: Vec<WinId> = wm.workspaces[0].iter().copied().collect();
+        assert_eq!(ids, vec![50, 30, 20, 10]);
+        assert_eq!(wm.workspaces[0].focused_client(), Some(30));
+    }
+
+    #[test]
+    fn clients_supporting_wm_delete_window_are_asked_to_close() {
+        let conn = MockXConn::new(test_screens()).with_protocols(&["WM_DELETE_WINDOW"]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        assert!(wm.client_map[&20].supports_protocol("WM_DELETE_WINDOW"));
+
+        // the client stays managed until its window is actually destroyed
+        wm.kill_focused_client();
+        assert_eq!(wm.workspaces[0].len(), 2);
+        wm.handle_destroy_notify(20);
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&10]);
+    }
+
+    #[test]
+    fn clients_without_wm_delete_window_are_killed() {
+        let conn = MockXConn::new(test_screens()).with_protocols(&["WM_TAKE_FOCUS"]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        assert!(!wm.client_map[&20].supports_protocol("WM_DELETE_WINDOW"));
+
+        wm.kill_focused_client();
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&10]);
+        assert!(!wm.client_map.contains_key(&20));
+    }
+
+    #[test]
+    fn clients_supporting_wm_take_focus_are_asked_to_take_it() {
+        let conn = MockXConn::new(test_screens()).with_protocols(&["WM_TAKE_FOCUS"]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        conn.clear_calls();
+
+        wm.handle_enter_notify(10);
+        let calls = conn.calls();
+        assert!(calls.contains(&MockCall::FocusClient(10)));
+        assert!(calls.contains(&MockCall::SendClientEvent(10, "WM_TAKE_FOCUS".into())));
+    }
+
+    #[test]
+    fn clients_without_the_input_hint_are_not_given_input_focus() {
+        let conn = MockXConn::new(test_screens()).with_no_input(vec![10]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        assert!(!wm.client_map[&10].accepts_input());
+        conn.clear_calls();
+
+        wm.handle_enter_notify(10);
+        let calls = conn.calls();
+        assert!(calls.contains(&MockCall::SetActiveWindow(10)));
+        assert!(!calls.contains(&MockCall::FocusClient(10)));
+        assert!(!calls
+            .iter()
+            .any(|c| matches!(c, MockCall::SendClientEvent(..))));
+        assert_eq!(wm.focused_client_id(), Some(10));
+    }
+
+    #[test]
+    fn moving_then_deleting_clients() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        wm.client_to_workspace(1);
+        wm.client_to_workspace(1);
+        wm.focus_workspace(1);
+        wm.kill_client();
+
+        // should have removed first client on ws::1 (last sent from ws::0)
+        assert_eq!(wm.workspaces[1].iter().collect::<Vec<&WinId>>(), vec![&20]);
+    }
+
+    #[test]
+    fn sending_a_client_inserts_at_head() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0); // [20, 10]
+        wm.client_to_workspace(1); // 20 -> ws::1
+        wm.client_to_workspace(1); // 10 -> ws::1, [10, 20]
+        wm.focus_workspace(1);
+
+        assert_eq!(
+            wm.workspaces[1].iter().collect::<Vec<&WinId>>(),
+            vec![&10, &20]
+        );
+    }
+
+    #[test]
+    fn sending_the_last_client_updates_x_state() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 1, 0);
+        conn.clear_calls();
+        wm.client_to_workspace(1);
+
+        let calls = conn.calls();
+        assert!(calls.contains(&MockCall::SetClientWorkspace(10, 1)));
+        assert!(calls.contains(&MockCall::ClearFocus));
+    }
+
+    #[test]
+    fn client_list_is_in_mapping_order() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        for id in &[30, 10, 20] {
+            wm.handle_map_notify(*id, false);
+        }
+        wm.remove_client(10);
+        wm.handle_map_notify(40, false);
+
+        let lists: Vec<Vec<WinId>> = conn
+            .calls()
+            .into_iter()
+            .filter_map(|c| match c {
+                MockCall::UpdateClientList(ids) => Some(ids),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lists.last(), Some(&vec![30, 20, 40]));
+        let ids: Vec<WinId> = wm.state().clients.iter().map(|c| c.id()).collect();
+        assert_eq!(ids, vec![30, 20, 40]);
+    }
+
+    #[test]
+    fn sending_a_client_sets_focus() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0); // [20, 10]
+        wm.client_to_workspace(1); // 20 -> ws::1
+        wm.client_to_workspace(1); // 10 -> ws::1, [10, 20]
+        wm.focus_workspace(1);
+
+        assert_eq!(wm.workspaces[1].focused_client(), Some(10));
+    }
+
+    #[test]
+    fn x_focus_events_set_workspace_focus() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 5, 0); // focus on last client: 50
+        wm.handle_enter_notify(10);
+
+        assert_eq!(wm.workspaces[0].focused_client(), Some(10));
+    }
+
+    #[test]
+    fn empty_hidden_dynamic_workspaces_are_removed_automatically() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let calls = Rc::new(RefCell::new(vec![]));
+        wm.hooks = vec![Box::new(RecordingHook {
+            calls: Rc::clone(&calls),
+        })];
+        wm.workspace_cleanup = Some(Duration::ZERO);
+        let a = wm.create_workspace("a");
+        let b = wm.create_workspace("b");
+        let c = wm.create_workspace("c");
+        wm.focus_workspace(a);
+        add_n_clients(&mut wm, 1, 0);
+        wm.client_to_workspace(b);
+        calls.borrow_mut().clear();
+
+        // 'a' is visible and 'b' has a client so only 'c' is removed
+        assert_eq!(wm.remove_empty_workspaces(), None);
+        assert_eq!(wm.workspaces.len(), c);
+        assert_eq!(*calls.borrow(), vec![format!("workspace_removed {} c", c)]);
+
+        // the client leaving 'b' and 'a' being hidden leaves both of them empty
+        wm.remove_client(10);
+        wm.focus_workspace(0);
+        calls.borrow_mut().clear();
+        wm.remove_empty_workspaces();
+        assert_eq!(wm.workspaces.len(), a);
+        assert_eq!(
+            *calls.borrow(),
+            vec![
+                format!("workspace_removed {} b", b),
+                format!("workspace_removed {} a", a),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_workspaces_are_kept_for_the_grace_period() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let grace = Duration::from_secs(60);
+        wm.workspace_cleanup = Some(grace);
+        let a = wm.create_workspace("a");
+        let b = wm.create_workspace("b");
+
+        let next = wm.remove_empty_workspaces().unwrap();
+        assert!(next <= grace);
+        assert_eq!(wm.workspaces[a].name(), "a");
+
+        // 'a' has been empty for long enough but 'b' has only just been seen as empty
+        let empty_since = Instant::now().checked_sub(grace).unwrap();
+        wm.empty_since.insert(a, empty_since);
+        wm.remove_empty_workspaces();
+        assert_eq!(wm.workspaces[a].name(), "b");
+        assert_eq!(wm.empty_since.keys().collect::<Vec<_>>(), vec![&a]);
+
+        // workspaces that are in use again are no longer tracked
+        wm.focus_workspace(a);
+        assert_eq!(wm.remove_empty_workspaces(), None);
+        assert!(wm.empty_since.is_empty());
+        assert_eq!(wm.workspaces.len(), b);
+    }
+
+    #[test]
+    fn deleting_a_workspace_keeps_cleanup_tracking_in_step() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.workspace_cleanup = Some(Duration::from_secs(60));
+        let a = wm.create_workspace("a");
+        let b = wm.create_workspace("b");
+        wm.remove_empty_workspaces();
+        let seen = wm.empty_since[&b];
+
+        wm.delete_workspace(a).unwrap();
+        assert_eq!(wm.empty_since.keys().collect::<Vec<_>>(), vec![&a]);
+        assert_eq!(wm.empty_since[&a], seen);
+    }
+
+    #[test]
+    fn toggling_fullscreen_is_tracked_by_client_and_workspace() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 3, 0);
+
+        wm.toggle_fullscreen();
+        assert!(wm.client_map[&30].is_fullscreen());
+        assert_eq!(wm.workspaces[0].fullscreen_client(), Some(30));
+
+        wm.toggle_fullscreen();
+        assert!(!wm.client_map[&30].is_fullscreen());
+        assert_eq!(wm.workspaces[0].fullscreen_client(), None);
+    }
+
+    #[test]
+    fn only_non_fullscreen_floating_positions_are_journaled() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(vec![Layout::floating("[f]")], &conn);
+        let entries = Rc::new(RefCell::new(vec![]));
+        wm.store = Box::new(RecordingStore {
+            entries: Rc::clone(&entries),
+        });
+        add_n_clients(&mut wm, 1, 0);
+        wm.client_map.get_mut(&10).unwrap().set_floating(true);
+        entries.borrow_mut().clear();
+
+        wm.toggle_fullscreen();
+        assert_eq!(*entries.borrow(), vec![]);
+
+        wm.toggle_fullscreen();
+        assert_eq!(
+            *entries.borrow(),
+            vec![Entry::Floating(10, Region::new(0, 0, 0, 0))]
+        );
+    }
+
+    #[cfg(feature = "ewmh-fullscreen")]
+    #[test]
+    fn fullscreen_client_messages_are_respected() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        let fs = conn.intern_atom("_NET_WM_STATE_FULLSCREEN").unwrap();
+
+        wm.handle_client_message(10, "_NET_WM_STATE", &[1, fs, 0, 0, 0]);
+        assert_eq!(wm.workspaces[0].fullscreen_client(), Some(10));
+
+        // only one fullscreen client per workspace
+        wm.handle_client_message(20, "_NET_WM_STATE", &[2, fs, 0, 0, 0]);
+        assert_eq!(wm.workspaces[0].fullscreen_client(), Some(20));
+        assert!(!wm.client_map[&10].is_fullscreen());
+
+        wm.handle_client_message(20, "_NET_WM_STATE", &[0, fs, 0, 0, 0]);
+        assert_eq!(wm.workspaces[0].fullscreen_client(), None);
+    }
+
+    #[test]
+    fn current_desktop_client_messages_switch_workspace() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.handle_client_message(0, "_NET_CURRENT_DESKTOP", &[3, 0, 0, 0, 0]);
+        assert_eq!(wm.active_ws_index(), 3);
+
+        // out of bounds requests are ignored
+        wm.handle_client_message(0, "_NET_CURRENT_DESKTOP", &[42, 0, 0, 0, 0]);
+        assert_eq!(wm.active_ws_index(), 3);
+    }
+
+    #[test]
+    fn active_window_client_messages_focus_the_client() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        wm.client_to_workspace(2); // 20 -> ws::2
+
+        wm.handle_client_message(20, "_NET_ACTIVE_WINDOW", &[1, 0, 0, 0, 0]);
+        assert_eq!(wm.active_ws_index(), 2);
+        assert_eq!(wm.focused_client().map(|c| c.id()), Some(20));
+    }
+
+    struct NullDraw;
+
+    impl Draw for NullDraw {
+        fn new_window(&mut self, _: &Region) -> Result<WinId, String> {
+            Ok(1000)
+        }
+        fn show_window(&mut self, _: WinId, _: &Region) {}
+        fn hide_window(&mut self, _: WinId) {}
+        fn set_opacity(&mut self, _: WinId, _: f32) {}
+        fn fill_rect(&mut self, _: WinId, _: &Region, _: u32) {}
+        fn text(&mut self, _: WinId, _: u32, _: u32, _: &str, _: u32, _: u32) {}
+        fn text_width(&self, _: &str) -> u32 {
+            0
+        }
+        fn flush(&self) {}
+    }
+
+    fn expose_key(name: &str) -> KeyCode {
+        let code = match name {
+            "Escape" => 9,
+            _ => 30 + name.as_bytes()[0] - b'a',
+        };
+        KeyCode { mask: 0, code }
+    }
+
+    #[test]
+    fn expose_picks_clients_from_any_workspace() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let codes: CodeMap = ["a", "s", "d", "Escape"]
+            .iter()
+            .map(|k| (k.to_string(), expose_key(k).code))
+            .collect();
+        let colors = wm.color_scheme;
+        wm.expose = Some(Expose::new(NullDraw, 20, colors).with_key_codes(codes));
+        add_n_clients(&mut wm, 2, 0);
+        wm.focus_workspace(1);
+        add_n_clients(&mut wm, 1, 2);
+        wm.focus_workspace(0);
+        conn.clear_calls();
+
+        wm.open_expose(true);
+        assert!(wm.expose_is_open());
+        assert!(conn.calls().contains(&MockCall::MapWindow(30)));
+
+        // labels follow stack order on each workspace: 20, 10 then 30
+        let bindings = HashMap::new();
+        wm.handle_key_press(expose_key("d"), &bindings, &mut vec![]);
+        assert!(!wm.expose_is_open());
+        assert_eq!(wm.active_ws_index(), 1);
+        assert_eq!(wm.focused_client_id(), Some(30));
+
+        wm.open_expose(false);
+        conn.clear_calls();
+        wm.handle_key_press(expose_key("Escape"), &bindings, &mut vec![]);
+        assert!(!wm.expose_is_open());
+        assert_eq!(wm.focused_client_id(), Some(30));
+        assert!(conn
+            .calls()
+            .iter()
+            .any(|c| matches!(c, MockCall::PositionWindow(30, _, _))));
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct SeenBy(&'static str);
+
+    struct MarkNewClients;
+
+    impl Hook for MarkNewClients {
+        fn new_client(&mut self, _: &mut WindowManager, c: &mut Client) {
+            c.set_data(SeenBy("hook"));
+        }
+    }
+
+    #[test]
+    fn hooks_can_attach_data_to_clients() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.hooks.push(Box::new(MarkNewClients));
+        add_n_clients(&mut wm, 1, 0);
+
+        assert_eq!(wm.client_data(10), Some(&SeenBy("hook")));
+        if let Some(seen) = wm.client_data_mut::<SeenBy>(10) {
+            seen.0 = "user";
+        }
+        assert_eq!(
+            wm.set_client_data(10, SeenBy("again")),
+            Some(SeenBy("user"))
+        );
+        assert_eq!(wm.set_client_data(99, SeenBy("unknown")), None);
+        assert_eq!(wm.client_data::<SeenBy>(99), None);
+        assert_eq!(wm.remove_client_data(10), Some(SeenBy("again")));
+        assert_eq!(wm.client_data::<SeenBy>(10), None);
+    }
+
+    fn workspace_switch_calls(strategy: WorkspaceSwitch) -> Vec<MockCall> {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.workspace_switch = strategy;
+        add_n_clients(&mut wm, 2, 0);
+        wm.focus_workspace(1);
+        add_n_clients(&mut wm, 1, 2);
+        wm.focus_workspace(0);
+        // clients on hidden workspaces are left alone so 30 now needs repositioning
+        wm.set_border_width(3);
+        conn.clear_calls();
+
+        wm.focus_workspace(1);
+        conn.calls()
+    }
+
+    #[test]
+    fn unmap_first_workspace_switches() {
+        let calls = workspace_switch_calls(WorkspaceSwitch::UnmapFirst);
+        let unmapped = calls
+            .iter()
+            .position(|c| *c == MockCall::UnmapWindow(10))
+            .unwrap();
+        let mapped = calls
+            .iter()
+            .position(|c| *c == MockCall::MapWindow(30))
+            .unwrap();
+        assert!(unmapped < mapped);
+    }
+
+    #[test]
+    fn map_first_workspace_switches() {
+        let calls = workspace_switch_calls(WorkspaceSwitch::MapFirst);
+        let positioned = calls
+            .iter()
+            .position(|c| matches!(c, MockCall::PositionWindow(30, _, _)))
+            .unwrap();
+        let mapped = calls
+            .iter()
+            .position(|c| *c == MockCall::MapWindow(30))
+            .unwrap();
+        let unmapped = calls
+            .iter()
+            .position(|c| *c == MockCall::UnmapWindow(10))
+            .unwrap();
+        assert!(positioned < mapped);
+        assert!(mapped < unmapped);
+    }
+
+    #[test]
+    fn offscreen_workspace_switches() {
+        let calls = workspace_switch_calls(WorkspaceSwitch::Offscreen);
+        assert_eq!(
+            calls.iter().position(|c| *c == MockCall::UnmapWindow(10)),
+            None
+        );
+
+        let mapped = calls
+            .iter()
+            .position(|c| *c == MockCall::MapWindow(30))
+            .unwrap();
+        let moved_off = calls
+            .iter()
+            .position(|c| match c {
+                MockCall::PositionWindow(10, r, _) => r.values().0 == 1366,
+                _ => false,
+            })
+            .unwrap();
+        assert!(mapped < moved_off);
+    }
+
+    fn positioned(conn: &MockXConn) -> Vec<WinId> {
+        conn.calls()
+            .into_iter()
+            .filter_map(|c| match c {
+                MockCall::PositionWindow(id, _, _) => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_clients_that_have_moved_are_repositioned() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 3, 0); // [30, 20, 10]
+        conn.clear_calls();
+
+        wm.apply_layout(0);
+        wm.handle_enter_notify(20);
+        assert!(positioned(&conn).is_empty());
+
+        wm.remove_client(10); // at the end of the stack so nothing else moves
+        wm.apply_layout(0);
+        assert!(positioned(&conn).is_empty());
+        wm.remove_client(30);
+        wm.apply_layout(0);
+        assert_eq!(positioned(&conn), vec![20]);
+    }
+
+    #[test]
+    fn clients_moved_offscreen_are_repositioned_when_shown() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.workspace_switch = WorkspaceSwitch::Offscreen;
+        add_n_clients(&mut wm, 2, 0);
+        wm.focus_workspace(1);
+        conn.clear_calls();
+
+        wm.focus_workspace(0);
+        let mut ids = positioned(&conn);
+        ids.sort_unstable();
+        assert_eq!(ids, vec![10, 20]);
+    }
+
+    #[test]
+    fn clients_moved_offscreen_are_marked_as_hidden() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.workspace_switch = WorkspaceSwitch::Offscreen;
+        add_n_clients(&mut wm, 1, 0);
+        conn.clear_calls();
+
+        wm.focus_workspace(1);
+        assert!(conn.calls().contains(&MockCall::SetHidden(10, true)));
+        conn.clear_calls();
+
+        wm.focus_workspace(0);
+        assert!(conn.calls().contains(&MockCall::SetHidden(10, false)));
+        conn.clear_calls();
+
+        // only clients that were parked need to be shown again
+        wm.focus_workspace(1);
+        wm.remove_client(10);
+        wm.focus_workspace(0);
+        assert!(!conn.calls().contains(&MockCall::SetHidden(10, false)));
+    }
+
+    #[test]
+    fn every_workspace_switch_uses_the_configured_strategy() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.workspace_switch = WorkspaceSwitch::Offscreen;
+        add_n_clients(&mut wm, 1, 0); // 10 on workspace 0 (left screen)
+        wm.focus_workspace(2);
+        add_n_clients(&mut wm, 1, 1); // 20 on workspace 2 (left screen)
+        conn.clear_calls();
+
+        // workspace 1 is on the right screen so workspaces 1 and 2 are swapped
+        wm.focus_workspace(1);
+        wm.swap_screens();
+        // workspace 0 is not shown so it replaces workspace 2 which is then parked
+        wm.focus_workspace(0);
+
+        let calls = conn.calls();
+        assert!(!calls.iter().any(|c| matches!(c, MockCall::UnmapWindow(_))));
+        assert!(calls.contains(&MockCall::SetHidden(10, false)));
+        assert!(calls.contains(&MockCall::SetHidden(20, true)));
+        assert!(calls.iter().any(|c| match c {
+            MockCall::PositionWindow(20, r, _) => r.values().0 == 2000,
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn transients_float_centered_over_their_parent() {
+        let conn = MockXConn::new(test_screens()).with_transients(vec![(20, 10)]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        let dialog = &wm.client_map[&20];
+        assert!(dialog.is_floating());
+        assert_eq!(dialog.transient_for(), Some(10));
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&10]);
+
+        let parent = wm.client_map[&10].region().unwrap();
+        let expected = Region::new(0, 0, 0, 0).centered_in(&parent);
+        assert_eq!(wm.client_map[&20].region(), Some(expected));
+        assert!(conn
+            .calls()
+            .contains(&MockCall::PositionWindow(20, expected, 2)));
+    }
+
+    #[test]
+    fn transients_follow_their_parent_until_it_closes() {
+        let conn = MockXConn::new(test_screens()).with_transients(vec![(20, 10)]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        conn.clear_calls();
+
+        wm.client_to_workspace(2);
+        assert_eq!(wm.client_map[&20].workspace(), 2);
+        assert!(conn.calls().contains(&MockCall::UnmapWindow(20)));
+
+        wm.focus_workspace(2);
+        assert!(conn.calls().contains(&MockCall::MapWindow(20)));
+
+        wm.remove_client(10);
+        assert_eq!(wm.client_map[&20].transient_for(), None);
+    }
+
+    #[test]
+    fn dialogs_without_a_parent_are_centered_on_screen() {
+        let conn = MockXConn::new(test_screens()).with_dialogs(vec![10]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 1, 0);
+
+        let screen = wm.screens[0].effective_region;
+        assert!(wm.client_map[&10].is_floating());
+        assert_eq!(
+            wm.client_map[&10].region(),
+            Some(Region::new(0, 0, 0, 0).centered_in(&screen))
+        );
+    }
+
+    #[test]
+    fn dnd_leaves_focus_alone_for_new_clients() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let calls = Rc::new(RefCell::new(vec![]));
+        wm.hooks.push(Box::new(RecordingHook {
+            calls: Rc::clone(&calls),
+        }));
+        add_n_clients(&mut wm, 1, 0);
+        wm.toggle_dnd();
+        wm.toggle_dnd();
+        wm.set_dnd(true);
+        wm.set_dnd(true); // already enabled so no hook is run
+        assert!(wm.dnd_enabled());
+        assert!(wm.snapshot().dnd);
+
+        add_n_clients(&mut wm, 1, 1);
+        assert_eq!(wm.focused_client_id(), Some(10));
+        assert_eq!(wm.workspaces[0].len(), 2);
+
+        wm.dnd_workspace = Some(3);
+        add_n_clients(&mut wm, 1, 2);
+        assert_eq!(wm.active_ws_index(), 0);
+        assert_eq!(wm.focused_client_id(), Some(10));
+        assert_eq!(wm.client_map[&30].workspace(), 3);
+
+        let dnd_calls: Vec<String> = calls
+            .borrow()
+            .iter()
+            .filter(|c| c.starts_with("dnd_change"))
+            .cloned()
+            .collect();
+        assert_eq!(
+            dnd_calls,
+            vec!["dnd_change true", "dnd_change false", "dnd_change true"]
+        );
+    }
+
+    #[test]
+    fn dnd_turns_active_window_requests_into_urgency() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        wm.client_to_workspace(2); // 20 -> ws::2
+        wm.set_dnd(true);
+
+        wm.handle_client_message(20, "_NET_ACTIVE_WINDOW", &[1, 0, 0, 0, 0]);
+        assert_eq!(wm.active_ws_index(), 0);
+        assert_eq!(wm.urgent_clients(), &[20]);
+
+        // pagers act on behalf of the user
+        wm.handle_client_message(20, "_NET_ACTIVE_WINDOW", &[2, 0, 0, 0, 0]);
+        assert_eq!(wm.active_ws_index(), 2);
+        assert_eq!(wm.focused_client_id(), Some(20));
+    }
+
+    #[test]
+    fn wm_desktop_client_messages_move_the_client() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        wm.handle_client_message(10, "_NET_WM_DESKTOP", &[4, 0, 0, 0, 0]);
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<&WinId>>(), vec![&20]);
+        assert_eq!(wm.workspaces[4].iter().collect::<Vec<&WinId>>(), vec![&10]);
+        assert_eq!(wm.client_map[&10].workspace(), 4);
+    }
+
+    #[test]
+    fn client_regions_are_cached_when_layouts_are_applied() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        assert_eq!(wm.client_region(10), None);
+
+        add_n_clients(&mut wm, 2, 0); // [20, 10]
+                                      // mock_layout offsets each client by its index: then the bar, gaps & borders are applied
+        assert_eq!(wm.client_region(20), Some(Region::new(5, 23, 1352, 736)));
+        assert_eq!(wm.client_region(10), Some(Region::new(6, 24, 1351, 735)));
+    }
+
+    #[test]
+    fn visibility_queries_track_workspace_changes() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 3, 0); // [30, 20, 10]
+        wm.client_to_workspace(1); // 30 -> ws::1
+
+        assert_eq!(wm.workspace_of(30), Some(1));
+        assert_eq!(wm.workspace_of(42), None);
+        assert!(!wm.is_visible(30));
+        assert!(wm.is_visible(20));
+        assert_eq!(wm.clients_on_screen(0), vec![20, 10]);
+
+        wm.focus_workspace(1);
+        assert!(wm.is_visible(30));
+        assert!(!wm.is_visible(20));
+        assert_eq!(wm.clients_on_screen(0), vec![30]);
+        assert_eq!(wm.clients_on_screen(1), Vec::<WinId>::new());
+    }
+
+    #[test]
+    fn hooks_are_run_in_response_to_actions() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let calls = Rc::new(RefCell::new(vec![]));
+        wm.hooks.push(Box::new(RecordingHook {
+            calls: Rc::clone(&calls),
+        }));
+
+        add_n_clients(&mut wm, 1, 0);
+        wm.next_layout();
+        wm.focus_workspace(1);
+        wm.kill_client(); // nothing to kill on ws::1
+        wm.focus_workspace(0);
+        wm.kill_client();
+
+        assert_eq!(
+            *calls.borrow(),
+            vec![
+                "new_client 10",
+                "focus_change 10",
+                "layout_change 0 0",
+                "workspace_change 0 1",
+                "focus_change 10",
+                "workspace_change 1 0",
+                "remove_client 10",
+            ]
+        );
+    }
+
+    #[test]
+    fn new_client_hooks_can_modify_the_client() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.hooks.push(Box::new(EvensToWorkspaceTwo {}));
+        add_n_clients(&mut wm, 4, 0);
+
+        assert_eq!(
+            wm.workspaces[0].iter().collect::<Vec<&WinId>>(),
+            vec![&30, &10]
+        );
+        assert_eq!(
+            wm.workspaces[1].iter().collect::<Vec<&WinId>>(),
+            vec![&40, &20]
+        );
+        assert_eq!(wm.client_map[&40].workspace(), 1);
+    }
+
+    #[test]
+    fn merging_workspaces_preserves_order() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        wm.focus_workspace(2);
+        add_n_clients(&mut wm, 2, 2);
+
+        assert!(wm.merge_workspaces(2, 2).is_err());
+        assert!(wm.merge_workspaces(2, 0).is_ok());
+        assert!(wm.workspaces[2].is_empty());
+        assert_eq!(wm.workspace_of(30), Some(0));
+        assert_eq!(
+            wm.workspaces[0].iter().collect::<Vec<&WinId>>(),
+            vec![&20, &10, &40, &30]
+        );
+    }
+
+    #[test]
+    fn splitting_workspaces_moves_matching_clients() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 4, 0);
+
+        let new = wm.split_workspace(0, |c| c.id() > 20).unwrap();
+        assert_eq!(wm.workspace_names()[new], "1-split");
+        assert_eq!(
+            wm.workspaces[0].iter().collect::<Vec<&WinId>>(),
+            vec![&20, &10]
+        );
+        assert_eq!(
+            wm.workspaces[new].iter().collect::<Vec<&WinId>>(),
+            vec![&40, &30]
+        );
+        assert!(wm.split_workspace(0, |_| false).is_err());
+    }
+
+    #[test]
+    fn journaled_clients_are_adopted_on_their_previous_workspace() {
+        let conn = MockXConn::new(test_screens()).with_active_windows(vec![1, 2, 3]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.recovered.insert(
+            2,
+            JournaledClient {
+                workspace: 3,
+                floating: None,
+            },
+        );
+        wm.recovered.insert(4, JournaledClient::default());
+        wm.adopt_journaled_windows();
+
+        assert_eq!(wm.client_map.len(), 1);
+        assert_eq!(wm.workspace_of(2), Some(3));
+        assert!(wm.recovered.is_empty());
+    }
+
+    #[test]
+    fn callback_budget_is_only_checked_when_set() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        assert!(!wm.over_callback_budget(Duration::from_secs(10)));
+
+        wm.callback_budget = Some(Duration::from_millis(20));
+        assert!(!wm.over_callback_budget(Duration::from_millis(5)));
+        assert!(wm.over_callback_budget(Duration::from_millis(25)));
+    }
+
+    #[test]
+    fn hooks_are_named_after_their_type() {
+        let hook = EvensToWorkspaceTwo {};
+        assert!(hook.name().ends_with("EvensToWorkspaceTwo"));
+    }
+
+    #[test]
+    fn cycling_screen_focus() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+
+        wm.next_screen();
+        assert_eq!(wm.focused_screen_index(), 1);
+        wm.next_screen();
+        assert_eq!(wm.focused_screen_index(), 0);
+        wm.previous_screen();
+        assert_eq!(wm.focused_screen_index(), 1);
+    }
+
+    #[test]
+    fn sending_clients_to_the_next_screen() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        wm.client_to_next_screen();
+        assert_eq!(wm.workspace_of(20), Some(1));
+        assert_eq!(wm.clients_on_screen(1), vec![20]);
+        assert_eq!(wm.focused_screen_index(), 0);
+    }
+
+    #[test]
+    fn throwing_clients_follows_them_to_the_other_screen() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        wm.throw_client_to_screen(ScreenDirection::Left); // nothing to the left
+        assert_eq!(wm.focused_screen_index(), 0);
+        wm.throw_client_to_screen(ScreenDirection::Right);
+        assert_eq!(wm.clients_on_screen(1), vec![20]);
+        assert_eq!(wm.focused_screen_index(), 1);
+        assert_eq!(wm.workspaces[1].focused_client(), Some(20));
+    }
+
+    #[test]
+    fn sending_clients_to_a_workspace_and_following() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        wm.client_to_workspace_and_follow(2);
+        assert_eq!(wm.active_ws_index(), 2);
+        assert_eq!(wm.workspace_of(20), Some(2));
+        assert_eq!(wm.workspaces[2].focused_client(), Some(20));
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&10]);
+        assert_eq!(wm.previous_workspace, 0);
+    }
+
+    #[test]
+    fn keyboard_focus_changes_warp_the_pointer() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        let n = conn.warps().len();
+
+        wm.next_client();
+        wm.next_screen(); // nothing to focus on the second screen
+        assert_eq!(conn.warps()[n..], [Some(10), None]);
+
+        wm.warp_pointer = false;
+        wm.previous_screen();
+        assert_eq!(conn.warps().len(), n + 2);
+    }
+
+    #[test]
+    fn pointer_focus_changes_do_not_warp_the_pointer() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(side_stack_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        let n = conn.warps().len();
+        let (bindings, mut spawned) = (HashMap::new(), vec![]);
+
+        // drag a client on to the other screen
+        let press = XEvent::ButtonPress {
+            window: 10,
+            button: 1,
+            x: 10,
+            y: 300,
+        };
+        wm.handle_event(press, &bindings, &mut spawned);
+        let release = XEvent::ButtonRelease { x: 1500, y: 300 };
+        wm.handle_event(release, &bindings, &mut spawned);
+
+        assert_eq!(wm.client_map[&10].workspace(), 1);
+        assert_eq!(conn.warps().len(), n);
+    }
+
+    #[test]
+    fn swapping_screens() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 1, 0);
+
+        wm.swap_screens();
+        assert_eq!(wm.workspace_on_screen(0), Some(1));
+        assert_eq!(wm.workspace_on_screen(1), Some(0));
+        assert_eq!(wm.clients_on_screen(1), vec![10]);
+    }
+
+    #[test]
+    fn detecting_screens_keeps_workspaces_where_possible() {
+        let single = MockXConn::new(test_screens());
+        let dual = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &single);
+        wm.focus_workspace(2);
+
+        wm.conn = &dual;
+        wm.detect_screens();
+        assert_eq!(wm.workspace_on_screen(0), Some(2));
+        assert_eq!(wm.workspace_on_screen(1), Some(0));
+
+        wm.next_screen();
+        wm.conn = &single;
+        wm.detect_screens();
+        assert_eq!(wm.screen_regions().len(), 1);
+        assert_eq!(wm.focused_screen_index(), 0);
+        assert_eq!(wm.workspace_on_screen(0), Some(2));
+    }
+
+    // the dual_screens setup with the left hand screen disconnected
+    fn right_screen_only() -> Vec<Screen> {
+        let right = Region::new(1000, 0, 1000, 600);
+        vec![Screen {
+            true_region: right,
+            effective_region: right,
+            wix: 0,
+            view_mask: 0,
+        }]
+    }
+
+    #[test]
+    fn detecting_screens_can_match_screen_geometry() {
+        let dual = MockXConn::new(dual_screens());
+        let right = MockXConn::new(right_screen_only());
+        let mut wm = wm_with_mock_conn(test_layouts(), &dual);
+        wm.screen_change_policy = ScreenChangePolicy::MatchGeometry;
+
+        wm.conn = &right;
+        wm.detect_screens();
+        assert_eq!(wm.workspace_on_screen(0), Some(1));
+        assert_eq!(wm.focused_screen_index(), 0);
+
+        wm.conn = &dual;
+        wm.detect_screens();
+        assert_eq!(wm.workspace_on_screen(0), Some(0));
+        assert_eq!(wm.workspace_on_screen(1), Some(1));
+    }
+
+    #[test]
+    fn detecting_screens_can_move_the_focused_workspace_to_the_primary_screen() {
+        let dual = MockXConn::new(dual_screens());
+        let right = MockXConn::new(right_screen_only());
+        let mut wm = wm_with_mock_conn(test_layouts(), &dual);
+        wm.screen_change_policy = ScreenChangePolicy::MoveToPrimary;
+        let mut floating = Client::new(10, "float".into(), 0, true);
+        floating.set_region(Region::new(100, 100, 200, 200));
+        wm.client_map.insert(10, floating);
+
+        wm.conn = &right;
+        wm.detect_screens();
+        assert_eq!(wm.workspace_on_screen(0), Some(0));
+        assert_eq!(wm.focused_screen_index(), 0);
+        assert_eq!(
+            wm.client_map[&10].region(),
+            Some(Region::new(1100, 100, 200, 200))
+        );
+    }
+
+    #[test]
+    fn client_rules_are_applied_to_new_clients() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        // MockXConn returns the property name as its value
+        wm.client_rules = vec![
+            Rule {
+                class: Some("WM_CLASS"),
+                workspace: Some(1),
+                ..Rule::default()
+            },
+            Rule {
+                title: Some("WM_NAME"),
+                position: Some(InsertPoint::Last),
+                ..Rule::default()
+            },
+            Rule {
+                role: Some("not-matching"),
+                floating: Some(true),
+                ..Rule::default()
+            },
+        ];
+        add_n_clients(&mut wm, 2, 2);
+
+        assert_eq!(wm.workspace_of(30), Some(1));
+        assert!(!wm.client_map[&30].is_floating());
+        assert_eq!(
+            wm.workspaces[1].iter().collect::<Vec<&WinId>>(),
+            vec![&30, &40]
+        );
+    }
+
+    #[test]
+    fn rules_can_spread_clients_over_workspaces() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.client_rules = vec![Rule {
+            assign: Some(Assignment::RoundRobin(&[1, 2])),
+            ..Rule::default()
+        }];
+        add_n_clients(&mut wm, 3, 0);
+        assert_eq!(
+            [10, 20, 30]
+                .iter()
+                .map(|id| wm.workspace_of(*id))
+                .collect::<Vec<_>>(),
+            vec![Some(1), Some(2), Some(1)]
+        );
+
+        wm.client_rules = vec![Rule {
+            assign: Some(Assignment::LeastPopulated(&[1, 2, 3])),
+            ..Rule::default()
+        }];
+        add_n_clients(&mut wm, 2, 3);
+        assert_eq!(wm.workspace_of(40), Some(3));
+        assert_eq!(wm.workspace_of(50), Some(2));
+    }
+
+    #[test]
+    fn filtered_classes_are_not_managed() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+
+        // MockXConn returns the property name as the value of string properties
+        wm.class_filter = ClassFilter::Deny(&["WM_CLASS"]);
+        add_n_clients(&mut wm, 2, 0);
+        assert!(wm.client_map.is_empty());
+        assert_eq!(wm.workspaces[0].len(), 0);
+
+        wm.class_filter = ClassFilter::Allow(&["WM_CLASS"]);
+        add_n_clients(&mut wm, 2, 0);
+        assert_eq!(wm.client_map.len(), 2);
+    }
+
+    #[test]
+    fn scratchpad_clients_are_captured_outside_of_workspaces() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 1, 0);
+
+        // MockXConn returns the property name as the value of string properties
+        wm.scratchpads[0].pending = true;
+        wm.handle_map_notify(42, false);
+        assert_eq!(wm.scratchpads[0].client_id(), Some(42));
+        assert!(wm.scratchpads[0].visible);
+        assert!(!wm.client_map.contains_key(&42));
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<&WinId>>(), vec![&10]);
+
+        // toggling hides and shows without respawning
+        assert!(wm.toggle_scratchpad("test").is_none());
+        assert!(!wm.scratchpads[0].visible);
+        assert!(wm.toggle_scratchpad("test").is_none());
+        assert!(wm.scratchpads[0].visible);
+
+        // destroying the client resets the scratchpad
+        wm.handle_destroy_notify(42);
+        assert_eq!(wm.scratchpads[0].client_id(), None);
+        assert_eq!(wm.workspaces[0].len(), 1);
+    }
+
+    #[test]
+    fn creating_workspaces_appends_them() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let ix = wm.create_workspace(String::from("dynamic"));
+
+        assert_eq!(ix, WORKSPACES.len());
+        assert_eq!(wm.workspace_index("dynamic"), Some(ix));
+        assert_eq!(wm.workspace_names()[..2], ["1", "2"]);
+
+        wm.focus_workspace(ix);
+        add_n_clients(&mut wm, 1, 0);
+        assert_eq!(wm.workspace_of(10), Some(ix));
+    }
+
+    #[test]
+    fn only_empty_hidden_dynamic_workspaces_can_be_deleted() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let a = wm.create_workspace("a");
+        let b = wm.create_workspace("b");
+
+        assert!(wm.delete_workspace(0).is_err()); // static
+        wm.focus_workspace(a);
+        assert!(wm.delete_workspace(a).is_err()); // visible
+        add_n_clients(&mut wm, 1, 0);
+        wm.client_to_workspace(b);
+        wm.focus_workspace(0);
+        assert!(wm.delete_workspace(b).is_err()); // has clients
+
+        // deleting 'a' shifts 'b' and its client down
+        assert!(wm.delete_workspace(a).is_ok());
+        assert_eq!(wm.workspace_index("b"), Some(a));
+        assert_eq!(wm.workspace_of(10), Some(a));
+        assert_eq!(wm.workspaces[a].iter().collect::<Vec<&WinId>>(), vec![&10]);
+    }
+
+    #[test]
+    fn renaming_workspaces() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.rename_workspace(1, "web");
+        assert_eq!(wm.workspace_index("web"), Some(1));
+        assert_eq!(wm.workspace_names()[1], "web");
+    }
+
+    #[test]
+    fn dragging_clients_forward_from_index_0() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 5, 0); // focus on last client (50) ix == 0
+
+        let clients = |w: &mut WindowManager| {
+            w.workspace_for_screen_mut(0)
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        wm.drag_client_forward();
+        assert_eq!(wm.focused_client().unwrap().id(), 50);
+        assert_eq!(clients(&mut wm), vec![40, 50, 30, 20, 10]);
+
+        wm.drag_client_forward();
+        assert_eq!(wm.focused_client().unwrap().id(), 50);
+        assert_eq!(clients(&mut wm), vec![40, 30, 50, 20, 10]);
+
+        wm.handle_enter_notify(20);
+        wm.drag_client_forward();
+        assert_eq!(wm.focused_client().unwrap().id(), 20);
+        assert_eq!(clients(&mut wm), vec![40, 30, 50, 10, 20]);
+    }
+
+    fn placement_key(name: &str) -> KeyCode {
+        let code = match name {
+            "Escape" => 9,
+            "Return" => 36,
+            "a" => 38,
+            "s" => 39,
+            _ => 40,
+        };
+        KeyCode { mask: 0, code }
+    }
+
+    fn placement_wm(conn: &MockXConn, layouts: Vec<Layout>) -> WindowManager<'_> {
+        let mut wm = wm_with_mock_conn(layouts, conn);
+        let codes: CodeMap = ["a", "s", "d", "Escape", "Return"]
+            .iter()
+            .map(|k| (k.to_string(), placement_key(k).code))
+            .collect();
+        let grid = PlacementGrid::new(NullDraw, 2, 2, COLOR_SCHEME).with_key_codes(codes);
+        wm.placement = Some(grid);
+        wm
+    }
+
+    #[test]
+    fn placement_grid_takes_key_presses_until_a_cell_is_picked() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = placement_wm(&conn, vec![Layout::floating("[f]")]);
+        add_n_clients(&mut wm, 2, 0);
+        let bindings = HashMap::new();
+        let press = |wm: &mut WindowManager, k: &str| {
+            wm.handle_key_press(placement_key(k), &bindings, &mut vec![]);
+        };
+
+        // two cells give the rectangle between them
+        wm.open_placement_grid();
+        assert!(wm.placement_grid_is_open());
+        assert_eq!(wm.placing, Some(20));
+        press(&mut wm, "a");
+        assert!(wm.placement_grid_is_open());
+        press(&mut wm, "d");
+        assert!(!wm.placement_grid_is_open());
+        assert_eq!(wm.placing, None);
+        assert_eq!(wm.client_region(20), Some(Region::new(0, 18, 683, 750)));
+
+        // a single cell is confirmed with Return
+        wm.open_placement_grid();
+        press(&mut wm, "s");
+        press(&mut wm, "Return");
+        assert!(!wm.placement_grid_is_open());
+        assert_eq!(wm.client_region(20), Some(Region::new(683, 18, 683, 375)));
+        assert!(conn.calls().contains(&MockCall::UngrabKeyboard));
+        assert_eq!(wm.focused_client().unwrap().id(), 20);
+
+        // Escape closes the grid without picking anything
+        wm.open_placement_grid();
+        press(&mut wm, "Escape");
+        assert!(!wm.placement_grid_is_open());
+        assert_eq!(wm.placing, None);
+    }
+
+    #[test]
+    fn tiled_clients_can_not_be_placed() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = placement_wm(&conn, test_layouts());
+        add_n_clients(&mut wm, 1, 0);
+        let seen = notifications(&mut wm);
+
+        wm.open_placement_grid();
+        assert!(!wm.placement_grid_is_open());
+        assert_eq!(wm.placing, None);
+        assert_eq!(*seen.borrow(), vec!["error: no floating client to place"]);
+
+        wm.client_map.get_mut(&10).unwrap().set_floating(true);
+        wm.open_placement_grid();
+        assert!(wm.placement_grid_is_open());
+    }
+
+    fn side_stack_layouts() -> Vec<Layout> {
+        vec![Layout::new("t", LayoutConf::default(), side_stack, 1, 0.5)]
+    }
+
+    #[test]
+    fn dragging_a_client_on_to_another_screen() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(side_stack_layouts(), &conn);
+        let calls = Rc::new(RefCell::new(vec![]));
+        add_n_clients(&mut wm, 2, 0);
+        wm.next_screen();
+        add_n_clients(&mut wm, 1, 2);
+        wm.hooks = vec![Box::new(RecordingHook {
+            calls: Rc::clone(&calls),
+        })];
+
+        wm.handle_button_press(10, 1, 10, 300);
+        let target = wm.drop_target(10, 1500, 300).unwrap();
+        assert_eq!(target.screen, Some(1));
+        assert_eq!(target.workspace, 1);
+        assert_eq!(target.position, Some(0));
+        wm.handle_motion_notify(1500, 300);
+        wm.handle_button_release(1500, 300);
+
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&20]);
+        assert_eq!(wm.workspaces[1].iter().collect::<Vec<_>>(), vec![&10, &30]);
+        assert_eq!(wm.client_map[&10].workspace(), 1);
+        let previews: Vec<String> = calls
+            .borrow()
+            .iter()
+            .filter(|c| c.starts_with("drag_preview"))
+            .cloned()
+            .collect();
+        assert_eq!(
+            previews,
+            vec![
+                "drag_preview Some(0)",
+                "drag_preview Some(1)",
+                "drag_preview None"
+            ]
+        );
+    }
+
+    #[test]
+    fn dragging_a_client_within_a_workspace_reorders_the_stack() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(side_stack_layouts(), &conn);
+        add_n_clients(&mut wm, 3, 0);
+        assert_eq!(
+            wm.workspaces[0].iter().collect::<Vec<_>>(),
+            vec![&30, &20, &10]
+        );
+
+        // the ghost covers the main area as that is where the client will end up
+        let target = wm.drop_target(10, 100, 300).unwrap();
+        let main = wm.client_map[&30].region().unwrap();
+        assert_eq!(target.position, Some(0));
+        assert_eq!(target.region.values().0, main.values().0);
+
+        wm.handle_button_press(10, 1, 1000, 700);
+        wm.handle_button_release(100, 300);
+        assert_eq!(
+            wm.workspaces[0].iter().collect::<Vec<_>>(),
+            vec![&10, &30, &20]
+        );
+    }
+
+    // Claims the top left corner of the screen as a pager entry for the fourth workspace
+    struct Pager {}
+
+    impl Hook for Pager {
+        fn workspace_at_point(&self, _: &WindowManager, x: u32, y: u32) -> Option<(usize, Region)> {
+            let r = Region::new(0, 0, 20, 18);
+            if r.contains_point(x, y) {
+                Some((3, r))
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn dropping_a_client_on_a_pager_entry() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(side_stack_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        wm.hooks = vec![Box::new(Pager {})];
+
+        let target = wm.drop_target(20, 5, 5).unwrap();
+        assert!(target.pager);
+        assert_eq!(target.screen, None);
+        assert_eq!(target.region, Region::new(0, 0, 20, 18));
+
+        wm.handle_button_press(20, 1, 300, 300);
+        wm.handle_button_release(5, 5);
+        assert_eq!(wm.client_map[&20].workspace(), 3);
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&10]);
+        assert_eq!(wm.focused_client().unwrap().id(), 10);
+    }
+
+    #[test]
+    fn only_the_left_button_starts_a_drag() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(side_stack_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        wm.handle_button_press(10, 3, 100, 100);
+        assert_eq!(wm.drag, None);
+        wm.handle_button_press(99, 1, 100, 100);
+        assert_eq!(wm.drag, None);
+        wm.handle_button_press(10, 1, 100, 100);
+        assert_eq!(wm.drag, Some(10));
+    }
+
+    #[test]
+    fn state_survives_a_restart() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 3, 0);
+        wm.handle_enter_notify(20);
+        wm.client_to_workspace(2);
+        wm.inc_main();
+        let wix = wm.create_workspace("extra");
+        wm.next_screen();
+        wm.focus_workspace(wix);
+
+        let json = serde_json::to_string(&wm.state()).unwrap();
+        let state: WindowManagerState = serde_json::from_str(&json).unwrap();
+
+        // client 30 has gone away while we were restarting
+        let conn = MockXConn::new(dual_screens()).with_active_windows(vec![10, 20]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.restored = Some(state);
+        assert!(wm.adopt_restored_windows());
+
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&10]);
+        assert_eq!(wm.workspaces[2].iter().collect::<Vec<_>>(), vec![&20]);
+        assert_eq!(wm.client_map.len(), 2);
+        assert_eq!(wm.client_map[&20].workspace(), 2);
+        assert_eq!(wm.workspace_names()[wix], "extra");
+        assert_eq!(
+            wm.screens.iter().map(|s| s.wix).collect::<Vec<_>>(),
+            vec![0, wix]
+        );
+        assert_eq!(wm.focused_screen, 1);
+    }
+
+    struct RecordingStore {
+        entries: Rc<RefCell<Vec<Entry>>>,
+    }
+
+    impl StateStore for RecordingStore {
+        fn save_state(&mut self, _: &WindowManagerState) -> errors::Result<()> {
+            Ok(())
+        }
+
+        fn load_state(&mut self) -> errors::Result<Option<WindowManagerState>> {
+            Ok(None)
+        }
+
+        fn record(&mut self, entry: Entry) -> errors::Result<()> {
+            self.entries.borrow_mut().push(entry);
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct SettingsStore {
+        saved: Rc<RefCell<HashMap<String, LayoutSettings>>>,
+    }
+
+    impl StateStore for SettingsStore {
+        fn save_state(&mut self, _: &WindowManagerState) -> errors::Result<()> {
+            Ok(())
+        }
+
+        fn load_state(&mut self) -> errors::Result<Option<WindowManagerState>> {
+            Ok(None)
+        }
+
+        fn save_layout_settings(
+            &mut self,
+            settings: &HashMap<String, LayoutSettings>,
+        ) -> errors::Result<()> {
+            *self.saved.borrow_mut() = settings.clone();
+            Ok(())
+        }
+
+        fn load_layout_settings(&mut self) -> errors::Result<HashMap<String, LayoutSettings>> {
+            Ok(self.saved.borrow().clone())
+        }
+    }
+
+    #[test]
+    fn layout_settings_carry_over_to_new_sessions() {
+        let layouts = vec![
+            Layout::new("a", LayoutConf::default(), mock_layout, 1, 0.6),
+            Layout::new("b", LayoutConf::default(), mock_layout, 1, 0.6),
+        ];
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(layouts.clone(), &conn);
+        let mut store = SettingsStore::default();
+        wm.store = Box::new(SettingsStore {
+            saved: Rc::clone(&store.saved),
+        });
+        wm.focus_workspace(1);
+        wm.next_layout();
+        wm.inc_main();
+        wm.save_layout_settings();
+
+        let saved = store.saved.borrow()["2"];
+        assert_eq!(saved.layout, 1);
+        assert_eq!(saved.max_main, 2);
+
+        let mut workspaces = wm_with_mock_conn(layouts, &conn).workspaces;
+        restore_layout_settings(&mut store, &mut workspaces);
+        assert_eq!(workspaces[1].layout_settings().unwrap(), saved);
+        assert_eq!(workspaces[0].layout_settings().unwrap().layout, 0);
+    }
+
+    #[test]
+    fn journal_entries_go_to_the_state_store() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let entries = Rc::new(RefCell::new(vec![]));
+        wm.store = Box::new(RecordingStore {
+            entries: Rc::clone(&entries),
+        });
+        add_n_clients(&mut wm, 1, 0);
+        wm.client_to_workspace(3);
+        wm.handle_destroy_notify(10);
+
+        assert_eq!(
+            *entries.borrow(),
+            vec![
+                Entry::Workspace(10, 0),
+                Entry::Workspace(10, 3),
+                Entry::Removed(10)
+            ]
+        );
+    }
+
+    #[test]
+    fn nothing_is_adopted_without_saved_state() {
+        let conn = MockXConn::new(test_screens()).with_active_windows(vec![10, 20]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        assert!(!wm.adopt_restored_windows());
+        assert!(wm.client_map.is_empty());
+    }
+
+    #[test]
+    fn ipc_requests_run_actions() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        assert_eq!(wm.run_ipc_request("focus_workspace 3"), Ok("ok".into()));
+        assert_eq!(wm.active_ws_index(), 3);
+        assert_eq!(wm.run_ipc_request("toggle_workspace"), Ok("ok".into()));
+        assert_eq!(wm.active_ws_index(), 0);
+        assert_eq!(
+            wm.run_ipc_request("create_workspace web stuff"),
+            Ok("9".into())
+        );
+        assert_eq!(wm.workspace_names()[9], "web stuff");
+        assert_eq!(
+            wm.run_ipc_request("rename_workspace 9 www"),
+            Ok("ok".into())
+        );
+        assert_eq!(wm.workspace_names()[9], "www");
+    }
+
+    #[test]
+    fn invalid_ipc_requests_are_errors() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+
+        assert!(wm.run_ipc_request("").is_err());
+        assert!(wm.run_ipc_request("not_an_action").is_err());
+        assert!(wm.run_ipc_request("focus_workspace").is_err());
+        assert!(wm.run_ipc_request("focus_workspace 42").is_err());
+        assert!(wm.run_ipc_request("focus_workspace two").is_err());
+        assert!(wm.run_ipc_request("rename_workspace 1").is_err());
+        assert_eq!(wm.active_ws_index(), 0);
+    }
+
+    #[test]
+    fn ipc_state_queries_are_json() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        let json = wm.run_ipc_request("state").unwrap();
+        let state: WindowManagerState = serde_json::from_str(&json).unwrap();
+        assert_eq!(state.clients.len(), 2);
+        assert_eq!(state.screen_workspaces, vec![0]);
+
+        let json = wm.run_ipc_request("snapshot").unwrap();
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(snapshot, wm.snapshot());
+    }
+
+    #[test]
+    fn snapshots_describe_screens_workspaces_and_clients() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        wm.client_to_next_screen();
+        wm.client_map
+            .insert(30, Client::new(30, "float".into(), 1, true));
+
+        let snapshot = wm.snapshot();
+        assert_eq!(snapshot.focused_screen, 0);
+        assert_eq!(snapshot.focused_client, Some(10));
+        assert_eq!(
+            snapshot
+                .screens
+                .iter()
+                .map(|s| s.workspace)
+                .collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(snapshot.screens[1].region, Region::new(1000, 0, 1000, 600));
+        assert_eq!(snapshot.workspaces[1].clients, vec![20]);
+        assert_eq!(snapshot.workspaces[1].screen, Some(1));
+        assert_eq!(snapshot.workspaces[2].screen, None);
+        assert_eq!(
+            snapshot
+                .clients_on_workspace(1)
+                .iter()
+                .map(|c| c.id)
+                .collect::<Vec<_>>(),
+            vec![20, 30]
+        );
+        assert!(snapshot.client(20).unwrap().visible);
+        assert!(snapshot.client(30).unwrap().floating);
+    }
+
+    #[test]
+    fn clients_from_other_hosts_are_flagged_as_remote() {
+        // the mock connection reports the property name as WM_CLIENT_MACHINE
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.remote_border_color = Some(0xff0000);
+        wm.hostname = "laptop".into();
+        add_n_clients(&mut wm, 1, 0);
+        wm.hostname = "WM_CLIENT_MACHINE".into();
+        add_n_clients(&mut wm, 1, 1);
+
+        assert!(wm.client_map[&10].is_remote());
+        assert_eq!(
+            wm.client_map[&10].client_machine(),
+            Some("WM_CLIENT_MACHINE")
+        );
+        assert!(!wm.client_map[&20].is_remote());
+        assert_eq!(wm.unfocused_border_color(10), 0xff0000);
+        assert_eq!(wm.unfocused_border_color(20), COLOR_SCHEME.fg_1);
+    }
+
+    #[test]
+    fn hiding_the_bar_gives_clients_the_full_screen() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 1, 0);
+        assert_eq!(wm.client_map[&10].region().unwrap().values().1, 18 + 5);
+
+        wm.set_bar_hidden(0, true);
+        assert!(wm.workspace_bar_hidden(0));
+        assert_eq!(wm.client_map[&10].region().unwrap().values().1, 5);
+
+        // the bar comes back when switching to a workspace that shows it
+        wm.client_to_workspace(1);
+        wm.focus_workspace(1);
+        assert_eq!(wm.client_map[&10].region().unwrap().values().1, 18 + 5);
+    }
+
+    #[test]
+    fn sticky_clients_follow_workspace_switches() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        wm.toggle_sticky();
+        assert!(wm.client_map[&20].is_sticky());
+
+        wm.focus_workspace(3);
+        assert_eq!(wm.client_map[&20].workspace(), 3);
+        assert_eq!(wm.workspaces[3].iter().collect::<Vec<_>>(), vec![&20]);
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&10]);
+
+        // pulling a workspace from the other screen brings the sticky client along too
+        wm.focus_workspace(1);
+        assert_eq!(wm.client_map[&20].workspace(), 1);
+        assert_eq!(wm.client_map[&10].workspace(), 0);
+
+        wm.toggle_sticky();
+        wm.focus_workspace(0);
+        assert_eq!(wm.client_map[&20].workspace(), 1);
+    }
+
+    #[test]
+    fn layouts_can_be_set_for_a_single_workspace() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let symbol = |wm: &WindowManager, i| wm.workspace_layout_symbol(i).unwrap();
+
+        assert!(wm
+            .set_layout_for_workspace("1", Layout::monocle("[m]"))
+            .is_ok());
+        assert_eq!(symbol(&wm, 0), "[m]");
+        assert_eq!(symbol(&wm, 1), symbol(&wm, 2));
+        assert_ne!(symbol(&wm, 1), "[m]");
+        assert!(wm
+            .set_layout_for_workspace("nope", Layout::monocle("[m]"))
+            .is_err());
+    }
+
+    #[test]
+    fn workspaces_can_have_their_own_layouts() {
+        let conn = MockXConn::new(test_screens());
+        let conf = Config {
+            layouts: test_layouts(),
+            workspace_layouts: map! {
+                "2" => vec![Layout::monocle("[m]"), Layout::floating("[f]")],
+                "extra" => vec![Layout::floating("[f]")],
+            },
+            ..Config::default()
+        };
+        let mut wm = WindowManager::init(conf, &conn).unwrap();
+        let symbol = |wm: &WindowManager, i| wm.workspace_layout_symbol(i).unwrap();
+
+        assert_eq!(symbol(&wm, 0), "t");
+        assert_eq!(symbol(&wm, 1), "[m]");
+        let wix = wm.create_workspace("extra");
+        assert_eq!(symbol(&wm, wix), "[f]");
+    }
+
+    #[test]
+    fn init_fails_without_layouts() {
+        let conn = MockXConn::new(test_screens());
+        let conf = Config {
+            layouts: vec![],
+            ..Config::default()
+        };
+        let err = WindowManager::init(conf, &conn).err();
+        assert_eq!(err, Some(PenroseError::NoLayouts("default layouts".into())));
+
+        let conf = Config {
+            layouts: test_layouts(),
+            workspace_layouts: map! { "extra" => vec![], },
+            ..Config::default()
+        };
+        let err = WindowManager::init(conf, &conn).err();
+        assert_eq!(err, Some(PenroseError::NoLayouts("extra".into())));
+    }
+
+    #[test]
+    fn layouts_can_be_picked_by_symbol() {
+        let conn = MockXConn::new(test_screens());
+        let layouts = vec![
+            Layout::new("t", LayoutConf::default(), mock_layout, 1, 0.6),
+            Layout::monocle("[m]"),
+            Layout::floating("[f]"),
+        ];
+        let mut wm = wm_with_mock_conn(layouts, &conn);
+
+        wm.set_layout_by_symbol("[f]");
+        assert_eq!(wm.current_layout_symbol(), "[f]");
+        wm.set_layout_by_symbol("nope");
+        assert_eq!(wm.current_layout_symbol(), "[f]");
+        assert!(wm.run_action("set_layout [m]").is_ok());
+        assert_eq!(wm.current_layout_symbol(), "[m]");
+        assert_eq!(wm.workspace_layout_symbol(1).unwrap(), "t");
+    }
+
+    #[test]
+    fn monocle_hides_all_but_the_focused_client() {
+        let conn = MockXConn::new(test_screens());
+        let layouts = vec![Layout::monocle("[{}]"), Layout::floating("[--]")];
+        let mut wm = wm_with_mock_conn(layouts, &conn);
+        add_n_clients(&mut wm, 3, 0);
+
+        assert_eq!(wm.hidden, [10, 20].iter().copied().collect());
+        assert_eq!(wm.current_layout_symbol(), "[2]");
+        wm.next_client();
+        assert_eq!(wm.hidden, [10, 30].iter().copied().collect());
+
+        wm.handle_destroy_notify(10);
+        assert_eq!(wm.current_layout_symbol(), "[1]");
+
+        // other layouts show everything again
+        wm.next_layout();
+        assert!(wm.hidden.is_empty());
+        assert_eq!(wm.current_layout_symbol(), "[--]");
+    }
+
+    #[test]
+    fn following_clients_stay_on_their_screen() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        wm.toggle_follow();
+        assert!(wm.client_map[&20].is_following());
+
+        wm.focus_workspace(3);
+        assert_eq!(wm.client_map[&20].workspace(), 3);
+        assert_eq!(wm.workspaces[3].iter().collect::<Vec<_>>(), vec![&20]);
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&10]);
+
+        // pulling workspace 1 from the other screen sends 3 over there but 20 stays put
+        wm.focus_workspace(1);
+        assert_eq!(wm.workspace_on_screen(1), Some(3));
+        assert_eq!(wm.client_map[&20].workspace(), 1);
+        assert!(wm.workspaces[3].is_empty());
+
+        wm.swap_screens();
+        assert_eq!(wm.workspace_on_screen(0), Some(3));
+        assert_eq!(wm.client_map[&20].workspace(), 3);
+
+        // changes on other screens are ignored
+        wm.next_screen();
+        wm.focus_workspace(5);
+        assert_eq!(wm.client_map[&20].workspace(), 3);
+        wm.next_screen();
+        wm.focus_workspace(6);
+        assert_eq!(wm.client_map[&20].workspace(), 6);
+    }
+
+    #[test]
+    fn new_clients_follow_the_insert_point() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.insert_point = InsertPoint::AfterFocused;
+        add_n_clients(&mut wm, 3, 0);
+
+        assert_eq!(
+            wm.workspaces[0].iter().collect::<Vec<_>>(),
+            vec![&10, &20, &30]
+        );
+        assert_eq!(wm.focused_client().unwrap().id(), 30);
+    }
+
+    #[test]
+    fn new_clients_can_leave_focus_alone() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.focus_new_clients = false;
+        add_n_clients(&mut wm, 3, 0);
+
+        assert_eq!(
+            wm.workspaces[0].iter().collect::<Vec<_>>(),
+            vec![&30, &20, &10]
+        );
+        assert_eq!(wm.focused_client().unwrap().id(), 10);
+    }
+
+    #[test]
+    fn all_named_actions_can_be_run() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        for name in NAMED_ACTIONS {
+            assert!(wm.run_action(name).is_ok(), "{} failed", name);
+        }
+        assert!(wm.run_action("not_an_action").is_err());
+    }
+
+    #[test]
+    fn registered_actions_can_be_run_by_name() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let calls = Rc::new(RefCell::new(0));
+        let counter = Rc::clone(&calls);
+        wm.register_action(
+            "count",
+            Box::new(move |_: &mut WindowManager| {
+                *counter.borrow_mut() += 1;
+                None
+            }),
+        );
+
+        assert!(wm.run_action("count").is_ok());
+        assert!(wm.run_action("count").is_ok());
+        assert_eq!(*calls.borrow(), 2);
+    }
+
+    #[test]
+    fn palette_entries_list_actions_and_bindings() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.register_action("count", Box::new(|_: &mut WindowManager| None));
+        let mut codes = CodeMap::new();
+        codes.insert("j".into(), 44);
+        codes.insert("J".into(), 44);
+        wm.binding_codes = vec![KeyCode {
+            mask: (xcb::MOD_MASK_4 | xcb::MOD_MASK_SHIFT) as u16,
+            code: 44,
+        }];
+
+        let labels: Vec<String> = wm
+            .palette_entries(&codes)
+            .into_iter()
+            .map(|e| e.label)
+            .collect();
+
+        assert_eq!(labels.len(), NAMED_ACTIONS.len() + 2);
+        assert_eq!(labels[0], NAMED_ACTIONS[0]);
+        assert_eq!(labels[labels.len() - 2], "count");
+        assert_eq!(labels[labels.len() - 1], "<M-S-j>");
+    }
+
+    #[test]
+    fn launchers_focus_workspaces_and_windows() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let codes: CodeMap = vec![
+            ("2".to_string(), 11),
+            ("3".to_string(), 12),
+            ("Return".to_string(), 36),
+        ]
+        .into_iter()
+        .collect();
+        let colors = wm.color_scheme;
+        wm.palette = Some(CommandPalette::new(NullDraw, 10, colors).with_key_codes(codes));
+        add_n_clients(&mut wm, 1, 0);
+        wm.focus_workspace(1);
+        add_n_clients(&mut wm, 1, 1);
+        wm.focus_workspace(0);
+
+        let bindings = HashMap::new();
+        let press = |wm: &mut WindowManager, code| {
+            wm.handle_key_press(KeyCode { mask: 0, code }, &bindings, &mut vec![])
+        };
+
+        wm.open_launcher(PaletteSource::Workspaces);
+        press(&mut wm, 12);
+        press(&mut wm, 36);
+        assert_eq!(wm.active_ws_index(), 2);
+
+        wm.open_launcher(PaletteSource::Windows);
+        press(&mut wm, 11);
+        press(&mut wm, 36);
+        assert_eq!(wm.active_ws_index(), 1);
+        assert_eq!(wm.focused_client_id(), Some(20));
+    }
+
+    #[test]
+    fn window_launcher_entries_are_grouped_by_workspace() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.focus_workspace(1);
+        add_n_clients(&mut wm, 1, 0);
+        wm.focus_workspace(0);
+        add_n_clients(&mut wm, 1, 1);
+
+        let actions: Vec<PaletteAction> = wm
+            .launcher_entries(PaletteSource::Windows)
+            .into_iter()
+            .map(|e| e.action)
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                PaletteAction::FocusClient(20),
+                PaletteAction::FocusClient(10)
+            ]
+        );
+    }
+
+    #[test]
+    fn focus_in_direction_uses_client_positions() {
+        let conn = MockXConn::new(test_screens());
+        let layouts = vec![Layout::new("grid", LayoutConf::default(), grid, 1, 0.6)];
+        let mut wm = wm_with_mock_conn(layouts, &conn);
+        add_n_clients(&mut wm, 4, 0); // grid of [40, 30] over [20, 10]
+        assert_eq!(wm.focused_client().unwrap().id(), 40);
+
+        let mut visited = vec![];
+        for d in &[
+            ScreenDirection::Right,
+            ScreenDirection::Down,
+            ScreenDirection::Left,
+            ScreenDirection::Up,
+            ScreenDirection::Up,
+        ] {
+            wm.focus_in_direction(*d);
+            visited.push(wm.focused_client().unwrap().id());
+        }
+
+        assert_eq!(visited, vec![30, 10, 20, 40, 40]);
+    }
+
+    #[test]
+    fn rotated_screens_swap_the_layout_axes() {
+        let conn = MockXConn::new(test_screens());
+        let layouts = vec![Layout::new(
+            "side",
+            LayoutConf::default(),
+            side_stack,
+            1,
+            0.6,
+        )];
+        let mut wm = wm_with_mock_conn(layouts, &conn);
+        add_n_clients(&mut wm, 2, 0);
+        let region = |wm: &WindowManager, id| wm.client_map[&id].region().unwrap().values();
+
+        // main on the left, stack on the right
+        let ((x1, y1, _, _), (x2, y2, _, _)) = (region(&wm, 20), region(&wm, 10));
+        assert!(x1 < x2 && y1 == y2);
+
+        // main on top, stack below
+        wm.toggle_screen_rotation();
+        assert!(wm.is_screen_rotated(0));
+        let ((x1, y1, w1, _), (x2, y2, w2, _)) = (region(&wm, 20), region(&wm, 10));
+        assert!(y1 < y2 && x1 == x2 && w1 == w2);
+
+        wm.set_screen_rotated(0, false);
+        let ((x1, y1, _, _), (x2, y2, _, _)) = (region(&wm, 20), region(&wm, 10));
+        assert!(x1 < x2 && y1 == y2);
+    }
+
+    #[test]
+    fn init_fails_if_another_wm_is_running() {
+        let conn = MockXConn::new(test_screens()).with_other_wm();
+        let conf = Config {
+            layouts: test_layouts(),
+            ..Config::default()
+        };
+        let err = WindowManager::init(conf, &conn).err();
+
+        assert_eq!(err, Some(PenroseError::OtherWmRunning));
+    }
+
+    #[test]
+    fn init_can_replace_a_running_wm() {
+        let conn = MockXConn::new(test_screens()).with_other_wm();
+        let conf = Config {
+            replace_existing_wm: true,
+            layouts: test_layouts(),
+            ..Config::default()
+        };
+        let wm = WindowManager::init(conf, &conn).unwrap();
+
+        assert_eq!(wm.screens.len(), 1);
+    }
+
+    #[test]
+    fn swap_in_direction_moves_the_focused_client() {
+        let conn = MockXConn::new(test_screens());
+        let layouts = vec![Layout::new("grid", LayoutConf::default(), grid, 1, 0.6)];
+        let mut wm = wm_with_mock_conn(layouts, &conn);
+        add_n_clients(&mut wm, 4, 0); // grid of [40, 30] over [20, 10]
+        let top_right = wm.client_map[&30].region();
+
+        wm.swap_in_direction(ScreenDirection::Right);
+        assert_eq!(
+            wm.workspaces[0].iter().collect::<Vec<_>>(),
+            vec![&30, &40, &20, &10]
+        );
+        assert_eq!(wm.focused_client().unwrap().id(), 40);
+        assert_eq!(wm.client_map[&40].region(), top_right);
+
+        wm.swap_in_direction(ScreenDirection::Down);
+        assert_eq!(
+            wm.workspaces[0].iter().collect::<Vec<_>>(),
+            vec![&30, &10, &20, &40]
+        );
+
+        wm.swap_in_direction(ScreenDirection::Right); // nothing further right
+        assert_eq!(
+            wm.workspaces[0].iter().collect::<Vec<_>>(),
+            vec![&30, &10, &20, &40]
+        );
+    }
+
+    #[test]
+    fn clients_can_demand_attention() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        wm.client_to_workspace(2);
+        let calls = Rc::new(RefCell::new(vec![]));
+        wm.hooks.push(Box::new(RecordingHook {
+            calls: Rc::clone(&calls),
+        }));
+        let attention = conn.intern_atom("_NET_WM_STATE_DEMANDS_ATTENTION").unwrap();
+
+        wm.handle_client_message(20, "_NET_WM_STATE", &[1, attention, 0, 0, 0]);
+        assert!(wm.client_map[&20].is_urgent());
+        assert!(wm.workspace_is_urgent(2));
+        assert!(!wm.workspace_is_urgent(0));
+        assert_eq!(wm.urgent_clients(), &[20]);
+
+        // the focused client is never urgent
+        wm.handle_client_message(10, "_NET_WM_STATE", &[1, attention, 0, 0, 0]);
+        assert!(!wm.client_map[&10].is_urgent());
+
+        // focusing an urgent client clears its urgency
+        wm.focus_urgent();
+        assert_eq!(wm.active_ws_index(), 2);
+        assert_eq!(wm.focused_client().unwrap().id(), 20);
+        assert!(!wm.workspace_is_urgent(2));
+        assert!(wm.urgent_clients().is_empty());
+        assert!(calls
+            .borrow()
+            .contains(&"urgency_change 20 true".to_string()));
+        assert!(calls
+            .borrow()
+            .contains(&"urgency_change 20 false".to_string()));
+    }
+
+    #[test]
+    fn title_changes_are_tracked() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 1, 0);
+        let calls = Rc::new(RefCell::new(vec![]));
+        wm.hooks.push(Box::new(RecordingHook {
+            calls: Rc::clone(&calls),
+        }));
+
+        // MockXConn returns the property name as its value
+        assert_eq!(wm.client_title(10), "_NET_WM_NAME");
+        wm.handle_property_notify(10, "_NET_WM_NAME");
+        assert!(calls.borrow().is_empty(), "unchanged titles run no hooks");
+
+        wm.client_map.get_mut(&10).unwrap().set_wm_name("old title");
+        wm.handle_property_notify(10, "WM_NAME");
+        assert_eq!(wm.client_title(10), "_NET_WM_NAME");
+        assert_eq!(*calls.borrow(), vec!["title_change 10 _NET_WM_NAME"]);
+    }
+
+    fn counting_submap(calls: &Rc<RefCell<Vec<u8>>>, codes: &[u8]) -> Rc<KeyBindings> {
+        let mut submap: KeyBindings = HashMap::new();
+        for &code in codes {
+            let calls = Rc::clone(calls);
+            submap.insert(
+                KeyCode { mask: 0, code },
+                Box::new(move |_: &mut WindowManager| {
+                    calls.borrow_mut().push(code);
+                    None
+                }),
+            );
+        }
+        Rc::new(submap)
+    }
+
+    #[test]
+    fn submap_bindings_run_once_then_exit() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let calls = Rc::new(RefCell::new(vec![]));
+        let top_level: KeyBindings = HashMap::new();
+        let mut spawned = vec![];
+
+        wm.enter_submap(counting_submap(&calls, &[10, 11]));
+        assert!(wm.in_submap());
+        // NumLock being on should not stop the binding from matching
+        wm.handle_key_press(KeyCode { mask: 16, code: 11 }, &top_level, &mut spawned);
+        assert!(!wm.in_submap());
+        wm.handle_key_press(KeyCode { mask: 0, code: 10 }, &top_level, &mut spawned);
+
+        assert_eq!(*calls.borrow(), vec![11]);
+    }
+
+    #[test]
+    fn unbound_keys_cancel_submaps() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let calls = Rc::new(RefCell::new(vec![]));
+        let mut spawned = vec![];
+
+        wm.enter_submap(counting_submap(&calls, &[10]));
+        wm.handle_key_press(KeyCode { mask: 0, code: 42 }, &HashMap::new(), &mut spawned);
+
+        assert!(!wm.in_submap());
+        assert!(calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn submaps_can_be_nested() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let calls = Rc::new(RefCell::new(vec![]));
+        let inner = counting_submap(&calls, &[20]);
+        let mut outer: KeyBindings = HashMap::new();
+        outer.insert(
+            KeyCode { mask: 0, code: 10 },
+            Box::new(move |wm: &mut WindowManager| {
+                wm.enter_submap(Rc::clone(&inner));
+                None
+            }),
+        );
+        let mut spawned = vec![];
+
+        wm.enter_submap(Rc::new(outer));
+        wm.handle_key_press(KeyCode { mask: 0, code: 10 }, &HashMap::new(), &mut spawned);
+        assert!(wm.in_submap());
+        wm.handle_key_press(KeyCode { mask: 0, code: 20 }, &HashMap::new(), &mut spawned);
+
+        assert!(!wm.in_submap());
+        assert_eq!(*calls.borrow(), vec![20]);
+    }
+
+    #[test]
+    fn spawned_programs_are_placed_on_their_workspace() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        // the mock connection reports each window's id as its pid
+        wm.pending_spawns.push(PendingSpawn {
+            startup_id: "penrose-test".into(),
+            pid: 30,
+            workspace: 2,
+        });
+        add_n_clients(&mut wm, 3, 0);
+
+        assert_eq!(wm.client_map[&30].workspace(), 2);
+        assert_eq!(wm.client_map[&20].workspace(), 0);
+        assert!(wm.pending_spawns.is_empty());
+    }
+
+    #[test]
+    fn first_view_spawn_rules_only_run_once() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.spawn_rules = vec![
+            SpawnRule::on_first_view("true", 1),
+            SpawnRule::on_startup("true", 2),
+        ];
+
+        wm.focus_workspace(1);
+        wm.focus_workspace(0);
+        wm.focus_workspace(1);
+
+        assert_eq!(wm.pending_spawns.len(), 1);
+        assert_eq!(wm.pending_spawns[0].workspace, 1);
+        assert_eq!(wm.spawn_rules, vec![SpawnRule::on_startup("true", 2)]);
+        wm.autostarted.iter_mut().for_each(|c| {
+            c.wait().unwrap();
+        });
+    }
+
+    #[test]
+    fn border_colors_follow_focus_and_urgency() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let style = BorderStyle {
+            width: 1,
+            focused: 1,
+            unfocused: 2,
+            urgent: 3,
+            alternate: Some(4),
+        };
+        wm.set_border_style(style);
+        add_n_clients(&mut wm, 3, 0); // [30, 20, 10] with 30 focused
+
+        assert_eq!(wm.border_color(30), 1);
+        assert_eq!(wm.border_color(20), 2);
+
+        wm.handle_enter_notify(20);
+        wm.set_client_urgent(10, true);
+        assert_eq!(wm.border_color(30), 4); // unfocused but in the main area
+        assert_eq!(wm.border_color(20), 1);
+        assert_eq!(wm.border_color(10), 3);
+
+        wm.handle_enter_notify(10);
+        assert_eq!(wm.border_color(10), 1);
+        assert_eq!(wm.border_color(20), 2);
+    }
+
+    #[test]
+    fn border_style_defaults_to_the_color_scheme() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let expected = BorderStyle::from_color_scheme(2, &COLOR_SCHEME);
+        assert_eq!(wm.border_style(), expected);
+
+        wm.set_border_width(5);
+        assert_eq!(
+            wm.border_style(),
+            BorderStyle {
+                width: 5,
+                ..expected
+            }
+        );
+    }
+
+    fn opacities(conn: &MockXConn) -> Vec<(WinId, f32)> {
+        conn.calls()
+            .into_iter()
+            .filter_map(|c| match c {
+                MockCall::SetOpacity(id, o) => Some((id, o)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn opacity_follows_focus() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0); // [20, 10] with 20 focused
+        assert!(opacities(&conn).is_empty());
+
+        wm.set_opacity(Some(Opacity {
+            focused: 1.0,
+            unfocused: 0.5,
+        }));
+        let mut set = opacities(&conn);
+        set.sort_by_key(|(id, _)| *id);
+        assert_eq!(set, vec![(10, 0.5), (20, 1.0)]);
+
+        conn.clear_calls();
+        wm.handle_enter_notify(10);
+        assert_eq!(opacities(&conn), vec![(20, 0.5), (10, 1.0)]);
+    }
+
+    #[test]
+    fn rules_override_the_default_opacity() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.set_opacity(Some(Opacity {
+            focused: 1.0,
+            unfocused: 0.5,
+        }));
+        wm.client_rules = vec![Rule {
+            opacity: Some(Opacity {
+                focused: 0.9,
+                unfocused: 0.9,
+            }),
+            ..Rule::default()
+        }];
+        add_n_clients(&mut wm, 1, 0);
+        wm.client_rules = vec![];
+        add_n_clients(&mut wm, 1, 1); // [20, 10] with 20 focused
+
+        conn.clear_calls();
+        wm.handle_enter_notify(10);
+        assert_eq!(opacities(&conn), vec![(20, 0.5), (10, 0.9)]);
+    }
+
+    // Makes every client other than 10 fully transparent when unfocused
+    struct OnlyTen {}
+
+    impl Hook for OnlyTen {
+        fn client_opacity(&self, _: &WindowManager, id: WinId, focused: bool) -> Option<f32> {
+            if id != 10 && !focused {
+                Some(0.0)
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn hooks_take_priority_when_setting_opacity() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0); // [20, 10] with 20 focused
+        wm.hooks = vec![Box::new(OnlyTen {})];
+
+        conn.clear_calls();
+        wm.handle_enter_notify(10);
+        assert_eq!(opacities(&conn), vec![(20, 0.0)]);
+
+        wm.set_opacity(Some(Opacity {
+            focused: 1.0,
+            unfocused: 0.5,
+        }));
+        conn.clear_calls();
+        wm.handle_enter_notify(20);
+        assert_eq!(opacities(&conn), vec![(10, 0.5), (20, 1.0)]);
+    }
+
+    #[test]
+    fn tagged_clients_are_shown_when_viewing_their_tag() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 3, 0); // [30, 20, 10] with 30 focused
+        wm.toggle_tag(1);
+        assert!(wm.tagged_clients_for_screen(0).is_empty());
+
+        wm.focus_workspace(1);
+        assert_eq!(wm.tagged_clients_for_screen(0), vec![30]);
+        let ws = &wm.workspaces[1];
+        let ids: Vec<WinId> = wm
+            .arrange_for_screen(ws, 0)
+            .unwrap()
+            .iter()
+            .map(|a| a.0)
+            .collect();
+        assert_eq!(ids, vec![30]);
+
+        wm.focus_workspace(0);
+        assert!(wm.tagged_clients_for_screen(0).is_empty());
+        assert!(wm.tag_shown.is_empty());
+    }
 
-        // target not currently displayed so unmap what we currently have
-        // displayed and replace it with the target workspace
-        self.workspaces[self.active_ws_index()]
-            .iter()
-            .for_each(|c| self.conn.unmap_window(*c));
+    #[test]
+    fn screens_can_view_a_union_of_workspaces() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+        wm.focus_workspace(1);
+        add_n_clients(&mut wm, 1, 2);
 
-        self.workspaces[index]
+        wm.toggle_view(0);
+        assert_eq!(wm.viewed_workspaces(0), vec![1, 0]);
+        let ws = &wm.workspaces[1];
+        let ids: Vec<WinId> = wm
+            .arrange_for_screen(ws, 0)
+            .unwrap()
             .iter()
-            .for_each(|c| self.conn.map_window(*c));
+            .map(|a| a.0)
+            .collect();
+        assert_eq!(ids, vec![30, 20, 10]);
 
-        self.screens[self.focused_screen].wix = index;
-        self.apply_layout(index);
-        self.conn.set_current_workspace(index);
+        wm.toggle_view(0);
+        assert_eq!(wm.viewed_workspaces(0), vec![1]);
+        assert!(wm.tagged_clients_for_screen(0).is_empty());
     }
 
-    pub fn toggle_workspace(&mut self) {
-        self.focus_workspace(self.previous_workspace);
+    #[test]
+    fn tagged_clients_are_only_shown_on_one_screen() {
+        let conn = MockXConn::new(dual_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.focus_workspace(2);
+        add_n_clients(&mut wm, 1, 0);
+        wm.focus_workspace(0);
+        wm.screens[0].toggle_view(2);
+        wm.screens[1].toggle_view(2);
+
+        assert_eq!(wm.tagged_clients_for_screen(0), vec![10]);
+        assert!(wm.tagged_clients_for_screen(1).is_empty());
     }
 
-    /**
-     * Move the focused client to the workspace at `index` in the workspaces list.
-     * This will panic if you pass an index that is out of bounds.
-     */
-    pub fn client_to_workspace(&mut self, index: usize) {
-        if index == self.screens[self.focused_screen].wix {
-            return;
-        }
+    #[test]
+    fn focus_history_is_pruned_when_clients_are_removed() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 3, 0);
+        wm.handle_enter_notify(20);
+        assert_eq!(wm.focus_history(), vec![20, 30, 10]);
 
-        let ws = self.workspace_for_screen_mut(self.focused_screen);
-        ws.remove_focused_client().map(|id| {
-            self.conn.unmap_window(id);
-            self.workspaces[index].add_client(id);
-            self.client_map.get_mut(&id).map(|c| c.set_workspace(index));
-            self.conn.set_client_workspace(id, index);
-            self.apply_layout(self.active_ws_index());
-        });
+        wm.remove_client(30);
+        assert_eq!(wm.focus_history(), vec![20, 10]);
     }
 
-    /// Move focus to the next client in the stack
-    pub fn next_client(&mut self) {
-        self.cycle_client(Direction::Forward);
+    #[test]
+    fn focus_previous_client_toggles_across_workspaces() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0); // [20, 10] on workspace 0
+        wm.focus_workspace(1);
+        add_n_clients(&mut wm, 1, 2); // [30] on workspace 1
+        wm.focus_workspace(0);
+        assert_eq!(wm.focused_client_id(), Some(20));
+
+        wm.focus_previous_client();
+        assert_eq!(wm.active_ws_index(), 1);
+        assert_eq!(wm.focused_client_id(), Some(30));
+
+        wm.focus_previous_client();
+        assert_eq!(wm.active_ws_index(), 0);
+        assert_eq!(wm.focused_client_id(), Some(20));
+        assert_eq!(wm.focus_history()[..2], [20, 30]);
     }
 
-    /// Move focus to the previous client in the stack
-    pub fn previous_client(&mut self) {
-        self.cycle_client(Direction::Backward);
+    #[test]
+    fn handled_layout_messages_reapply_the_layout() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        add_n_clients(&mut wm, 2, 0);
+
+        assert!(wm.send_layout_message(&LayoutMessage::SetMaxMain(3)));
+        assert!(!wm.send_layout_message(&"not a layout message"));
+        wm.toggle_gaps();
+
+        let ws = &wm.workspaces[0];
+        assert!(ws.layout_conf().unwrap().gapless);
     }
 
-    pub fn drag_client_forward(&mut self) {
-        self.drag_client(Direction::Forward);
+    #[test]
+    fn docks_are_not_managed_and_reserve_space() {
+        let mut values = vec![0, 0, 20, 0];
+        values.extend(&[0, 0, 0, 0, 1000, 1999, 0, 0]);
+        let strut = Strut::from_cardinals(&values);
+        let conn = MockXConn::new(dual_screens()).with_docks(vec![(99, strut)]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+
+        wm.handle_map_notify(99, false);
+        assert!(!wm.client_map.contains_key(&99));
+        assert_eq!(wm.usable_screen_region(0), Region::new(0, 18, 1000, 582));
+        assert_eq!(wm.usable_screen_region(1), Region::new(1000, 20, 1000, 580));
+
+        wm.handle_destroy_notify(99);
+        assert_eq!(wm.usable_screen_region(1), Region::new(1000, 18, 1000, 582));
     }
 
-    pub fn drag_client_backward(&mut self) {
-        self.drag_client(Direction::Backward);
+    #[test]
+    fn docks_without_struts_do_not_change_screen_regions() {
+        let conn = MockXConn::new(test_screens()).with_docks(vec![(99, None)]);
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+
+        wm.handle_map_notify(99, false);
+        assert!(wm.docks.contains_key(&99));
+        assert!(!wm.client_map.contains_key(&99));
+        assert_eq!(wm.usable_screen_region(0), Region::new(0, 18, 1366, 750));
     }
 
-    /// Kill the focused client window.
-    pub fn kill_client(&mut self) {
-        if let Some(client) = self.focused_client() {
-            let id = client.id();
-            self.conn.send_client_event(id, "WM_DELETE_WINDOW");
-            self.conn.flush();
+    static HANDLED_ERRORS: AtomicUsize = AtomicUsize::new(0);
 
-            self.remove_client(id);
-            self.apply_layout(self.active_ws_index());
-        }
+    fn count_errors(_: &mut WindowManager, _: &PenroseError) {
+        HANDLED_ERRORS.fetch_add(1, Ordering::SeqCst);
     }
 
-    /// Rearrange the windows on the focused screen using the next available layout
-    pub fn next_layout(&mut self) {
-        self.cycle_layout(Direction::Forward);
+    #[test]
+    fn layout_errors_are_passed_to_the_error_handler() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        wm.error_handler = Some(count_errors);
+        add_n_clients(&mut wm, 2, 0);
+
+        // a client that we have lost track of should not bring everything down
+        wm.client_map.remove(&10);
+        wm.apply_layout(0);
+        assert_eq!(HANDLED_ERRORS.load(Ordering::SeqCst), 1);
     }
 
-    /// Rearrange the windows on the focused screen using the previous layout
-    pub fn previous_layout(&mut self) {
-        self.cycle_layout(Direction::Backward);
+    // 30 was run from a shell (25) inside of the terminal 10
+    fn test_parent_pid(pid: u32) -> Option<u32> {
+        match pid {
+            30 => Some(25),
+            25 => Some(10),
+            _ => None,
+        }
     }
 
-    /// Increase the number of windows in the main layout area
-    pub fn inc_main(&mut self) {
-        self.update_max_main(Change::More);
+    fn wm_with_terminal(conn: &MockXConn) -> WindowManager<'_> {
+        let mut wm = wm_with_mock_conn(test_layouts(), conn);
+        wm.terminal_classes = &["term"];
+        wm.parent_pid = test_parent_pid;
+        add_n_clients(&mut wm, 2, 0); // [20, 10]
+        wm.client_map
+            .insert(10, Client::new(10, "term".into(), 0, false));
+        wm
     }
 
-    /// Reduce the number of windows in the main layout area
-    pub fn dec_main(&mut self) {
-        self.update_max_main(Change::Less);
+    #[test]
+    fn programs_run_from_terminals_swallow_them() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_terminal(&conn);
+
+        wm.handle_map_notify(30, false);
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&20, &30]);
+        assert!(!wm.client_map.contains_key(&10));
+
+        wm.handle_destroy_notify(30);
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&20, &10]);
+        assert!(wm.client_map.contains_key(&10));
     }
 
-    /// Make the main area larger relative to sub-areas
-    pub fn inc_ratio(&mut self) {
-        self.update_main_ratio(Change::More);
+    #[test]
+    fn swallowed_terminals_that_exit_are_not_restored() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_terminal(&conn);
+
+        wm.handle_map_notify(30, false);
+        wm.handle_destroy_notify(10);
+        wm.handle_destroy_notify(30);
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&20]);
+        assert!(!wm.client_map.contains_key(&10));
     }
 
-    /// Make the main area smaller relative to sub-areas
-    pub fn dec_ratio(&mut self) {
-        self.update_main_ratio(Change::Less);
+    fn wm_with_key_codes(conn: &MockXConn) -> WindowManager<'_> {
+        let mut wm = wm_with_mock_conn(test_layouts(), conn);
+        let mut codes = CodeMap::new();
+        codes.insert("j".into(), 44);
+        codes.insert("Return".into(), 36);
+        wm.key_codes = Some(codes);
+        wm
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::data_types::*;
-    use crate::layout::*;
-    use crate::screen::*;
-    use crate::xconnection::*;
+    #[test]
+    fn config_files_are_applied_to_a_running_wm() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_key_codes(&conn);
+        add_n_clients(&mut wm, 2, 0);
+        let file = ConfigFile::parse(
+            r##"
+            workspaces = ["main", "2", "3", "4", "5", "6", "7", "8", "9", "extra"]
+            gap_px = 0
+            border_px = 0
 
-    const FONTS: &[&str] = &["Comic Sans:size=88"];
-    const WORKSPACES: &[&str] = &["1", "2", "3", "4", "5", "6", "7", "8", "9"];
-    const FLOATING_CLASSES: &[&str] = &["clouds", "birds"];
-    const COLOR_SCHEME: ColorScheme = ColorScheme {
-        bg: 0x282828,        // #282828
-        fg_1: 0x3c3836,      // #3c3836
-        fg_2: 0xa89984,      // #a89984
-        fg_3: 0xf2e5bc,      // #f2e5bc
-        highlight: 0xcc241d, // #cc241d
-        urgent: 0x458588,    // #458588
-    };
+            [colors]
+            highlight = "#ffffff"
 
-    fn wm_with_mock_conn<'a>(layouts: Vec<Layout>, conn: &'a MockXConn) -> WindowManager<'a> {
-        let conf = Config {
-            workspaces: WORKSPACES,
-            fonts: FONTS,
-            floating_classes: FLOATING_CLASSES,
-            layouts: layouts,
-            color_scheme: COLOR_SCHEME,
-            border_px: 2,
-            gap_px: 5,
-            main_ratio_step: 0.05,
-            systray_spacing_px: 2,
-            show_systray: true,
-            show_bar: true,
-            top_bar: true,
-            bar_height: 18,
-            respect_resize_hints: true,
-        };
+            [keys]
+            "M-j" = "next_client"
+            "##,
+        )
+        .unwrap();
 
-        WindowManager::init(conf, conn)
-    }
+        wm.apply_config_file(&file).unwrap();
+        assert_eq!(wm.workspace_names()[0], "main");
+        assert_eq!(wm.workspace_names().len(), 10);
+        assert_eq!(wm.border_style().focused, 0xffffff);
+        assert_eq!(wm.client_region(20), Some(Region::new(0, 18, 1366, 750)));
 
-    fn test_layouts() -> Vec<Layout> {
-        vec![Layout::new("t", LayoutConf::default(), mock_layout, 1, 0.6)]
+        let key = KeyCode { mask: 64, code: 44 };
+        assert_eq!(wm.file_bindings.get(&key), Some(&"next_client".to_string()));
+        wm.handle_key_press(key, &HashMap::new(), &mut vec![]);
+        assert_eq!(wm.focused_client_id(), Some(10));
     }
 
-    fn test_screens() -> Vec<Screen> {
-        let r = Region::new(0, 0, 1366, 768);
-        vec![Screen {
-            true_region: r,
-            effective_region: r,
-            wix: 0,
-        }]
-    }
+    #[test]
+    fn invalid_config_files_change_nothing() {
+        let conn = MockXConn::new(test_screens());
+        let mut wm = wm_with_key_codes(&conn);
+        let file = ConfigFile::parse(
+            r##"
+            workspaces = ["main"]
+            gap_px = 0
 
-    fn add_n_clients(wm: &mut WindowManager, n: usize, offset: usize) {
-        for i in 0..n {
-            wm.handle_map_notify(10 * (i + offset + 1) as u32, false);
-        }
+            [keys]
+            "M-j" = "not_an_action"
+            "M-q" = "kill_client"
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(
+            wm.apply_config_file(&file),
+            Err(PenroseError::InvalidConfig(
+                "unknown key 'M-q'; unknown action 'not_an_action'".into()
+            ))
+        );
+        assert_eq!(wm.workspace_names()[0], "1");
+        assert_eq!(wm.gap_px, 5);
+        assert!(wm.file_bindings.is_empty());
     }
 
     #[test]
-    fn worspace_switching_with_active_clients() {
+    fn reload_config_rereads_the_config_file() {
         let conn = MockXConn::new(test_screens());
-        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
+        let mut wm = wm_with_key_codes(&conn);
+        let path = env::temp_dir().join(format!("penrose-config-{}.toml", process::id()));
+        wm.config_file = Some(path.clone());
 
-        // add clients to the first workspace: final client should have focus
-        add_n_clients(&mut wm, 3, 0);
-        assert_eq!(wm.workspaces[0].len(), 3);
-        assert_eq!(wm.workspaces[0].focused_client(), Some(30));
+        fs::write(&path, "gap_px = 1").unwrap();
+        wm.reload_config();
+        assert_eq!(wm.gap_px, 1);
 
-        // switch and add to the second workspace: final client should have focus
-        wm.focus_workspace(1);
-        add_n_clients(&mut wm, 2, 3);
-        assert_eq!(wm.workspaces[1].len(), 2);
-        assert_eq!(wm.workspaces[1].focused_client(), Some(50));
+        fs::write(&path, "gap_px = -1").unwrap();
+        wm.reload_config();
+        assert_eq!(wm.gap_px, 1);
 
-        // switch back: clients should be the same, same client should have focus
-        wm.focus_workspace(0);
-        assert_eq!(wm.workspaces[0].len(), 3);
-        assert_eq!(wm.workspaces[0].focused_client(), Some(30));
+        fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn killing_a_client_removes_it_from_the_workspace() {
-        let conn = MockXConn::new(test_screens());
+    fn scripted_events_drive_the_event_loop() {
+        let key = KeyCode { mask: 64, code: 44 };
+        let conn = MockXConn::new(test_screens()).with_events(vec![
+            XEvent::Map {
+                window: 10,
+                ignore: false,
+            },
+            XEvent::Map {
+                window: 20,
+                ignore: false,
+            },
+            XEvent::KeyPress { code: key },
+        ]);
         let mut wm = wm_with_mock_conn(test_layouts(), &conn);
-        add_n_clients(&mut wm, 1, 0);
-        wm.kill_client();
+        let mut bindings: KeyBindings = HashMap::new();
+        bindings.insert(key, run_internal!(next_client));
 
-        assert_eq!(wm.workspaces[0].len(), 0);
+        wm.run_until_idle(bindings);
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&20, &10]);
+        assert_eq!(wm.focused_client_id(), Some(10));
+
+        let calls = conn.calls();
+        assert!(calls
+            .iter()
+            .any(|c| matches!(c, MockCall::PositionWindow(20, _, _))));
+        assert_eq!(
+            calls
+                .iter()
+                .rev()
+                .find(|c| matches!(c, MockCall::FocusClient(_))),
+            Some(&MockCall::FocusClient(10))
+        );
     }
 
     #[test]
-    fn kill_client_kills_focused_not_first() {
+    fn minimized_clients_are_restored_last_in_first_out() {
         let conn = MockXConn::new(test_screens());
         let mut wm = wm_with_mock_conn(test_layouts(), &conn);
-        add_n_clients(&mut wm, 5, 0); // 50 40 30 20 10, 50 focused
-        wm.next_client(); // 40 focused
-        wm.kill_client(); // remove 40, focus 30
+        add_n_clients(&mut wm, 3, 0); // [30, 20, 10]
 
-        let ids: Vec<WinId> = wm.workspaces[0].iter().map(|c| *c).collect();
-        assert_eq!(ids, vec![50, 30, 20, 10]);
-        assert_eq!(wm.workspaces[0].focused_client(), Some(30));
+        wm.minimize_client();
+        wm.minimize_client();
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&10]);
+        assert_eq!(wm.minimized_clients(0), vec![30, 20]);
+        assert!(conn.calls().contains(&MockCall::UnmapWindow(30)));
+        assert_eq!(wm.snapshot().workspaces[0].minimized, vec![30, 20]);
+
+        wm.restore_minimized();
+        assert_eq!(wm.workspaces[0].iter().collect::<Vec<_>>(), vec![&20, &10]);
+        assert_eq!(wm.focused_client_id(), Some(20));
+        assert_eq!(wm.minimized_clients(0), vec![30]);
+
+        wm.handle_destroy_notify(30);
+        assert!(wm.minimized_clients(0).is_empty());
     }
 
     #[test]
-    fn moving_then_deleting_clients() {
+    fn cycling_minimized_clients_swaps_with_the_oldest() {
         let conn = MockXConn::new(test_screens());
         let mut wm = wm_with_mock_conn(test_layouts(), &conn);
-        add_n_clients(&mut wm, 2, 0);
-        wm.client_to_workspace(1);
-        wm.client_to_workspace(1);
-        wm.focus_workspace(1);
-        wm.kill_client();
+        add_n_clients(&mut wm, 3, 0); // [30, 20, 10]
+        wm.minimize_client();
+        wm.minimize_client();
 
-        // should have removed first client on ws::1 (last sent from ws::0)
-        assert_eq!(wm.workspaces[1].iter().collect::<Vec<&WinId>>(), vec![&20]);
+        wm.cycle_minimized();
+        assert_eq!(wm.focused_client_id(), Some(30));
+        assert_eq!(wm.minimized_clients(0), vec![20, 10]);
+
+        wm.cycle_minimized();
+        assert_eq!(wm.focused_client_id(), Some(20));
+        assert_eq!(wm.minimized_clients(0), vec![10, 30]);
+        assert!(wm.minimized_clients(1).is_empty());
+    }
+
+    struct Notifications {
+        seen: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Hook for Notifications {
+        fn notification(&mut self, _: &mut WindowManager, n: &Notification) {
+            self.seen.borrow_mut().push(n.to_string());
+        }
+    }
+
+    fn notifications(wm: &mut WindowManager) -> Rc<RefCell<Vec<String>>> {
+        let seen = Rc::new(RefCell::new(vec![]));
+        wm.hooks = vec![Box::new(Notifications {
+            seen: Rc::clone(&seen),
+        })];
+        seen
+    }
+
+    struct LayoutNotifier {}
+
+    impl Hook for LayoutNotifier {
+        fn layout_change(&mut self, wm: &mut WindowManager, _: usize, _: usize) {
+            wm.notify(Notification::info("from a hook"));
+        }
     }
 
     #[test]
-    fn sending_a_client_inserts_at_head() {
+    fn notifications_raised_by_hooks_are_passed_to_hooks() {
         let conn = MockXConn::new(test_screens());
-        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
-        add_n_clients(&mut wm, 2, 0); // [20, 10]
-        wm.client_to_workspace(1); // 20 -> ws::1
-        wm.client_to_workspace(1); // 10 -> ws::1, [10, 20]
-        wm.focus_workspace(1);
+        let layouts = vec![
+            Layout::new("t", LayoutConf::default(), mock_layout, 1, 0.6),
+            Layout::new("g", LayoutConf::default(), grid, 1, 0.6),
+        ];
+        let mut wm = wm_with_mock_conn(layouts, &conn);
+        let seen = notifications(&mut wm);
+        wm.hooks.insert(0, Box::new(LayoutNotifier {}));
+
+        wm.set_layout_by_symbol("g");
 
         assert_eq!(
-            wm.workspaces[1].iter().collect::<Vec<&WinId>>(),
-            vec![&10, &20]
+            *seen.borrow(),
+            vec![
+                "from a hook".to_string(),
+                "1: layout switched to \"g\"".to_string(),
+            ]
         );
     }
 
     #[test]
-    fn sending_a_client_sets_focus() {
+    fn layout_changes_and_moved_clients_are_reported() {
         let conn = MockXConn::new(test_screens());
-        let mut wm = wm_with_mock_conn(test_layouts(), &conn);
-        add_n_clients(&mut wm, 2, 0); // [20, 10]
-        wm.client_to_workspace(1); // 20 -> ws::1
-        wm.client_to_workspace(1); // 10 -> ws::1, [10, 20]
-        wm.focus_workspace(1);
+        let layouts = vec![
+            Layout::new("t", LayoutConf::default(), mock_layout, 1, 0.6),
+            Layout::new("g", LayoutConf::default(), grid, 1, 0.6),
+        ];
+        let mut wm = wm_with_mock_conn(layouts, &conn);
+        add_n_clients(&mut wm, 1, 0);
+        let seen = notifications(&mut wm);
 
-        assert_eq!(wm.workspaces[1].focused_client(), Some(10));
+        wm.set_layout_by_symbol("g");
+        wm.client_to_workspace(2);
+        wm.set_layout_by_symbol("nope");
+
+        let title = wm.client_title(10);
+        assert_eq!(
+            *seen.borrow(),
+            vec![
+                "1: layout switched to \"g\"".to_string(),
+                format!("sent \"{}\" to 3", title),
+                "error: workspace 1 has no layout \"nope\"".to_string(),
+            ]
+        );
     }
 
     #[test]
-    fn x_focus_events_set_workspace_focus() {
+    fn invalid_workspaces_are_reported_rather_than_panicking() {
         let conn = MockXConn::new(test_screens());
         let mut wm = wm_with_mock_conn(test_layouts(), &conn);
-        add_n_clients(&mut wm, 5, 0); // focus on last client: 50
-        wm.handle_enter_notify(10);
+        add_n_clients(&mut wm, 1, 0);
+        let seen = notifications(&mut wm);
 
-        assert_eq!(wm.workspaces[0].focused_client(), Some(10));
+        wm.focus_workspace(42);
+        wm.client_to_workspace(42);
+        wm.client_to_workspace_and_follow(42);
+
+        assert_eq!(wm.active_ws_index(), 0);
+        assert_eq!(wm.workspaces[0].len(), 1);
+        assert_eq!(*seen.borrow(), vec!["error: no such workspace: 42"; 3]);
     }
 
     #[test]
-    fn dragging_clients_forward_from_index_0() {
+    fn failed_file_bindings_are_reported() {
         let conn = MockXConn::new(test_screens());
         let mut wm = wm_with_mock_conn(test_layouts(), &conn);
-        add_n_clients(&mut wm, 5, 0); // focus on last client (50) ix == 0
-
-        let clients = |w: &mut WindowManager| {
-            w.workspace_for_screen_mut(0)
-                .iter()
-                .cloned()
-                .collect::<Vec<_>>()
-        };
-
-        wm.drag_client_forward();
-        assert_eq!(wm.focused_client().unwrap().id(), 50);
-        assert_eq!(clients(&mut wm), vec![40, 50, 30, 20, 10]);
+        let seen = notifications(&mut wm);
 
-        wm.drag_client_forward();
-        assert_eq!(wm.focused_client().unwrap().id(), 50);
-        assert_eq!(clients(&mut wm), vec![40, 30, 50, 20, 10]);
-
-        wm.handle_enter_notify(20);
-        wm.drag_client_forward();
-        assert_eq!(wm.focused_client().unwrap().id(), 20);
-        assert_eq!(clients(&mut wm), vec![40, 30, 50, 10, 20]);
+        file_binding("not_an_action".into())(&mut wm);
+        assert_eq!(*seen.borrow(), vec!["error: unknown action: not_an_action"]);
     }
 }
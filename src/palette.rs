@@ -0,0 +1,598 @@
+/*!
+ * A fuzzy searchable command palette
+ *
+ * When opened (see `WindowManager::open_command_palette`) the palette is drawn as an overlay
+ * at the top of the focused screen, listing every named WindowManager action (including any
+ * added with `WindowManager::register_action`) along with the current key bindings. While it
+ * is open the keyboard is grabbed: typing filters and ranks the entries, Up / Down (or Tab)
+ * move the selection, Return runs the selected entry and Escape closes the palette.
+ *
+ * The same palette doubles as a dmenu style launcher (see `WindowManager::open_launcher` and
+ * `launcher`) that lists entries from another PaletteSource instead: programs to run, windows
+ * to jump to, workspaces to switch to or a list of entries provided by the user.
+ */
+use crate::data_types::{CodeMap, ColorScheme, FireAndForget, KeyCode, Region, WinId};
+use crate::draw::Draw;
+use crate::helpers::keycodes_from_xmodmap;
+use crate::manager::WindowManager;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Something that can be run from the command palette
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteAction {
+    /// A named action, run using `WindowManager::run_action`
+    Named(String),
+    /// A key binding, run as if the keys had been pressed
+    Binding(KeyCode),
+    /// An external program, run using `helpers::spawn`
+    Spawn(String),
+    /// Focus a client, switching to its workspace if needed
+    FocusClient(WinId),
+    /// Switch to the workspace at this index
+    FocusWorkspace(usize),
+}
+
+/// Where the entries shown in the palette come from when it is opened
+#[derive(Debug, Clone, Copy)]
+pub enum PaletteSource {
+    /// Named actions and key bindings
+    Actions,
+    /// Programs on $PATH. Return runs the query as a command if nothing matches it.
+    Programs,
+    /// Managed clients, by title
+    Windows,
+    /// Workspaces, by name
+    Workspaces,
+    /// Entries generated by the given function each time the palette is opened
+    Custom(fn(&WindowManager) -> Vec<PaletteEntry>),
+}
+
+impl PaletteSource {
+    /// The prompt shown in front of the query
+    pub fn prompt(&self) -> &'static str {
+        match self {
+            PaletteSource::Programs => "run>",
+            PaletteSource::Windows => "window>",
+            PaletteSource::Workspaces => "workspace>",
+            PaletteSource::Actions | PaletteSource::Custom(_) => ">",
+        }
+    }
+}
+
+/**
+ * A key binding action that opens the palette with entries from `source`. Use
+ * PaletteSource::Custom with a function returning your own entries to pick from any list of
+ * candidates, with each entry running a PaletteAction (such as a registered named action).
+ */
+pub fn launcher(source: PaletteSource) -> FireAndForget {
+    Box::new(move |wm: &mut WindowManager| {
+        wm.open_launcher(source);
+        None
+    })
+}
+
+/// A single selectable line in the command palette
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteEntry {
+    /// The text that is shown and matched against
+    pub label: String,
+    /// What to run when the entry is selected
+    pub action: PaletteAction,
+}
+
+/// What the WindowManager should do after passing a key press to an open palette
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PaletteInput {
+    /// Keep the palette open
+    Continue,
+    /// Close the palette without running anything
+    Cancel,
+    /// Close the palette and run the selected action
+    Run(PaletteAction),
+}
+
+// The keys that the palette responds to: everything else is ignored while it is open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PaletteKey {
+    Char(char),
+    Backspace,
+    Up,
+    Down,
+    Enter,
+    Escape,
+}
+
+impl PaletteKey {
+    fn from_name(name: &str) -> Option<PaletteKey> {
+        match name {
+            "BackSpace" => Some(PaletteKey::Backspace),
+            "Up" => Some(PaletteKey::Up),
+            "Down" | "Tab" => Some(PaletteKey::Down),
+            "Return" | "KP_Enter" => Some(PaletteKey::Enter),
+            "Escape" => Some(PaletteKey::Escape),
+            "space" => Some(PaletteKey::Char(' ')),
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c.is_ascii_alphanumeric() => {
+                        Some(PaletteKey::Char(c.to_ascii_lowercase()))
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Score how well `query` matches `candidate`, ignoring case and any whitespace in the query.
+ * Every query character must appear in the candidate in order: the score is the number of
+ * skipped candidate characters, so lower scores are better matches and an empty query
+ * matches everything with a score of 0. None is returned if the candidate does not match.
+ */
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<usize> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let offset = candidate[pos..].iter().position(|c| *c == q)?;
+        score += offset;
+        pos += offset + 1;
+    }
+
+    Some(score)
+}
+
+/// An overlay for searching and running WindowManager actions and key bindings
+pub struct CommandPalette {
+    draw: Box<dyn Draw>,
+    line_height: u32,
+    max_entries: usize,
+    colors: ColorScheme,
+    codes: Option<CodeMap>,
+    keys: HashMap<u8, PaletteKey>,
+    // one window per line: the query followed by the matching entries
+    windows: Vec<WinId>,
+    region: Region,
+    entries: Vec<PaletteEntry>,
+    source: PaletteSource,
+    query: String,
+    selected: usize,
+    open: bool,
+}
+
+impl fmt::Debug for CommandPalette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandPalette")
+            .field("line_height", &self.line_height)
+            .field("max_entries", &self.max_entries)
+            .field("query", &self.query)
+            .field("selected", &self.selected)
+            .field("open", &self.open)
+            .finish()
+    }
+}
+
+impl CommandPalette {
+    /**
+     * Create a new CommandPalette that shows up to 10 matching entries at a time. Key names
+     * are looked up using xmodmap the first time that the palette is opened.
+     */
+    pub fn new(draw: impl Draw + 'static, line_height: u32, colors: ColorScheme) -> Self {
+        CommandPalette {
+            draw: Box::new(draw),
+            line_height,
+            max_entries: 10,
+            colors,
+            codes: None,
+            keys: HashMap::new(),
+            windows: vec![],
+            region: Region::new(0, 0, 0, 0),
+            entries: vec![],
+            source: PaletteSource::Actions,
+            query: String::new(),
+            selected: 0,
+            open: false,
+        }
+    }
+
+    /// Use an existing CodeMap (see helpers::keycodes_from_xmodmap) rather than running xmodmap
+    pub fn with_key_codes(mut self, codes: CodeMap) -> Self {
+        self.set_key_codes(codes);
+        self
+    }
+
+    /// Set the maximum number of matching entries to show at once
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+
+    fn set_key_codes(&mut self, codes: CodeMap) {
+        self.keys = codes
+            .iter()
+            .filter_map(|(name, code)| PaletteKey::from_name(name).map(|k| (*code, k)))
+            .collect();
+        self.codes = Some(codes);
+    }
+
+    /// The known key names, used to label key bindings
+    pub(crate) fn key_codes(&mut self) -> &CodeMap {
+        if self.codes.is_none() {
+            self.set_key_codes(keycodes_from_xmodmap());
+        }
+        self.codes.as_ref().unwrap()
+    }
+
+    /// Whether or not the palette is currently shown and accepting input
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// The current search query
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// The entries matching the current query, best match first
+    pub fn matches(&self) -> Vec<&PaletteEntry> {
+        let mut scored: Vec<(usize, &PaletteEntry)> = self
+            .entries
+            .iter()
+            .filter_map(|e| fuzzy_match(&self.query, &e.label).map(|s| (s, e)))
+            .collect();
+        scored.sort_by_key(|(score, e)| (*score, e.label.len()));
+
+        scored.into_iter().map(|(_, e)| e).collect()
+    }
+
+    /// The currently selected entry (if any entries match the query)
+    pub fn selected(&self) -> Option<&PaletteEntry> {
+        self.matches().get(self.selected).copied()
+    }
+
+    /// Show the palette on the given screen region with a fresh query
+    pub(crate) fn open(
+        &mut self,
+        entries: Vec<PaletteEntry>,
+        source: PaletteSource,
+        screen: &Region,
+    ) {
+        let (x, y, w, _) = screen.values();
+        let width = (w / 2).max(1);
+        self.region = Region::new(x + (w - width) / 2, y, width, self.line_height);
+        self.entries = entries;
+        self.source = source;
+        self.query.clear();
+        self.selected = 0;
+        self.open = true;
+        self.redraw();
+    }
+
+    /// Hide the palette
+    pub(crate) fn close(&mut self) {
+        self.open = false;
+        for id in self.windows.iter() {
+            self.draw.hide_window(*id);
+        }
+        self.draw.flush();
+    }
+
+    /// Update the query or selection in response to a key press
+    pub(crate) fn handle_key(&mut self, key: KeyCode) -> PaletteInput {
+        let key = match self.keys.get(&key.code) {
+            Some(k) => *k,
+            None => return PaletteInput::Continue, // modifiers and unsupported keys
+        };
+
+        let n_matches = self.matches().len().min(self.max_entries);
+        match key {
+            PaletteKey::Escape => return PaletteInput::Cancel,
+            PaletteKey::Enter => {
+                return match (self.selected(), self.source) {
+                    (Some(entry), _) => PaletteInput::Run(entry.action.clone()),
+                    (None, PaletteSource::Programs) if !self.query.trim().is_empty() => {
+                        PaletteInput::Run(PaletteAction::Spawn(self.query.trim().to_string()))
+                    }
+                    (None, _) => PaletteInput::Cancel,
+                }
+            }
+            PaletteKey::Up if n_matches > 0 => {
+                self.selected = (self.selected + n_matches - 1) % n_matches
+            }
+            PaletteKey::Down if n_matches > 0 => self.selected = (self.selected + 1) % n_matches,
+            PaletteKey::Up | PaletteKey::Down => (),
+            PaletteKey::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+            }
+            PaletteKey::Char(c) => {
+                self.query.push(c);
+                self.selected = 0;
+            }
+        }
+
+        self.redraw();
+        PaletteInput::Continue
+    }
+
+    // Make sure that there is a window for the given line, returning its id
+    fn line_window(&mut self, line: usize, r: &Region) -> Option<WinId> {
+        if let Some(id) = self.windows.get(line) {
+            self.draw.show_window(*id, r);
+            return Some(*id);
+        }
+
+        match self.draw.new_window(r) {
+            Ok(id) => {
+                self.windows.push(id);
+                Some(id)
+            }
+            Err(e) => {
+                warn!("unable to create command palette window: {}", e);
+                None
+            }
+        }
+    }
+
+    fn redraw(&mut self) {
+        let labels: Vec<String> = self
+            .matches()
+            .iter()
+            .take(self.max_entries)
+            .map(|e| e.label.clone())
+            .collect();
+        let lines: Vec<(String, u32, u32)> = Some((
+            format!("{} {}", self.source.prompt(), self.query),
+            self.colors.fg_3,
+            self.colors.fg_1,
+        ))
+        .into_iter()
+        .chain(labels.into_iter().enumerate().map(|(i, label)| {
+            if i == self.selected {
+                (label, self.colors.fg_3, self.colors.highlight)
+            } else {
+                (label, self.colors.fg_2, self.colors.bg)
+            }
+        }))
+        .collect();
+
+        let (x, y, w, h) = self.region.values();
+        for (i, (text, fg, bg)) in lines.iter().enumerate() {
+            let r = Region::new(x, y + i as u32 * h, w, h);
+            if let Some(id) = self.line_window(i, &r) {
+                self.draw.fill_rect(id, &Region::new(0, 0, w, h), *bg);
+                self.draw.text(id, 2, h, text, *fg, *bg);
+            }
+        }
+        for id in self.windows.iter().skip(lines.len()) {
+            self.draw.hide_window(*id);
+        }
+
+        self.draw.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct MockDraw {
+        text: Rc<RefCell<HashMap<WinId, String>>>,
+        next_id: WinId,
+    }
+
+    impl Draw for MockDraw {
+        fn new_window(&mut self, _: &Region) -> Result<WinId, String> {
+            self.next_id += 1;
+            Ok(self.next_id)
+        }
+        fn show_window(&mut self, _: WinId, _: &Region) {}
+        fn hide_window(&mut self, id: WinId) {
+            self.text.borrow_mut().remove(&id);
+        }
+        fn set_opacity(&mut self, _: WinId, _: f32) {}
+        fn fill_rect(&mut self, _: WinId, _: &Region, _: u32) {}
+        fn text(&mut self, id: WinId, _: u32, _: u32, s: &str, _: u32, _: u32) {
+            self.text.borrow_mut().insert(id, s.to_string());
+        }
+        fn text_width(&self, s: &str) -> u32 {
+            s.len() as u32
+        }
+        fn flush(&self) {}
+    }
+
+    fn codes() -> CodeMap {
+        let mut codes: CodeMap = "abcdefghijklmnopqrstuvwxyz"
+            .chars()
+            .enumerate()
+            .map(|(i, c)| (c.to_string(), 30 + i as u8))
+            .collect();
+        codes.insert("A".into(), 30);
+        for (name, code) in &[("BackSpace", 1), ("Return", 2), ("Escape", 3), ("Down", 4)] {
+            codes.insert(name.to_string(), *code);
+        }
+        codes
+    }
+
+    fn key(name: &str) -> KeyCode {
+        KeyCode {
+            mask: 0,
+            code: codes()[name],
+        }
+    }
+
+    fn test_palette() -> (CommandPalette, Rc<RefCell<HashMap<WinId, String>>>) {
+        let text = Rc::new(RefCell::new(HashMap::new()));
+        let draw = MockDraw {
+            text: Rc::clone(&text),
+            next_id: 0,
+        };
+        let colors = ColorScheme {
+            bg: 0,
+            fg_1: 1,
+            fg_2: 2,
+            fg_3: 3,
+            highlight: 4,
+            urgent: 5,
+        };
+        let palette = CommandPalette::new(draw, 10, colors).with_key_codes(codes());
+        (palette, text)
+    }
+
+    fn entries(names: &[&str]) -> Vec<PaletteEntry> {
+        names
+            .iter()
+            .map(|n| PaletteEntry {
+                label: n.to_string(),
+                action: PaletteAction::Named(n.to_string()),
+            })
+            .collect()
+    }
+
+    fn type_query(p: &mut CommandPalette, query: &str) {
+        for c in query.chars() {
+            assert_eq!(p.handle_key(key(&c.to_string())), PaletteInput::Continue);
+        }
+    }
+
+    #[test]
+    fn fuzzy_match_requires_characters_in_order() {
+        assert_eq!(fuzzy_match("", "next_client"), Some(0));
+        assert_eq!(fuzzy_match("next", "next_client"), Some(0));
+        assert_eq!(fuzzy_match("nc", "next_client"), Some(4));
+        assert_eq!(fuzzy_match("NEXT cl", "next_client"), Some(1));
+        assert_eq!(fuzzy_match("tx", "next_client"), None);
+        assert_eq!(fuzzy_match("x", "inc_main"), None);
+    }
+
+    #[test]
+    fn matches_are_ranked_by_score() {
+        let (mut p, _) = test_palette();
+        p.open(
+            entries(&["previous_layout", "next_client", "next_layout"]),
+            PaletteSource::Actions,
+            &Region::new(0, 0, 100, 100),
+        );
+        type_query(&mut p, "nl");
+
+        let labels: Vec<&str> = p.matches().iter().map(|e| e.label.as_ref()).collect();
+        assert_eq!(labels, vec!["next_layout", "next_client"]);
+    }
+
+    #[test]
+    fn query_and_matches_are_drawn() {
+        let (mut p, text) = test_palette();
+        p.open(
+            entries(&["inc_main", "dec_main"]),
+            PaletteSource::Actions,
+            &Region::new(0, 0, 100, 100),
+        );
+        type_query(&mut p, "dec");
+
+        let mut drawn: Vec<String> = text.borrow().values().cloned().collect();
+        drawn.sort();
+        assert_eq!(drawn, vec!["> dec", "dec_main"]);
+    }
+
+    #[test]
+    fn uppercase_key_names_are_typed_as_lowercase() {
+        let (mut p, _) = test_palette();
+        p.open(
+            entries(&["inc_main"]),
+            PaletteSource::Actions,
+            &Region::new(0, 0, 100, 100),
+        );
+        p.handle_key(KeyCode {
+            mask: xcb::MOD_MASK_SHIFT as u16,
+            code: 30,
+        });
+
+        assert_eq!(p.query(), "a");
+    }
+
+    #[test]
+    fn backspace_removes_the_last_character() {
+        let (mut p, _) = test_palette();
+        p.open(
+            entries(&["inc_main"]),
+            PaletteSource::Actions,
+            &Region::new(0, 0, 100, 100),
+        );
+        type_query(&mut p, "inx");
+        p.handle_key(key("BackSpace"));
+
+        assert_eq!(p.query(), "in");
+        assert_eq!(p.matches().len(), 1);
+    }
+
+    #[test]
+    fn selection_wraps_and_runs_on_return() {
+        let (mut p, _) = test_palette();
+        p.open(
+            entries(&["a", "b"]),
+            PaletteSource::Actions,
+            &Region::new(0, 0, 100, 100),
+        );
+        p.handle_key(key("Down"));
+        assert_eq!(
+            p.handle_key(key("Return")),
+            PaletteInput::Run(PaletteAction::Named("b".into()))
+        );
+
+        p.handle_key(key("Down"));
+        p.handle_key(key("Down"));
+        assert_eq!(p.selected().unwrap().label, "b");
+    }
+
+    #[test]
+    fn escape_and_return_without_matches_cancel() {
+        let (mut p, _) = test_palette();
+        p.open(
+            entries(&["a"]),
+            PaletteSource::Actions,
+            &Region::new(0, 0, 100, 100),
+        );
+        assert_eq!(p.handle_key(key("Escape")), PaletteInput::Cancel);
+
+        type_query(&mut p, "zz");
+        assert_eq!(p.handle_key(key("Return")), PaletteInput::Cancel);
+    }
+
+    #[test]
+    fn program_launchers_run_the_query_when_nothing_matches() {
+        let (mut p, text) = test_palette();
+        p.open(
+            entries(&["abc"]),
+            PaletteSource::Programs,
+            &Region::new(0, 0, 100, 100),
+        );
+        type_query(&mut p, "xy");
+
+        assert_eq!(text.borrow().values().next().unwrap(), "run> xy");
+        assert_eq!(
+            p.handle_key(key("Return")),
+            PaletteInput::Run(PaletteAction::Spawn("xy".into()))
+        );
+    }
+
+    #[test]
+    fn closing_hides_all_lines() {
+        let (mut p, text) = test_palette();
+        p.open(
+            entries(&["a", "b"]),
+            PaletteSource::Actions,
+            &Region::new(0, 0, 100, 100),
+        );
+        p.close();
+
+        assert!(!p.is_open());
+        assert!(text.borrow().is_empty());
+    }
+}
@@ -0,0 +1,409 @@
+/*!
+ * A keyboard driven grid for moving and resizing floating clients
+ *
+ * When opened (see `WindowManager::open_placement_grid`) the focused screen is split into a
+ * grid of cells that are drawn over the top of everything else, each with a letter in the
+ * middle. While it is open the keyboard is grabbed: typing the labels of two cells moves and
+ * resizes the focused floating client to cover the rectangle with those cells at its corners,
+ * while typing one label followed by Return places it in just that cell. Escape closes the
+ * grid without moving anything.
+ */
+use crate::data_types::{CodeMap, ColorScheme, KeyCode, Region, WinId};
+use crate::draw::Draw;
+use crate::helpers::keycodes_from_xmodmap;
+
+use std::collections::HashMap;
+use std::fmt;
+
+// Cell labels, starting with the home row so that the most common cells are easy to reach
+pub(crate) const LABELS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+/// What the WindowManager should do after passing a key press to an open grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PlacementInput {
+    /// Keep the grid open
+    Continue,
+    /// Close the grid without moving the client
+    Cancel,
+    /// Close the grid and move the client to cover the given region
+    Place(Region),
+}
+
+// The keys that the grid responds to: everything else is ignored while it is open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlacementKey {
+    Label(char),
+    Return,
+    Escape,
+}
+
+/// An overlay dividing the screen into labelled cells that clients can be placed in
+pub struct PlacementGrid {
+    draw: Box<dyn Draw>,
+    columns: u32,
+    rows: u32,
+    colors: ColorScheme,
+    opacity: f32,
+    keys: Option<HashMap<u8, PlacementKey>>,
+    // one window per cell
+    windows: Vec<WinId>,
+    cells: Vec<(char, Region)>,
+    first: Option<char>,
+    open: bool,
+}
+
+impl fmt::Debug for PlacementGrid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PlacementGrid")
+            .field("columns", &self.columns)
+            .field("rows", &self.rows)
+            .field("cells", &self.cells)
+            .field("first", &self.first)
+            .field("open", &self.open)
+            .finish()
+    }
+}
+
+impl PlacementGrid {
+    /**
+     * Create a new grid with the given number of columns and rows. There is one label per
+     * cell so the number of rows is reduced if needed to keep the grid to 26 cells. Key names
+     * are looked up using xmodmap the first time that the grid is opened.
+     */
+    pub fn new(draw: impl Draw + 'static, columns: u32, rows: u32, colors: ColorScheme) -> Self {
+        let columns = columns.clamp(1, LABELS.len() as u32);
+        let rows = rows.clamp(1, LABELS.len() as u32 / columns);
+        PlacementGrid {
+            draw: Box::new(draw),
+            columns,
+            rows,
+            colors,
+            opacity: 0.6,
+            keys: None,
+            windows: vec![],
+            cells: vec![],
+            first: None,
+            open: false,
+        }
+    }
+
+    /// Use an existing CodeMap (see helpers::keycodes_from_xmodmap) rather than running xmodmap
+    pub fn with_key_codes(mut self, codes: CodeMap) -> Self {
+        self.set_key_codes(&codes);
+        self
+    }
+
+    /// Set the opacity that the grid is drawn with (see Draw::set_opacity)
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    fn set_key_codes(&mut self, codes: &CodeMap) {
+        let keys = codes
+            .iter()
+            .filter_map(|(name, code)| match name.as_ref() {
+                "Escape" => Some((*code, PlacementKey::Escape)),
+                "Return" => Some((*code, PlacementKey::Return)),
+                _ => {
+                    let mut chars = name.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if LABELS.contains(c) => {
+                            Some((*code, PlacementKey::Label(c)))
+                        }
+                        _ => None,
+                    }
+                }
+            })
+            .collect();
+        self.keys = Some(keys);
+    }
+
+    /// Whether or not the grid is currently shown and accepting input
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// The label for each cell in the grid along with the area of the screen that it covers
+    pub fn cells(&self) -> &[(char, Region)] {
+        &self.cells
+    }
+
+    /// Split `r` into labelled cells (left to right, top to bottom) and show them
+    pub(crate) fn open(&mut self, r: &Region) {
+        if self.keys.is_none() {
+            self.set_key_codes(&keycodes_from_xmodmap());
+        }
+
+        let (x, y, w, h) = r.values();
+        let (cw, ch) = (w / self.columns, h / self.rows);
+        self.cells = LABELS
+            .chars()
+            .take((self.columns * self.rows) as usize)
+            .enumerate()
+            .map(|(i, c)| {
+                let (col, row) = (i as u32 % self.columns, i as u32 / self.columns);
+                // the last row and column take up any space left over from the division
+                let width = if col == self.columns - 1 {
+                    w - col * cw
+                } else {
+                    cw
+                };
+                let height = if row == self.rows - 1 {
+                    h - row * ch
+                } else {
+                    ch
+                };
+                (c, Region::new(x + col * cw, y + row * ch, width, height))
+            })
+            .collect();
+
+        self.first = None;
+        for i in 0..self.cells.len() {
+            self.draw_cell(i, false);
+        }
+        self.open = true;
+        self.draw.flush();
+    }
+
+    /// Hide the grid
+    pub(crate) fn close(&mut self) {
+        self.open = false;
+        self.first = None;
+        self.cells.clear();
+        for id in self.windows.iter() {
+            self.draw.hide_window(*id);
+        }
+        self.draw.flush();
+    }
+
+    /// Pick a cell (or close the grid) in response to a key press
+    pub(crate) fn handle_key(&mut self, key: KeyCode) -> PlacementInput {
+        let key = self
+            .keys
+            .as_ref()
+            .and_then(|keys| keys.get(&key.code))
+            .copied();
+        match (key, self.first) {
+            (Some(PlacementKey::Escape), _) => PlacementInput::Cancel,
+            (Some(PlacementKey::Return), Some(first)) => match self.cell(first) {
+                Some(r) => PlacementInput::Place(r),
+                None => PlacementInput::Continue,
+            },
+            (Some(PlacementKey::Label(c)), None) => {
+                if let Some(i) = self.cells.iter().position(|(l, _)| *l == c) {
+                    self.first = Some(c);
+                    self.draw_cell(i, true);
+                    self.draw.flush();
+                }
+                PlacementInput::Continue
+            }
+            (Some(PlacementKey::Label(c)), Some(first)) => match (self.cell(first), self.cell(c)) {
+                (Some(a), Some(b)) => PlacementInput::Place(a.union(&b)),
+                _ => PlacementInput::Continue,
+            },
+            // modifiers, unsupported keys and Return before a cell has been picked
+            _ => PlacementInput::Continue,
+        }
+    }
+
+    fn cell(&self, label: char) -> Option<Region> {
+        self.cells
+            .iter()
+            .find(|(l, _)| *l == label)
+            .map(|(_, r)| *r)
+    }
+
+    // Draw the cell at `index` with its label in the middle, highlighting it if it is selected
+    fn draw_cell(&mut self, index: usize, selected: bool) {
+        let (c, r) = self.cells[index];
+        let id = match self.cell_window(index, &r) {
+            Some(id) => id,
+            None => return,
+        };
+
+        let bg = if selected {
+            self.colors.highlight
+        } else {
+            self.colors.fg_1
+        };
+        let (_, _, w, h) = r.values();
+        let text = c.to_string();
+        let x = w.saturating_sub(self.draw.text_width(&text)) / 2;
+        self.draw.fill_rect(id, &Region::new(0, 0, w, h), bg);
+        // outline the cell so that neighbouring cells can be told apart
+        self.draw
+            .fill_rect(id, &Region::new(0, 0, w, 1), self.colors.fg_2);
+        self.draw
+            .fill_rect(id, &Region::new(0, 0, 1, h), self.colors.fg_2);
+        self.draw.text(id, x, h, &text, self.colors.fg_3, bg);
+    }
+
+    // Make sure that there is a window for the given cell, returning its id
+    fn cell_window(&mut self, index: usize, r: &Region) -> Option<WinId> {
+        if let Some(id) = self.windows.get(index) {
+            self.draw.show_window(*id, r);
+            return Some(*id);
+        }
+
+        match self.draw.new_window(r) {
+            Ok(id) => {
+                self.draw.set_opacity(id, self.opacity);
+                self.windows.push(id);
+                Some(id)
+            }
+            Err(e) => {
+                warn!("unable to create placement grid window: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // The text and background color last drawn in each window that is currently shown
+    type Drawn = Rc<RefCell<HashMap<WinId, (String, u32)>>>;
+
+    struct MockDraw {
+        drawn: Drawn,
+        next_id: WinId,
+    }
+
+    impl Draw for MockDraw {
+        fn new_window(&mut self, _: &Region) -> Result<WinId, String> {
+            self.next_id += 1;
+            Ok(self.next_id)
+        }
+        fn show_window(&mut self, _: WinId, _: &Region) {}
+        fn hide_window(&mut self, id: WinId) {
+            self.drawn.borrow_mut().remove(&id);
+        }
+        fn set_opacity(&mut self, _: WinId, _: f32) {}
+        fn fill_rect(&mut self, _: WinId, _: &Region, _: u32) {}
+        fn text(&mut self, id: WinId, _: u32, _: u32, s: &str, _: u32, bg: u32) {
+            self.drawn.borrow_mut().insert(id, (s.to_string(), bg));
+        }
+        fn text_width(&self, s: &str) -> u32 {
+            s.len() as u32
+        }
+        fn flush(&self) {}
+    }
+
+    fn codes() -> CodeMap {
+        let mut codes: CodeMap = "abcdefghijklmnopqrstuvwxyz"
+            .chars()
+            .enumerate()
+            .map(|(i, c)| (c.to_string(), 30 + i as u8))
+            .collect();
+        codes.insert("Escape".into(), 3);
+        codes.insert("Return".into(), 4);
+        codes
+    }
+
+    fn key(name: &str) -> KeyCode {
+        KeyCode {
+            mask: 0,
+            code: codes()[name],
+        }
+    }
+
+    fn test_grid(columns: u32, rows: u32) -> (PlacementGrid, Drawn) {
+        let drawn = Rc::new(RefCell::new(HashMap::new()));
+        let draw = MockDraw {
+            drawn: Rc::clone(&drawn),
+            next_id: 0,
+        };
+        let colors = ColorScheme {
+            bg: 0,
+            fg_1: 1,
+            fg_2: 2,
+            fg_3: 3,
+            highlight: 4,
+            urgent: 5,
+        };
+        let grid = PlacementGrid::new(draw, columns, rows, colors).with_key_codes(codes());
+        (grid, drawn)
+    }
+
+    #[test]
+    fn the_screen_is_split_into_labelled_cells() {
+        let (mut g, drawn) = test_grid(3, 2);
+        g.open(&Region::new(100, 0, 301, 200));
+
+        assert!(g.is_open());
+        assert_eq!(
+            g.cells(),
+            &[
+                ('a', Region::new(100, 0, 100, 100)),
+                ('s', Region::new(200, 0, 100, 100)),
+                ('d', Region::new(300, 0, 101, 100)),
+                ('f', Region::new(100, 100, 100, 100)),
+                ('g', Region::new(200, 100, 100, 100)),
+                ('h', Region::new(300, 100, 101, 100)),
+            ]
+        );
+        let mut labels: Vec<String> = drawn.borrow().values().map(|(s, _)| s.clone()).collect();
+        labels.sort();
+        assert_eq!(labels, vec!["a", "d", "f", "g", "h", "s"]);
+    }
+
+    #[test]
+    fn grids_are_limited_to_one_cell_per_label() {
+        let (mut g, _) = test_grid(10, 10);
+        g.open(&Region::new(0, 0, 1000, 1000));
+
+        assert_eq!(g.cells().len(), 20);
+        assert_eq!(g.cells()[19].0, 'z');
+    }
+
+    #[test]
+    fn one_label_and_return_picks_a_single_cell() {
+        let (mut g, drawn) = test_grid(2, 2);
+        g.open(&Region::new(0, 0, 200, 200));
+
+        assert_eq!(g.handle_key(key("Return")), PlacementInput::Continue);
+        assert_eq!(g.handle_key(key("s")), PlacementInput::Continue);
+        assert!(drawn.borrow().values().any(|(s, bg)| s == "s" && *bg == 4));
+        assert_eq!(
+            g.handle_key(key("Return")),
+            PlacementInput::Place(Region::new(100, 0, 100, 100))
+        );
+    }
+
+    #[test]
+    fn two_labels_pick_the_rectangle_between_them() {
+        let (mut g, _) = test_grid(3, 3);
+        g.open(&Region::new(0, 0, 300, 300));
+
+        // unknown labels are ignored
+        assert_eq!(g.handle_key(key("z")), PlacementInput::Continue);
+        assert_eq!(g.handle_key(key("k")), PlacementInput::Continue);
+        assert_eq!(
+            g.handle_key(key("d")),
+            PlacementInput::Place(Region::new(100, 0, 200, 300))
+        );
+    }
+
+    #[test]
+    fn escape_cancels_and_closing_hides_the_grid() {
+        let (mut g, drawn) = test_grid(2, 2);
+        g.open(&Region::new(0, 0, 200, 200));
+        g.handle_key(key("a"));
+
+        assert_eq!(g.handle_key(key("Escape")), PlacementInput::Cancel);
+        g.close();
+        assert!(!g.is_open());
+        assert!(g.cells().is_empty());
+        assert!(drawn.borrow().is_empty());
+
+        // a new selection is started each time the grid is opened
+        g.open(&Region::new(0, 0, 200, 200));
+        assert_eq!(g.handle_key(key("Return")), PlacementInput::Continue);
+    }
+}
@@ -0,0 +1,177 @@
+//! Rules for automatically placing new clients when they are first mapped
+use crate::data_types::{InsertPoint, Opacity};
+
+/**
+ * A Rule is matched against each new client when it is mapped and is used to override
+ * where and how the client is managed.
+ *
+ * All of the match fields that are set must match for the rule to apply and a rule
+ * with no match fields set will match every client. `class` is compared against both
+ * the instance and class parts of WM_CLASS, `title` matches if it is a substring of
+ * the window title and `role` is compared against WM_WINDOW_ROLE. When multiple rules
+ * match a client they are applied in order, so later rules take precedence.
+ */
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Rule {
+    /// Match on either part of WM_CLASS
+    pub class: Option<&'static str>,
+    /// Match on a substring of the window title
+    pub title: Option<&'static str>,
+    /// Match on WM_WINDOW_ROLE
+    pub role: Option<&'static str>,
+    /// Send matching clients to the workspace with this index
+    pub workspace: Option<usize>,
+    /// Pick a workspace for matching clients from a set of workspaces (overriding `workspace`)
+    pub assign: Option<Assignment>,
+    /// Force matching clients to be floating (or tiled)
+    pub floating: Option<bool>,
+    /// Make matching clients sticky (shown on every workspace)
+    pub sticky: Option<bool>,
+    /// Make matching clients follow whichever workspace is shown on their screen
+    pub follow: Option<bool>,
+    /// Where to insert matching clients in the workspace stack (overriding Config::insert_point)
+    pub position: Option<InsertPoint>,
+    /// Use these opacity levels for matching clients (overriding Config::opacity)
+    pub opacity: Option<Opacity>,
+}
+
+/// How to pick a workspace for clients matching a Rule from a set of candidate workspace indices
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Assignment {
+    /// Use each workspace in turn
+    RoundRobin(&'static [usize]),
+    /// Use whichever workspace has the fewest clients, preferring earlier workspaces on a tie
+    LeastPopulated(&'static [usize]),
+}
+
+impl Assignment {
+    /**
+     * Pick a workspace given the number of clients on each workspace and the number of clients
+     * that have already been assigned using this policy. Indices that are not valid for
+     * `populations` are skipped, returning None if there are no valid workspaces to pick from.
+     */
+    pub fn choose(&self, n_assigned: usize, populations: &[usize]) -> Option<usize> {
+        let (Assignment::RoundRobin(ixs) | Assignment::LeastPopulated(ixs)) = self;
+        let valid: Vec<usize> = ixs
+            .iter()
+            .copied()
+            .filter(|i| *i < populations.len())
+            .collect();
+        if valid.is_empty() {
+            return None;
+        }
+
+        match self {
+            Assignment::RoundRobin(_) => Some(valid[n_assigned % valid.len()]),
+            Assignment::LeastPopulated(_) => valid.into_iter().min_by_key(|i| populations[*i]),
+        }
+    }
+}
+
+/// When the program for a SpawnRule should be launched
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpawnTrigger {
+    /// As soon as the WindowManager starts
+    Startup,
+    /// The first time that the workspace is shown on a screen
+    FirstView,
+}
+
+/**
+ * A program to launch automatically, placing the windows it opens on a given workspace.
+ *
+ * Spawned programs are given a startup ID (via DESKTOP_STARTUP_ID) and the first window
+ * mapped with that ID in _NET_STARTUP_ID, or with the pid of the program in _NET_WM_PID,
+ * is sent to `workspace` in place of the active workspace. None of the programs are launched
+ * again when restarting in place.
+ */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SpawnRule {
+    /// The command to run
+    pub cmd: &'static str,
+    /// The index of the workspace that the program's window should be placed on
+    pub workspace: usize,
+    /// When the command should be run
+    pub trigger: SpawnTrigger,
+}
+
+impl SpawnRule {
+    /// Run `cmd` on startup, placing its window on `workspace`
+    pub fn on_startup(cmd: &'static str, workspace: usize) -> SpawnRule {
+        SpawnRule {
+            cmd,
+            workspace,
+            trigger: SpawnTrigger::Startup,
+        }
+    }
+
+    /// Run `cmd` the first time that `workspace` is viewed, placing its window there
+    pub fn on_first_view(cmd: &'static str, workspace: usize) -> SpawnRule {
+        SpawnRule {
+            cmd,
+            workspace,
+            trigger: SpawnTrigger::FirstView,
+        }
+    }
+}
+
+impl Rule {
+    /// Does this rule apply to a client with the given properties
+    pub fn matches(&self, wm_class: &[&str], title: &str, role: &str) -> bool {
+        self.class.is_none_or(|c| wm_class.contains(&c))
+            && self.title.is_none_or(|t| title.contains(t))
+            && self.role.is_none_or(|r| r == role)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_set_fields_must_match() {
+        let rule = Rule {
+            class: Some("st"),
+            title: Some("vim"),
+            ..Rule::default()
+        };
+
+        assert!(rule.matches(&["st-256color", "st"], "nvim: main.rs", ""));
+        assert!(!rule.matches(&["st-256color", "st"], "zsh", ""));
+        assert!(!rule.matches(&["xterm"], "vim", ""));
+    }
+
+    #[test]
+    fn empty_rules_match_everything() {
+        assert!(Rule::default().matches(&[], "", ""));
+    }
+
+    #[test]
+    fn role_must_match_exactly() {
+        let rule = Rule {
+            role: Some("pop-up"),
+            ..Rule::default()
+        };
+
+        assert!(rule.matches(&[], "", "pop-up"));
+        assert!(!rule.matches(&[], "", "pop-up-menu"));
+    }
+
+    #[test]
+    fn round_robin_skips_invalid_workspaces() {
+        let a = Assignment::RoundRobin(&[1, 7, 3]);
+        let picked: Vec<_> = (0..3).map(|n| a.choose(n, &[0, 0, 0, 0])).collect();
+
+        assert_eq!(picked, vec![Some(1), Some(3), Some(1)]);
+        assert_eq!(Assignment::RoundRobin(&[9]).choose(0, &[0]), None);
+    }
+
+    #[test]
+    fn least_populated_prefers_earlier_workspaces() {
+        let a = Assignment::LeastPopulated(&[2, 0, 1]);
+
+        assert_eq!(a.choose(0, &[1, 3, 1]), Some(2));
+        assert_eq!(a.choose(0, &[0, 3, 1]), Some(0));
+        assert_eq!(Assignment::LeastPopulated(&[]).choose(0, &[0]), None);
+    }
+}
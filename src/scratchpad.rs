@@ -0,0 +1,83 @@
+//! Named floating clients that can be toggled on top of any workspace
+use crate::client::Client;
+use crate::data_types::{Region, WinId};
+
+/**
+ * A Scratchpad is a program (e.g. a terminal) that is spawned on demand and then shown or
+ * hidden on top of whatever workspace is currently active with a single key binding.
+ *
+ * The first time a scratchpad is toggled, `prog` is run and the next window that is mapped
+ * with a WM_CLASS matching `class` is captured as the scratchpad client. Scratchpad clients
+ * are kept out of the normal workspace tiling and remember their position when hidden.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scratchpad {
+    /// The name used to toggle this scratchpad
+    pub name: &'static str,
+    /// The program to spawn when the scratchpad does not yet have a client
+    pub prog: &'static str,
+    /// The WM_CLASS used to identify the window belonging to this scratchpad
+    pub class: &'static str,
+    /// Initial width as a fraction of the screen width
+    pub w: f32,
+    /// Initial height as a fraction of the screen height
+    pub h: f32,
+}
+
+impl Scratchpad {
+    /// A new scratchpad that will initially be centered and cover 80% of the screen
+    pub fn new(name: &'static str, prog: &'static str, class: &'static str) -> Scratchpad {
+        Scratchpad {
+            name,
+            prog,
+            class,
+            w: 0.8,
+            h: 0.8,
+        }
+    }
+
+    /// The region this scratchpad should occupy on a screen before it has been moved
+    pub fn initial_region(&self, screen: &Region) -> Region {
+        let (sx, sy, sw, sh) = screen.values();
+        let w = (sw as f32 * self.w.clamp(0.0, 1.0)) as u32;
+        let h = (sh as f32 * self.h.clamp(0.0, 1.0)) as u32;
+
+        Region::new(sx + (sw - w) / 2, sy + (sh - h) / 2, w, h)
+    }
+}
+
+/// Runtime state for a configured Scratchpad, held by the WindowManager
+#[derive(Debug)]
+pub(crate) struct ScratchpadState {
+    pub(crate) conf: Scratchpad,
+    pub(crate) client: Option<Client>,
+    pub(crate) pending: bool,
+    pub(crate) visible: bool,
+}
+
+impl ScratchpadState {
+    pub(crate) fn new(conf: Scratchpad) -> ScratchpadState {
+        ScratchpadState {
+            conf,
+            client: None,
+            pending: false,
+            visible: false,
+        }
+    }
+
+    pub(crate) fn client_id(&self) -> Option<WinId> {
+        self.client.as_ref().map(|c| c.id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initial_region_is_centered() {
+        let s = Scratchpad::new("term", "st", "st");
+        let r = s.initial_region(&Region::new(100, 0, 1000, 500));
+        assert_eq!(r, Region::new(200, 50, 800, 400));
+    }
+}
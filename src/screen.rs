@@ -1,5 +1,6 @@
 //! Information on connected displays
-use crate::data_types::Region;
+use crate::data_types::{Region, ScreenChangePolicy, Strut};
+use crate::helpers::tag_bit;
 use xcb;
 use xcb::base::Reply;
 use xcb::ffi::randr::xcb_randr_get_crtc_info_reply_t;
@@ -15,9 +16,21 @@ pub struct Screen {
     pub effective_region: Region,
     /// The current workspace index being displayed
     pub wix: usize,
+    /// Additional workspaces being viewed (see helpers::tag_bit): their clients are shown too
+    pub view_mask: u64,
 }
 
 impl Screen {
+    /// Create a new Screen covering `region` that is showing the workspace at `wix`
+    pub fn new(region: Region, wix: usize) -> Screen {
+        Screen {
+            true_region: region,
+            effective_region: region,
+            wix,
+            view_mask: 0,
+        }
+    }
+
     /// Create a new Screen from information obtained from the X server
     pub fn from_crtc_info_reply(r: CRTCInfoReply, wix: usize) -> Screen {
         let region = Region::new(
@@ -27,11 +40,17 @@ impl Screen {
             r.height() as u32,
         );
 
-        Screen {
-            true_region: region,
-            effective_region: region,
-            wix,
-        }
+        Screen::new(region, wix)
+    }
+
+    /// The workspaces whose clients are shown on this screen as a bit mask
+    pub fn views(&self) -> u64 {
+        self.view_mask | tag_bit(self.wix)
+    }
+
+    /// Start (or stop) viewing the clients of the workspace at `index` on this screen
+    pub fn toggle_view(&mut self, index: usize) {
+        self.view_mask ^= tag_bit(index);
     }
 
     pub fn update_effective_region(&mut self, bar_height: u32, top_bar: bool) {
@@ -43,6 +62,16 @@ impl Screen {
         }
     }
 
+    /**
+     * The region of this screen that is available for clients once the space reserved by the
+     * given struts has been removed. `root` is the region covered by the X root window.
+     */
+    pub fn usable_region(&self, effective_only: bool, struts: &[Strut], root: &Region) -> Region {
+        struts
+            .iter()
+            .fold(*self.region(effective_only), |r, s| s.reserve(&r, root))
+    }
+
     pub fn region(&self, effective_only: bool) -> &Region {
         if effective_only {
             &self.effective_region
@@ -51,3 +80,64 @@ impl Screen {
         }
     }
 }
+
+/**
+ * Assign workspaces to a newly detected set of screens based on what was shown on the screens
+ * that we had before. Workspaces left without a screen go to any screens that are still
+ * unassigned, followed by workspaces that were not being shown at all. Returns the index of the
+ * screen that should now have focus.
+ */
+pub fn reassign_workspaces(
+    prev: &[Screen],
+    screens: &mut [Screen],
+    focused: usize,
+    n_workspaces: usize,
+    policy: ScreenChangePolicy,
+) -> usize {
+    let mut assigned: Vec<Option<usize>> = vec![None; screens.len()];
+    let mut used = vec![false; prev.len()];
+
+    for (i, s) in screens.iter().enumerate() {
+        let matching = match policy {
+            ScreenChangePolicy::ByIndex => Some(i).filter(|i| *i < prev.len()),
+            _ => (0..prev.len()).find(|j| !used[*j] && prev[*j].true_region == s.true_region),
+        };
+        if let Some(j) = matching {
+            assigned[i] = Some(j);
+            used[j] = true;
+        }
+    }
+
+    let focus_orphaned = focused < prev.len() && !used[focused];
+    if policy == ScreenChangePolicy::MoveToPrimary && focus_orphaned && !screens.is_empty() {
+        if let Some(j) = assigned[0] {
+            used[j] = false;
+        }
+        assigned[0] = Some(focused);
+        used[focused] = true;
+    }
+
+    let mut orphans = (0..prev.len()).filter(|j| !used[*j]);
+    let mut hidden = (0..n_workspaces).filter(|w| !prev.iter().any(|p| p.wix == *w));
+    for (i, s) in screens.iter_mut().enumerate() {
+        match assigned[i].or_else(|| orphans.next()) {
+            Some(j) => {
+                s.wix = prev[j].wix;
+                s.view_mask = prev[j].view_mask;
+            }
+            None => {
+                if let Some(wix) = hidden.next() {
+                    s.wix = wix;
+                }
+            }
+        }
+    }
+
+    match prev.get(focused) {
+        Some(p) => screens
+            .iter()
+            .position(|s| s.wix == p.wix)
+            .unwrap_or(if focused < screens.len() { focused } else { 0 }),
+        None => 0,
+    }
+}
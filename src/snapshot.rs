@@ -0,0 +1,117 @@
+/*!
+ * Read only snapshots of the state of a running WindowManager
+ *
+ * A Snapshot (see WindowManager::snapshot) is a plain copy of everything that hooks, status
+ * bars, scripts and tests tend to need to know about: which workspace is on which screen,
+ * the client stack for each workspace and where every client currently is. Unlike the
+ * WindowManagerState that is saved when restarting it is flattened into simple values so
+ * that it is easy to inspect and serializes to JSON that external programs can make sense
+ * of (it is returned by the `snapshot` IPC request).
+ */
+use crate::data_types::{Region, WinId};
+use serde::{Deserialize, Serialize};
+
+/// The state of a WindowManager at a single point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// All screens, in the order penrose knows them
+    pub screens: Vec<ScreenSnapshot>,
+    /// All workspaces, in index order
+    pub workspaces: Vec<WorkspaceSnapshot>,
+    /// All managed clients, sorted by ID
+    pub clients: Vec<ClientSnapshot>,
+    /// The index of the focused screen
+    pub focused_screen: usize,
+    /// The index of the workspace shown on the focused screen
+    pub focused_workspace: usize,
+    /// The ID of the focused client, if there is one
+    pub focused_client: Option<WinId>,
+    /// Whether or not do not disturb mode is enabled
+    pub dnd: bool,
+}
+
+/// A single screen
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScreenSnapshot {
+    /// The index of this screen
+    pub index: usize,
+    /// The full region of the screen
+    pub region: Region,
+    /// The region available for clients once any bar has been accounted for
+    pub effective_region: Region,
+    /// The index of the workspace being shown on this screen
+    pub workspace: usize,
+    /// Whether or not this screen is rotated
+    pub rotated: bool,
+}
+
+/// A single workspace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkspaceSnapshot {
+    /// The index of this workspace
+    pub index: usize,
+    /// The name of this workspace
+    pub name: String,
+    /// The symbol for the active layout, as it would be shown in a status bar
+    pub layout: String,
+    /// The tiled clients on this workspace, in stack order
+    pub clients: Vec<WinId>,
+    /// The focused client in the stack, if there is one
+    pub focused_client: Option<WinId>,
+    /// The screen this workspace is being shown on, if it is visible
+    pub screen: Option<usize>,
+    /// Whether or not any clients on this workspace are asking for attention
+    pub urgent: bool,
+    /// The minimized clients on this workspace, oldest first
+    pub minimized: Vec<WinId>,
+}
+
+/// A single client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientSnapshot {
+    /// The X window ID of this client
+    pub id: WinId,
+    /// The class part of WM_CLASS
+    pub class: String,
+    /// The window title
+    pub title: String,
+    /// The index of the workspace this client is on
+    pub workspace: usize,
+    /// The region this client was last positioned at (None if it has not been positioned)
+    pub region: Option<Region>,
+    /// Whether or not this client is currently mapped on a visible workspace
+    pub visible: bool,
+    /// Whether or not this client is floating
+    pub floating: bool,
+    /// Whether or not this client is fullscreen
+    pub fullscreen: bool,
+    /// Whether or not this client is shown on every workspace
+    pub sticky: bool,
+    /// Whether or not this client is asking for attention
+    pub urgent: bool,
+}
+
+impl Snapshot {
+    /// The snapshot of the client with the given ID
+    pub fn client(&self, id: WinId) -> Option<&ClientSnapshot> {
+        self.clients.iter().find(|c| c.id == id)
+    }
+
+    /// The clients on the workspace at `index`: tiled clients in stack order then floating
+    pub fn clients_on_workspace(&self, index: usize) -> Vec<&ClientSnapshot> {
+        let stack = match self.workspaces.get(index) {
+            Some(ws) => &ws.clients,
+            None => return vec![],
+        };
+
+        stack
+            .iter()
+            .filter_map(|id| self.client(*id))
+            .chain(
+                self.clients
+                    .iter()
+                    .filter(|c| c.workspace == index && !stack.contains(&c.id)),
+            )
+            .collect()
+    }
+}
@@ -0,0 +1,393 @@
+/*!
+ * Pluggable backends for persisting WindowManager state
+ *
+ * A StateStore is responsible for holding on to the state that penrose needs to carry over
+ * between processes: the full snapshot saved when restarting in place and (optionally) the
+ * append-only client journal used to recover from crashes. Two stores are provided:
+ *
+ *   - FileStore: keeps state in files on disk
+ *   - RootPropertyStore: keeps state in properties on the X root window so that it lives
+ *     exactly as long as the X session does
+ *
+ * Anything else (a dotfile manager, a network service...) can be used by implementing the
+ * trait and setting it as the `state_store` in your Config.
+ */
+use crate::data_types::WinId;
+use crate::errors::{PenroseError, Result};
+use crate::journal::{self, Entry, Journal, JournaledClient};
+use crate::manager::WindowManagerState;
+use crate::workspace::LayoutSettings;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use xcb;
+
+/**
+ * Somewhere to persist WindowManager state.
+ *
+ * Only `save_state` and `load_state` are required: stores that do not support journaling
+ * can rely on the default no-op implementations of `open_journal` and `record`.
+ */
+pub trait StateStore {
+    /// A name for this store to use in log messages. Defaults to the name of the type.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Persist a snapshot of the current state so that it can be restored after a restart
+    fn save_state(&mut self, state: &WindowManagerState) -> Result<()>;
+
+    /**
+     * Load and then clear the most recently saved snapshot (if there is one) so that it is
+     * only ever restored once.
+     */
+    fn load_state(&mut self) -> Result<Option<WindowManagerState>>;
+
+    /**
+     * Prepare the journal for recording new entries, returning the client state recovered
+     * from any existing entries.
+     */
+    fn open_journal(&mut self) -> Result<HashMap<WinId, JournaledClient>> {
+        Ok(HashMap::new())
+    }
+
+    /// Record a change to client state in the journal
+    fn record(&mut self, _entry: Entry) -> Result<()> {
+        Ok(())
+    }
+
+    /**
+     * Persist the layout settings of each workspace (keyed by workspace name) so that they
+     * can be picked up again by a new session. Unlike `save_state` this is kept across logins.
+     */
+    fn save_layout_settings(&mut self, _settings: &HashMap<String, LayoutSettings>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Load the layout settings saved by a previous session
+    fn load_layout_settings(&mut self) -> Result<HashMap<String, LayoutSettings>> {
+        Ok(HashMap::new())
+    }
+}
+
+impl fmt::Debug for dyn StateStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StateStore({})", self.name())
+    }
+}
+
+/// A StateStore that saves state as JSON and journals to a separate file
+#[derive(Debug)]
+pub struct FileStore {
+    state_path: PathBuf,
+    journal_path: Option<PathBuf>,
+    journal: Option<Journal>,
+    layouts_path: Option<PathBuf>,
+}
+
+impl FileStore {
+    /// Save state snapshots to `state_path`. Journaling is disabled.
+    pub fn new(state_path: impl Into<PathBuf>) -> FileStore {
+        FileStore {
+            state_path: state_path.into(),
+            journal_path: None,
+            journal: None,
+            layouts_path: None,
+        }
+    }
+
+    /// Journal client state changes to `path`
+    pub fn with_journal(mut self, path: impl Into<PathBuf>) -> FileStore {
+        self.journal_path = Some(path.into());
+        self
+    }
+
+    /// Save the layout settings of each workspace to `path` to carry them over to new sessions
+    pub fn with_layout_settings(mut self, path: impl Into<PathBuf>) -> FileStore {
+        self.layouts_path = Some(path.into());
+        self
+    }
+}
+
+impl StateStore for FileStore {
+    fn save_state(&mut self, state: &WindowManagerState) -> Result<()> {
+        let json = serde_json::to_string(state).map_err(store_error)?;
+        fs::write(&self.state_path, json)
+            .map_err(|e| store_error(format!("unable to write {:?}: {}", self.state_path, e)))
+    }
+
+    fn load_state(&mut self) -> Result<Option<WindowManagerState>> {
+        let contents = match fs::read_to_string(&self.state_path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                let msg = format!("unable to read {:?}: {}", self.state_path, e);
+                return Err(store_error(msg));
+            }
+        };
+        let _ = fs::remove_file(&self.state_path);
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(store_error)
+    }
+
+    fn open_journal(&mut self) -> Result<HashMap<WinId, JournaledClient>> {
+        match self.journal_path.as_ref() {
+            Some(path) => {
+                let (j, state) = Journal::open(path)?;
+                self.journal = Some(j);
+                Ok(state)
+            }
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    fn record(&mut self, entry: Entry) -> Result<()> {
+        match self.journal.as_mut() {
+            Some(j) => j.record(entry),
+            None => Ok(()),
+        }
+    }
+
+    fn save_layout_settings(&mut self, settings: &HashMap<String, LayoutSettings>) -> Result<()> {
+        let path = match self.layouts_path.as_ref() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let json = serde_json::to_string(settings).map_err(store_error)?;
+        fs::write(path, json).map_err(|e| store_error(format!("unable to write {:?}: {}", path, e)))
+    }
+
+    fn load_layout_settings(&mut self) -> Result<HashMap<String, LayoutSettings>> {
+        let path = match self.layouts_path.as_ref() {
+            Some(path) => path,
+            None => return Ok(HashMap::new()),
+        };
+        match fs::read_to_string(path) {
+            Ok(s) => serde_json::from_str(&s).map_err(store_error),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(store_error(format!("unable to read {:?}: {}", path, e))),
+        }
+    }
+}
+
+// Failures to read, write or (de)serialize state
+fn store_error(e: impl fmt::Display) -> PenroseError {
+    PenroseError::Store(e.to_string())
+}
+
+/**
+ * A StateStore that keeps state in properties on the X root window.
+ *
+ * Properties are held by the X server so state survives penrose restarting (or crashing) but
+ * is discarded along with everything else when the session ends. The store uses its own
+ * connection to the X server in the same way as XcbDraw.
+ */
+pub struct RootPropertyStore {
+    conn: xcb::Connection,
+    root: WinId,
+    state_atom: u32,
+    journal_atom: Option<u32>,
+}
+
+impl fmt::Debug for RootPropertyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RootPropertyStore")
+            .field("root", &self.root)
+            .field("journal", &self.journal_atom.is_some())
+            .finish()
+    }
+}
+
+// The root window properties used by RootPropertyStore
+const STATE_PROP: &str = "_PENROSE_STATE";
+const JOURNAL_PROP: &str = "_PENROSE_JOURNAL";
+
+impl RootPropertyStore {
+    /// Connect to the X server, optionally journaling client state changes as well
+    pub fn new(journal: bool) -> Result<RootPropertyStore> {
+        let (conn, _) = xcb::Connection::connect(None)
+            .map_err(|e| PenroseError::X(format!("unable to connect to X server: {}", e)))?;
+
+        let root = match conn.get_setup().roots().next() {
+            None => return Err(PenroseError::X("unable to get handle for screen".into())),
+            Some(s) => s.root(),
+        };
+
+        let intern = |name: &str| {
+            xcb::intern_atom(&conn, false, name)
+                .get_reply()
+                .map(|r| r.atom())
+                .map_err(|e| PenroseError::X(format!("unable to intern atom '{}': {}", name, e)))
+        };
+        let state_atom = intern(STATE_PROP)?;
+        let journal_atom = if journal {
+            Some(intern(JOURNAL_PROP)?)
+        } else {
+            None
+        };
+
+        Ok(RootPropertyStore {
+            conn,
+            root,
+            state_atom,
+            journal_atom,
+        })
+    }
+
+    // The current value of a root window property, if it is set
+    fn read(&self, atom: u32, delete: bool) -> Result<Option<String>> {
+        // xcb docs: https://www.mankier.com/3/xcb_get_property
+        let reply = xcb::get_property(
+            &self.conn,    // xcb connection to X11
+            delete,        // should the property be deleted
+            self.root,     // target window to query
+            atom,          // the property we want
+            xcb::ATOM_ANY, // the type of the property
+            0,             // offset in the property to retrieve data from
+            u32::MAX / 4,  // how many 32bit multiples of data to retrieve
+        )
+        .get_reply()
+        .map_err(|e| PenroseError::X(format!("unable to fetch root window property: {}", e)))?;
+
+        if reply.type_() == xcb::ATOM_NONE {
+            return Ok(None);
+        }
+        String::from_utf8(reply.value().to_vec())
+            .map(Some)
+            .map_err(|e| store_error(format!("invalid utf8 resonse from xcb: {}", e)))
+    }
+
+    fn write(&self, atom: u32, mode: u32, s: &str) {
+        xcb::change_property(
+            &self.conn,
+            mode as u8,
+            self.root,
+            atom,
+            xcb::ATOM_STRING,
+            8,
+            s.as_bytes(),
+        );
+        self.conn.flush();
+    }
+}
+
+impl StateStore for RootPropertyStore {
+    fn save_state(&mut self, state: &WindowManagerState) -> Result<()> {
+        let json = serde_json::to_string(state).map_err(store_error)?;
+        self.write(self.state_atom, xcb::PROP_MODE_REPLACE, &json);
+        Ok(())
+    }
+
+    fn load_state(&mut self) -> Result<Option<WindowManagerState>> {
+        match self.read(self.state_atom, true)? {
+            Some(s) => serde_json::from_str(&s).map(Some).map_err(store_error),
+            None => Ok(None),
+        }
+    }
+
+    fn open_journal(&mut self) -> Result<HashMap<WinId, JournaledClient>> {
+        let atom = match self.journal_atom {
+            Some(atom) => atom,
+            None => return Ok(HashMap::new()),
+        };
+
+        let entries: Vec<Entry> = self
+            .read(atom, false)?
+            .unwrap_or_default()
+            .lines()
+            .filter_map(Entry::from_line)
+            .collect();
+        let state = journal::replay(&entries);
+        self.write(atom, xcb::PROP_MODE_REPLACE, &journal::compact(&state));
+
+        Ok(state)
+    }
+
+    fn record(&mut self, entry: Entry) -> Result<()> {
+        if let Some(atom) = self.journal_atom {
+            self.write(atom, xcb::PROP_MODE_APPEND, &entry.to_line());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("penrose-store-{}-{}", name, std::process::id()))
+    }
+
+    fn empty_state() -> WindowManagerState {
+        WindowManagerState {
+            workspaces: vec![],
+            clients: vec![],
+            screen_workspaces: vec![0, 1],
+            focused_screen: 1,
+            previous_workspace: 0,
+        }
+    }
+
+    #[test]
+    fn file_store_state_is_only_loaded_once() {
+        let path = temp_path("state");
+        let mut store = FileStore::new(&path);
+        assert!(store.load_state().unwrap().is_none());
+
+        store.save_state(&empty_state()).unwrap();
+        let state = store.load_state().unwrap().unwrap();
+        assert_eq!(state.screen_workspaces, vec![0, 1]);
+        assert_eq!(state.focused_screen, 1);
+        assert!(store.load_state().unwrap().is_none());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn file_store_journals_when_enabled() {
+        let path = temp_path("journal");
+        let _ = fs::remove_file(&path);
+
+        let mut store = FileStore::new(temp_path("unused"));
+        assert!(store.open_journal().unwrap().is_empty());
+        store.record(Entry::Workspace(1, 2)).unwrap();
+        assert!(!path.exists());
+
+        let mut store = FileStore::new(temp_path("unused")).with_journal(&path);
+        assert!(store.open_journal().unwrap().is_empty());
+        store.record(Entry::Workspace(1, 2)).unwrap();
+        let recovered = store.open_journal().unwrap();
+        assert_eq!(recovered[&1].workspace, 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn file_store_layout_settings_persist_when_enabled() {
+        let path = temp_path("layouts");
+        let _ = fs::remove_file(&path);
+        let mut settings = HashMap::new();
+        settings.insert(
+            "web".to_string(),
+            LayoutSettings {
+                layout: 1,
+                max_main: 2,
+                ratio: 0.7,
+            },
+        );
+
+        let mut store = FileStore::new(temp_path("unused"));
+        store.save_layout_settings(&settings).unwrap();
+        assert!(store.load_layout_settings().unwrap().is_empty());
+
+        let mut store = FileStore::new(temp_path("unused")).with_layout_settings(&path);
+        assert!(store.load_layout_settings().unwrap().is_empty());
+        store.save_layout_settings(&settings).unwrap();
+        assert_eq!(store.load_layout_settings().unwrap(), settings);
+        // settings are kept for the next session
+        assert_eq!(store.load_layout_settings().unwrap(), settings);
+        fs::remove_file(&path).unwrap();
+    }
+}
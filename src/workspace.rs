@@ -1,9 +1,26 @@
 //! A Workspace is a set of displayed clients and a set of Layouts for arranging them
 use crate::client::Client;
-use crate::data_types::{Change, Direction, Region, ResizeAction, Ring, WinId};
-use crate::layout::{Layout, LayoutConf};
+use crate::data_types::{Change, Direction, InsertPoint, Region, ResizeAction, Ring, WinId};
+use crate::errors::{PenroseError, Result};
+use crate::layout::{Layout, LayoutConf, Slot};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
 use std::collections::HashMap;
 
+/**
+ * The layout settings for a Workspace that are carried over between sessions: which layout
+ * is active along with the number of clients in its main area and the size of that area.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LayoutSettings {
+    /// The index of the active layout
+    pub layout: usize,
+    /// The max_main of the active layout
+    pub max_main: u32,
+    /// The ratio of the active layout
+    pub ratio: f32,
+}
+
 /**
  * A Workspace represents a named set of clients that are tiled according
  * to a specific layout. Layout properties are tracked per workspace and
@@ -13,25 +30,85 @@ use std::collections::HashMap;
  * The parent WindowManager struct tracks which client is focused from the
  * point of view of the X server by checking focus at the Workspace level
  * whenever a new Workspace becomes active.
+ *
+ * At most one client per workspace can be fullscreen at a time: the region it
+ * occupied before going fullscreen is held on to so that it can be restored.
  */
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Workspace {
-    name: &'static str,
+    name: String,
     clients: Ring<WinId>,
     layouts: Ring<Layout>,
+    fullscreen: Option<(WinId, Region)>,
+    bar_hidden: bool,
 }
 
 impl Workspace {
-    pub fn new(name: &'static str, layouts: Vec<Layout>) -> Workspace {
-        if layouts.len() == 0 {
-            panic!("{}: require at least one layout function", name);
+    /// Create a new, empty Workspace. At least one layout must be provided.
+    pub fn new(name: impl Into<String>, layouts: Vec<Layout>) -> Result<Workspace> {
+        let name = name.into();
+        if layouts.is_empty() {
+            return Err(PenroseError::NoLayouts(name));
         }
 
-        Workspace {
+        Ok(Workspace {
             name,
             clients: Ring::new(Vec::new()),
             layouts: Ring::new(layouts),
+            fullscreen: None,
+            bar_hidden: false,
+        })
+    }
+
+    /**
+     * Take on the name, clients and layout settings of a Workspace that was saved before a
+     * restart. Only clients matching `keep` are retained. Layout functions are not saved so
+     * settings are copied over to the layouts of this workspace with the same symbol.
+     */
+    pub fn restore(&mut self, saved: &Workspace, keep: impl Fn(&WinId) -> bool) {
+        self.name = saved.name.clone();
+        self.clients = Ring::new(
+            saved
+                .clients
+                .iter()
+                .copied()
+                .filter(|id| keep(id))
+                .collect(),
+        );
+        if let Some(id) = saved.focused_client() {
+            self.clients.focus_by(|c| *c == id);
+        }
+        self.fullscreen = saved.fullscreen.filter(|(id, _)| keep(id));
+
+        for i in 0..self.layouts.len() {
+            let symbol = &self.layouts[i].symbol;
+            if let Some(l) = saved.layouts.iter().find(|l| &l.symbol == symbol) {
+                self.layouts[i].restore_settings(l);
+            }
         }
+        if let Some(active) = saved.layouts.focused() {
+            self.layouts.focus_by(|l| l.symbol == active.symbol);
+        }
+    }
+
+    /// The name of this workspace
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Give this workspace a new name
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+
+    /// Whether the status bar should be hidden while this workspace is shown
+    pub fn bar_hidden(&self) -> bool {
+        self.bar_hidden
+    }
+
+    /// Hide (or show) the status bar while this workspace is shown
+    pub fn set_bar_hidden(&mut self, hidden: bool) {
+        self.bar_hidden = hidden;
     }
 
     /// The number of clients currently on this workspace
@@ -39,29 +116,55 @@ impl Workspace {
         self.clients.len()
     }
 
+    /// Whether or not this workspace currently has any clients
+    pub fn is_empty(&self) -> bool {
+        self.clients.len() == 0
+    }
+
     /// Iterate over the clients on this workspace in position order
-    pub fn iter(&self) -> std::collections::vec_deque::Iter<WinId> {
+    pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, WinId> {
         self.clients.iter()
     }
 
     /// A reference to the currently focused client if there is one
     pub fn focused_client(&self) -> Option<WinId> {
-        self.clients.focused().map(|c| *c)
+        self.clients.focused().copied()
     }
 
-    /// Add a new client to this workspace at the top of the stack and focus it
-    pub fn add_client(&mut self, id: WinId) {
-        self.clients.insert(0, id);
+    /**
+     * Add a new client to this workspace at the given point in the stack. Focus remains
+     * with the currently focused client (if there is one).
+     */
+    pub fn add_client(&mut self, id: WinId, insert_point: InsertPoint) {
+        let focused = self.clients.focused_index();
+        let index = match (insert_point, focused) {
+            (InsertPoint::First, _) | (_, None) => 0,
+            (InsertPoint::Last, _) => self.clients.len(),
+            (InsertPoint::AfterFocused, Some(i)) => i + 1,
+            (InsertPoint::BeforeFocused, Some(i)) => i,
+        };
+
+        let prev = self.focused_client();
+        self.clients.insert(index, id);
+        if let Some(prev) = prev {
+            self.clients.focus_by(|c| *c == prev);
+        }
+    }
+
+    /// Add a new client to this workspace at the bottom of the stack
+    pub fn append_client(&mut self, id: WinId) {
+        self.clients.insert(self.clients.len(), id);
+    }
+
+    /// Insert a client at the given position in the stack, appending if `index` is too large
+    pub fn insert_client(&mut self, index: usize, id: WinId) {
+        self.clients.insert(index.min(self.clients.len()), id);
     }
 
     /// Focus the client with the given id, returns an option of the previously focused
     /// client if there was one
     pub fn focus_client(&mut self, id: WinId) -> Option<WinId> {
-        if self.clients.len() == 0 {
-            return None;
-        }
-
-        let prev = self.clients.focused().unwrap().clone();
+        let prev = *self.clients.focused()?;
         self.clients.focus_by(|c| c == &id);
         Some(prev)
     }
@@ -69,58 +172,227 @@ impl Workspace {
     /// Remove a target client, retaining focus at the same position in the stack.
     /// Returns the removed client if there was one to remove.
     pub fn remove_client(&mut self, id: WinId) -> Option<WinId> {
+        if self.fullscreen_client() == Some(id) {
+            self.fullscreen = None;
+        }
         self.clients.remove_by(|c| c == &id)
     }
 
+    /**
+     * Replace the client `old` with `new`, keeping its position in the stack and focus if it
+     * had it. Returns false if `old` is not on this workspace.
+     */
+    pub fn replace_client(&mut self, old: WinId, new: WinId) -> bool {
+        match self.clients.iter().position(|c| *c == old) {
+            Some(i) => {
+                self.clients[i] = new;
+                if self.fullscreen_client() == Some(old) {
+                    self.fullscreen = None;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Remove the currently focused client, keeping focus at the same position in the stack.
     /// Returns the removed client if there was one to remove.
     pub fn remove_focused_client(&mut self) -> Option<WinId> {
-        self.clients.remove_focused()
+        let removed = self.clients.remove_focused();
+        if removed.is_some() && removed == self.fullscreen_client() {
+            self.fullscreen = None;
+        }
+        removed
+    }
+
+    /// The ID of the client currently fullscreen on this workspace if there is one
+    pub fn fullscreen_client(&self) -> Option<WinId> {
+        self.fullscreen.map(|(id, _)| id)
+    }
+
+    /**
+     * Mark a client as being fullscreen on this workspace, remembering the region that it
+     * occupied beforehand. Returns the previous fullscreen client and its original region
+     * if there was one.
+     */
+    pub fn set_fullscreen(&mut self, id: WinId, prev: Region) -> Option<(WinId, Region)> {
+        self.fullscreen.replace((id, prev))
+    }
+
+    /// Clear the fullscreen client for this workspace, returning it along with the region it
+    /// occupied before it went fullscreen.
+    pub fn clear_fullscreen(&mut self) -> Option<(WinId, Region)> {
+        self.fullscreen.take()
     }
 
-    /// Run the current layout function, generating a list of resize actions to be
-    /// applied byt the window manager.
+    // Only clients in the ring are tiled and the fullscreen client is positioned separately
+    fn tiled_clients<'a>(&self, client_map: &'a HashMap<WinId, Client>) -> Result<Vec<&'a Client>> {
+        let fullscreen = self.fullscreen_client();
+        self.clients
+            .iter()
+            .filter(|id| Some(**id) != fullscreen)
+            .map(|id| client_map.get(id).ok_or(PenroseError::UnknownClient(*id)))
+            .collect()
+    }
+
+    // Smart borders drop gaps and borders when there is only a single tiled client
+    fn smart_borders_active(&self, client_map: &HashMap<WinId, Client>) -> bool {
+        self.layout_conf().is_ok_and(|c| c.smart_borders)
+            && self
+                .tiled_clients(client_map)
+                .is_ok_and(|clients| clients.len() == 1)
+    }
+
+    /// Whether or not any of the clients on this workspace are asking for attention
+    pub fn has_urgent(&self, client_map: &HashMap<WinId, Client>) -> bool {
+        self.clients
+            .iter()
+            .any(|id| client_map.get(id).is_some_and(|c| c.is_urgent()))
+    }
+
+    /**
+     * The border width that should be used for tiled clients given the configured width.
+     * This will be 0 if the current layout uses smart borders and there is only one client.
+     */
+    pub fn border_px(&self, border_px: u32, client_map: &HashMap<WinId, Client>) -> u32 {
+        if self.smart_borders_active(client_map) {
+            0
+        } else {
+            border_px
+        }
+    }
+
+    /**
+     * Run the current layout function, generating a list of resize actions to be applied by the
+     * window manager. Fullscreen clients are not passed to the layout. The outer gap is removed
+     * from the screen region before running the layout and each client region is then shrunk
+     * to leave space for the inner gap and borders. `gap_px` is used as the inner gap if the
+     * current layout does not set one.
+     */
     pub fn arrange(
         &self,
         screen_region: &Region,
         client_map: &HashMap<WinId, Client>,
-    ) -> Vec<ResizeAction> {
-        if self.clients.len() > 0 {
-            let layout = self.layouts.focused().unwrap();
-            let clients: Vec<&Client> = self
-                .clients
-                .iter()
-                .map(|id| client_map.get(id).unwrap())
-                .collect();
-            debug!(
-                "applying '{}' layout for {} clients on workspace '{}'",
-                layout.symbol,
-                self.clients.len(),
-                self.name
-            );
-            layout.arrange(&clients, self.focused_client(), screen_region)
-        } else {
-            vec![]
+        gap_px: u32,
+        border_px: u32,
+    ) -> Result<Vec<ResizeAction>> {
+        self.arrange_with(&[], screen_region, client_map, gap_px, border_px)
+    }
+
+    /**
+     * Run the current layout function as with `arrange`, including clients from other
+     * workspaces after those from this workspace. This is used to lay out the union of the
+     * clients being viewed on a screen when tags are in use.
+     */
+    pub fn arrange_with(
+        &self,
+        extra: &[WinId],
+        screen_region: &Region,
+        client_map: &HashMap<WinId, Client>,
+        gap_px: u32,
+        border_px: u32,
+    ) -> Result<Vec<ResizeAction>> {
+        let mut clients = self.tiled_clients(client_map)?;
+        clients.extend(extra.iter().filter_map(|id| client_map.get(id)));
+        if clients.is_empty() {
+            return Ok(vec![]);
         }
+
+        let layout = self.active_layout()?;
+        debug!(
+            "applying '{}' layout for {} clients on workspace '{}'",
+            layout.symbol,
+            clients.len(),
+            self.name
+        );
+
+        let (inner, outer) = if self.smart_borders_active(client_map) {
+            (0, 0)
+        } else {
+            layout.conf.gaps(gap_px)
+        };
+        let border = self.border_px(border_px, client_map);
+        let (x, y, w, h) = screen_region.values();
+        let r = Region::new(
+            x + outer,
+            y + outer,
+            w.saturating_sub(2 * outer),
+            h.saturating_sub(2 * outer),
+        );
+        let padding = 2 * (border + inner);
+
+        let actions = layout
+            .arrange(&clients, self.focused_client(), &r)
+            .into_iter()
+            .map(|(id, region)| {
+                let (x, y, w, h) = region.values();
+                let r = Region::new(
+                    x + inner,
+                    y + inner,
+                    w.saturating_sub(padding),
+                    h.saturating_sub(padding),
+                );
+                match client_map.get(&id).and_then(|c| c.size_hints()) {
+                    Some(hints) if layout.conf.respect_resize_hints => (id, hints.apply(&r)),
+                    _ => (id, r),
+                }
+            })
+            .collect();
+
+        Ok(actions)
     }
 
     /// Cycle through the available layouts on this workspace
-    pub fn cycle_layout(&mut self, direction: Direction) -> &str {
+    pub fn cycle_layout(&mut self, direction: Direction) -> Result<&str> {
         self.layouts.cycle_focus(direction);
         self.layout_symbol()
     }
 
+    /**
+     * Switch to using `layout`, replacing any existing layout with the same symbol. Layouts
+     * with a new symbol are added after the current layout so they are included when cycling.
+     */
+    pub fn set_layout(&mut self, layout: Layout) {
+        let symbol = layout.symbol.clone();
+        match self.layouts.iter().position(|l| l.symbol == symbol) {
+            Some(i) => self.layouts[i] = layout,
+            None => {
+                let index = self.layouts.focused_index().map_or(0, |i| i + 1);
+                self.layouts.insert(index, layout);
+            }
+        }
+        self.layouts.focus_by(|l| l.symbol == symbol);
+    }
+
+    /// Switch to the layout with the given symbol, returning false if there is no such layout
+    pub fn focus_layout(&mut self, symbol: &str) -> bool {
+        self.layouts.focus_by(|l| l.symbol == symbol).is_some()
+    }
+
+    // Workspaces are created with at least one layout but those deserialized from saved
+    // state are not checked in the same way
+    fn active_layout(&self) -> Result<&Layout> {
+        self.layouts
+            .focused()
+            .ok_or_else(|| PenroseError::NoLayouts(self.name.clone()))
+    }
+
     /// The symbol of the currently used layout (passed on creation)
-    pub fn layout_symbol(&self) -> &str {
-        self.layouts.focused().unwrap().symbol
+    pub fn layout_symbol(&self) -> Result<&str> {
+        self.active_layout().map(|l| l.symbol.as_str())
+    }
+
+    /// The symbol of the current layout for display when `n_hidden` clients are not shown
+    pub fn display_layout_symbol(&self, n_hidden: usize) -> Result<String> {
+        self.active_layout().map(|l| l.display_symbol(n_hidden))
     }
 
     /**
      * The LayoutConf of the currently active Layout. Used by the WindowManager to
      * determine when and how the layout function should be applied.
      */
-    pub fn layout_conf(&self) -> LayoutConf {
-        self.layouts.focused().unwrap().conf
+    pub fn layout_conf(&self) -> Result<LayoutConf> {
+        self.active_layout().map(|l| l.conf)
     }
 
     /// Cycle focus through the clients on this workspace
@@ -128,7 +400,8 @@ impl Workspace {
         if self.clients.len() < 2 {
             return None; // need at least two clients to cycle
         }
-        if self.layout_conf().follow_focus && self.clients.would_wrap(direction) {
+        let follow_focus = self.layout_conf().is_ok_and(|c| c.follow_focus);
+        if follow_focus && self.clients.would_wrap(direction) {
             return None; // When following focus, don't allow wrapping focus
         }
 
@@ -146,10 +419,71 @@ impl Workspace {
      * Drag the focused client through the stack, retaining focus
      */
     pub fn drag_client(&mut self, direction: Direction) -> Option<WinId> {
-        if self.layout_conf().follow_focus && self.clients.would_wrap(direction) {
+        let follow_focus = self.layout_conf().is_ok_and(|c| c.follow_focus);
+        if follow_focus && self.clients.would_wrap(direction) {
             return None; // When following focus, don't allow wrapping focus
         }
-        self.clients.drag_focused(direction).map(|c| *c)
+        self.clients.drag_focused(direction).copied()
+    }
+
+    /// Swap the stack positions of the focused client and the given client, retaining focus.
+    /// Returns false if the client is not on this workspace.
+    pub fn swap_focused_with(&mut self, id: WinId) -> bool {
+        self.clients.swap_focused_with(|c| c == &id).is_some()
+    }
+
+    /// Shift every client one position through the stack (wrapping at the ends), retaining focus
+    pub fn rotate_clients(&mut self, direction: Direction) {
+        if let Some(id) = self.focused_client() {
+            self.clients.rotate(direction);
+            self.clients.focus_by(|c| c == &id);
+        }
+    }
+
+    /**
+     * Swap the focused client with the client in the main position, retaining focus. If the
+     * focused client is already the main client it is swapped with the next client instead.
+     * Returns false if there are fewer than two clients.
+     */
+    pub fn swap_focused_with_main(&mut self) -> bool {
+        if self.clients.len() < 2 {
+            return false;
+        }
+        let other = match self.clients.focused_index() {
+            Some(0) => self.clients[1],
+            _ => self.clients[0],
+        };
+        self.swap_focused_with(other)
+    }
+
+    /// Reverse the order of the stack, retaining focus
+    pub fn reverse_clients(&mut self) {
+        self.clients.reverse();
+    }
+
+    /// The index of the active layout along with its max_main and ratio
+    pub fn layout_settings(&self) -> Result<LayoutSettings> {
+        let layout = self.active_layout()?;
+        Ok(LayoutSettings {
+            layout: self.layouts.focused_index().unwrap_or(0),
+            max_main: layout.max_main(),
+            ratio: layout.ratio(),
+        })
+    }
+
+    /**
+     * Switch to the layout at the index given in `settings` and update its max_main and ratio.
+     * Settings for a layout index that no longer exists are ignored.
+     */
+    pub fn apply_layout_settings(&mut self, settings: &LayoutSettings) {
+        let symbol = match self.layouts.iter().nth(settings.layout) {
+            Some(l) => l.symbol.clone(),
+            None => return,
+        };
+        self.layouts.focus_by(|l| l.symbol == symbol);
+        if let Some(layout) = self.layouts.focused_mut() {
+            layout.set_main(settings.max_main, settings.ratio);
+        }
     }
 
     pub fn update_max_main(&mut self, change: Change) {
@@ -163,12 +497,38 @@ impl Workspace {
             layout.update_main_ratio(change, step);
         }
     }
+
+    pub fn set_layout_slots(&mut self, slots: Vec<Slot>) {
+        if let Some(layout) = self.layouts.focused_mut() {
+            layout.set_slots(slots);
+        }
+    }
+
+    /// Pass a message to the active layout, returning whether or not it was handled
+    pub fn handle_layout_message(&mut self, msg: &dyn Any) -> bool {
+        self.layouts
+            .focused_mut()
+            .is_some_and(|layout| layout.handle_message(msg))
+    }
+
+    pub fn update_gaps(&mut self, change: Change, step: u32, gap_px: u32) {
+        if let Some(layout) = self.layouts.focused_mut() {
+            let current = layout.conf.gaps(gap_px);
+            layout.update_gaps(change, step, current);
+        }
+    }
+
+    pub fn update_slot_width(&mut self, index: usize, change: Change, step: u32) {
+        if let Some(layout) = self.layouts.focused_mut() {
+            layout.update_slot_width(index, change, step);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::data_types::Direction;
+    use crate::data_types::{Direction, SizeHints};
     use crate::layout::*;
 
     fn test_layouts() -> Vec<Layout> {
@@ -178,19 +538,19 @@ mod tests {
     fn add_n_clients(ws: &mut Workspace, n: usize) {
         for i in 0..n {
             let k = ((i + 1) * 10) as u32; // ensure win_id != index
-            ws.add_client(k);
+            ws.add_client(k, InsertPoint::First);
         }
     }
 
     #[test]
     fn ref_to_focused_client_when_empty() {
-        let ws = Workspace::new("test", test_layouts());
+        let ws = Workspace::new("test", test_layouts()).unwrap();
         assert_eq!(ws.focused_client(), None);
     }
 
     #[test]
     fn ref_to_focused_client_when_populated() {
-        let mut ws = Workspace::new("test", test_layouts());
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
         ws.clients = Ring::new(vec![42, 123]);
 
         let c = ws.focused_client().expect("should have had a client for 0");
@@ -203,7 +563,7 @@ mod tests {
 
     #[test]
     fn removing_a_client_when_present() {
-        let mut ws = Workspace::new("test", test_layouts());
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
         ws.clients = Ring::new(vec![13, 42]);
 
         let removed = ws
@@ -214,7 +574,7 @@ mod tests {
 
     #[test]
     fn removing_a_client_when_not_present() {
-        let mut ws = Workspace::new("test", test_layouts());
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
         ws.clients = Ring::new(vec![13]);
 
         let removed = ws.remove_client(42);
@@ -223,28 +583,169 @@ mod tests {
 
     #[test]
     fn adding_a_client() {
-        let mut ws = Workspace::new("test", test_layouts());
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
         add_n_clients(&mut ws, 3);
-        let ids: Vec<WinId> = ws.clients.iter().map(|c| *c).collect();
+        let ids: Vec<WinId> = ws.clients.iter().copied().collect();
         assert_eq!(ids, vec![30, 20, 10], "not pushing at the top of the stack")
     }
 
+    #[test]
+    fn adding_a_client_at_each_insert_point() {
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
+        ws.clients = Ring::new(vec![1, 2, 3]);
+        ws.focus_client(2);
+
+        ws.add_client(4, InsertPoint::AfterFocused);
+        ws.add_client(5, InsertPoint::BeforeFocused);
+        ws.add_client(6, InsertPoint::Last);
+        ws.add_client(7, InsertPoint::First);
+
+        assert_eq!(ws.clients.as_vec(), vec![7, 1, 5, 2, 4, 3, 6]);
+        assert_eq!(ws.focused_client(), Some(2));
+    }
+
     #[test]
     fn applying_a_layout_gives_one_action_per_client() {
-        let mut ws = Workspace::new("test", test_layouts());
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
         ws.clients = Ring::new(vec![1, 2, 3]);
         let client_map = map! {
             1 => Client::new(1, "".into(), 1, false),
             2 => Client::new(2, "".into(), 1, false),
             3 => Client::new(3, "".into(), 1, false),
         };
-        let actions = ws.arrange(&Region::new(0, 0, 2000, 1000), &client_map);
+        let actions = ws
+            .arrange(&Region::new(0, 0, 2000, 1000), &client_map, 0, 0)
+            .unwrap();
         assert_eq!(actions.len(), 3, "actions are not 1-1 for clients")
     }
 
+    #[test]
+    fn workspaces_need_at_least_one_layout() {
+        assert_eq!(
+            Workspace::new("empty", vec![]).unwrap_err(),
+            PenroseError::NoLayouts("empty".into())
+        );
+    }
+
+    #[test]
+    fn arranging_unknown_clients_is_an_error() {
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
+        ws.clients = Ring::new(vec![1, 2]);
+        let client_map = map! { 1 => Client::new(1, "".into(), 1, false), };
+        let res = ws.arrange(&Region::new(0, 0, 2000, 1000), &client_map, 0, 0);
+
+        assert_eq!(res, Err(PenroseError::UnknownClient(2)));
+    }
+
+    #[test]
+    fn arrange_skips_the_fullscreen_client() {
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
+        ws.clients = Ring::new(vec![1, 2, 3]);
+        let client_map = map! {
+            1 => Client::new(1, "".into(), 1, false),
+            2 => Client::new(2, "".into(), 1, false),
+            3 => Client::new(3, "".into(), 1, false),
+        };
+        ws.set_fullscreen(2, Region::new(0, 0, 100, 100));
+        let actions = ws
+            .arrange(&Region::new(0, 0, 2000, 1000), &client_map, 0, 0)
+            .unwrap();
+        let ids: Vec<WinId> = actions.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![1, 3]);
+
+        ws.clear_fullscreen();
+        let actions = ws
+            .arrange(&Region::new(0, 0, 2000, 1000), &client_map, 0, 0)
+            .unwrap();
+        assert_eq!(actions.len(), 3);
+    }
+
+    #[test]
+    fn arrange_applies_gaps_and_borders() {
+        let mut layouts = test_layouts();
+        layouts[0].conf.outer_gap_px = Some(10);
+        let mut ws = Workspace::new("test", layouts).unwrap();
+        ws.clients = Ring::new(vec![1, 2]);
+        let client_map = map! {
+            1 => Client::new(1, "".into(), 1, false),
+            2 => Client::new(2, "".into(), 1, false),
+        };
+
+        // mock_layout offsets the nth client by n in each direction
+        let actions = ws
+            .arrange(&Region::new(0, 0, 1000, 500), &client_map, 5, 2)
+            .unwrap();
+        assert_eq!(
+            actions,
+            vec![
+                (1, Region::new(15, 15, 966, 466)),
+                (2, Region::new(16, 16, 965, 465))
+            ]
+        );
+        assert_eq!(ws.border_px(2, &client_map), 2);
+    }
+
+    #[test]
+    fn smart_borders_drop_gaps_for_a_single_client() {
+        let mut layouts = test_layouts();
+        layouts[0].conf.smart_borders = true;
+        let mut ws = Workspace::new("test", layouts).unwrap();
+        ws.clients = Ring::new(vec![1]);
+        let client_map = map! { 1 => Client::new(1, "".into(), 1, false), };
+
+        let actions = ws
+            .arrange(&Region::new(0, 0, 1000, 500), &client_map, 5, 2)
+            .unwrap();
+        assert_eq!(actions, vec![(1, Region::new(0, 0, 1000, 500))]);
+        assert_eq!(ws.border_px(2, &client_map), 0);
+    }
+
+    #[test]
+    fn resize_hints_are_only_respected_when_requested() {
+        let mut layouts = test_layouts();
+        layouts.push(Layout::new(
+            "h",
+            LayoutConf {
+                respect_resize_hints: true,
+                ..LayoutConf::default()
+            },
+            mock_layout,
+            1,
+            0.6,
+        ));
+        let mut ws = Workspace::new("test", layouts).unwrap();
+        ws.clients = Ring::new(vec![1]);
+        let mut client = Client::new(1, "".into(), 1, false);
+        client.set_size_hints(Some(SizeHints {
+            max: Some((300, 200)),
+            ..SizeHints::default()
+        }));
+        let client_map = map! { 1 => client, };
+        let r = Region::new(0, 0, 1000, 500);
+
+        assert_eq!(
+            ws.arrange(&r, &client_map, 0, 0).unwrap(),
+            vec![(1, Region::new(0, 0, 1000, 500))]
+        );
+        ws.cycle_layout(Direction::Forward).unwrap();
+        assert_eq!(
+            ws.arrange(&r, &client_map, 0, 0).unwrap(),
+            vec![(1, Region::new(350, 150, 300, 200))]
+        );
+    }
+
+    #[test]
+    fn removing_the_fullscreen_client_clears_fullscreen() {
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
+        ws.clients = Ring::new(vec![1, 2]);
+        ws.set_fullscreen(2, Region::new(0, 0, 100, 100));
+        ws.remove_client(2);
+        assert_eq!(ws.fullscreen_client(), None);
+    }
+
     #[test]
     fn dragging_a_client_forward() {
-        let mut ws = Workspace::new("test", test_layouts());
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
         ws.clients = Ring::new(vec![1, 2, 3, 4]);
         assert_eq!(ws.focused_client(), Some(1));
 
@@ -265,7 +766,7 @@ mod tests {
 
     #[test]
     fn dragging_non_index_0_client_backward() {
-        let mut ws = Workspace::new("test", test_layouts());
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
         ws.clients = Ring::new(vec![1, 2, 3, 4]);
         ws.focus_client(3);
         assert_eq!(ws.focused_client(), Some(3));
@@ -284,4 +785,127 @@ mod tests {
 
         assert_eq!(ws.focused_client(), Some(3));
     }
+
+    #[test]
+    fn setting_a_layout_at_runtime() {
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
+        let n_layouts = ws.layouts.len();
+        ws.set_layout(Layout::new(
+            "new",
+            LayoutConf::default(),
+            mock_layout,
+            1,
+            0.6,
+        ));
+        assert_eq!(ws.layout_symbol().unwrap(), "new");
+        assert_eq!(ws.layouts.len(), n_layouts + 1);
+        assert_eq!(ws.layouts.focused_index(), Some(1));
+
+        // replacing an existing layout keeps the number of layouts the same
+        let conf = LayoutConf {
+            gapless: true,
+            ..LayoutConf::default()
+        };
+        ws.set_layout(Layout::new("new", conf, mock_layout, 1, 0.6));
+        assert_eq!(ws.layouts.len(), n_layouts + 1);
+        assert!(ws.layout_conf().unwrap().gapless);
+    }
+
+    #[test]
+    fn restoring_a_saved_workspace() {
+        let layouts = vec![
+            Layout::new("a", LayoutConf::default(), mock_layout, 1, 0.6),
+            Layout::new("b", LayoutConf::default(), mock_layout, 1, 0.6),
+        ];
+        let mut saved = Workspace::new("saved", layouts.clone()).unwrap();
+        saved.clients = Ring::new(vec![1, 2, 3, 4]);
+        saved.focus_client(3);
+        saved.cycle_layout(Direction::Forward).unwrap();
+        saved.set_layout_slots(vec![Slot::Flexible, Slot::Fixed(100)]);
+
+        let json = serde_json::to_string(&saved).unwrap();
+        let saved: Workspace = serde_json::from_str(&json).unwrap();
+        let mut ws = Workspace::new("new", layouts).unwrap();
+        ws.restore(&saved, |id| *id != 2);
+
+        assert_eq!(ws.name(), "saved");
+        assert_eq!(ws.clients.as_vec(), vec![1, 3, 4]);
+        assert_eq!(ws.focused_client(), Some(3));
+        assert_eq!(ws.layout_symbol().unwrap(), "b");
+        assert_eq!(
+            ws.layouts.focused().unwrap().slots(),
+            &[Slot::Flexible, Slot::Fixed(100)]
+        );
+    }
+
+    #[test]
+    fn urgency_is_tracked_via_the_client_map() {
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
+        ws.clients = Ring::new(vec![1, 2]);
+        let mut client_map = map! {
+            1 => Client::new(1, "".into(), 0, false),
+            2 => Client::new(2, "".into(), 0, false),
+        };
+        assert!(!ws.has_urgent(&client_map));
+
+        client_map.get_mut(&2).unwrap().set_urgent(true);
+        assert!(ws.has_urgent(&client_map));
+    }
+
+    #[test]
+    fn replacing_a_client_keeps_its_stack_position_and_focus() {
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
+        ws.clients = Ring::new(vec![1, 2, 3]);
+        ws.focus_client(2);
+
+        assert!(ws.replace_client(2, 42));
+        assert!(!ws.replace_client(2, 43));
+        assert_eq!(ws.clients.as_vec(), vec![1, 42, 3]);
+        assert_eq!(ws.focused_client(), Some(42));
+    }
+
+    #[test]
+    fn rotating_the_stack_retains_focus() {
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
+        ws.clients = Ring::new(vec![1, 2, 3]);
+        ws.focus_client(2);
+
+        ws.rotate_clients(Direction::Forward);
+        assert_eq!(ws.clients.as_vec(), vec![3, 1, 2]);
+        assert_eq!(ws.focused_client(), Some(2));
+        ws.rotate_clients(Direction::Backward);
+        ws.rotate_clients(Direction::Backward);
+        assert_eq!(ws.clients.as_vec(), vec![2, 3, 1]);
+        assert_eq!(ws.focused_client(), Some(2));
+    }
+
+    #[test]
+    fn swapping_with_main() {
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
+        ws.clients = Ring::new(vec![1, 2, 3]);
+        ws.focus_client(3);
+
+        assert!(ws.swap_focused_with_main());
+        assert_eq!(ws.clients.as_vec(), vec![3, 2, 1]);
+        assert_eq!(ws.focused_client(), Some(3));
+
+        // the main client is swapped with the next one
+        assert!(ws.swap_focused_with_main());
+        assert_eq!(ws.clients.as_vec(), vec![2, 3, 1]);
+        assert_eq!(ws.focused_client(), Some(3));
+
+        ws.clients = Ring::new(vec![1]);
+        assert!(!ws.swap_focused_with_main());
+    }
+
+    #[test]
+    fn reversing_the_stack_retains_focus() {
+        let mut ws = Workspace::new("test", test_layouts()).unwrap();
+        ws.clients = Ring::new(vec![1, 2, 3]);
+        ws.focus_client(1);
+
+        ws.reverse_clients();
+        assert_eq!(ws.clients.as_vec(), vec![3, 2, 1]);
+        assert_eq!(ws.focused_client(), Some(1));
+    }
 }
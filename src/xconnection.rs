@@ -9,18 +9,27 @@
  *
  *  [EWMH](https://specifications.freedesktop.org/wm-spec/wm-spec-1.3.html)
  */
-use crate::data_types::{KeyBindings, KeyCode, Region, WinId};
+use crate::data_types::{DpmsState, KeyBindings, KeyCode, Region, SizeHints, Strut, WinId};
+use crate::errors::{PenroseError, Result};
 use crate::screen::Screen;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::thread;
+use std::time::{Duration, Instant};
 use xcb;
 
-const WM_NAME: &'static str = "penrose";
+const WM_NAME: &str = "penrose";
+
+// How long to wait for a window manager that we are replacing to release the screen
+const SELECTION_HANDOVER_TIMEOUT: Duration = Duration::from_secs(5);
 
 /*
  * pulling out bitmasks to make the following xcb / xrandr calls easier to parse visually
  */
 const WINDOW_CLASS_INPUT_ONLY: u16 = xcb::xproto::WINDOW_CLASS_INPUT_ONLY as u16;
-const NOTIFY_MASK: u16 = xcb::randr::NOTIFY_MASK_CRTC_CHANGE as u16;
+const NOTIFY_MASK: u16 =
+    (xcb::randr::NOTIFY_MASK_CRTC_CHANGE | xcb::randr::NOTIFY_MASK_SCREEN_CHANGE) as u16;
 const GRAB_MODE_ASYNC: u8 = xcb::GRAB_MODE_ASYNC as u8;
 const INPUT_FOCUS_PARENT: u8 = xcb::INPUT_FOCUS_PARENT as u8;
 const PROP_MODE_REPLACE: u8 = xcb::PROP_MODE_REPLACE as u8;
@@ -30,34 +39,42 @@ const WIN_HEIGHT: u16 = xcb::CONFIG_WINDOW_HEIGHT as u16;
 const WIN_WIDTH: u16 = xcb::CONFIG_WINDOW_WIDTH as u16;
 const WIN_X: u16 = xcb::CONFIG_WINDOW_X as u16;
 const WIN_Y: u16 = xcb::CONFIG_WINDOW_Y as u16;
+const WIN_STACK_MODE: u16 = xcb::CONFIG_WINDOW_STACK_MODE as u16;
+const STACK_MODE_ABOVE: u32 = xcb::STACK_MODE_ABOVE;
+// The InputHint flag in WM_HINTS (ICCCM 4.1.2.4)
+const INPUT_HINT: u32 = 1;
 const NEW_WINDOW_MASK: &[(u32, u32)] = &[(
     xcb::CW_EVENT_MASK,
-    xcb::EVENT_MASK_ENTER_WINDOW | xcb::EVENT_MASK_LEAVE_WINDOW,
+    xcb::EVENT_MASK_ENTER_WINDOW | xcb::EVENT_MASK_LEAVE_WINDOW | xcb::EVENT_MASK_PROPERTY_CHANGE,
 )];
 const MOUSE_MASK: u16 = (xcb::EVENT_MASK_BUTTON_PRESS
     | xcb::EVENT_MASK_BUTTON_RELEASE
     | xcb::EVENT_MASK_POINTER_MOTION) as u16;
-const EVENT_MASK: &[(u32, u32)] = &[(
-    xcb::CW_EVENT_MASK,
-    xcb::EVENT_MASK_SUBSTRUCTURE_NOTIFY as u32,
-)];
+const EVENT_MASK: &[(u32, u32)] = &[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_SUBSTRUCTURE_NOTIFY)];
 
 // TODO: this list has been copied from atoms used in other WMs, not using everything
 //       yet so work out which ones we need to keep and which we can drop.
-const ATOMS: &[&'static str] = &[
+const ATOMS: &[&str] = &[
     "MANAGER",
     "UTF8_STRING",
     "WM_CLASS",
+    "WM_CLIENT_MACHINE",
     "WM_DELETE_WINDOW",
+    "WM_HINTS",
     "WM_PROTOCOLS",
+    "WM_S0",
     "WM_STATE",
     "WM_NAME",
+    "WM_NORMAL_HINTS",
     "WM_TAKE_FOCUS",
+    "WM_TRANSIENT_FOR",
+    "WM_WINDOW_ROLE",
     "_NET_ACTIVE_WINDOW",
     "_NET_CLIENT_LIST",
     "_NET_CURRENT_DESKTOP",
     "_NET_DESKTOP_NAMES",
     "_NET_NUMBER_OF_DESKTOPS",
+    "_NET_STARTUP_ID",
     "_NET_SUPPORTED",
     "_NET_SUPPORTING_WM_CHECK",
     "_NET_SYSTEM_TRAY_OPCODE",
@@ -66,8 +83,15 @@ const ATOMS: &[&'static str] = &[
     "_NET_SYSTEM_TRAY_S0",
     "_NET_WM_DESKTOP",
     "_NET_WM_NAME",
+    "_NET_WM_PID",
     "_NET_WM_STATE",
+    "_NET_WM_STATE_DEMANDS_ATTENTION",
     "_NET_WM_STATE_FULLSCREEN",
+    "_NET_WM_STATE_HIDDEN",
+    "_NET_WM_STATE_MODAL",
+    "_NET_WM_STRUT",
+    "_NET_WM_STRUT_PARTIAL",
+    "_NET_WM_WINDOW_OPACITY",
     "_NET_WM_WINDOW_TYPE",
     "_NET_WM_WINDOW_TYPE_DIALOG",
     "_XEMBED",
@@ -86,9 +110,51 @@ const ATOMS: &[&'static str] = &[
     "_NET_WM_WINDOW_TYPE_COMBO",
     "_NET_WM_WINDOW_TYPE_DND",
     "_NET_WM_WINDOW_TYPE_NORMAL",
+    VERSION_PROP,
 ];
 
-const AUTO_FLOAT_WINDOW_TYPES: &[&'static str] = &[
+// The EWMH atoms that penrose actually implements, grouped by whether the cargo feature that
+// enables them is on. Only the enabled groups are published as _NET_SUPPORTED so external
+// tools know what they can rely on.
+const EWMH_SUPPORTED_ATOMS: &[(bool, &[&str])] = &[
+    (
+        true,
+        &[
+            "_NET_ACTIVE_WINDOW",
+            "_NET_CLIENT_LIST",
+            "_NET_CURRENT_DESKTOP",
+            "_NET_DESKTOP_NAMES",
+            "_NET_NUMBER_OF_DESKTOPS",
+            "_NET_SUPPORTED",
+            "_NET_SUPPORTING_WM_CHECK",
+            "_NET_WM_DESKTOP",
+            "_NET_WM_NAME",
+            "_NET_WM_STATE",
+            "_NET_WM_STATE_DEMANDS_ATTENTION",
+            "_NET_WM_STATE_HIDDEN",
+            "_NET_WM_STRUT",
+            "_NET_WM_STRUT_PARTIAL",
+        ],
+    ),
+    (
+        cfg!(feature = "ewmh-fullscreen"),
+        &["_NET_WM_STATE_FULLSCREEN"],
+    ),
+];
+
+// Property set on the root and check windows holding the running penrose version
+const VERSION_PROP: &str = "_PENROSE_VERSION";
+
+/// The EWMH atoms supported by this build of penrose, as published in _NET_SUPPORTED
+pub fn ewmh_supported_atoms() -> Vec<&'static str> {
+    EWMH_SUPPORTED_ATOMS
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .flat_map(|(_, atoms)| atoms.iter().copied())
+        .collect()
+}
+
+const AUTO_FLOAT_WINDOW_TYPES: &[&str] = &[
     "_NET_WM_WINDOW_TYPE_DESKTOP",
     "_NET_WM_WINDOW_TYPE_DOCK",
     "_NET_WM_WINDOW_TYPE_TOOLBAR",
@@ -187,14 +253,33 @@ const AUTO_FLOAT_WINDOW_TYPES: &[&'static str] = &[
  *
  * *ButtonRelease* - a mouse button was released
  *   - same fields as *ButtonPress*
+ *
+ * *ClientMessage* - a client is requesting something of the window manager
+ *   - _window_ (WinId):
+ *     The window that the message relates to.
+ *   - _type_ (Atom):
+ *     The message type, converted to the atom name for use by the WindowManager.
+ *   - _data_ ([u32; 5]):
+ *     The message payload: interpretation depends on the message type.
  */
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub enum XEvent {
+    /// A grabbed mouse button was pressed over `window` with the pointer at (x, y) on the root
     /// xcb docs: https://www.mankier.com/3/xcb_input_raw_button_press_event_t
-    ButtonPress,
-
+    ButtonPress {
+        window: WinId,
+        button: u8,
+        x: u32,
+        y: u32,
+    },
+
+    /// A grabbed mouse button was released with the pointer at (x, y) on the root
     /// xcb docs: https://www.mankier.com/3/xcb_input_raw_button_press_event_t
-    ButtonRelease,
+    ButtonRelease { x: u32, y: u32 },
+
+    /// The pointer moved to (x, y) on the root while a grabbed mouse button was held
+    /// xcb docs: https://www.mankier.com/3/xcb_motion_notify_event_t
+    Motion { x: u32, y: u32 },
 
     /// xcb docs: https://www.mankier.com/3/xcb_input_device_key_press_event_t
     KeyPress { code: KeyCode },
@@ -212,6 +297,25 @@ pub enum XEvent {
     /// MapNotifyEvent
     /// xcb docs: https://www.mankier.com/3/xcb_destroy_notify_event_t
     Destroy { window: WinId },
+
+    /// xcb docs: https://www.mankier.com/3/xcb_client_message_event_t
+    ClientMessage {
+        window: WinId,
+        dtype: String,
+        data: Vec<u32>,
+    },
+
+    /// A property on a window has been changed or deleted
+    /// xcb docs: https://www.mankier.com/3/xcb_property_notify_event_t
+    PropertyNotify { window: WinId, atom: String },
+
+    /// The connected outputs have changed and screens need to be re-detected
+    /// xcb docs: https://www.mankier.com/3/xcb_randr_screen_change_notify_event_t
+    RandrNotify,
+
+    /// Another window manager has taken the ICCCM manager selection (WM_Sn) from us
+    /// xcb docs: https://www.mankier.com/3/xcb_selection_clear_event_t
+    Replaced,
 }
 
 /// A handle on a running X11 connection that we can use for issuing X requests
@@ -222,12 +326,27 @@ pub trait XConn {
     /// Wait for the next event from the X server and return it as an XEvent
     fn wait_for_event(&self) -> Option<XEvent>;
 
+    /// Return the next pending event from the X server if there is one without blocking
+    fn poll_for_event(&self) -> Option<XEvent>;
+
+    /**
+     * The file descriptor of the underlying connection to the X server if there is one. This
+     * can be polled alongside other file descriptors to wait for incoming events.
+     */
+    fn connection_fd(&self) -> Option<RawFd>;
+
     /// Determine the currently connected CRTCs and return their details
     fn current_outputs(&self) -> Vec<Screen>;
 
     /// Reposition the window identified by 'id' to the specifed region
     fn position_window(&self, id: WinId, r: Region, border: u32);
 
+    /// Raise the window identified by 'id' to the top of the stacking order
+    fn raise_window(&self, id: WinId);
+
+    /// The current size and position of the window identified by 'id'
+    fn window_geometry(&self, id: WinId) -> Result<Region>;
+
     /// Mark the given window as newly created
     fn mark_new_window(&self, id: WinId);
 
@@ -240,12 +359,39 @@ pub trait XConn {
     /// Send an X event to the target window
     fn send_client_event(&self, id: WinId, atom_name: &str);
 
-    /// Mark the given client as having focus
+    /// The names of the ICCCM protocols listed in WM_PROTOCOLS for the given window
+    fn window_protocols(&self, id: WinId) -> Vec<String>;
+
+    /// Forcibly disconnect the client that owns the given window from the X server
+    fn kill_window(&self, id: WinId);
+
+    /// Give input focus to the given client and mark it as the active window
     fn focus_client(&self, id: WinId);
 
+    /**
+     * Mark the given client as the active window without giving it input focus, for clients
+     * that set the focus themselves (or never take input).
+     */
+    fn set_active_window(&self, id: WinId);
+
+    /// Return input focus to the root window and clear the active window
+    fn clear_focus(&self);
+
     /// Change the border color for the given client
     fn set_client_border_color(&self, id: WinId, color: u32);
 
+    /// Set _NET_WM_WINDOW_OPACITY (from 0.0 to 1.0) for the given client
+    fn set_client_opacity(&self, id: WinId, opacity: f32);
+
+    /// Set or clear _NET_WM_STATE_FULLSCREEN for the given client
+    fn toggle_client_fullscreen(&self, id: WinId, client_is_fullscreen: bool);
+
+    /**
+     * Mark the given client as hidden (ICCCM IconicState and _NET_WM_STATE_HIDDEN) while it is
+     * left mapped somewhere off screen, or as a normal visible window again.
+     */
+    fn set_client_hidden(&self, id: WinId, hidden: bool);
+
     /**
      * Notify the X server that we are intercepting the user specified key bindings
      * and prevent them being passed through to the underlying applications. This
@@ -254,8 +400,31 @@ pub trait XConn {
      */
     fn grab_keys(&self, key_bindings: &KeyBindings);
 
+    /**
+     * Grab the whole keyboard so that every key press is sent to the WindowManager rather
+     * than the focused client. Returns false if another program already holds the grab.
+     */
+    fn grab_keyboard(&self) -> bool;
+
+    /// Release a keyboard grab taken with grab_keyboard
+    fn ungrab_keyboard(&self);
+
+    /// The key codes of keys that act as modifiers (Shift, Control, Super etc)
+    fn modifier_keycodes(&self) -> Vec<u8>;
+
+    /**
+     * Take ownership of the ICCCM manager selection (WM_Sn) for the screen, announcing
+     * ourselves as the running window manager. If another window manager holds the selection
+     * then this fails unless `replace` is true, in which case the selection is taken and we
+     * wait for the previous window manager to release the screen before returning.
+     */
+    fn acquire_wm_selection(&self, replace: bool) -> Result<()>;
+
     /// Set required EWMH properties to ensure compatability with external programs
-    fn set_wm_properties(&self, workspaces: &[&'static str]);
+    fn set_wm_properties(&self, workspaces: &[&str]);
+
+    /// Update the number and names of the available desktops
+    fn update_desktops(&self, workspaces: &[&str]);
 
     /// Update which desktop is currently focused
     fn set_current_workspace(&self, wix: usize);
@@ -266,6 +435,9 @@ pub trait XConn {
     /// Update which desktop a client is currently on
     fn set_client_workspace(&self, id: WinId, wix: usize);
 
+    /// Update the list of clients currently managed by the window manager
+    fn update_client_list(&self, clients: &[WinId]);
+
     /// Determine whether the target window should be tiled or allowed to float
     fn window_should_float(&self, id: WinId, floating_classes: &[&str]) -> bool;
 
@@ -275,17 +447,53 @@ pub trait XConn {
      */
     fn warp_cursor(&self, win_id: Option<WinId>);
 
+    /// Warp the cursor to the point (x, y) on the root window
+    fn warp_cursor_to_point(&self, x: u32, y: u32);
+
     /// Run on startup/restart to determine already running windows that we need to track
     fn query_for_active_windows(&self) -> Vec<WinId>;
 
+    /// Run on startup/restart to find dock windows that are already mapped
+    fn query_for_docks(&self) -> Vec<WinId>;
+
+    /// Whether or not the given window is a dock or status bar (_NET_WM_WINDOW_TYPE_DOCK)
+    fn window_is_dock(&self, id: WinId) -> bool;
+
+    /// The space reserved by the given window, if it has set _NET_WM_STRUT(_PARTIAL)
+    fn window_strut(&self, id: WinId) -> Option<Strut>;
+
+    /// The size hints (WM_NORMAL_HINTS) set by the given window, if any
+    fn window_size_hints(&self, id: WinId) -> Option<SizeHints>;
+
+    /// The window that the given window is a transient for (WM_TRANSIENT_FOR), if any
+    fn window_transient_for(&self, id: WinId) -> Option<WinId>;
+
+    /// Whether or not the given window is a dialog (_NET_WM_WINDOW_TYPE_DIALOG) or is modal
+    fn window_is_dialog(&self, id: WinId) -> bool;
+
+    /**
+     * Whether or not the given window wants input focus to be set on it, from the input field
+     * of WM_HINTS (ICCCM 4.1.7). Windows that do not set the field are assumed to want it.
+     */
+    fn window_accepts_input(&self, id: WinId) -> bool;
+
     /**
      * Use the xcb api to query a string property for a window by window ID and poperty name.
      * Can fail if the property name is invalid or we get a malformed response from xcb.
      */
-    fn str_prop(&self, id: u32, name: &str) -> Result<String, String>;
+    fn str_prop(&self, id: u32, name: &str) -> Result<String>;
 
     /// Fetch an atom prop by name for a particular window ID
-    fn atom_prop(&self, id: u32, name: &str) -> Result<u32, String>;
+    fn atom_prop(&self, id: u32, name: &str) -> Result<u32>;
+
+    /// Look up the X atom value for the given atom name, interning it if required
+    fn intern_atom(&self, name: &str) -> Result<u32>;
+
+    /// Force connected displays into the given DPMS power level
+    fn set_dpms_state(&self, state: DpmsState);
+
+    /// The current DPMS power level of connected displays, None if DPMS is unavailable
+    fn dpms_state(&self) -> Option<DpmsState>;
 
     /// Perform any state cleanup required prior to shutting down the window manager
     fn cleanup(&self);
@@ -298,6 +506,15 @@ pub struct XcbConnection {
     check_win: WinId,
     atoms: HashMap<&'static str, u32>,
     auto_float_types: Vec<u32>,
+    randr_base: u8,
+    // the server time of the most recent event carrying a timestamp (CurrentTime until then)
+    last_timestamp: Cell<u32>,
+}
+
+impl Default for XcbConnection {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl XcbConnection {
@@ -320,7 +537,7 @@ impl XcbConnection {
                 // false == always return the atom, even if exists already
                 let val = xcb::intern_atom(&conn, false, atom)
                     .get_reply()
-                    .expect(&format!("unable to intern xcb atom '{}'", atom))
+                    .unwrap_or_else(|_| panic!("unable to intern xcb atom '{}'", atom))
                     .atom();
 
                 (*atom, val)
@@ -332,6 +549,11 @@ impl XcbConnection {
             .map(|t| *atoms.get(t).unwrap())
             .collect();
 
+        let randr_base = match conn.get_extension_data(xcb::randr::id()) {
+            None => panic!("unable to fetch randr extension data"),
+            Some(data) => data.first_event(),
+        };
+
         let check_win = conn.generate_id();
 
         // xcb docs: https://www.mankier.com/3/xcb_create_window
@@ -356,6 +578,8 @@ impl XcbConnection {
             check_win,
             atoms,
             auto_float_types,
+            randr_base,
+            last_timestamp: Cell::new(xcb::CURRENT_TIME),
         }
     }
 
@@ -363,24 +587,75 @@ impl XcbConnection {
         *self
             .atoms
             .get(name)
-            .expect(&format!("{} is not a known atom", name))
+            .unwrap_or_else(|| panic!("{} is not a known atom", name))
     }
 
-    fn window_geometry(&self, id: WinId) -> Result<Region, String> {
-        let cookie = xcb::get_geometry(&self.conn, id);
+    fn selection_owner(&self, selection: u32) -> Result<WinId> {
+        // xcb docs: https://www.mankier.com/3/xcb_get_selection_owner
+        match xcb::get_selection_owner(&self.conn, selection).get_reply() {
+            Ok(reply) => Ok(reply.owner()),
+            Err(e) => Err(PenroseError::X(format!(
+                "unable to query selection owner: {}",
+                e
+            ))),
+        }
+    }
+
+    // Block until the given window is destroyed, killing its client if it takes too long.
+    // Only used before we start managing windows so any other events can be dropped.
+    fn wait_for_destroy(&self, id: WinId, timeout: Duration) {
+        self.conn.flush();
+        let deadline = Instant::now() + timeout;
+
+        while Instant::now() < deadline {
+            while let Some(event) = self.conn.poll_for_event() {
+                if event.response_type() & !0x80 == xcb::DESTROY_NOTIFY {
+                    let e: &xcb::DestroyNotifyEvent = unsafe { xcb::cast_event(&event) };
+                    if e.window() == id {
+                        return;
+                    }
+                }
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        warn!("window manager did not release the screen in time: killing it");
+        xcb::kill_client(&self.conn, id);
+        self.conn.flush();
+    }
+
+    fn atom_name(&self, atom: u32) -> Result<String> {
+        if let Some((name, _)) = self.atoms.iter().find(|(_, a)| **a == atom) {
+            return Ok((*name).to_string());
+        }
+
+        // xcb docs: https://www.mankier.com/3/xcb_get_atom_name
+        match xcb::get_atom_name(&self.conn, atom).get_reply() {
+            Err(e) => Err(PenroseError::X(format!("unable to fetch atom name: {}", e))),
+            Ok(reply) => Ok(reply.name().to_string()),
+        }
+    }
+
+    // Up to `len` 32bit values of a CARDINAL[] (or struct) property, empty if it is not set
+    fn cardinal_list_prop(&self, id: WinId, name: &str, len: u32) -> Vec<u32> {
+        // xcb docs: https://www.mankier.com/3/xcb_get_property
+        let cookie = xcb::get_property(
+            &self.conn,      // xcb connection to X11
+            false,           // should the property be deleted
+            id,              // target window to query
+            self.atom(name), // the property we want
+            xcb::ATOM_ANY,   // the type of the property
+            0,               // offset in the property to retrieve data from
+            len,             // how many 32bit multiples of data to retrieve
+        );
 
         match cookie.get_reply() {
-            Err(e) => Err(format!("unable to fetch window property: {}", e)),
-            Ok(r) => Ok(Region::new(
-                r.x() as u32,
-                r.y() as u32,
-                r.width() as u32,
-                r.height() as u32,
-            )),
+            Err(_) => vec![],
+            Ok(reply) => reply.value::<u32>().to_vec(),
         }
     }
 
-    fn window_has_type_in(&self, id: WinId, win_types: &Vec<u32>) -> bool {
+    fn window_has_type_in(&self, id: WinId, win_types: &[u32]) -> bool {
         // xcb docs: https://www.mankier.com/3/xcb_get_property
         let cookie = xcb::get_property(
             &self.conn,                       // xcb connection to X11
@@ -397,55 +672,149 @@ impl XcbConnection {
             Ok(types) => types.value().iter().any(|t| win_types.contains(t)),
         }
     }
-}
 
-impl XConn for XcbConnection {
-    fn flush(&self) -> bool {
-        self.conn.flush()
-    }
+    // Convert a raw xcb event into an XEvent, returning None for events we don't handle
+    fn convert_event(&self, event: xcb::GenericEvent) -> Option<XEvent> {
+        let etype = event.response_type();
+        match etype {
+            // buttons are grabbed on the root so the client window is the child
+            xcb::BUTTON_PRESS => {
+                let e: &xcb::ButtonPressEvent = unsafe { xcb::cast_event(&event) };
+                self.last_timestamp.set(e.time());
+                Some(XEvent::ButtonPress {
+                    window: e.child(),
+                    button: e.detail(),
+                    x: e.root_x().max(0) as u32,
+                    y: e.root_y().max(0) as u32,
+                })
+            }
 
-    fn wait_for_event(&self) -> Option<XEvent> {
-        self.conn.wait_for_event().and_then(|event| {
-            let etype = event.response_type();
-            match etype {
-                xcb::BUTTON_PRESS => None,
-
-                xcb::BUTTON_RELEASE => None,
-
-                xcb::KEY_PRESS => {
-                    let e: &xcb::KeyPressEvent = unsafe { xcb::cast_event(&event) };
-                    Some(XEvent::KeyPress {
-                        code: KeyCode::from_key_press(e),
-                    })
-                }
+            xcb::BUTTON_RELEASE => {
+                let e: &xcb::ButtonReleaseEvent = unsafe { xcb::cast_event(&event) };
+                self.last_timestamp.set(e.time());
+                Some(XEvent::ButtonRelease {
+                    x: e.root_x().max(0) as u32,
+                    y: e.root_y().max(0) as u32,
+                })
+            }
 
-                xcb::MAP_NOTIFY => {
-                    let e: &xcb::MapNotifyEvent = unsafe { xcb::cast_event(&event) };
-                    Some(XEvent::Map {
+            xcb::MOTION_NOTIFY => {
+                let e: &xcb::MotionNotifyEvent = unsafe { xcb::cast_event(&event) };
+                self.last_timestamp.set(e.time());
+                Some(XEvent::Motion {
+                    x: e.root_x().max(0) as u32,
+                    y: e.root_y().max(0) as u32,
+                })
+            }
+
+            xcb::KEY_PRESS => {
+                let e: &xcb::KeyPressEvent = unsafe { xcb::cast_event(&event) };
+                self.last_timestamp.set(e.time());
+                Some(XEvent::KeyPress {
+                    code: KeyCode::from_key_press(e),
+                })
+            }
+
+            xcb::MAP_NOTIFY => {
+                let e: &xcb::MapNotifyEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::Map {
+                    window: e.window(),
+                    ignore: e.override_redirect(),
+                })
+            }
+
+            xcb::ENTER_NOTIFY => {
+                let e: &xcb::EnterNotifyEvent = unsafe { xcb::cast_event(&event) };
+                self.last_timestamp.set(e.time());
+                Some(XEvent::Enter { window: e.event() })
+            }
+
+            xcb::LEAVE_NOTIFY => {
+                let e: &xcb::LeaveNotifyEvent = unsafe { xcb::cast_event(&event) };
+                self.last_timestamp.set(e.time());
+                Some(XEvent::Leave { window: e.event() })
+            }
+
+            xcb::PROPERTY_NOTIFY => {
+                let e: &xcb::PropertyNotifyEvent = unsafe { xcb::cast_event(&event) };
+                self.last_timestamp.set(e.time());
+                match self.atom_name(e.atom()) {
+                    Ok(atom) => Some(XEvent::PropertyNotify {
                         window: e.window(),
-                        ignore: e.override_redirect(),
-                    })
+                        atom,
+                    }),
+                    Err(e) => {
+                        warn!("unknown property: {}", e);
+                        None
+                    }
                 }
+            }
 
-                xcb::ENTER_NOTIFY => {
-                    let e: &xcb::EnterNotifyEvent = unsafe { xcb::cast_event(&event) };
-                    Some(XEvent::Enter { window: e.event() })
+            // we only ever own the WM_S0 selection, on our check window
+            xcb::SELECTION_CLEAR => {
+                let e: &xcb::SelectionClearEvent = unsafe { xcb::cast_event(&event) };
+                if e.owner() == self.check_win && e.selection() == self.atom("WM_S0") {
+                    Some(XEvent::Replaced)
+                } else {
+                    None
                 }
+            }
 
-                xcb::LEAVE_NOTIFY => {
-                    let e: &xcb::LeaveNotifyEvent = unsafe { xcb::cast_event(&event) };
-                    Some(XEvent::Leave { window: e.event() })
-                }
+            xcb::DESTROY_NOTIFY => {
+                let e: &xcb::MapNotifyEvent = unsafe { xcb::cast_event(&event) };
+                Some(XEvent::Destroy { window: e.window() })
+            }
 
-                xcb::DESTROY_NOTIFY => {
-                    let e: &xcb::MapNotifyEvent = unsafe { xcb::cast_event(&event) };
-                    Some(XEvent::Destroy { window: e.window() })
+            xcb::CLIENT_MESSAGE => {
+                let e: &xcb::ClientMessageEvent = unsafe { xcb::cast_event(&event) };
+                match self.atom_name(e.type_()) {
+                    Err(e) => {
+                        warn!("unknown client message type: {}", e);
+                        None
+                    }
+                    Ok(dtype) => Some(XEvent::ClientMessage {
+                        window: e.window(),
+                        dtype,
+                        data: e.data().data32().to_vec(),
+                    }),
                 }
+            }
 
-                // NOTE: ignoring other event types
-                _ => None,
+            // randr events are offset from the first event code of the extension
+            e if e == self.randr_base + xcb::randr::SCREEN_CHANGE_NOTIFY => {
+                Some(XEvent::RandrNotify)
+            }
+            e if e == self.randr_base + xcb::randr::NOTIFY => Some(XEvent::RandrNotify),
+
+            // NOTE: ignoring other event types
+            _ => None,
+        }
+    }
+}
+
+impl XConn for XcbConnection {
+    fn flush(&self) -> bool {
+        self.conn.flush()
+    }
+
+    fn wait_for_event(&self) -> Option<XEvent> {
+        self.conn
+            .wait_for_event()
+            .and_then(|event| self.convert_event(event))
+    }
+
+    fn poll_for_event(&self) -> Option<XEvent> {
+        // skip over events that we don't handle so that None means the queue is empty
+        while let Some(event) = self.conn.poll_for_event() {
+            if let Some(e) = self.convert_event(event) {
+                return Some(e);
             }
-        })
+        }
+        None
+    }
+
+    fn connection_fd(&self) -> Option<RawFd> {
+        Some(self.conn.as_raw_fd())
     }
 
     fn current_outputs(&self) -> Vec<Screen> {
@@ -483,6 +852,29 @@ impl XConn for XcbConnection {
         );
     }
 
+    fn raise_window(&self, id: WinId) {
+        // xcb docs: https://www.mankier.com/3/xcb_configure_window
+        xcb::configure_window(&self.conn, id, &[(WIN_STACK_MODE, STACK_MODE_ABOVE)]);
+    }
+
+    fn window_geometry(&self, id: WinId) -> Result<Region> {
+        // xcb docs: https://www.mankier.com/3/xcb_get_geometry
+        let cookie = xcb::get_geometry(&self.conn, id);
+
+        match cookie.get_reply() {
+            Err(e) => Err(PenroseError::X(format!(
+                "unable to fetch window property: {}",
+                e
+            ))),
+            Ok(r) => Ok(Region::new(
+                r.x() as u32,
+                r.y() as u32,
+                r.width() as u32,
+                r.height() as u32,
+            )),
+        }
+    }
+
     fn mark_new_window(&self, id: WinId) {
         // xcb docs: https://www.mankier.com/3/xcb_change_window_attributes
         xcb::change_window_attributes(&self.conn, id, NEW_WINDOW_MASK);
@@ -499,14 +891,44 @@ impl XConn for XcbConnection {
     fn send_client_event(&self, id: WinId, atom_name: &str) {
         let atom = self.atom(atom_name);
         let wm_protocols = self.atom("WM_PROTOCOLS");
-        let data = xcb::ClientMessageData::from_data32([atom, xcb::CURRENT_TIME, 0, 0, 0]);
+        // ICCCM requires a real timestamp for WM_TAKE_FOCUS: some toolkits ignore CurrentTime
+        let time = self.last_timestamp.get();
+        let data = xcb::ClientMessageData::from_data32([atom, time, 0, 0, 0]);
         let event = xcb::ClientMessageEvent::new(32, id, wm_protocols, data);
         xcb::send_event(&self.conn, false, id, xcb::EVENT_MASK_NO_EVENT, &event);
     }
 
-    fn focus_client(&self, id: WinId) {
-        let prop = self.atom("_NET_ACTIVE_WINDOW");
+    fn window_protocols(&self, id: WinId) -> Vec<String> {
+        // xcb docs: https://www.mankier.com/3/xcb_get_property
+        let cookie = xcb::get_property(
+            &self.conn,                // xcb connection to X11
+            false,                     // should the property be deleted
+            id,                        // target window to query
+            self.atom("WM_PROTOCOLS"), // the property we want
+            xcb::ATOM_ATOM,            // the type of the property
+            0,                         // offset in the property to retrieve data from
+            1024,                      // how many 32bit multiples of data to retrieve
+        );
 
+        match cookie.get_reply() {
+            Err(e) => {
+                warn!("unable to fetch WM_PROTOCOLS for {}: {}", id, e);
+                vec![]
+            }
+            Ok(reply) => reply
+                .value::<u32>()
+                .iter()
+                .filter_map(|a| self.atom_name(*a).ok())
+                .collect(),
+        }
+    }
+
+    fn kill_window(&self, id: WinId) {
+        // xcb docs: https://www.mankier.com/3/xcb_kill_client
+        xcb::kill_client(&self.conn, id);
+    }
+
+    fn focus_client(&self, id: WinId) {
         // xcb docs: https://www.mankier.com/3/xcb_set_input_focus
         xcb::set_input_focus(
             &self.conn,         // xcb connection to X11
@@ -514,6 +936,11 @@ impl XConn for XcbConnection {
             id,                 // window to focus
             0,                  // current time to avoid network race conditions (0 == current time)
         );
+        self.set_active_window(id);
+    }
+
+    fn set_active_window(&self, id: WinId) {
+        let prop = self.atom("_NET_ACTIVE_WINDOW");
 
         // xcb docs: https://www.mankier.com/3/xcb_change_property
         xcb::change_property(
@@ -527,10 +954,89 @@ impl XConn for XcbConnection {
         );
     }
 
+    fn clear_focus(&self) {
+        // xcb docs: https://www.mankier.com/3/xcb_set_input_focus
+        xcb::set_input_focus(&self.conn, INPUT_FOCUS_PARENT, self.root, 0);
+        xcb::delete_property(&self.conn, self.root, self.atom("_NET_ACTIVE_WINDOW"));
+    }
+
     fn set_client_border_color(&self, id: WinId, color: u32) {
         xcb::change_window_attributes(&self.conn, id, &[(xcb::CW_BORDER_PIXEL, color)]);
     }
 
+    fn set_client_opacity(&self, id: WinId, opacity: f32) {
+        let value = (opacity.clamp(0.0, 1.0) as f64 * u32::MAX as f64) as u32;
+        xcb::change_property(
+            &self.conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            id,
+            self.atom("_NET_WM_WINDOW_OPACITY"),
+            xcb::xproto::ATOM_CARDINAL,
+            32,
+            &[value],
+        );
+    }
+
+    fn toggle_client_fullscreen(&self, id: WinId, client_is_fullscreen: bool) {
+        let data = if client_is_fullscreen {
+            vec![self.atom("_NET_WM_STATE_FULLSCREEN")]
+        } else {
+            vec![]
+        };
+
+        // xcb docs: https://www.mankier.com/3/xcb_change_property
+        xcb::change_property(
+            &self.conn,                 // xcb connection to X11
+            PROP_MODE_REPLACE,          // discard current prop and replace
+            id,                         // window to change prop on
+            self.atom("_NET_WM_STATE"), // prop to change
+            xcb::xproto::ATOM_ATOM,     // type of prop
+            32,                         // data format (8/16/32-bit)
+            &data,                      // data
+        );
+    }
+
+    fn set_client_hidden(&self, id: WinId, hidden: bool) {
+        // ICCCM WM_STATE is (state, icon window) with NormalState = 1 and IconicState = 3
+        let wm_state = self.atom("WM_STATE");
+        let state = if hidden { 3 } else { 1 };
+        xcb::change_property(
+            &self.conn,
+            PROP_MODE_REPLACE,
+            id,
+            wm_state,
+            wm_state,
+            32,
+            &[state, 0],
+        );
+
+        // keep any other states (such as fullscreen) that are already set
+        let net_wm_state = self.atom("_NET_WM_STATE");
+        let hidden_atom = self.atom("_NET_WM_STATE_HIDDEN");
+        let cookie =
+            xcb::get_property(&self.conn, false, id, net_wm_state, xcb::ATOM_ATOM, 0, 1024);
+        let mut states: Vec<u32> = match cookie.get_reply() {
+            Ok(reply) => reply.value::<u32>().to_vec(),
+            Err(e) => {
+                warn!("unable to fetch _NET_WM_STATE for {}: {}", id, e);
+                vec![]
+            }
+        };
+        states.retain(|a| *a != hidden_atom);
+        if hidden {
+            states.push(hidden_atom);
+        }
+        xcb::change_property(
+            &self.conn,
+            PROP_MODE_REPLACE,
+            id,
+            net_wm_state,
+            xcb::ATOM_ATOM,
+            32,
+            &states,
+        );
+    }
+
     fn grab_keys(&self, key_bindings: &KeyBindings) {
         // xcb docs: https://www.mankier.com/3/xcb_randr_select_input
         let input = xcb::randr::select_input(&self.conn, self.root, NOTIFY_MASK);
@@ -571,10 +1077,99 @@ impl XConn for XcbConnection {
 
         // xcb docs: https://www.mankier.com/3/xcb_change_window_attributes
         xcb::change_window_attributes(&self.conn, self.root, EVENT_MASK);
-        &self.conn.flush();
+        self.conn.flush();
+    }
+
+    fn grab_keyboard(&self) -> bool {
+        // xcb docs: https://www.mankier.com/3/xcb_grab_keyboard
+        let cookie = xcb::grab_keyboard(
+            &self.conn,        // xcb connection to X11
+            false,             // don't pass grabbed events through to the client
+            self.root,         // the window to grab: in this case the root window
+            xcb::CURRENT_TIME, // grab immediately
+            GRAB_MODE_ASYNC,   // don't lock pointer input while grabbing
+            GRAB_MODE_ASYNC,   // don't lock keyboard input while grabbing
+        );
+        match cookie.get_reply() {
+            Ok(r) => r.status() == xcb::GRAB_STATUS_SUCCESS as u8,
+            Err(e) => {
+                warn!("unable to grab keyboard: {}", e);
+                false
+            }
+        }
+    }
+
+    fn ungrab_keyboard(&self) {
+        xcb::ungrab_keyboard(&self.conn, xcb::CURRENT_TIME);
+        self.conn.flush();
+    }
+
+    fn modifier_keycodes(&self) -> Vec<u8> {
+        // xcb docs: https://www.mankier.com/3/xcb_get_modifier_mapping
+        match xcb::get_modifier_mapping(&self.conn).get_reply() {
+            Ok(reply) => reply
+                .keycodes()
+                .iter()
+                .copied()
+                .filter(|c| *c != 0)
+                .collect(),
+            Err(e) => {
+                warn!("unable to fetch modifier mapping: {}", e);
+                vec![]
+            }
+        }
+    }
+
+    fn acquire_wm_selection(&self, replace: bool) -> Result<()> {
+        let selection = self.atom("WM_S0");
+        let previous = self.selection_owner(selection)?;
+        if previous != xcb::NONE {
+            if !replace {
+                return Err(PenroseError::OtherWmRunning);
+            }
+            info!("replacing the running window manager (window {})", previous);
+            // xcb docs: https://www.mankier.com/3/xcb_change_window_attributes
+            xcb::change_window_attributes(
+                &self.conn,
+                previous,
+                &[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_STRUCTURE_NOTIFY)],
+            );
+        }
+
+        // xcb docs: https://www.mankier.com/3/xcb_set_selection_owner
+        xcb::set_selection_owner(&self.conn, self.check_win, selection, xcb::CURRENT_TIME);
+        if self.selection_owner(selection)? != self.check_win {
+            return Err(PenroseError::X(
+                "unable to take ownership of the WM_S0 selection".into(),
+            ));
+        }
+
+        if previous != xcb::NONE {
+            self.wait_for_destroy(previous, SELECTION_HANDOVER_TIMEOUT);
+        }
+
+        // ICCCM 2.8: let anyone interested know that there is a new manager for the screen
+        let data = xcb::ClientMessageData::from_data32([
+            xcb::CURRENT_TIME,
+            selection,
+            self.check_win,
+            0,
+            0,
+        ]);
+        let event = xcb::ClientMessageEvent::new(32, self.root, self.atom("MANAGER"), data);
+        xcb::send_event(
+            &self.conn,
+            false,
+            self.root,
+            xcb::EVENT_MASK_STRUCTURE_NOTIFY,
+            &event,
+        );
+        self.conn.flush();
+
+        Ok(())
     }
 
-    fn set_wm_properties(&self, workspaces: &[&'static str]) {
+    fn set_wm_properties(&self, workspaces: &[&str]) {
         // xcb docs: https://www.mankier.com/3/xcb_change_property
         xcb::change_property(
             &self.conn,                            // xcb connection to X11
@@ -613,8 +1208,23 @@ impl XConn for XcbConnection {
             WM_NAME.as_bytes(),        // data
         );
 
+        for win in &[self.check_win, self.root] {
+            xcb::change_property(
+                &self.conn,                           // xcb connection to X11
+                PROP_MODE_REPLACE,                    // discard current prop and replace
+                *win,                                 // window to change prop on
+                self.atom(VERSION_PROP),              // prop to change
+                self.atom("UTF8_STRING"),             // type of prop
+                8,                                    // data format (8/16/32-bit)
+                env!("CARGO_PKG_VERSION").as_bytes(), // data
+            );
+        }
+
         // EWMH support
-        let supported: Vec<u32> = ATOMS.iter().map(|a| self.atom(a)).collect();
+        let supported: Vec<u32> = ewmh_supported_atoms()
+            .iter()
+            .map(|a| self.atom(a))
+            .collect();
         xcb::change_property(
             &self.conn,                  // xcb connection to X11
             PROP_MODE_REPLACE,           // discard current prop and replace
@@ -624,6 +1234,12 @@ impl XConn for XcbConnection {
             32,                          // data format (8/16/32-bit)
             &supported,                  // data
         );
+
+        self.update_desktops(workspaces);
+        xcb::delete_property(&self.conn, self.root, self.atom("_NET_CLIENT_LIST"));
+    }
+
+    fn update_desktops(&self, workspaces: &[&str]) {
         xcb::change_property(
             &self.conn,                           // xcb connection to X11
             PROP_MODE_REPLACE,                    // discard current prop and replace
@@ -642,8 +1258,6 @@ impl XConn for XcbConnection {
             8,                                // data format (8/16/32-bit)
             workspaces.join("\0").as_bytes(), // data
         );
-
-        xcb::delete_property(&self.conn, self.root, self.atom("_NET_CLIENT_LIST"));
     }
 
     fn set_current_workspace(&self, wix: usize) {
@@ -682,15 +1296,24 @@ impl XConn for XcbConnection {
         );
     }
 
+    fn update_client_list(&self, clients: &[WinId]) {
+        xcb::change_property(
+            &self.conn,                    // xcb connection to X11
+            PROP_MODE_REPLACE,             // discard current prop and replace
+            self.root,                     // window to change prop on
+            self.atom("_NET_CLIENT_LIST"), // prop to change
+            xcb::xproto::ATOM_WINDOW,      // type of prop
+            32,                            // data format (8/16/32-bit)
+            clients,                       // data
+        );
+    }
+
     fn window_should_float(&self, id: WinId, floating_classes: &[&str]) -> bool {
-        match self.str_prop(id, "WM_CLASS") {
-            Ok(s) => {
-                if s.split("\0").any(|c| floating_classes.contains(&c)) {
-                    return true;
-                }
+        if let Ok(s) = self.str_prop(id, "WM_CLASS") {
+            if s.split('\0').any(|c| floating_classes.contains(&c)) {
+                return true;
             }
-            Err(_) => (), // no WM_CLASS set
-        };
+        }
 
         // self.window_has_type_in(id, &self.auto_float_types)
         // xcb docs: https://www.mankier.com/3/xcb_get_property
@@ -715,11 +1338,20 @@ impl XConn for XcbConnection {
 
     fn warp_cursor(&self, win_id: Option<WinId>) {
         let (x, y, id) = match win_id {
-            Some(id) => {
-                let (_, _, w, h) = self.window_geometry(id).unwrap().values();
-                ((w / 2) as i16, (h / 2) as i16, id)
-            }
-            None => todo!("warp cursor to center of screen"),
+            Some(id) => match self.window_geometry(id) {
+                Ok(r) => {
+                    let (_, _, w, h) = r.values();
+                    ((w / 2) as i16, (h / 2) as i16, id)
+                }
+                Err(e) => return warn!("unable to warp cursor to {}: {}", id, e),
+            },
+            None => match self.window_geometry(self.root) {
+                Ok(r) => {
+                    let (_, _, w, h) = r.values();
+                    ((w / 2) as i16, (h / 2) as i16, self.root)
+                }
+                Err(e) => return warn!("unable to warp cursor to the root window: {}", e),
+            },
         };
 
         xcb::warp_pointer(
@@ -735,6 +1367,11 @@ impl XConn for XcbConnection {
         );
     }
 
+    fn warp_cursor_to_point(&self, x: u32, y: u32) {
+        // xcb docs: https://www.mankier.com/3/xcb_warp_pointer
+        xcb::warp_pointer(&self.conn, 0, self.root, 0, 0, 0, 0, x as i16, y as i16);
+    }
+
     fn query_for_active_windows(&self) -> Vec<WinId> {
         let all_ids = match xcb::query_tree(&self.conn, self.root).get_reply() {
             Err(_) => Vec::new(),
@@ -753,7 +1390,60 @@ impl XConn for XcbConnection {
             .collect()
     }
 
-    fn str_prop(&self, id: u32, name: &str) -> Result<String, String> {
+    fn query_for_docks(&self) -> Vec<WinId> {
+        let all_ids: Vec<WinId> = match xcb::query_tree(&self.conn, self.root).get_reply() {
+            Err(_) => Vec::new(),
+            Ok(reply) => reply.children().into(),
+        };
+
+        all_ids
+            .into_iter()
+            .filter(|id| self.window_is_dock(*id))
+            .filter(|id| {
+                // xcb docs: https://www.mankier.com/3/xcb_get_window_attributes
+                xcb::get_window_attributes(&self.conn, *id)
+                    .get_reply()
+                    .is_ok_and(|a| a.map_state() == xcb::MAP_STATE_VIEWABLE as u8)
+            })
+            .collect()
+    }
+
+    fn window_is_dock(&self, id: WinId) -> bool {
+        self.window_has_type_in(id, &[self.atom("_NET_WM_WINDOW_TYPE_DOCK")])
+    }
+
+    fn window_strut(&self, id: WinId) -> Option<Strut> {
+        ["_NET_WM_STRUT_PARTIAL", "_NET_WM_STRUT"]
+            .iter()
+            .find_map(|name| Strut::from_cardinals(&self.cardinal_list_prop(id, name, 12)))
+    }
+
+    fn window_size_hints(&self, id: WinId) -> Option<SizeHints> {
+        SizeHints::from_cardinals(&self.cardinal_list_prop(id, "WM_NORMAL_HINTS", 18))
+    }
+
+    fn window_transient_for(&self, id: WinId) -> Option<WinId> {
+        self.cardinal_list_prop(id, "WM_TRANSIENT_FOR", 1)
+            .first()
+            .copied()
+            .filter(|parent| *parent != 0 && *parent != id)
+    }
+
+    fn window_is_dialog(&self, id: WinId) -> bool {
+        self.window_has_type_in(id, &[self.atom("_NET_WM_WINDOW_TYPE_DIALOG")])
+            || self
+                .cardinal_list_prop(id, "_NET_WM_STATE", 32)
+                .contains(&self.atom("_NET_WM_STATE_MODAL"))
+    }
+
+    fn window_accepts_input(&self, id: WinId) -> bool {
+        match self.cardinal_list_prop(id, "WM_HINTS", 2).as_slice() {
+            [flags, input] if flags & INPUT_HINT != 0 => *input != 0,
+            _ => true,
+        }
+    }
+
+    fn str_prop(&self, id: u32, name: &str) -> Result<String> {
         // xcb docs: https://www.mankier.com/3/xcb_get_property
         let cookie = xcb::get_property(
             &self.conn,      // xcb connection to X11
@@ -766,15 +1456,21 @@ impl XConn for XcbConnection {
         );
 
         match cookie.get_reply() {
-            Err(e) => Err(format!("unable to fetch window property: {}", e)),
+            Err(e) => Err(PenroseError::X(format!(
+                "unable to fetch window property: {}",
+                e
+            ))),
             Ok(reply) => match String::from_utf8(reply.value().to_vec()) {
-                Err(e) => Err(format!("invalid utf8 resonse from xcb: {}", e)),
+                Err(e) => Err(PenroseError::X(format!(
+                    "invalid utf8 resonse from xcb: {}",
+                    e
+                ))),
                 Ok(s) => Ok(s),
             },
         }
     }
 
-    fn atom_prop(&self, id: u32, name: &str) -> Result<u32, String> {
+    fn atom_prop(&self, id: u32, name: &str) -> Result<u32> {
         // xcb docs: https://www.mankier.com/3/xcb_get_property
         let cookie = xcb::get_property(
             &self.conn,      // xcb connection to X11
@@ -787,10 +1483,13 @@ impl XConn for XcbConnection {
         );
 
         match cookie.get_reply() {
-            Err(e) => Err(format!("unable to fetch window property: {}", e)),
+            Err(e) => Err(PenroseError::X(format!(
+                "unable to fetch window property: {}",
+                e
+            ))),
             Ok(reply) => {
-                if reply.value_len() <= 0 {
-                    Err(format!("property '{}' was empty for id: {}", name, id))
+                if reply.value_len() == 0 {
+                    Err(PenroseError::MissingProperty(id, name.into()))
                 } else {
                     Ok(reply.value()[0])
                 }
@@ -798,6 +1497,67 @@ impl XConn for XcbConnection {
         }
     }
 
+    fn intern_atom(&self, name: &str) -> Result<u32> {
+        if let Some(atom) = self.atoms.get(name) {
+            return Ok(*atom);
+        }
+
+        // https://www.mankier.com/3/xcb_intern_atom
+        match xcb::intern_atom(&self.conn, false, name).get_reply() {
+            Err(e) => Err(PenroseError::X(format!(
+                "unable to intern xcb atom '{}': {}",
+                name, e
+            ))),
+            Ok(reply) => Ok(reply.atom()),
+        }
+    }
+
+    #[cfg(feature = "dpms")]
+    fn set_dpms_state(&self, state: DpmsState) {
+        let level = match state {
+            DpmsState::On => xcb::dpms::DPMS_MODE_ON,
+            DpmsState::Standby => xcb::dpms::DPMS_MODE_STANDBY,
+            DpmsState::Suspend => xcb::dpms::DPMS_MODE_SUSPEND,
+            DpmsState::Off => xcb::dpms::DPMS_MODE_OFF,
+        };
+
+        // DPMS needs to be enabled before the server will honour forcing a power level
+        xcb::dpms::enable(&self.conn);
+        xcb::dpms::force_level(&self.conn, level as u16);
+        self.conn.flush();
+    }
+
+    #[cfg(not(feature = "dpms"))]
+    fn set_dpms_state(&self, state: DpmsState) {
+        warn!(
+            "unable to set DPMS state to {:?}: built without 'dpms'",
+            state
+        );
+    }
+
+    #[cfg(feature = "dpms")]
+    fn dpms_state(&self) -> Option<DpmsState> {
+        match xcb::dpms::info(&self.conn).get_reply() {
+            Err(e) => {
+                warn!("unable to query DPMS info: {}", e);
+                None
+            }
+            Ok(r) if !r.state() => None, // DPMS is disabled
+            Ok(r) => match r.power_level() as u32 {
+                xcb::dpms::DPMS_MODE_ON => Some(DpmsState::On),
+                xcb::dpms::DPMS_MODE_STANDBY => Some(DpmsState::Standby),
+                xcb::dpms::DPMS_MODE_SUSPEND => Some(DpmsState::Suspend),
+                xcb::dpms::DPMS_MODE_OFF => Some(DpmsState::Off),
+                _ => None,
+            },
+        }
+    }
+
+    #[cfg(not(feature = "dpms"))]
+    fn dpms_state(&self) -> Option<DpmsState> {
+        None
+    }
+
     // - Release all of the keybindings we are holding on to
     // - destroy the check window
     // - mark ourselves as no longer being the active root window
@@ -816,13 +1576,144 @@ impl XConn for XcbConnection {
     }
 }
 
+/// A call made to a MockXConn that would have changed the state of the X server
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockCall {
+    PositionWindow(WinId, Region, u32),
+    RaiseWindow(WinId),
+    MapWindow(WinId),
+    UnmapWindow(WinId),
+    SendClientEvent(WinId, String),
+    KillWindow(WinId),
+    FocusClient(WinId),
+    SetActiveWindow(WinId),
+    SetBorderColor(WinId, u32),
+    SetOpacity(WinId, f32),
+    SetFullscreen(WinId, bool),
+    SetHidden(WinId, bool),
+    SetCurrentWorkspace(usize),
+    SetRootWindowName(String),
+    MarkNewWindow(WinId),
+    ClearFocus,
+    GrabKeys(usize),
+    GrabKeyboard,
+    UngrabKeyboard,
+    SetWmProperties(Vec<String>),
+    UpdateDesktops(Vec<String>),
+    SetClientWorkspace(WinId, usize),
+    UpdateClientList(Vec<WinId>),
+    SetDpmsState(DpmsState),
+    Cleanup,
+}
+
+/**
+ * An XConn that does not need a running X server, for testing key bindings, hooks and layouts.
+ *
+ * Events to be handled are queued up front (or pushed as the test runs) and calls that would
+ * have changed the state of the X server are recorded so that they can be checked once the
+ * WindowManager has run (see WindowManager::run_until_idle). Queries return fixed values.
+ */
 pub struct MockXConn {
     screens: Vec<Screen>,
+    events: RefCell<VecDeque<XEvent>>,
+    calls: RefCell<Vec<MockCall>>,
+    windows: Vec<WinId>,
+    docks: Vec<(WinId, Option<Strut>)>,
+    transients: Vec<(WinId, WinId)>,
+    dialogs: Vec<WinId>,
+    no_input: Vec<WinId>,
+    protocols: Vec<String>,
+    other_wm: bool,
+    warps: RefCell<Vec<Option<WinId>>>,
 }
 
 impl MockXConn {
     pub fn new(screens: Vec<Screen>) -> Self {
-        MockXConn { screens }
+        MockXConn {
+            screens,
+            events: RefCell::new(VecDeque::new()),
+            calls: RefCell::new(vec![]),
+            windows: vec![],
+            docks: vec![],
+            transients: vec![],
+            dialogs: vec![],
+            no_input: vec![],
+            protocols: vec![],
+            other_wm: false,
+            warps: RefCell::new(vec![]),
+        }
+    }
+
+    /// Events to return (in order) from wait_for_event and poll_for_event
+    pub fn with_events(self, events: Vec<XEvent>) -> Self {
+        self.events.borrow_mut().extend(events);
+        self
+    }
+
+    /// Add an event to the end of the queue of events to be handled
+    pub fn push_event(&self, event: XEvent) {
+        self.events.borrow_mut().push_back(event);
+    }
+
+    /// Every call made so far that would have changed the state of the X server
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.borrow().clone()
+    }
+
+    /// Forget all of the calls made so far
+    pub fn clear_calls(&self) {
+        self.calls.borrow_mut().clear();
+    }
+
+    fn record(&self, call: MockCall) {
+        self.calls.borrow_mut().push(call);
+    }
+
+    /// Every warp of the cursor so far: the target window or None for warps to a point
+    pub fn warps(&self) -> Vec<Option<WinId>> {
+        self.warps.borrow().clone()
+    }
+
+    /// Act as though another window manager is already running
+    pub fn with_other_wm(mut self) -> Self {
+        self.other_wm = true;
+        self
+    }
+
+    /// Existing windows to report from query_for_active_windows
+    pub fn with_active_windows(mut self, windows: Vec<WinId>) -> Self {
+        self.windows = windows;
+        self
+    }
+
+    /// Windows to report as docks, along with the space that they reserve
+    pub fn with_docks(mut self, docks: Vec<(WinId, Option<Strut>)>) -> Self {
+        self.docks = docks;
+        self
+    }
+
+    /// (window, parent) pairs to report from window_transient_for
+    pub fn with_transients(mut self, transients: Vec<(WinId, WinId)>) -> Self {
+        self.transients = transients;
+        self
+    }
+
+    /// Windows to report as dialogs
+    pub fn with_dialogs(mut self, dialogs: Vec<WinId>) -> Self {
+        self.dialogs = dialogs;
+        self
+    }
+
+    /// Windows that should report the input field of WM_HINTS as false
+    pub fn with_no_input(mut self, no_input: Vec<WinId>) -> Self {
+        self.no_input = no_input;
+        self
+    }
+
+    /// The ICCCM protocols to report as supported for every window
+    pub fn with_protocols(mut self, protocols: &[&str]) -> Self {
+        self.protocols = protocols.iter().map(|p| p.to_string()).collect();
+        self
     }
 }
 
@@ -831,35 +1722,178 @@ impl XConn for MockXConn {
         true
     }
     fn wait_for_event(&self) -> Option<XEvent> {
+        self.events.borrow_mut().pop_front()
+    }
+    fn poll_for_event(&self) -> Option<XEvent> {
+        self.events.borrow_mut().pop_front()
+    }
+    fn connection_fd(&self) -> Option<RawFd> {
         None
     }
     fn current_outputs(&self) -> Vec<Screen> {
         self.screens.clone()
     }
-    fn position_window(&self, _: WinId, _: Region, _: u32) {}
-    fn mark_new_window(&self, _: WinId) {}
-    fn map_window(&self, _: WinId) {}
-    fn unmap_window(&self, _: WinId) {}
-    fn send_client_event(&self, _: WinId, _: &str) {}
-    fn focus_client(&self, _: WinId) {}
-    fn set_client_border_color(&self, _: WinId, _: u32) {}
-    fn grab_keys(&self, _: &KeyBindings) {}
-    fn set_wm_properties(&self, _: &[&'static str]) {}
-    fn set_current_workspace(&self, _: usize) {}
-    fn set_root_window_name(&self, _: &str) {}
-    fn set_client_workspace(&self, _: WinId, _: usize) {}
+    fn position_window(&self, id: WinId, r: Region, border: u32) {
+        self.record(MockCall::PositionWindow(id, r, border));
+    }
+    fn raise_window(&self, id: WinId) {
+        self.record(MockCall::RaiseWindow(id));
+    }
+    fn window_geometry(&self, _: WinId) -> Result<Region> {
+        Ok(Region::new(0, 0, 0, 0))
+    }
+    fn mark_new_window(&self, id: WinId) {
+        self.record(MockCall::MarkNewWindow(id));
+    }
+    fn map_window(&self, id: WinId) {
+        self.record(MockCall::MapWindow(id));
+    }
+    fn unmap_window(&self, id: WinId) {
+        self.record(MockCall::UnmapWindow(id));
+    }
+    fn send_client_event(&self, id: WinId, atom_name: &str) {
+        self.record(MockCall::SendClientEvent(id, atom_name.to_string()));
+    }
+    fn window_protocols(&self, _: WinId) -> Vec<String> {
+        self.protocols.clone()
+    }
+    fn kill_window(&self, id: WinId) {
+        self.record(MockCall::KillWindow(id));
+    }
+    fn focus_client(&self, id: WinId) {
+        self.record(MockCall::FocusClient(id));
+    }
+    fn set_active_window(&self, id: WinId) {
+        self.record(MockCall::SetActiveWindow(id));
+    }
+    fn clear_focus(&self) {
+        self.record(MockCall::ClearFocus);
+    }
+    fn set_client_border_color(&self, id: WinId, color: u32) {
+        self.record(MockCall::SetBorderColor(id, color));
+    }
+    fn set_client_opacity(&self, id: WinId, opacity: f32) {
+        self.record(MockCall::SetOpacity(id, opacity));
+    }
+    fn toggle_client_fullscreen(&self, id: WinId, fullscreen: bool) {
+        self.record(MockCall::SetFullscreen(id, fullscreen));
+    }
+    fn set_client_hidden(&self, id: WinId, hidden: bool) {
+        self.record(MockCall::SetHidden(id, hidden));
+    }
+    fn grab_keys(&self, bindings: &KeyBindings) {
+        self.record(MockCall::GrabKeys(bindings.len()));
+    }
+    fn grab_keyboard(&self) -> bool {
+        self.record(MockCall::GrabKeyboard);
+        true
+    }
+    fn ungrab_keyboard(&self) {
+        self.record(MockCall::UngrabKeyboard);
+    }
+    fn modifier_keycodes(&self) -> Vec<u8> {
+        vec![]
+    }
+    fn acquire_wm_selection(&self, replace: bool) -> Result<()> {
+        if self.other_wm && !replace {
+            return Err(PenroseError::OtherWmRunning);
+        }
+        Ok(())
+    }
+    fn set_wm_properties(&self, workspaces: &[&str]) {
+        let names = workspaces.iter().map(|w| w.to_string()).collect();
+        self.record(MockCall::SetWmProperties(names));
+    }
+    fn update_desktops(&self, workspaces: &[&str]) {
+        let names = workspaces.iter().map(|w| w.to_string()).collect();
+        self.record(MockCall::UpdateDesktops(names));
+    }
+    fn set_current_workspace(&self, wix: usize) {
+        self.record(MockCall::SetCurrentWorkspace(wix));
+    }
+    fn set_root_window_name(&self, name: &str) {
+        self.record(MockCall::SetRootWindowName(name.to_string()));
+    }
+    fn set_client_workspace(&self, id: WinId, wix: usize) {
+        self.record(MockCall::SetClientWorkspace(id, wix));
+    }
+    fn update_client_list(&self, clients: &[WinId]) {
+        self.record(MockCall::UpdateClientList(clients.to_vec()));
+    }
     fn window_should_float(&self, _: WinId, _: &[&str]) -> bool {
         true
     }
-    fn warp_cursor(&self, _: Option<WinId>) {}
+    fn warp_cursor(&self, win_id: Option<WinId>) {
+        self.warps.borrow_mut().push(win_id);
+    }
+    fn warp_cursor_to_point(&self, _: u32, _: u32) {
+        self.warps.borrow_mut().push(None);
+    }
     fn query_for_active_windows(&self) -> Vec<WinId> {
-        Vec::new()
+        self.windows.clone()
+    }
+    fn query_for_docks(&self) -> Vec<WinId> {
+        self.docks.iter().map(|(id, _)| *id).collect()
+    }
+    fn window_is_dock(&self, id: WinId) -> bool {
+        self.docks.iter().any(|(d, _)| *d == id)
+    }
+    fn window_strut(&self, id: WinId) -> Option<Strut> {
+        self.docks
+            .iter()
+            .find(|(d, _)| *d == id)
+            .and_then(|(_, s)| *s)
+    }
+    fn window_size_hints(&self, _: WinId) -> Option<SizeHints> {
+        None
+    }
+    fn window_transient_for(&self, id: WinId) -> Option<WinId> {
+        self.transients
+            .iter()
+            .find(|(t, _)| *t == id)
+            .map(|(_, parent)| *parent)
+    }
+    fn window_is_dialog(&self, id: WinId) -> bool {
+        self.dialogs.contains(&id)
     }
-    fn str_prop(&self, _: u32, name: &str) -> Result<String, String> {
+    fn window_accepts_input(&self, id: WinId) -> bool {
+        !self.no_input.contains(&id)
+    }
+    fn str_prop(&self, _: u32, name: &str) -> Result<String> {
         Ok(String::from(name))
     }
-    fn atom_prop(&self, id: u32, _: &str) -> Result<u32, String> {
+    fn atom_prop(&self, id: u32, _: &str) -> Result<u32> {
         Ok(id)
     }
-    fn cleanup(&self) {}
+    fn set_dpms_state(&self, state: DpmsState) {
+        self.record(MockCall::SetDpmsState(state));
+    }
+    fn dpms_state(&self) -> Option<DpmsState> {
+        None
+    }
+    fn intern_atom(&self, name: &str) -> Result<u32> {
+        match ATOMS.iter().position(|a| *a == name) {
+            Some(i) => Ok(i as u32),
+            None => Err(PenroseError::X(format!("{} is not a known atom", name))),
+        }
+    }
+    fn cleanup(&self) {
+        self.record(MockCall::Cleanup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supported_atoms_follow_enabled_features() {
+        let supported = ewmh_supported_atoms();
+        assert!(supported.contains(&"_NET_SUPPORTED"));
+        assert_eq!(
+            supported.contains(&"_NET_WM_STATE_FULLSCREEN"),
+            cfg!(feature = "ewmh-fullscreen")
+        );
+        assert!(supported.iter().all(|a| ATOMS.contains(a)));
+    }
 }